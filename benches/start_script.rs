@@ -4,7 +4,7 @@ use criterion::{criterion_group, criterion_main, Criterion};
 pub fn criterion_benchmark(c: &mut Criterion) {
     let command_args: Vec<String> = Vec::new();
     c.bench_function("start script", |b| {
-        b.iter(|| run_one_script("benches/start.lisp", &command_args))
+        b.iter(|| run_one_script("benches/start.lisp", &command_args, false, false, None))
     });
 }
 
@@ -54,6 +54,9 @@ NOTE: list on this table can be a vector or a list.")
 		("random" nil)
 		("struct" nil)
 		("core" nil)
+		("conditions" "Errors and signals are conditions with a kind (a keyword).  Use handler-bind to
+handle them where they are raised (before unwinding), handler-case to unwind to
+a handler and restart-case with invoke-restart to provide ways to recover.")
 		("file" " Options to open, one or more of these can be added to open after the filename.
 A file can only be opened for reading or writing (read is default).
 
@@ -74,6 +77,8 @@ all are released.  Close will also flush the file even if it is not the final
 reference.  If a reference to a file is captured in a closure that can also keep
 it open (closures currently capture the entire scope not just used symbols).")
 		("hashmap" nil)
+		("set" "Sets hold unique items, the same kinds of values that can be hash map keys.
+Use #{} to declare them in code (i.e. #{1 2 3}), the items are evaluated.")
 		("scripting" nil)
 		("math" nil)
 		("namespace" nil)
@@ -163,6 +163,16 @@ Example:
 let-body with all values of binding bound to the result of the evaluation of
 sexp.
 
+A binding can be a destructuring pattern instead of a symbol (the same patterns
+work for fn parameters).  A list or vector pattern (a b & rest) binds each
+symbol to an element of a list or vector and rest to the remaining elements,
+it is an error if the number of elements does not match.  Patterns nest, for
+example ((a b) c).  A map pattern {:keys (host port)} binds host and port to
+the values of :host and :port (or the string keys \"host\" and \"port\") in a
+hashmap, {h :host p \"port\"} binds h and p to the values of the given keys,
+:or {port 80} gives defaults for missing keys and :as cfg binds the whole map.
+Missing keys without a default are nil.
+
 Section: core
 
 Example:
@@ -180,6 +190,16 @@ Example:
     (test::assert-equal (+ idx 2) v2)
     (test::assert-equal (+ idx 3) v3)
     (if (< idx 5) (this-fn (+ idx 1)))))0)
+(let (((a b & rest) '(1 2 3 4))
+      ((c #(d e)) (list 5 (vec 6 7)))
+      ({:keys (host port) :or {port 80} :as cfg} {:host \"localhost\"}))
+    (test::assert-equal '(1 2 (3 4)) (list a b rest))
+    (test::assert-equal '(5 6 7) (list c d e))
+    (test::assert-equal '(\"localhost\" 80) (list host port))
+    (test::assert-equal \"localhost\" (hash-get cfg :host)))
+(let (({h :host p \"port\"} (make-hash '((:host . \"h\") (\"port\" . 22)))))
+    (test::assert-equal '(\"h\" 22) (list h p)))
+(test::assert-equal :arity (error-kind (get-error (let (((a b) '(1 2 3))) a))))
 "
   (vals &rest let-body)
   ((fn (vars binds)
@@ -586,4 +606,156 @@ Example:
       (= (length args) 1) `(set! ,s (- ,s ,(vec-nth args 0)))
       (err "dec!: requires a symbol and optional number")))
 
+(defmacro handler-case
+"Usage: (handler-case form (:kind (condition?) body*)*) -> result
+
+Evaluate form and return its result.  If a condition of one of the clause kinds
+is raised (see err and signal) in form then unwind to here and return the result
+of that clause's body (in an implicit do) with condition bound to the condition
+(the same list get-error returns).  Clauses are checked in order and :error
+matches every kind of error.
+
+Section: conditions
+
+Example:
+(test::assert-equal 3 (handler-case (+ 1 2) (:error (c) 0)))
+(test::assert-equal \"Oops!\" (handler-case (err :oops \"Oops!\") (:io () :io) (:oops (c) (error-message c))))
+(test::assert-equal :any (handler-case (err \"Oops!\") (:io () :io) (:error () :any)))
+(test::assert-equal :type (handler-case (handler-case (+ 1 \"one\") (:io () :io)) (:type () :type)))
+(let ((handler-case-test 1))
+  (test::assert-equal 11 (handler-case (err :oops \"Oops!\") (:oops () (+ handler-case-test 10)))))
+"
+  (form &rest clauses)
+  (let ((build-clauses))
+    (set! build-clauses
+          (fn (clauses)
+              (if (empty-seq? clauses) nil
+                  (let ((kind (first (first clauses)))
+                        (params (first (rest (first clauses))))
+                        (body (rest (rest (first clauses)))))
+                    `(,kind (fn ,(if (empty-seq? params) (list (gensym)) params) ,@body)
+                      ,@(build-clauses (rest clauses)))))))
+    `(handler-case-fn (fn () ,form) ,@(build-clauses clauses))))
+
+(defmacro restart-case
+"Usage: (restart-case form (name (params*) body*)*) -> result
+
+Evaluate form and return its result with the named restarts available to
+invoke-restart (for instance from a handler-bind handler).  Invoking one unwinds
+to here and returns the result of its body (in an implicit do) with params bound
+to the invoke-restart arguments.
+
+Section: conditions
+
+Example:
+(test::assert-equal 3 (restart-case (+ 1 2) (use-value (v) v)))
+(test::assert-equal 10 (restart-case (invoke-restart 'use-value 10) (use-value (v) v)))
+(test::assert-equal :skipped (restart-case (invoke-restart 'skip) (use-value (v) v) (skip () :skipped)))
+(defn restart-case-test (x)
+  (restart-case
+    (if (< x 0) (err :negative \"Negative!\" x) x)
+    (use-zero () 0)
+    (use-abs () (- 0 x))))
+(test::assert-equal 5 (handler-bind ((:negative (fn (c) (invoke-restart 'use-abs))))
+  (restart-case-test -5)))
+(test::assert-equal 0 (handler-bind ((:negative (fn (c) (invoke-restart 'use-zero))))
+  (restart-case-test -5)))
+(test::assert-equal :negative (error-kind (get-error (restart-case-test -5))))
+"
+  (form &rest clauses)
+  (let ((build-clauses))
+    (set! build-clauses
+          (fn (clauses)
+              (if (empty-seq? clauses) nil
+                  (let ((name (first (first clauses)))
+                        (params (first (rest (first clauses))))
+                        (body (rest (rest (first clauses)))))
+                    `((quote ,name) (fn ,params ,@body)
+                      ,@(build-clauses (rest clauses)))))))
+    `(restart-case-fn (fn () ,form) ,@(build-clauses clauses))))
+
+(defmacro defgeneric
+"Usage: (defgeneric name doc-string? (param*) [:dispatch dispatch-fn])
+
+Define a generic function, name calls the method (see defmethod) for the types
+of its required arguments.  The type of an argument is what type returns
+(Int, String, Vector, HashMap, ... or the name of the struct for a struct
+instance, see struct::defstruct).  If dispatch-fn is given it is called with the
+arguments instead and returns the value (or a list with a value for each
+required param) methods are matched against.  The method picked for each list
+of types (or dispatch values) is cached.  Calling name when no method matches
+raises a :no-applicable-method error with the arguments as the error data.
+Defining a generic again (reloading a file for example) keeps its methods, it
+is an :arity error to change the number of required params of one with methods.
+
+Section: struct
+
+Example:
+(defgeneric defgeneric-describe \"Describe x.\" (x))
+(defmethod defgeneric-describe ((x Int)) (str \"int \" x))
+(defmethod defgeneric-describe ((x String)) (str \"string \" x))
+(defmethod defgeneric-describe (x) \"something\")
+(struct::defstruct defgeneric-point (x 0 :ro) (y 0 :ro))
+(defmethod defgeneric-describe ((p defgeneric-point)) (str \"point \" (p :x)))
+(test::assert-equal \"int 1\" (defgeneric-describe 1))
+(test::assert-equal \"string s\" (defgeneric-describe \"s\"))
+(test::assert-equal \"point 0\" (defgeneric-describe (defgeneric-point)))
+(test::assert-equal \"something\" (defgeneric-describe 1.5))
+(defgeneric defgeneric-area (shape) :dispatch (fn (shape) (hash-get shape :kind)))
+(defmethod defgeneric-area ((s :square)) (* (hash-get s :side) (hash-get s :side)))
+(test::assert-equal 4 (defgeneric-area {:kind :square :side 2}))
+(test::assert-equal :no-applicable-method (error-kind (get-error (defgeneric-area {:kind :circle}))))
+"
+  (name &rest args)
+  (let ((doc (if (string? (first args)) (first args) nil))
+        (args (if (string? (first args)) (rest args) args)))
+    (if (not (or (= 1 (length args))
+                 (and (= 3 (length args)) (= :dispatch (first (rest args))))))
+        (err "defgeneric: takes a name, optional doc string, params and optional :dispatch function"))
+    ; The generic is a (fn (&rest args) ...) so give the doc the real usage.
+    (let ((usage (str "Usage: " (join name (first args)))))
+      (set! doc (if (not doc) usage (str-contains "Usage:" doc) doc (str usage "\n\n" doc)))
+      `(def ,name ,doc (make-generic ',name ',(first args) ,(first (rest (rest args))))))))
+
+(defmacro defmethod
+"Usage: (defmethod name (param*) body*)
+
+Add a method to the generic function name (see defgeneric).  A required param
+can be (param type) to only match arguments of type (a symbol like Int or the
+name of a struct, or the keyword or string a dispatch function returns), other
+params match any argument.  The most specific matching method is called, the
+first param specialized in one method and not the other decides.  Defining a
+method with the same types again replaces it.  Use a vector pattern to
+destructure a required param since a two element list is a type.
+
+Section: struct
+
+Example:
+(defgeneric defmethod-join (a b))
+(defmethod defmethod-join ((a Int) (b Int)) (+ a b))
+(defmethod defmethod-join ((a String) b) (str a b))
+(defmethod defmethod-join (a (b String)) (str b a))
+(test::assert-equal 3 (defmethod-join 1 2))
+(test::assert-equal \"ab\" (defmethod-join \"a\" \"b\"))
+(test::assert-equal \"b1\" (defmethod-join 1 \"b\"))
+(defmethod defmethod-join ((a Int) (b Int)) (* a b))
+(test::assert-equal 2 (defmethod-join 1 2))
+(test::assert-error (defmethod-join 1.0 2))
+"
+  (name params &rest body)
+  (let ((plain (vec))
+        (specs (vec))
+        (split))
+    (set! split
+          (fn (params required)
+              (if (not (empty-seq? params))
+                  (let ((p (first params)))
+                    (if (and (symbol? p) (str-starts-with "&" (sym->str p))) (set! required nil))
+                    (if (and required (list? p) (= 2 (length p)) (symbol? (first p)))
+                        (do (vec-push! plain (first p)) (vec-push! specs (first (rest p))))
+                        (do (vec-push! plain p) (if required (vec-push! specs nil))))
+                    (recur (rest params) required)))))
+    (split params #t)
+    `(generic-add-method ,name ',specs (fn ,plain ,@body))))
+
 (load "collection.lisp")
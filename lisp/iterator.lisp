@@ -9,7 +9,7 @@
 Loops over each element in an iterator.  Will call iter on the input object.
 bind is bound to the current element of items and is accesible
 in body. body is evaluated a number of times equal to the the number of items
-in in_list.
+in in_list.  bind can be a destructuring pattern (see let).
 
 Section: iterator
 
@@ -17,14 +17,23 @@ Example:
 (def i 0)
 (iterator::for x in (iterator::range 11) (set! i (+ 1 i)))
 (assert-equal 11 i)
+(def i 0)
+(iterator::for (x y) in '((1 2) (3 4)) (set! i (+ i (* x y))))
+(assert-equal 14 i)
+(def i 0)
+(iterator::for {:keys (n)} in (list {:n 1} {:n 2}) (set! i (+ i n)))
+(assert-equal 3 i)
+(assert-error-msg (iterator::for (x y) in '((1 2 3)) nil) \"wrong number of elements to destructure (1 2 3) into (x y), expected 2 got 3\")
 "
     (bind in items body) (do
     (if (not (= in 'in)) (err "Invalid for: (for [i] in [iterator] (body))"))
     (var plist (gensym))
+    (var step (if (symbol? bind)
+                  `(do (var ,bind (,plist :next!)) (,@body))
+                  `(let ((,bind (,plist :next!))) (,@body))))
     `(loop (,plist) ((iterator::iter ,items))
          (if (not (,plist :empty?)) (do
-             (var ,bind (,plist :next!))
-             (,@body)
+             ,step
              (recur ,plist))))))
 
 (defmacro for-i
@@ -729,6 +738,7 @@ Example:
 (assert-true (iterator::iter? (iterator::iter '#(1 2 3))))
 (assert-true (iterator::iter? (iterator::iter \"abc\")))
 (assert-true (iterator::iter? (iterator::iter (iterator::iter '(1 2 3)))))
+(assert-equal '(1 2 3) (qsort (iterator::collect (iterator::iter #{1 2 3}))))
 "
   (thing)
   (if (iter? thing)
@@ -737,11 +747,13 @@ Example:
         ((list-iter) :init thing)
       (vec? thing)
         ((vec-iter) :init thing 0)
+      (set? thing)
+        ((vec-iter) :init (set-items thing) 0)
       (string? thing)
         ((string-iter) :init thing)
       (file? thing)
         ((file-iter) :init thing)
-      (err "iter: requires a list, vector, string, file or existing iterator")))
+      (err "iter: requires a list, vector, set, string, file or existing iterator")))
 
 (defn iter-or-single
 "Return thing as an iterator if possible (if it is an iterator just return thing).
@@ -764,6 +776,8 @@ Example:
         ((list-iter) :init thing)
       (vec? thing)
         ((vec-iter) :init thing 0)
+      (set? thing)
+        ((vec-iter) :init (set-items thing) 0)
       (string? thing)
         ((string-iter) :init thing)
       (file? thing)
@@ -356,6 +356,9 @@ Example:
 (test::assert-equal \"......test\" (with-padding \"test\" 10 \".\" :left))
 (test::assert-equal \"...test...\" (with-padding \"test\" 10 \".\" :center))
 (test::assert-equal \"..tests...\" (with-padding \"tests\" 10 \".\" :center))
+(set! *int-div-truncate* #t)
+(test::assert-equal \"..tests...\" (with-padding \"tests\" 10 \".\" :center))
+(set! *int-div-truncate* nil)
 "
     (target padding padding-char &rest padding-keyword)
     (let ((pad (fn (x)
@@ -370,11 +373,9 @@ Example:
                     (:left (str (pad (- padding (length target))) target))
                     (:right (str target (pad (- padding (length target)))))
                     (:center (let* ((padding-len (- padding (length target)))
-                                    (even? (= 0 (% padding-len 2)))
-                                    (right-padding (pad (/ padding-len 2)))
-                                    (left-padding (pad (if even?
-                                                    (/ padding-len 2)
-                                                    (math::ceil (/ padding-len 2.0))))))
+                                    (right-len (math::floor (/ padding-len 2)))
+                                    (right-padding (pad right-len))
+                                    (left-padding (pad (- padding-len right-len))))
                               (str right-padding target left-padding)))
                     (nil (err err-msg)))
                 (err err-msg)))))
@@ -37,11 +37,27 @@
              (apply com new-args))
       (ns-pop))))
 
+; a | b | c reads as (pipe a (pipe b c)), splice the nested pipes into one
+; pipe so it is waited on as a whole (for *pipe-status*).
+(defn flatten-pipe (form args)
+  (let ((last-idx (- (length args) 1)))
+    (if (and (>= last-idx 0)
+             (list? (vec-nth args last-idx))
+             (= form (car (vec-nth args last-idx))))
+        (let ((new-args (if (> last-idx 0) (vec-slice args 0 last-idx) (vec))))
+          ((fn (nested)
+               (if (pair? nested)
+                   (do (vec-push! new-args (car nested))
+                       (recur (cdr nested)))))
+           (cdr (vec-nth args last-idx)))
+          (flatten-pipe form new-args))
+        args)))
+
 (defmacro pipe (&rest args)
-  `(pipe ,@args))
+  `(pipe ,@(flatten-pipe 'shell-read::pipe args)))
 
 (defmacro pipe-err (&rest args)
-  `(pipe :err ,@args))
+  `(pipe :err ,@(flatten-pipe 'shell-read::pipe-err args)))
 
 ; sys-apply needs to be able to handle no args to make the shell reader simpler.
 (defmacro sys-apply (&rest args)
@@ -80,6 +96,94 @@
 (defmacro redir&> (exp file) `(out-err> ,file ,exp))
 (defmacro redir&>> (exp file) `(out-err>> ,file ,exp))
 
+; cmd 3>a 4>&3 reads as nested redir-fd forms with the last redirect outermost,
+; collect them into one fd-redirect so they are applied in the order written.
+(defn fd-redir-spec (fd op target)
+  (if (= op :close) (list fd op) (list fd op target)))
+
+(defmacro redir-fd (exp fd op target)
+  (let ((redirs (list (fd-redir-spec fd op target))))
+    ((fn ()
+         (if (and (list? exp)(= 'shell-read::redir-fd (car exp)))
+             (do
+               (set! redirs (join (apply fd-redir-spec (cdr (cdr exp))) redirs))
+               (set! exp (car (cdr exp)))
+               (recur)))))
+    `(fd-redirect ,redirs ,exp)))
+
+(defn fd-char? (ch)
+  (and (char? ch)(str-contains (str ch) "0123456789")))
+
+; Read the rest of a file descriptor number that started with first-ch.
+(defn read-fd-num (stream first-ch)
+  (let ((num (str first-ch)))
+    ((fn ()
+         (if (fd-char? (str-iter-peek stream))
+             (do (str-push! num (str-iter-next! stream))
+                 (recur)))))
+    (str->int num)))
+
+(defmacro redir<<< (exp text) `(with-stdin-str (str ,text "\n") ,exp))
+; text is a vector that gets the body once the here-document has been read.
+(defmacro redir<< (exp text) `(with-stdin-str ,(vec-nth text 0) ,exp))
+
+; Here-documents started on the current line, each is a vector of the body
+; placeholder, delimiter word and if the word was quoted (no expansion).
+(def *heredocs* (vec))
+
+; Read the delimiter word after <<, returns a pair of the word and #t if it was
+; quoted.
+(defn read-heredoc-word (stream)
+  (consume-whitespace stream)
+  (let ((word (str))
+        (quote-ch (str-iter-peek stream)))
+    (if (or (= quote-ch #\')(= quote-ch #\"))
+        (do
+         (str-iter-next! stream)
+         ((fn (ch)
+              (if (not (char? ch)) (err "Missing closing quote for here-document word")
+                  (not (= ch quote-ch)) (do (str-push! word ch)
+                                            (recur (str-iter-next! stream)))))
+          (str-iter-next! stream))
+         (join word #t))
+        (do
+         ((fn (ch)
+              (if (and (char? ch)(not (char-whitespace? ch))(not (= ch #\))))
+                  (do (str-push! word (str-iter-next! stream))
+                      (recur (str-iter-peek stream)))))
+          (str-iter-peek stream))
+         (if (str-empty? word) (err "Missing here-document word after <<"))
+         (join word nil)))))
+
+(defn read-heredoc-line (stream delim)
+  (if (str-iter-empty? stream) (err (str "Here-document not terminated by " delim)))
+  (let ((line (str)))
+    ((fn (ch)
+         (if (and (char? ch)(not (= ch #\newline)))
+             (do (str-push! line ch)
+                 (recur (str-iter-next! stream)))))
+     (str-iter-next! stream))
+    line))
+
+; Read lines up to one that is just delim and fill in the heredoc's placeholder.
+(defn read-heredoc-body (stream heredoc)
+  (let ((text (str))
+        (delim (vec-nth heredoc 1)))
+    ((fn (line)
+         (if (not (= line delim))
+             (do (str-push! text line "\n")
+                 (recur (read-heredoc-line stream delim)))))
+     (read-heredoc-line stream delim))
+    (vec-push! (vec-nth heredoc 0) (if (vec-nth heredoc 2) text (expand-dollar text nil)))))
+
+(defn read-heredocs (stream)
+  ((fn (i)
+       (if (< i (length *heredocs*))
+           (do (read-heredoc-body stream (vec-nth *heredocs* i))
+               (recur (+ i 1)))))
+   0)
+  (vec-clear! *heredocs*))
+
 (defn handle-process (cmd-proc)
 	(if (process? cmd-proc) (= 0 (wait cmd-proc)) (not (not cmd-proc))))
 
@@ -348,6 +452,40 @@
             (add-exp (list (sym wrapper) temp-result))
             (add-exp temp-result))))
 
+    (defn setup-fd-chainer (fd op target)
+      (let ((temp-result))
+        (close-token)
+        (set! temp-result (get-result))
+        (clear-result)
+        (add-exp (sym "shell-read::redir-fd"))
+        (add-exp temp-result)
+        (add-exp fd)
+        (add-exp op)
+        (add-exp (if target target (read-string stream #\space (str) #t nil)))))
+
+    (defn setup-heredoc-chainer (placeholder)
+      (let ((temp-result))
+        (close-token)
+        (set! temp-result (get-result))
+        (clear-result)
+        (add-exp (sym "shell-read::redir<<"))
+        (add-exp temp-result)
+        (add-exp placeholder)))
+
+    ; Read the command in a <(...) or >(...) as an argument.
+    (defn read-proc-sub (form)
+      (close-token)
+      (str-iter-next! stream) ; skip (
+      (set! paren-level (+ paren-level 1))
+      (add-exp (list form (shell-read-int stream #t))))
+
+    ; Read the m or - after a n>& or n<&.
+    (defn read-fd-dup (fd)
+      (let ((next-ch (str-iter-next! stream)))
+        (if (= next-ch #\-) (setup-fd-chainer fd :close :close)
+            (fd-char? next-ch) (setup-fd-chainer fd :dup (read-fd-num stream next-ch))
+            (err "Invalid redirect, expected a file descriptor or '-' after '&'"))))
+
     (let ((just-read)
           (done))
       (cond
@@ -357,6 +495,9 @@
         ((= ch #\\) nil)  ; skip '\'
         ((= ch #\#)
          (consume-comment stream))
+        ((and (= ch #\newline)(not (vec-empty? *heredocs*)))
+         (close-token)
+         (read-heredocs stream))
         ((and (= ch #\)) (> paren-level 0))
          (set! paren-level (- paren-level 1))
          (set! done #t))
@@ -403,6 +544,43 @@
         ((= ch #\|) ; PIPE
          (setup-chainer "shell-read::pipe" nil nil)
          (set! done #t))
+        ((and (= ch #\<)(= peek-ch #\()) ; process substitution, read from it
+         (read-proc-sub 'proc-sub-in)
+         (set! just-read #t))
+        ((and (= ch #\>)(= peek-ch #\()) ; process substitution, write to it
+         (read-proc-sub 'proc-sub-out)
+         (set! just-read #t))
+        ((and (= ch #\<)(= peek-ch #\<)) ; here-document (<<word) or here-string (<<<word)
+         (str-iter-next! stream)
+         (if (= (str-iter-peek stream) #\<)
+             (do (str-iter-next! stream)
+                 (setup-chainer "shell-read::redir<<<" nil #t))
+             (let ((word (read-heredoc-word stream))
+                   (placeholder (vec)))
+               (vec-push! *heredocs* (vec placeholder (car word) (cdr word)))
+               (setup-heredoc-chainer placeholder)))
+         (set! just-read #t))
+        ((and (= ch #\>)(= peek-ch #\&)) ; >&m or >&- (same as 1>&m)
+         (str-iter-next! stream)
+         (read-fd-dup 1)
+         (set! just-read #t))
+        ((and (char-whitespace? last-ch)(fd-char? ch)(or (= peek-ch #\>)(= peek-ch #\<)))
+         ; n>file, n>>file, n<file, n>&m, n<&m, n>&-, n<&- (1 and 2 to files use out>/err>)
+         (let ((fd (str->int (str ch)))
+               (dir (str-iter-next! stream)))
+           (if (= (str-iter-peek stream) #\&)
+               (do (str-iter-next! stream)
+                   (read-fd-dup fd))
+               (= dir #\<) (setup-fd-chainer fd :read nil)
+               (= (str-iter-peek stream) #\>)
+               (do (str-iter-next! stream)
+                   (if (= fd 1) (setup-chainer "shell-read::redir>>" nil #t)
+                       (= fd 2) (setup-chainer "shell-read::redir2>>" nil #t)
+                       (setup-fd-chainer fd :append nil)))
+               (= fd 1) (setup-chainer "shell-read::redir>" nil #t)
+               (= fd 2) (setup-chainer "shell-read::redir2>" nil #t)
+               (setup-fd-chainer fd :write nil)))
+         (set! just-read #t))
         ((and (= ch #\>)(= peek-ch #\>)) ; out>>
          (str-iter-next! stream)
          (setup-chainer "shell-read::redir>>" nil #t))
@@ -532,12 +710,22 @@
          (read-var stream #\space ch peek-ch add-exp token)))
       result)))
 
+; Read a complete shell form, the bodies of any here-documents in it must be
+; read by the time it is done.
+(defn read-shell-form (stream in-paren)
+  (dyn *heredocs* (vec)
+       (let ((result (shell-read-int stream in-paren)))
+         (if (not (vec-empty? *heredocs*))
+             (err (str "Here-document body for " (vec-nth (vec-nth *heredocs* 0) 1)
+                       " must start on the line after the <<")))
+         result)))
+
 (defn shell-read (stream ch_start)
   (if (= (str-iter-peek stream) #\%)
       (do
        (str-iter-next! stream)
-       (shell-read::shell-read-int stream nil))
-      (list 'str (shell-read::shell-read-int stream nil))))
+       (shell-read::read-shell-form stream nil))
+      (list 'str (shell-read::read-shell-form stream nil))))
 
 (def *ns-exports* nil)
 
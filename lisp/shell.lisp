@@ -751,6 +751,28 @@ Section: shell
   () '(undef __line_handler))
 
 
+(defn run-err-trap
+  "Run the :err trap (see trap) with error, the result of a get-error that
+produced an error.  An error from the trap itself is printed.
+
+Section: shell
+
+Example:
+(def err-trap-test nil)
+(trap :err (fn (error) (set! err-trap-test (car (cdr error)))))
+(shell::run-err-trap (get-error (err \"Oops!\")))
+(untrap :err)
+(test::assert-equal \"Oops!\" err-trap-test)
+"
+  (error)
+  (let ((handler (trap :err)))
+    (when handler
+      (let ((result (get-error (handler error))))
+        (when (= :error (car result))
+          (do
+            (println "Error running trap :err:")
+            (print-error result)))))))
+
 (defn repl-eof (result)
   (do
    (if (and (values? result)
@@ -779,10 +801,42 @@ Section: shell
   ;; Set global var *last-command*
   (set! *last-command* line))
 
+(defn repl-restart
+  "Used by the repl to offer the active restarts (see restart-case) for an error
+that was not handled.  Prints the error and the restarts then reads the restart
+to invoke from *stdin* as its number followed by any arguments (evaluated), 0
+or anything else aborts back to the repl.  Returns true if the error was printed
+(only when there are restarts).
+
+Section: shell
+"
+  (condition)
+  (let ((restarts (compute-restarts))
+        (choice))
+    (when restarts
+      (do
+        (print-error condition)
+        (println "Restarts:")
+        (println "  0: [abort] Return to the repl")
+        (for-i idx restart in restarts (println "  " (+ idx 1) ": [" restart "]"))
+        (print "Restart: ")
+        (flush *stdout*)
+        (set! choice (read-line *stdin*))
+        (set! choice (if choice (get-error (read-all choice)) '(:ok)))
+        (when (and (= :ok (car choice))
+                   (not (empty-seq? (cdr choice)))
+                   (int? (first (cdr choice)))
+                   (> (first (cdr choice)) 0)
+                   (<= (first (cdr choice)) (length restarts)))
+          (eval `(invoke-restart (quote ,(nth (- (first (cdr choice)) 1) restarts))
+                                 ,@(rest (cdr choice)))))
+        #t))))
+
 (defn repl-line (line line-len)
   (export 'LAST_STATUS "0")
   (set! *last-status* 0)
   (let ((result nil)
+        (error-shown)
         (do-eval)
         (prep-ast (fn (line)
                       (if (string? line)
@@ -791,7 +845,7 @@ Section: shell
                                   (= #\$ (str-nth 0 line))
                                   (= #\( (str-nth 1 line))) (read line))
                             ((= #\( (str-nth 0 line)) (read line))
-                            (#t (shell-read::shell-read-int (str-iter-start line) #t)))
+                            (#t (shell-read::read-shell-form (str-iter-start line) #t)))
                           line))))
 
     ; This next section is odd, it makes sure the eval happens in the active
@@ -805,7 +859,8 @@ Section: shell
                                       (prep-ast line))))
                         (eval ast))))
     (ns-pop)
-    (set! result (get-error (do-eval)))
+    (set! result (get-error (handler-bind ((:error (fn (c) (set! error-shown (repl-restart c)))))
+                              (do-eval))))
     ; end weird namespace section
 
     (if (= :ok (car result))
@@ -822,7 +877,8 @@ Section: shell
          (set! *last-command* line)
          ; Save temp history
          (if (and (> line-len 0)(not (def? *repl-std-only*))) (history-push-throwaway :repl line))
-          (print-error result)))))
+          (if (not error-shown) (print-error result))
+          (run-err-trap result)))))
 
 (defn repl ()
   (let ((get-prompt)
@@ -948,8 +1004,8 @@ Example:
 (test::assert-true (int? (str->int (vec-nth timer-str-vec 1))))
 (test::assert-true (int? (str->int (vec-nth timer-str-vec 4))))
 (def elapsed (vec-nth timer-str-vec 1))
-(def difference (vec-nth timer-str-vec 4))
-(test::assert-equal elapsed difference)
+(def timer-diff (vec-nth timer-str-vec 4))
+(test::assert-equal elapsed timer-diff)
 "
   ;; fields
   (start-time 0)
@@ -2,6 +2,12 @@
 "Prints out an error with a backtrace.  Used with the return of get-error
 when it produces an error
 
+Prints the message with the error kind, then where the error was raised along
+with that line of source (when the file can be read) and finally the backtrace,
+a line like \"at my-fn (deploy.lisp:42:7) with 1, 2\" for each function call
+the error unwound through (innermost first) showing where in the function it
+was and the arguments it was called with.
+
 Section: core
 
 Example:
@@ -13,27 +19,75 @@ Example:
 (set! topen (open file-name :read))
 (test::assert-true (> (length (read-line topen)) 5))
 (close topen))
+(let ((src-file \"$(temp-dir)/print-error-src.lisp\")
+      (out-file \"$(temp-dir)/print-error-src.test\")
+      (topen))
+(set! topen (open src-file :create :truncate))
+(write-line topen \"(def x 1)\")
+(write-line topen \"  (err :bad-thing \\\"Oops!\\\")\")
+(close topen)
+(out> out-file (print-error (get-error (load src-file))))
+(set! topen (open out-file :read))
+(test::assert-equal \"Error (:bad-thing): Oops!\n\" (read-line topen))
+(test::assert-equal (str \"  at \" src-file \":2:3\n\") (read-line topen))
+(test::assert-equal \"    2 |   (err :bad-thing \\\"Oops!\\\")\n\" (read-line topen))
+(test::assert-equal \"      |   ^\n\" (read-line topen))
+(close topen))
 "
     (fn (error)
-        ((fn (print-backtrace)
+        ((fn (print-backtrace print-source)
              (set! print-backtrace
                    (fn (backtrace)
                        ((fn (idx len)
-                            (if (and (not (vec-empty? backtrace))(< idx len))
-                                ((fn :no-recur (b file line col)
-                                     (if (builtin? b)(print "BUILTIN")
-                                         (print (if (set! file (meta-file-name b)) file "NO FILE") ":\t"
-                                                "line " (if (set! line (meta-line-no b)) line "XX") ":\t"
-                                                "column " (if (set! col (meta-column-no b)) col "XX") "\n"))
+                            (if (and (vec? backtrace) (< idx len))
+                                ((fn :no-recur (frame name location args)
+                                     (set! name (vec-nth frame 0))
+                                     (set! location (vec-nth frame 1))
+                                     (set! args (vec-nth frame 2))
+                                     (set! location
+                                           (if location
+                                               (str (vec-nth location 0) ":" (vec-nth location 1) ":" (vec-nth location 2))
+                                               nil))
+                                     (println "  at "
+                                              (if name
+                                                  (if location (str name " (" location ")") name)
+                                                  location)
+                                              (if args (str " with " (str-cat-list ", " args)) ""))
                                      (recur (+ idx 1) len))
                                  (vec-nth backtrace idx) nil nil nil)))
                         0 (length backtrace))))
+             (set! print-source
+                   (fn (location)
+                       ((fn (file line col fin src)
+                            (println "  at " file ":" line ":" col)
+                            (if (and (fs-file? file)
+                                     (= :ok (car (set! fin (get-error (open file :read))))))
+                                (do
+                                 (set! fin (cdr fin))
+                                 (set! src ((fn (idx l)
+                                                (if (and l (< idx line)) (recur (+ idx 1) (read-line fin)) l))
+                                            1 (read-line fin)))
+                                 (close fin)
+                                 (if src
+                                     (do
+                                      (set! src (str-rtrim src))
+                                      (println "    " line " | " src)
+                                      (println "    " (str-map (fn (ch) #\space) (str line)) " | "
+                                               (if (<= col (+ (length src) 1))
+                                                   (str-map (fn (ch) (if (= ch #\tab) ch #\space))
+                                                            (str-sub src 0 (- col 1)))
+                                                   "")
+                                               "^"))))))
+                        (vec-nth location 0) (vec-nth location 1) (vec-nth location 2) nil nil)))
              (if (= :error (car error))
-                 (do
-                  (println (car (cdr error)))
-                  (print-backtrace (car (cdr (cdr error)))))
+                 ((fn (kind location)
+                      (println (if kind (str "Error (" kind "): ") "") (car (cdr error)))
+                      (if (vec? location) (print-source location))
+                      (print-backtrace (car (cdr (cdr error)))))
+                  (car (cdr (cdr (cdr error))))
+                  (car (cdr (cdr (cdr (cdr (cdr error)))))))
                  (err "Not an error!")))
-         nil)))
+         nil nil)))
 
 (def load-std-file
     (fn (file)
@@ -70,6 +124,84 @@ Example:
 "
       () nil)
 
+(defn error-message
+"Return the message of an error returned by get-error.
+
+Section: core
+
+Example:
+(test::assert-equal \"Oops!\" (error-message (get-error (err \"Oops!\"))))
+"
+      (error) (car (cdr error)))
+
+(defn error-kind
+"Return the kind (a keyword) of an error returned by get-error.
+
+Builtin kinds are :io, :type, :arity and :process, errors raised with a string
+only have kind :generic and err can raise errors with any keyword as kind.
+
+Section: core
+
+Example:
+(test::assert-equal :generic (error-kind (get-error (err \"Oops!\"))))
+(test::assert-equal :oops (error-kind (get-error (err :oops \"Oops!\"))))
+(test::assert-equal :type (error-kind (get-error (+ 1 \"one\"))))
+"
+      (error) (car (cdr (cdr (cdr error)))))
+
+(defn error-data
+"Return the data of an error returned by get-error (or nil if none).
+
+Section: core
+
+Example:
+(test::assert-equal '(1 2) (error-data (get-error (err :oops \"Oops!\" '(1 2)))))
+(test::assert-false (error-data (get-error (err \"Oops!\"))))
+"
+      (error) (car (cdr (cdr (cdr (cdr error))))))
+
+(defn error-location
+"Return where an error returned by get-error was raised as a vector of
+#(file line column) or nil if not known.
+
+Section: core
+
+Example:
+(let ((src-file \"$(temp-dir)/error-location.lisp\")
+      (topen))
+(set! topen (open src-file :create :truncate))
+(write-line topen \"\")
+(write-line topen \"  (err :oops \\\"Oops!\\\")\")
+(close topen)
+(test::assert-equal (vec src-file 2 3) (error-location (get-error (load src-file)))))
+"
+      (error) (car (cdr (cdr (cdr (cdr (cdr error)))))))
+
+(defn error-backtrace
+"Return the backtrace of an error returned by get-error, a vector with a
+#(name location args) vector for each function call the error unwound through
+(innermost first).  Name is the symbol the function was defined with, location
+is the #(file line column) of the form the error was in inside the call and
+args are the (printed and truncated) arguments.  Any of them may be nil, the
+last frame is usually the top level form with no name.
+
+Section: core
+
+Example:
+(let ((src-file \"$(temp-dir)/error-backtrace.lisp\")
+      (topen))
+(set! topen (open src-file :create :truncate))
+(write-line topen \"(defn error-backtrace-fn (x)\")
+(write-line topen \"  (err \\\"Oops!\\\"))\")
+(write-line topen \"(error-backtrace-fn 1)\")
+(close topen)
+(def error-backtrace-test (error-backtrace (get-error (load src-file))))
+(test::assert-equal (vec 'error-backtrace-fn (vec src-file 2 3) (vec \"1\"))
+                    (vec-nth error-backtrace-test 0))
+(test::assert-equal (vec nil (vec src-file 3 1) nil) (vec-nth error-backtrace-test 1)))
+"
+      (error) (car (cdr (cdr error))))
+
 (def *last-status*
      "Return code of last run sl-sh command on the repl
      Section: globals"
@@ -80,6 +212,24 @@ Example:
      ""
      )
 
+(def *pipe-status*
+     "Vector of the exit status of each part of the last pipe, nil for a part
+     with no status (stopped for instance)
+     Section: globals"
+     (vec))
+
+(def *pipefail*
+     "If true then the status of a pipe is the last non-zero status of any of
+     it's parts instead of the status of the last part
+     Section: globals"
+     nil)
+
+(def *int-div-truncate*
+     "If true then dividing ints that do not divide evenly truncates to an int
+     (the old behavior) instead of making a rational
+     Section: globals"
+     nil)
+
 (def *repl-settings*
      "hash map of repl settings
      Section: globals"
@@ -157,10 +307,14 @@ Example:
 (if (def? *interactive*)
   (do
     (if (not (def? repl))(def repl shell::repl))
+    (profile-end)
     (repl)))
 
 (if (def? *run-script*)
   (do
     (def result (get-error (load *run-script*)))
     (if (= :error (car result))
-      (print-error result))))
+      (do
+        (print-error result)
+        (shell::run-err-trap result)
+        (if *errexit* (exit (if (= 0 *last-status*) 1 *last-status*)))))))
@@ -1,8 +1,7 @@
 (ns-push 'struct)
 
-(defn method (field prefix methods dispatcher tags doc doc-exp)
+(defn method (field methods tags doc doc-exp)
   (let ((tsym (sym ":" (car field)))
-        (msym (sym prefix (car field)))
         (second (cadr field))
         (m-params nil)
         (m-body nil))
@@ -17,9 +16,7 @@
          (str-push! doc "method: " tsym "\n")
          (set! m-params (cadr field))
           (set! m-body (caddr field))))
-    (vec-push! methods `(,msym (fn ,m-params ,m-body)))
-    (vec-push! dispatcher `(= msg ,tsym))
-    (vec-push! dispatcher `(apply ,msym this-fn args))
+    (vec-push! methods `(,tsym (fn ,m-params ,m-body)))
     (vec-push! tags (sym ":method" tsym))))
 
 ; macro to build a "trait" that can be implemented by a struct
@@ -62,9 +59,7 @@ Example:
   (name &rest fields)
   ((fn ()
        (let ((tags (vec))
-             (dispatch-map (make-hash))
              (methods (vec))
-             (dispatcher (vec))
              (fields-len (length fields))
              (doc (str))
              (doc-exp (str))
@@ -81,39 +76,26 @@ Example:
               (if (< idx fields-len)
                   (let ((field (vec-nth fields idx)))
                     (if (= (car field) :fn)
-                        (method (cdr field) (str name "^") methods dispatcher tags doc doc-exp)
+                        (method (cdr field) methods tags doc doc-exp)
                         (err "Traits only have :fn fields!"))
                     (recur (+ idx 1))
                     )))idx-start)
-         (let ((keys (hash-keys dispatch-map)))
-           (let ((keys-len (length keys))
-                 (tags-len (length tags))
-                 (methods-len (length methods))
-                 (dispatcher-len (length dispatcher))
-                 (doc-final (str)))
-             (if (not (str-contains "Usage:" doc)) (str-push! doc-final "Usage: (deftrait ... (:impl " name "))\n\n"))
-             (str-push! doc-final doc (if (> (length doc-exp) 0) (str "\nSection:" doc-exp)""))
-             `(def ,name ,doc-final (fn (target-dispatch-map target-methods target-dispatcher target-tags)
-                                        ((fn (idx)
-                                             (if (< idx ,tags-len)
-                                                 (do
-                                                  (vec-push! target-tags (vec-nth ',tags idx))
-                                                  (recur (+ idx 1)))))0)
-                                        ((fn (idx)
-                                             (if (< idx ,methods-len)
-                                                 (do
-                                                  (vec-push! target-methods (vec-nth ',methods idx))
-                                                  (recur (+ idx 1)))))0)
-                                        ((fn (idx)
-                                             (if (< idx ,dispatcher-len)
-                                                 (do
-                                                  (vec-push! target-dispatcher (vec-nth ',dispatcher idx))
-                                                  (recur (+ idx 1)))))0)
-                                        ((fn (idx)
-                                             (if (< idx ,keys-len)
-                                                 (let ((key (vec-nth ',keys idx)))
-                                                   (if (not (hash-haskey target-dispatch-map key)) (hash-set! target-dispatch-map key (hash-get ,dispatch-map key)))
-                                                   (recur (+ idx 1)) )))0) )) ))))))
+         (let ((tags-len (length tags))
+               (methods-len (length methods))
+               (doc-final (str)))
+           (if (not (str-contains "Usage:" doc)) (str-push! doc-final "Usage: (deftrait ... (:impl " name "))\n\n"))
+           (str-push! doc-final doc (if (> (length doc-exp) 0) (str "\nSection:" doc-exp)""))
+           `(def ,name ,doc-final (fn (target-methods target-tags)
+                                      ((fn (idx)
+                                           (if (< idx ,tags-len)
+                                               (do
+                                                (vec-push! target-tags (vec-nth ',tags idx))
+                                                (recur (+ idx 1)))))0)
+                                      ((fn (idx)
+                                           (if (< idx ,methods-len)
+                                               (do
+                                                (vec-push! target-methods (vec-nth ',methods idx))
+                                                (recur (+ idx 1)))))0) )) )))))
 
 ; macro to build a "struct"
 (defmacro defstruct
@@ -123,6 +105,10 @@ Use (attr-name doc-str? default? [:rw | :ro | :wo]?) if a final access modifier
   NOTE: for attributes, if the default value is a string then doc-str is not optional (but can be empty).
 Use (:fn name doc-str? body) to add a method.
 Use (:impl trait) to add a trait.
+The lambda takes a keyword argument for each attribute to override its default.
+Instances are native structs (see make-struct-type), methods see the attributes
+as variables, (type instance) is the name of the struct and instances print as
+(name :attr value ...) and are = if they have equal attributes.
 
 Section: struct
 
@@ -146,133 +132,100 @@ Example:
 (assert-equal \"see\" (ts :c))
 (ts :set-b \"queen\")
 (assert-equal \"queen\" (ts :b))
+(assert-equal \"test-struct\" (type ts))
+(assert-equal \"(test-struct :a 1 :b \\\"bee\\\" :c \\\"see\\\" :d \\\"dee\\\")\" (str (test-struct :a 1)))
+(assert-true (= (test-struct :a 1) (test-struct :a 1)))
+(assert-false (= (test-struct :a 1) (test-struct :a 2)))
 "
   (name &rest fields)
-  ((fn (params bindings)
-       (let ((tags (vec))
-             (dispatch-map (make-hash))
-             (methods (vec))
-             (dispatcher (vec))
-             (fields-len (length fields))
-             (doc (str))
-             (doc-exp (str))
-             (idx-start 0)
-             (attrib)
-             (impl))
-         (vec-push! tags :struct)
-         (vec-push! tags (sym ":struct-" name))
-         (if (and (> fields-len 1)(string? (vec-nth fields 0)))
-             (let ((doc-split (str-splitn 2 "Section:" (vec-nth fields 0))))
-               (set! doc (vec-nth doc-split 0))
-               (set! idx-start 1)
-               (if (= 2 (length doc-split)) (set! doc-exp (vec-nth doc-split 1))(set! doc-exp (str)))))
-         (if (not (str-contains "Example:" doc-exp)) (str-push! doc-exp "\nExample:\n"))
-
-         (set! attrib
-               (fn (field doc doc-exp)
-                   (let ((second (cadr field))
-                         (fdoc (str)))
-                     (if (string? second)
-                         (let ((doc-split (str-splitn 2 "Example:" second)))
-                           (set! fdoc (str "\n\t" (vec-nth doc-split 0)))
-                           (if (= 2 (length doc-split)) (str-push! doc-exp (vec-nth doc-split 1)))
-                           (xdr! field (cddr field))))
-                     (if (= 1 (length field))
-                         (do
-                          (str-push! doc "attribute: " (car field) " private" fdoc "\n")
-                          (vec-push! params (car field))
-                           (vec-push! bindings nil))
-                         (= 2 (length field))
-                         (do
-                          (str-push! doc "attribute: " (car field) " private" fdoc "\n")
-                          (vec-push! params (car field))
-                           (vec-push! bindings (cadr field)))
-                         (= 3 (length field))
-                         (let ((param (car field))
-                               (binding (cadr field))
-                               (perm (caddr field))
-                               (tsym nil)
-                               (msym nil))
-                           (vec-push! params param)
-                           (vec-push! bindings binding)
-                           (if (= perm :rw)
-                               (do
-                                (str-push! doc "attribute: " (car field) " read/write" fdoc "\n")
-                                (set! tsym (sym ":" param))
-                                 (set! msym (sym name "^acc^" param))
-                                 (hash-set! dispatch-map tsym `(fn (_) ,param))
-                                 (vec-push! methods `(,msym (fn (_) ,param)))
-                                 (vec-push! tags (sym ":accessor:" param))
-                                 (vec-push! dispatcher `(= msg ,tsym))
-                                 (vec-push! dispatcher `(apply ,msym this-fn args))
-                                 (set! tsym (sym ":set-" param))
-                                 (set! msym (sym name "^set^" param))
-                                 (hash-set! dispatch-map tsym `(fn (_ arg) (set! ,param arg)))
-                                 (vec-push! methods `(,msym (fn (_ arg) (set! ,param arg))))
-                                 (vec-push! dispatcher `(= msg ,tsym))
-                                 (vec-push! dispatcher `(apply ,msym this-fn args))
-                                 (vec-push! tags (sym ":setter:" param)))
-                               (= perm :ro)
-                               (do
-                                (str-push! doc "attribute: " (car field) " read" fdoc "\n")
-                                (set! tsym (sym ":" param))
-                                 (set! msym (sym name "^acc^" param))
-                                 (hash-set! dispatch-map tsym `(fn (_) ,param))
-                                 (vec-push! methods `(,msym (fn (_) ,param)))
-                                 (vec-push! dispatcher `(= msg ,tsym))
-                                 (vec-push! dispatcher `(apply ,msym this-fn args))
-                                 (vec-push! tags (sym ":accessor:" param)))
-                               (= perm :wo)
-                               (do
-                                (str-push! doc "attribute: " (car field) " write" fdoc "\n")
-                                (set! tsym (sym ":set-" param))
-                                 (set! msym (sym name "^set^" param))
-                                 (hash-set! dispatch-map tsym `(fn (_ arg) (set! ,param arg)))
-                                 (vec-push! methods `(,msym (fn (_ arg) (set! ,param arg))))
-                                 (vec-push! dispatcher `(= msg ,tsym))
-                                 (vec-push! dispatcher `(apply ,msym this-fn args))
-                                 (vec-push! tags (sym ":setter:" param)))
-                               (err "defstruct: invalid field access key (valid are :rw, :ro and :wo)")))
-                         (err "ERROR: invalid attribute bindings on defstruct")))))
-
-         (set! impl
-               (fn (field doc)
-                   (if (not (not field))
-                       (do
-                        (str-push! doc "impl " (car field) "\n")
-                        (apply (car field) dispatch-map methods dispatcher tags nil)
-                         (recur (cdr field) doc)))))
-
-         ((fn (idx)
-              (if (< idx fields-len)
-                  (let ((field (vec-nth fields idx)))
-                    (if (= (car field) :fn) (method (cdr field) (str name "^") methods dispatcher tags doc doc-exp)
-                        (= (car field) :impl) nil ; do impls last (struct methods take precident).
-                        (attrib field doc doc-exp))
-                    (recur (+ idx 1)))))idx-start)
-
-         ((fn (idx)
-              (if (< idx fields-len)
-                  (let ((field (vec-nth fields idx)))
-                    (if (= (car field) :impl) (impl (cdr field) doc))
-                    (recur (+ idx 1)))))idx-start)
-
-         (hash-set! dispatch-map :type `(fn (_) (sym->str ',name)))
-         (let ((msym (sym name "^type"))
-               (doc-final (str)))
-           (vec-push! methods `(,msym (fn (_) (sym->str ',name))))
-           (vec-push! dispatcher `(= msg :type))
-           (vec-push! dispatcher `(apply ,msym this-fn args))
-           (str-push! doc-final doc (if (> (length doc-exp) 0) (str "\nSection:" doc-exp)""))
-           `(def ,name ,doc-final (fn () ((fn (,@params)
-                                              (let (,@methods)
-                                                (let ((self
-                                                        (fn (msg &rest args)
-                                                            (if ,@dispatcher
-                                                                (err (str "Invalid message (" msg ") to struct: " ',name))))))
-                                                  (meta-add-tags self ',tags) self))) ,@bindings))))))
-   (make-vec (length fields)) ; params
-   (make-vec (length fields)))) ; bindings
+  (let ((tags (vec))
+        (methods (vec))
+        (field-specs (vec))
+        (field-vars (vec))
+        (key-params (vec))
+        (params (vec))
+        (method-adds (vec))
+        (seen-methods (make-hash))
+        (fields-len (length fields))
+        (doc (str))
+        (doc-exp (str))
+        (idx-start 0)
+        (struct-type (gensym))
+        (attrib)
+        (impl))
+    (if (and (> fields-len 1)(string? (vec-nth fields 0)))
+        (let ((doc-split (str-splitn 2 "Section:" (vec-nth fields 0))))
+          (set! doc (vec-nth doc-split 0))
+          (set! idx-start 1)
+          (if (= 2 (length doc-split)) (set! doc-exp (vec-nth doc-split 1))(set! doc-exp (str)))))
+    (if (not (str-contains "Example:" doc-exp)) (str-push! doc-exp "\nExample:\n"))
+
+    (set! attrib
+          (fn (field doc doc-exp)
+              (let ((second (cadr field))
+                    (fdoc (str)))
+                (if (string? second)
+                    (let ((doc-split (str-splitn 2 "Example:" second)))
+                      (set! fdoc (str "\n\t" (vec-nth doc-split 0)))
+                      (if (= 2 (length doc-split)) (str-push! doc-exp (vec-nth doc-split 1)))
+                      (xdr! field (cddr field))))
+                (if (> (length field) 3) (err "ERROR: invalid attribute bindings on defstruct"))
+                (let ((param (car field))
+                      (binding (if (> (length field) 1) (cadr field) nil)))
+                  (vec-push! params param)
+                  (vec-push! field-vars `(,param nil))
+                  (vec-push! key-params `(,param ,binding))
+                  (if (< (length field) 3)
+                      (do
+                       (str-push! doc "attribute: " param " private" fdoc "\n")
+                       (vec-push! field-specs param))
+                      (let ((perm (caddr field)))
+                        (if (= perm :rw) (str-push! doc "attribute: " param " read/write" fdoc "\n")
+                            (= perm :ro) (str-push! doc "attribute: " param " read" fdoc "\n")
+                            (= perm :wo) (str-push! doc "attribute: " param " write" fdoc "\n")
+                            (err "defstruct: invalid field access key (valid are :rw, :ro and :wo)"))
+                        (vec-push! field-specs `(,param ,perm))))))))
+
+    (set! impl
+          (fn (field doc)
+              (if (not (not field))
+                  (do
+                   (str-push! doc "impl " (car field) "\n")
+                   (apply (car field) methods tags nil)
+                   (recur (cdr field) doc)))))
+
+    ((fn (idx)
+         (if (< idx fields-len)
+             (let ((field (vec-nth fields idx)))
+               (if (= (car field) :fn) (method (cdr field) methods tags doc doc-exp)
+                   (= (car field) :impl) nil ; do impls last (struct methods take precident).
+                   (attrib field doc doc-exp))
+               (recur (+ idx 1)))))idx-start)
+
+    ((fn (idx)
+         (if (< idx fields-len)
+             (let ((field (vec-nth fields idx)))
+               (if (= (car field) :impl) (impl (cdr field) doc))
+               (recur (+ idx 1)))))idx-start)
+
+    ; The first method for a message wins (the struct's own before traits).
+    ((fn (idx)
+         (if (< idx (length methods))
+             (let ((m (vec-nth methods idx)))
+               (if (not (hash-haskey seen-methods (car m)))
+                   (do
+                    (hash-set! seen-methods (car m) #t)
+                    (vec-push! method-adds `(root::struct-add-method ,struct-type ,(car m) ,(cadr m)))))
+               (recur (+ idx 1))))) 0)
+
+    (let ((doc-final (str)))
+      (str-push! doc-final doc (if (> (length doc-exp) 0) (str "\nSection:" doc-exp)""))
+      `(def ,name ,doc-final
+         (let ((,struct-type (root::make-struct-type ',name ',field-specs ',tags)))
+           ; The methods capture the fields, they are bound to an instance's when called.
+           (let (,@field-vars) nil ,@method-adds)
+           (fn ,(if (> (length params) 0) `(&key ,@key-params) '())
+               (root::make-struct ,struct-type ,@params)))))))
 
 ; Due to the bootstrap order of std lib files can not use the ns-export macro at this point.
 (def *ns-exports* (vec 'deftrait 'defstruct))
@@ -1,8 +1,223 @@
 use crate::builtins::expand_macro;
+use crate::bytecode::LambdaCode;
 use crate::environment::*;
 use crate::symbols::*;
 use crate::types::*;
 
+#[derive(Clone, Copy, PartialEq)]
+enum ParamState {
+    Required,
+    Optional,
+    Rest,
+    Key,
+}
+
+// Parse an &optional or &key param, name or (name default supplied-p).
+fn make_opt_param(param: &Expression, syms: &mut Symbols) -> Result<OptParam, LispError> {
+    let mut parts: Vec<Expression> = if let ExpEnum::Symbol(_, _) = param.get().data {
+        vec![param.clone()]
+    } else {
+        param.iter().collect()
+    };
+    let mut names = Vec::new();
+    for (i, part) in parts.iter().enumerate() {
+        if i == 1 {
+            continue;
+        }
+        if let ExpEnum::Symbol(s, _) = part.get().data {
+            if s.starts_with('&') {
+                return Err(LispError::new(format!("fn: {} out of place", s)));
+            }
+            names.push(s);
+        } else {
+            return Err(LispError::new(format!(
+                "fn: optional parameters must be a symbol or (symbol default supplied-p), got {}",
+                param
+            )));
+        }
+    }
+    if names.is_empty() || parts.len() > 3 {
+        return Err(LispError::new(format!(
+            "fn: optional parameters must be a symbol or (symbol default supplied-p), got {}",
+            param
+        )));
+    }
+    for name in &names {
+        syms.insert(name);
+    }
+    let default = if parts.len() > 1 {
+        // Copy for the same reason as the body.
+        Some(parts.remove(1).copy())
+    } else {
+        None
+    };
+    Ok(OptParam {
+        name: names[0],
+        default,
+        supplied: names.get(1).copied(),
+    })
+}
+
+fn pattern_error(pattern: &Expression) -> LispError {
+    LispError::new(format!(
+        "fn: destructuring patterns must be symbols, lists, vectors or {{}} maps, got {}",
+        pattern
+    ))
+}
+
+// Parse a destructuring pattern, the symbols in it get slots in syms and any
+// :or defaults are added to defaults (to be analyzed).
+fn make_pattern(
+    environment: &mut Environment,
+    pattern: &Expression,
+    syms: &mut Symbols,
+    defaults: &mut Vec<Expression>,
+) -> Result<Pattern, LispError> {
+    let parts: Vec<Expression> = match &pattern.get().data {
+        ExpEnum::Symbol(s, _) => {
+            if s.starts_with('&') || s.starts_with(':') {
+                return Err(LispError::new(format!(
+                    "fn: {} out of place in destructuring pattern",
+                    s
+                )));
+            }
+            return Ok(Pattern::Bind(syms.insert(s)));
+        }
+        ExpEnum::Pair(_, _) | ExpEnum::Vector(_) => pattern.iter().collect(),
+        _ => return Err(pattern_error(pattern)),
+    };
+    if let Some(ExpEnum::Symbol("hash-map", _)) = parts.first().map(|p| p.get().data.clone()) {
+        return make_map_pattern(environment, pattern, &parts[1..], syms, defaults);
+    }
+    let mut items = Vec::new();
+    let mut parts = parts.iter();
+    while let Some(part) = parts.next() {
+        if let ExpEnum::Symbol("&", _) = part.get().data {
+            return match (parts.next(), parts.next()) {
+                (Some(rest), None) => Ok(Pattern::Seq(
+                    items,
+                    Some(Box::new(make_pattern(environment, rest, syms, defaults)?)),
+                )),
+                _ => Err(LispError::new(format!(
+                    "fn: & must be followed by one pattern in {}",
+                    pattern
+                ))),
+            };
+        }
+        items.push(make_pattern(environment, part, syms, defaults)?);
+    }
+    Ok(Pattern::Seq(items, None))
+}
+
+// Parse the key value pairs of a {} pattern.
+fn make_map_pattern(
+    environment: &mut Environment,
+    pattern: &Expression,
+    parts: &[Expression],
+    syms: &mut Symbols,
+    defaults: &mut Vec<Expression>,
+) -> Result<Pattern, LispError> {
+    let mut entries = Vec::new();
+    // Name of each entry that binds a symbol, for :or.
+    let mut names = Vec::new();
+    let mut or_map = None;
+    let mut as_slot = None;
+    for pair in parts.chunks(2) {
+        let (part, key) = if let [part, key] = pair {
+            (part, key)
+        } else {
+            return Err(pattern_error(pattern));
+        };
+        let part_d = part.get();
+        match &part_d.data {
+            ExpEnum::Symbol(":keys", _) => {
+                let key_names: Vec<Expression> = match &key.get().data {
+                    ExpEnum::Pair(_, _) | ExpEnum::Vector(_) => key.iter().collect(),
+                    _ => return Err(pattern_error(pattern)),
+                };
+                for name in key_names {
+                    let name = if let ExpEnum::Symbol(name, _) = name.get().data {
+                        name
+                    } else {
+                        return Err(pattern_error(pattern));
+                    };
+                    let keyword = environment.interner.intern(&format!(":{}", name));
+                    entries.push(MapPattern {
+                        pattern: Pattern::Bind(syms.insert(name)),
+                        keys: vec![keyword, name],
+                        default: None,
+                    });
+                    names.push(Some(name));
+                }
+            }
+            ExpEnum::Symbol(":as", _) => {
+                if let ExpEnum::Symbol(name, _) = key.get().data {
+                    as_slot = Some(syms.insert(name));
+                } else {
+                    return Err(pattern_error(pattern));
+                }
+            }
+            ExpEnum::Symbol(":or", _) => or_map = Some(key.clone()),
+            _ => {
+                drop(part_d);
+                let key_name = match &key.get().data {
+                    ExpEnum::Symbol(k, _) if k.starts_with(':') => *k,
+                    ExpEnum::String(k, _) | ExpEnum::Char(k) => environment.interner.intern(k),
+                    _ => {
+                        return Err(LispError::new(format!(
+                            "fn: destructuring keys must be keywords, strings or chars, got {}",
+                            key
+                        )))
+                    }
+                };
+                names.push(if let ExpEnum::Symbol(name, _) = part.get().data {
+                    Some(name)
+                } else {
+                    None
+                });
+                entries.push(MapPattern {
+                    pattern: make_pattern(environment, part, syms, defaults)?,
+                    keys: vec![key_name],
+                    default: None,
+                });
+            }
+        }
+    }
+    if let Some(or_map) = or_map {
+        let or_parts: Vec<Expression> = or_map.iter().collect();
+        if !matches!(
+            or_parts.first().map(|p| p.get().data.clone()),
+            Some(ExpEnum::Symbol("hash-map", _))
+        ) {
+            return Err(LispError::new(format!(
+                "fn: :or must be a {{}} map of defaults in {}",
+                pattern
+            )));
+        }
+        for pair in or_parts[1..].chunks(2) {
+            let name = if let ExpEnum::Symbol(name, _) = pair[0].get().data {
+                name
+            } else {
+                return Err(pattern_error(pattern));
+            };
+            if let (Some(idx), Some(default)) =
+                (names.iter().position(|n| *n == Some(name)), pair.get(1))
+            {
+                // Copy for the same reason as the body.
+                let default = default.copy();
+                defaults.push(default.clone());
+                entries[idx].default = Some(default);
+            } else {
+                return Err(LispError::new(format!(
+                    "fn: :or default for {} which is not bound in {}",
+                    name, pattern
+                )));
+            }
+        }
+    }
+    Ok(Pattern::Map(entries, as_slot))
+}
+
 pub fn make_fn(
     environment: &mut Environment,
     args: &mut dyn Iterator<Item = Expression>,
@@ -43,36 +258,81 @@ pub fn make_fn(
         let mut has_rest = false;
         let mut num_params = 0;
         let mut num_post_rest = 0;
+        let mut optional = Vec::new();
+        let mut keys = Vec::new();
+        let mut state = ParamState::Required;
+        let mut pattern_params = Vec::new();
         for p in params_exp.iter() {
-            if let ExpEnum::Symbol(s, _) = p.get().data {
-                params.push(s);
-                if s == "&rest" {
-                    if has_rest {
-                        return Err(LispError::new("fn: &rest can only appear once"));
-                    }
+            let sym = if let ExpEnum::Symbol(s, _) = p.get().data {
+                Some(s)
+            } else {
+                None
+            };
+            match (sym, state) {
+                (Some("&optional"), ParamState::Required) => state = ParamState::Optional,
+                (Some("&optional"), _) => {
+                    return Err(LispError::new(
+                        "fn: &optional can only appear once and before &rest and &key",
+                    ))
+                }
+                (Some("&rest"), ParamState::Required) | (Some("&rest"), ParamState::Optional) => {
+                    params.push("&rest");
                     has_rest = true;
-                } else {
+                    state = ParamState::Rest;
+                }
+                (Some("&rest"), _) => {
+                    return Err(LispError::new(
+                        "fn: &rest can only appear once and before &key",
+                    ))
+                }
+                (Some("&key"), ParamState::Key) => {
+                    return Err(LispError::new("fn: &key can only appear once"))
+                }
+                (Some("&key"), _) => state = ParamState::Key,
+                (_, ParamState::Optional) => optional.push(make_opt_param(&p, &mut syms)?),
+                (_, ParamState::Key) => keys.push(make_opt_param(&p, &mut syms)?),
+                (Some(s), _) => {
+                    params.push(s);
                     syms.insert(s);
                     num_params += 1;
                     if has_rest {
                         num_post_rest += 1;
                     }
                 }
-            } else {
-                return Err(LispError::new(format!(
-                    "fn: parameters must be symbols, got {}",
-                    p
-                )));
+                (None, ParamState::Required) | (None, ParamState::Rest) => {
+                    // A destructured param has no name so it gets an anonymous
+                    // slot, the pattern's symbols get slots after this-fn.
+                    params.push("");
+                    pattern_params.push((syms.insert_anon(), p.clone()));
+                    num_params += 1;
+                    if has_rest {
+                        num_post_rest += 1;
+                    }
+                }
             }
         }
         if has_rest && num_post_rest != 1 {
             return Err(LispError::new(
-                "fn: &rest must be before the last parameter",
+                "fn: &rest must be before the last parameter (or &key)",
             ));
         }
         if !no_recur {
             syms.insert("this-fn");
         }
+        let mut patterns = Vec::new();
+        let mut pattern_defaults = Vec::new();
+        for (slot, text) in pattern_params {
+            let pattern = make_pattern(environment, &text, &mut syms, &mut pattern_defaults)?;
+            patterns.push((slot, text, pattern));
+        }
+        for param in optional.iter().chain(keys.iter()) {
+            if let Some(default) = &param.default {
+                analyze(environment, default, &mut Some(syms.clone()))?;
+            }
+        }
+        for default in &pattern_defaults {
+            analyze(environment, default, &mut Some(syms.clone()))?;
+        }
         match &body {
             MultiExpression::None => {}
             MultiExpression::Single(arg) => analyze(environment, &arg, &mut Some(syms.clone()))?,
@@ -86,10 +346,15 @@ pub fn make_fn(
             params,
             num_params,
             has_rest,
+            optional,
+            keys,
+            patterns,
             body,
             syms,
             namespace: environment.namespace.clone(),
             no_recur,
+            code: LambdaCode::new_shared(),
+            name: None,
         });
     }
     Err(LispError::new("fn: needs at least one form"))
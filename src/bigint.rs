@@ -0,0 +1,709 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, BitAnd, BitOr, BitXor, Mul, Neg, Not, Sub};
+
+/// An arbitrary precision integer, used when an Int overflows.
+/// The magnitude is little endian base 2^32 with no high zero limbs (so zero
+/// is an empty magnitude and never negative).
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BigInt {
+    negative: bool,
+    mag: Vec<u32>,
+}
+
+/// Largest result (in bits) checked_pow and checked_shl will compute, about
+/// 300,000 digits.
+pub const MAX_POW_BITS: u64 = 1 << 20;
+
+fn trim(mag: &mut Vec<u32>) {
+    while let Some(0) = mag.last() {
+        mag.pop();
+    }
+}
+
+fn cmp_mag(a: &[u32], b: &[u32]) -> Ordering {
+    if a.len() != b.len() {
+        return a.len().cmp(&b.len());
+    }
+    for (x, y) in a.iter().rev().zip(b.iter().rev()) {
+        if x != y {
+            return x.cmp(y);
+        }
+    }
+    Ordering::Equal
+}
+
+fn add_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (a, b) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    let mut res = Vec::with_capacity(a.len() + 1);
+    let mut carry = 0u64;
+    for (i, x) in a.iter().enumerate() {
+        let sum = *x as u64 + *b.get(i).unwrap_or(&0) as u64 + carry;
+        res.push(sum as u32);
+        carry = sum >> 32;
+    }
+    if carry > 0 {
+        res.push(carry as u32);
+    }
+    res
+}
+
+// a - b, a must be at least as large as b.
+fn sub_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut res = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (i, x) in a.iter().enumerate() {
+        let mut diff = *x as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        borrow = 0;
+        if diff < 0 {
+            diff += 1 << 32;
+            borrow = 1;
+        }
+        res.push(diff as u32);
+    }
+    trim(&mut res);
+    res
+}
+
+fn mul_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    if a.is_empty() || b.is_empty() {
+        return Vec::new();
+    }
+    let mut res = vec![0u32; a.len() + b.len()];
+    for (i, x) in a.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, y) in b.iter().enumerate() {
+            let prod = *x as u64 * *y as u64 + res[i + j] as u64 + carry;
+            res[i + j] = prod as u32;
+            carry = prod >> 32;
+        }
+        res[i + b.len()] = carry as u32;
+    }
+    trim(&mut res);
+    res
+}
+
+// Multiply in place by a small value and add a small value.
+fn mul_add_small(mag: &mut Vec<u32>, mul: u32, add: u32) {
+    let mut carry = add as u64;
+    for limb in mag.iter_mut() {
+        let val = *limb as u64 * mul as u64 + carry;
+        *limb = val as u32;
+        carry = val >> 32;
+    }
+    if carry > 0 {
+        mag.push(carry as u32);
+    }
+}
+
+// Divide in place by a small value, returns the remainder.
+fn div_small(mag: &mut Vec<u32>, div: u32) -> u32 {
+    let mut rem = 0u64;
+    for limb in mag.iter_mut().rev() {
+        let cur = (rem << 32) | *limb as u64;
+        *limb = (cur / div as u64) as u32;
+        rem = cur % div as u64;
+    }
+    trim(mag);
+    rem as u32
+}
+
+fn shl_bits(mag: &[u32], shift: u32) -> Vec<u32> {
+    if shift == 0 {
+        return mag.to_vec();
+    }
+    let mut res = Vec::with_capacity(mag.len() + 1);
+    let mut carry = 0u32;
+    for limb in mag {
+        res.push((limb << shift) | carry);
+        carry = limb >> (32 - shift);
+    }
+    res.push(carry);
+    res
+}
+
+fn shr_bits(mag: &[u32], shift: u32) -> Vec<u32> {
+    let mut res = mag.to_vec();
+    if shift > 0 {
+        for i in 0..res.len() {
+            let high = res.get(i + 1).map_or(0, |h| h << (32 - shift));
+            res[i] = (res[i] >> shift) | high;
+        }
+    }
+    trim(&mut res);
+    res
+}
+
+// Long division (Knuth's algorithm D), b must not be zero.
+fn div_rem_mag(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if cmp_mag(a, b) == Ordering::Less {
+        return (Vec::new(), a.to_vec());
+    }
+    if b.len() == 1 {
+        let mut quot = a.to_vec();
+        let rem = div_small(&mut quot, b[0]);
+        let rem = if rem == 0 { Vec::new() } else { vec![rem] };
+        return (quot, rem);
+    }
+    let shift = b[b.len() - 1].leading_zeros();
+    let div = shl_bits(b, shift);
+    let n = b.len();
+    let div = &div[..n];
+    let mut rem = shl_bits(a, shift);
+    if rem.len() == a.len() {
+        rem.push(0);
+    }
+    let m = a.len() - n;
+    let mut quot = vec![0u32; m + 1];
+    let base = 1u64 << 32;
+    for j in (0..=m).rev() {
+        let num = ((rem[j + n] as u64) << 32) | rem[j + n - 1] as u64;
+        let mut qhat = num / div[n - 1] as u64;
+        let mut rhat = num % div[n - 1] as u64;
+        while qhat >= base || qhat * div[n - 2] as u64 > ((rhat << 32) | rem[j + n - 2] as u64) {
+            qhat -= 1;
+            rhat += div[n - 1] as u64;
+            if rhat >= base {
+                break;
+            }
+        }
+        let mut borrow = 0i64;
+        let mut carry = 0u64;
+        for i in 0..n {
+            let prod = qhat * div[i] as u64 + carry;
+            carry = prod >> 32;
+            let diff = rem[i + j] as i64 - borrow - (prod & 0xffff_ffff) as i64;
+            rem[i + j] = diff as u32;
+            borrow = if diff < 0 { 1 } else { 0 };
+        }
+        let diff = rem[j + n] as i64 - borrow - carry as i64;
+        rem[j + n] = diff as u32;
+        if diff < 0 {
+            // Went one too far, add the divisor back.
+            qhat -= 1;
+            let mut carry = 0u64;
+            for i in 0..n {
+                let sum = rem[i + j] as u64 + div[i] as u64 + carry;
+                rem[i + j] = sum as u32;
+                carry = sum >> 32;
+            }
+            rem[j + n] = rem[j + n].wrapping_add(carry as u32);
+        }
+        quot[j] = qhat as u32;
+    }
+    trim(&mut quot);
+    (quot, shr_bits(&rem[..n], shift))
+}
+
+impl BigInt {
+    fn new(negative: bool, mut mag: Vec<u32>) -> BigInt {
+        trim(&mut mag);
+        BigInt {
+            negative: negative && !mag.is_empty(),
+            mag,
+        }
+    }
+
+    pub fn from_i64(i: i64) -> BigInt {
+        let abs = i.unsigned_abs();
+        BigInt::new(i < 0, vec![abs as u32, (abs >> 32) as u32])
+    }
+
+    /// The value as an i64 if it fits.
+    pub fn to_i64(&self) -> Option<i64> {
+        if self.mag.len() > 2 {
+            return None;
+        }
+        let abs = self.mag.first().map_or(0, |l| *l as u64)
+            | self.mag.get(1).map_or(0, |h| (*h as u64) << 32);
+        if self.negative {
+            if abs <= i64::MAX as u64 + 1 {
+                Some((abs as i64).wrapping_neg())
+            } else {
+                None
+            }
+        } else if abs <= i64::MAX as u64 {
+            Some(abs as i64)
+        } else {
+            None
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        let mut res = 0.0;
+        for limb in self.mag.iter().rev() {
+            res = res * 4_294_967_296.0 + *limb as f64;
+        }
+        if self.negative {
+            -res
+        } else {
+            res
+        }
+    }
+
+    /// Parse an optionally signed integer in radix (2 to 36), None if not valid.
+    pub fn parse(s: &str, radix: u32) -> Option<BigInt> {
+        let (negative, digits) = match s.as_bytes().first() {
+            Some(b'-') => (true, &s[1..]),
+            Some(b'+') => (false, &s[1..]),
+            _ => (false, s),
+        };
+        if digits.is_empty() {
+            return None;
+        }
+        let mut mag = Vec::new();
+        for ch in digits.chars() {
+            mul_add_small(&mut mag, radix, ch.to_digit(radix)?);
+        }
+        Some(BigInt::new(negative, mag))
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.mag.is_empty()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn abs(&self) -> BigInt {
+        BigInt::new(false, self.mag.clone())
+    }
+
+    /// Truncating division and remainder (like i64 / and %), None if other is 0.
+    pub fn div_rem(&self, other: &BigInt) -> Option<(BigInt, BigInt)> {
+        if other.is_zero() {
+            return None;
+        }
+        let (quot, rem) = div_rem_mag(&self.mag, &other.mag);
+        Some((
+            BigInt::new(self.negative != other.negative, quot),
+            BigInt::new(self.negative, rem),
+        ))
+    }
+
+    /// Greatest common divisor, never negative.
+    pub fn gcd(&self, other: &BigInt) -> BigInt {
+        let mut a = self.abs();
+        let mut b = other.abs();
+        while let Some((_, rem)) = a.div_rem(&b) {
+            a = b;
+            b = rem;
+        }
+        a
+    }
+
+    /// Format as digits in radix (2 to 36), digits past 9 are lower case.
+    pub fn to_str_radix(&self, radix: u32) -> String {
+        if self.is_zero() {
+            return "0".to_string();
+        }
+        let mut mag = self.mag.clone();
+        let mut digits = Vec::new();
+        while !mag.is_empty() {
+            digits.push(std::char::from_digit(div_small(&mut mag, radix), radix).unwrap_or('?'));
+        }
+        if self.negative {
+            digits.push('-');
+        }
+        digits.iter().rev().collect()
+    }
+
+    /// Number of bits in the magnitude (zero for zero).
+    pub fn bits(&self) -> u64 {
+        match self.mag.last() {
+            Some(high) => self.mag.len() as u64 * 32 - high.leading_zeros() as u64,
+            None => 0,
+        }
+    }
+
+    /// self to the power exp, None if the result would be over MAX_POW_BITS.
+    pub fn checked_pow(&self, exp: u32) -> Option<BigInt> {
+        let bits = self.bits();
+        if bits > 1 && (bits - 1) * exp as u64 > MAX_POW_BITS {
+            None
+        } else {
+            Some(self.pow(exp))
+        }
+    }
+
+    /// self * 2^count, None if the result would be over MAX_POW_BITS.
+    pub fn checked_shl(&self, count: u32) -> Option<BigInt> {
+        if self.is_zero() {
+            Some(self.clone())
+        } else if self.bits() + count as u64 > MAX_POW_BITS {
+            None
+        } else {
+            let mut mag = vec![0u32; (count / 32) as usize];
+            mag.extend(shl_bits(&self.mag, count % 32));
+            Some(BigInt::new(self.negative, mag))
+        }
+    }
+
+    /// Arithmetic shift right (floor of self / 2^count), the sign is kept.
+    pub fn shr(&self, count: u32) -> BigInt {
+        let limbs = (count / 32) as usize;
+        let shift = count % 32;
+        if limbs >= self.mag.len() {
+            return BigInt::from_i64(if self.negative { -1 } else { 0 });
+        }
+        let res = BigInt::new(self.negative, shr_bits(&self.mag[limbs..], shift));
+        // A negative value rounds down if any set bits were shifted out.
+        let lost =
+            self.mag[..limbs].iter().any(|l| *l != 0) || self.mag[limbs] & ((1 << shift) - 1) != 0;
+        if self.negative && lost {
+            &res - &BigInt::from_i64(1)
+        } else {
+            res
+        }
+    }
+
+    /// Number of set bits in the magnitude.
+    pub fn count_ones(&self) -> u64 {
+        self.mag.iter().map(|l| l.count_ones() as u64).sum()
+    }
+
+    // Two's complement limbs sign extended to len limbs, -m is !(m - 1).
+    fn to_twos(&self, len: usize) -> Vec<u32> {
+        let mut limbs = self.mag.clone();
+        limbs.resize(len, 0);
+        if self.negative {
+            let mut borrow = 1;
+            for limb in limbs.iter_mut() {
+                let (val, under) = limb.overflowing_sub(borrow);
+                *limb = !val;
+                borrow = under as u32;
+            }
+        }
+        limbs
+    }
+
+    // Inverse of to_twos, the high bit of the last limb is the sign.
+    fn from_twos(mut limbs: Vec<u32>) -> BigInt {
+        let negative = limbs.last().is_some_and(|h| h & 0x8000_0000 != 0);
+        if negative {
+            let mut carry = 1;
+            for limb in limbs.iter_mut() {
+                let (val, over) = (!*limb).overflowing_add(carry);
+                *limb = val;
+                carry = over as u32;
+            }
+        }
+        BigInt::new(negative, limbs)
+    }
+
+    // Apply f to the limbs of self and other as (infinite) two's complement.
+    fn bitwise(&self, other: &BigInt, f: fn(u32, u32) -> u32) -> BigInt {
+        let len = self.mag.len().max(other.mag.len()) + 1;
+        let limbs = self
+            .to_twos(len)
+            .iter()
+            .zip(other.to_twos(len).iter())
+            .map(|(a, b)| f(*a, *b))
+            .collect();
+        BigInt::from_twos(limbs)
+    }
+
+    pub fn pow(&self, mut exp: u32) -> BigInt {
+        let mut base = self.clone();
+        let mut res = BigInt::from_i64(1);
+        while exp > 0 {
+            if exp & 1 == 1 {
+                res = &res * &base;
+            }
+            exp >>= 1;
+            if exp > 0 {
+                base = &base * &base;
+            }
+        }
+        res
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &BigInt) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_mag(&self.mag, &other.mag),
+            (true, true) => cmp_mag(&other.mag, &self.mag),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &BigInt) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Add for &BigInt {
+    type Output = BigInt;
+
+    fn add(self, other: &BigInt) -> BigInt {
+        if self.negative == other.negative {
+            BigInt::new(self.negative, add_mag(&self.mag, &other.mag))
+        } else if cmp_mag(&self.mag, &other.mag) == Ordering::Less {
+            BigInt::new(other.negative, sub_mag(&other.mag, &self.mag))
+        } else {
+            BigInt::new(self.negative, sub_mag(&self.mag, &other.mag))
+        }
+    }
+}
+
+impl Sub for &BigInt {
+    type Output = BigInt;
+
+    fn sub(self, other: &BigInt) -> BigInt {
+        self + &-other
+    }
+}
+
+impl Mul for &BigInt {
+    type Output = BigInt;
+
+    fn mul(self, other: &BigInt) -> BigInt {
+        BigInt::new(
+            self.negative != other.negative,
+            mul_mag(&self.mag, &other.mag),
+        )
+    }
+}
+
+impl Neg for &BigInt {
+    type Output = BigInt;
+
+    fn neg(self) -> BigInt {
+        BigInt::new(!self.negative, self.mag.clone())
+    }
+}
+
+impl BitAnd for &BigInt {
+    type Output = BigInt;
+
+    fn bitand(self, other: &BigInt) -> BigInt {
+        self.bitwise(other, |a, b| a & b)
+    }
+}
+
+impl BitOr for &BigInt {
+    type Output = BigInt;
+
+    fn bitor(self, other: &BigInt) -> BigInt {
+        self.bitwise(other, |a, b| a | b)
+    }
+}
+
+impl BitXor for &BigInt {
+    type Output = BigInt;
+
+    fn bitxor(self, other: &BigInt) -> BigInt {
+        self.bitwise(other, |a, b| a ^ b)
+    }
+}
+
+impl Not for &BigInt {
+    type Output = BigInt;
+
+    // !n is -n - 1 in two's complement.
+    fn not(self) -> BigInt {
+        &-self - &BigInt::from_i64(1)
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_zero() {
+            return f.write_str("0");
+        }
+        // Peel off nine decimal digits at a time.
+        let mut mag = self.mag.clone();
+        let mut chunks = Vec::new();
+        while !mag.is_empty() {
+            chunks.push(div_small(&mut mag, 1_000_000_000));
+        }
+        if self.negative {
+            f.write_str("-")?;
+        }
+        write!(f, "{}", chunks.pop().unwrap_or(0))?;
+        for chunk in chunks.iter().rev() {
+            write!(f, "{:09}", chunk)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn big(s: &str) -> BigInt {
+        BigInt::parse(s, 10).unwrap()
+    }
+
+    #[test]
+    fn test_i64_round_trip() {
+        for i in &[0, 1, -1, i64::MAX, i64::MIN, 1 << 32, -(1 << 32)] {
+            let b = BigInt::from_i64(*i);
+            assert_eq!(b.to_i64(), Some(*i));
+            assert_eq!(b.to_string(), i.to_string());
+        }
+        assert_eq!(
+            (&BigInt::from_i64(i64::MAX) + &BigInt::from_i64(1)).to_i64(),
+            None
+        );
+        assert_eq!(
+            (&BigInt::from_i64(i64::MIN) - &BigInt::from_i64(1)).to_i64(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_arith() {
+        let a = big("123456789012345678901234567890");
+        let b = big("-987654321098765432109876543210");
+        assert_eq!((&a + &b).to_string(), "-864197532086419753208641975320");
+        assert_eq!((&a - &b).to_string(), "1111111110111111111011111111100");
+        assert_eq!(
+            (&a * &b).to_string(),
+            "-121932631137021795226185032733622923332237463801111263526900"
+        );
+        assert_eq!(
+            BigInt::from_i64(2).pow(100).to_string(),
+            "1267650600228229401496703205376"
+        );
+        assert!(b < a);
+        assert!(&b + &b < b);
+    }
+
+    #[test]
+    fn test_checked_pow() {
+        assert_eq!(BigInt::from_i64(0).bits(), 0);
+        assert_eq!(BigInt::from_i64(-1).bits(), 1);
+        assert_eq!(big("4294967296").bits(), 33);
+        assert_eq!(
+            BigInt::from_i64(3).checked_pow(40),
+            Some(BigInt::from_i64(3).pow(40))
+        );
+        assert!(BigInt::from_i64(3).checked_pow(4_000_000_000).is_none());
+        assert_eq!(
+            BigInt::from_i64(-1).checked_pow(4_000_000_001),
+            Some(BigInt::from_i64(-1))
+        );
+    }
+
+    #[test]
+    fn test_shifts() {
+        let n = big("-123456789012345678901234567890");
+        for count in &[0, 1, 31, 32, 33, 64, 100] {
+            let pow = BigInt::from_i64(2).pow(*count);
+            assert_eq!(n.checked_shl(*count), Some(&n * &pow));
+            assert_eq!(n.checked_shl(*count).unwrap().shr(*count), n);
+            // Floored, so -7 >> 1 is -4 like i64.
+            let (quot, rem) = n.div_rem(&pow).unwrap();
+            let floor = if rem.is_zero() {
+                quot
+            } else {
+                &quot - &BigInt::from_i64(1)
+            };
+            assert_eq!(n.shr(*count), floor);
+        }
+        assert_eq!(BigInt::from_i64(-7).shr(1), BigInt::from_i64(-4));
+        assert_eq!(BigInt::from_i64(-1).shr(u32::MAX), BigInt::from_i64(-1));
+        assert_eq!(n.abs().shr(u32::MAX), BigInt::from_i64(0));
+        assert_eq!(BigInt::from_i64(0).checked_shl(u32::MAX), Some(big("0")));
+        assert!(BigInt::from_i64(1).checked_shl(u32::MAX).is_none());
+        assert_eq!(
+            BigInt::from_i64(1).checked_shl(100_000).map(|b| b.bits()),
+            Some(100_001)
+        );
+    }
+
+    #[test]
+    fn test_bitwise() {
+        // Should agree with i64 wherever both fit.
+        let vals = [
+            0,
+            1,
+            -1,
+            12,
+            -12,
+            i64::MAX,
+            i64::MIN,
+            1 << 32,
+            -(1 << 32) - 1,
+        ];
+        for a in &vals {
+            assert_eq!((!&BigInt::from_i64(*a)).to_i64(), Some(!a));
+            for b in &vals {
+                let (x, y) = (BigInt::from_i64(*a), BigInt::from_i64(*b));
+                assert_eq!((&x & &y).to_i64(), Some(a & b));
+                assert_eq!((&x | &y).to_i64(), Some(a | b));
+                assert_eq!((&x ^ &y).to_i64(), Some(a ^ b));
+            }
+        }
+        let n = big("-18446744073709551617");
+        assert_eq!(
+            (&n & &big("18446744073709551615")).to_string(),
+            "18446744073709551615"
+        );
+        assert_eq!((&n | &BigInt::from_i64(1)), n);
+        assert_eq!((&n ^ &n), big("0"));
+        assert_eq!(!&n, big("18446744073709551616"));
+        assert_eq!(big("18446744073709551615").count_ones(), 64);
+    }
+
+    #[test]
+    fn test_div_rem() {
+        let a = big("121932631137021795226185032733622923332237463801111263526901");
+        let b = big("-987654321098765432109876543210");
+        let (q, r) = a.div_rem(&b).unwrap();
+        assert_eq!(q.to_string(), "-123456789012345678901234567890");
+        assert_eq!(r.to_string(), "1");
+        let (q, r) = big("-18446744073709551617")
+            .div_rem(&big("4294967296"))
+            .unwrap();
+        assert_eq!(q.to_string(), "-4294967296");
+        assert_eq!(r.to_string(), "-1");
+        assert!(a.div_rem(&big("0")).is_none());
+        // Every quotient and remainder should put the dividend back together.
+        let n = BigInt::from_i64(3).pow(200);
+        let mut d = BigInt::from_i64(7).pow(40);
+        for _ in 0..20 {
+            let (q, r) = n.div_rem(&d).unwrap();
+            assert_eq!(&(&q * &d) + &r, n);
+            assert!(r.abs() < d.abs());
+            d = &d * &BigInt::from_i64(-65537);
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(BigInt::parse("ff", 16).unwrap().to_string(), "255");
+        assert_eq!(BigInt::parse("-1_0", 10), None);
+        assert_eq!(BigInt::parse("-", 10), None);
+        assert_eq!(big("-000").to_string(), "0");
+        assert_eq!(
+            big("1000000000000000000000").to_string(),
+            "1000000000000000000000"
+        );
+        assert_eq!(
+            big("-18446744073709551616").to_f64(),
+            -18446744073709551616.0
+        );
+    }
+
+    #[test]
+    fn test_to_str_radix() {
+        assert_eq!(BigInt::from_i64(255).to_str_radix(16), "ff");
+        assert_eq!(BigInt::from_i64(-5).to_str_radix(2), "-101");
+        assert_eq!(BigInt::from_i64(0).to_str_radix(8), "0");
+        let n = big("-123456789012345678901234567890");
+        for radix in 2..=36 {
+            assert_eq!(
+                BigInt::parse(&n.to_str_radix(radix), radix),
+                Some(n.clone())
+            );
+        }
+    }
+}
@@ -11,10 +11,14 @@ use std::str::from_utf8;
 use unicode_segmentation::UnicodeSegmentation;
 
 use crate::backquote::*;
+use crate::builtins_math::{parse_list_of_numbers, Number};
+use crate::builtins_struct::struct_call;
 use crate::builtins_util::*;
+use crate::bytecode::*;
 use crate::config::VERSION_STRING;
 use crate::environment::*;
 use crate::eval::*;
+use crate::gc::*;
 use crate::interner::*;
 use crate::pretty_print::*;
 use crate::reader::*;
@@ -63,6 +67,7 @@ fn apply_fn_call(
                         environment.supress_eval = old_sup;
                         ret
                     }
+                    ExpEnum::Struct(_, _) => struct_call(environment, &exp, args.collect()),
                     ExpEnum::Lambda(_) => {
                         if environment.allow_lazy_fn {
                             //                        make_lazy(environment, exp.clone(), args)
@@ -109,6 +114,7 @@ fn apply_fn_call(
             environment.supress_eval = old_sup;
             ret
         }
+        ExpEnum::Struct(_, _) => struct_call(environment, &command, args.collect()),
         _ => {
             let msg = format!(
                 "Called an invalid command {}, type {}.",
@@ -174,17 +180,72 @@ fn builtin_unwind_protect(
     }
 }
 
+/// Rebuild a LispError from an error returned by get-error (so it can be re-raised).
+pub fn error_from_exp(
+    environment: &mut Environment,
+    error: &Expression,
+) -> Result<Option<LispError>, LispError> {
+    let parts: Vec<Expression> = error.iter().collect();
+    let is_error = match parts.first() {
+        Some(tag) => matches!(&tag.get().data, ExpEnum::Symbol(":error", _)),
+        None => false,
+    };
+    if !is_error || parts.len() < 2 || !is_proper_list(error) {
+        return Ok(None);
+    }
+    let mut err = LispError::new(parts[1].as_string(environment)?);
+    if let Some(kind) = parts.get(3) {
+        if let ExpEnum::Symbol(sym, _) = &kind.get().data {
+            err.kind = Some(sym);
+        }
+    }
+    if let Some(data) = parts.get(4) {
+        if !data.is_nil() {
+            err.data = Some(data.clone());
+        }
+    }
+    if let Some(location) = parts.get(5) {
+        if let ExpEnum::Vector(loc) = &location.get().data {
+            if let [file, line, col] = &loc[..] {
+                err.location = Some(ExpMeta {
+                    file: environment.interner.intern(&file.as_string(environment)?),
+                    line: line.make_int(environment)? as usize,
+                    col: col.make_int(environment)? as usize,
+                });
+            }
+        }
+    }
+    Ok(Some(err))
+}
+
 fn builtin_err(
     environment: &mut Environment,
     args: &mut dyn Iterator<Item = Expression>,
 ) -> Result<Expression, LispError> {
-    if let Some(arg) = args.next() {
-        if args.next().is_none() {
-            let arg = eval(environment, arg)?;
-            return Err(LispError::new(arg.as_string(environment)?));
+    let first = param_eval(environment, args, "err")?;
+    let kind = if let ExpEnum::Symbol(sym, _) = &first.get().data {
+        if sym.starts_with(':') {
+            Some(*sym)
+        } else {
+            None
         }
+    } else {
+        None
+    };
+    if let Some(kind) = kind {
+        let msg = param_eval(environment, args, "err")?;
+        let mut err = LispError::with_kind(kind, msg.as_string(environment)?);
+        if let Some(data) = args.next() {
+            err.data = Some(eval(environment, data)?);
+        }
+        params_done(args, "err")?;
+        return Err(err);
+    }
+    params_done(args, "err")?;
+    if let Some(err) = error_from_exp(environment, &first)? {
+        return Err(err);
     }
-    Err(LispError::new("err can only have one form"))
+    Err(LispError::new(first.as_string(environment)?))
 }
 
 pub fn load(environment: &mut Environment, file_name: &str) -> Result<Expression, LispError> {
@@ -366,6 +427,7 @@ fn builtin_length(
                 }
                 ExpEnum::Nil => Ok(Expression::alloc_data(ExpEnum::Int(0))),
                 ExpEnum::HashMap(map) => Ok(Expression::alloc_data(ExpEnum::Int(map.len() as i64))),
+                ExpEnum::Set(set) => Ok(Expression::alloc_data(ExpEnum::Int(set.len() as i64))),
                 _ => Err(LispError::new(format!(
                     "expression of type {} has no length",
                     arg.display_type()
@@ -384,8 +446,7 @@ fn builtin_if(
     let mut next_arg = args.next();
     while let Some(arg) = next_arg {
         if args.peek().is_some() {
-            let cond = eval(environment, arg)?;
-            if cond.is_falsy() {
+            if eval_condition(environment, arg, true)?.is_falsy() {
                 args.next();
             } else {
                 return eval_nr(environment, args.next().unwrap());
@@ -603,13 +664,31 @@ pub fn builtin_bquote(
     Err(LispError::new("back-quote: takes one form"))
 }
 
+// Eval arg, if is_condition then as a condition (a failed command is not an
+// error with *errexit*).  The last form of an and/or is not a condition.
+fn eval_condition(
+    environment: &mut Environment,
+    arg: Expression,
+    is_condition: bool,
+) -> Result<Expression, LispError> {
+    if is_condition {
+        environment.in_condition += 1;
+        let res = eval(environment, arg);
+        environment.in_condition -= 1;
+        res
+    } else {
+        eval(environment, arg)
+    }
+}
+
 fn builtin_and(
     environment: &mut Environment,
     args: &mut dyn Iterator<Item = Expression>,
 ) -> Result<Expression, LispError> {
+    let mut args = args.peekable();
     let mut last_exp = None;
-    for arg in args {
-        let arg = eval(environment, arg)?;
+    while let Some(arg) = args.next() {
+        let arg = eval_condition(environment, arg, args.peek().is_some())?;
         if arg.is_falsy() {
             return Ok(Expression::make_false());
         } else {
@@ -623,8 +702,9 @@ fn builtin_or(
     environment: &mut Environment,
     args: &mut dyn Iterator<Item = Expression>,
 ) -> Result<Expression, LispError> {
-    for arg in args {
-        let arg = eval(environment, arg)?;
+    let mut args = args.peekable();
+    while let Some(arg) = args.next() {
+        let arg = eval_condition(environment, arg, args.peek().is_some())?;
         if !arg.is_falsy() {
             return Ok(arg);
         }
@@ -638,7 +718,7 @@ fn builtin_not(
 ) -> Result<Expression, LispError> {
     if let Some(arg0) = args.next() {
         if args.next().is_none() {
-            let arg0 = eval(environment, arg0)?;
+            let arg0 = eval_condition(environment, arg0, true)?;
             return if arg0.is_falsy() {
                 Ok(Expression::make_true())
             } else {
@@ -883,56 +963,119 @@ fn builtin_version(
     }
 }
 
-// Suppress this lint because we have to return a Result here since it is a builtin...
-#[allow(clippy::unnecessary_wraps)]
+fn meta_to_exp(meta: &ExpMeta) -> Expression {
+    Expression::with_list(vec![
+        Expression::alloc_data(ExpEnum::String(meta.file.into(), None)),
+        Expression::alloc_data(ExpEnum::Int(meta.line as i64)),
+        Expression::alloc_data(ExpEnum::Int(meta.col as i64)),
+    ])
+}
+
+// A backtrace frame as #(name #(file line column) #(args...)), any part may be
+// nil.
+fn frame_to_exp(frame: &ErrorFrame) -> Expression {
+    let name = match frame.name {
+        Some(name) => Expression::alloc_data(ExpEnum::Symbol(name, SymLoc::None)),
+        None => Expression::make_nil(),
+    };
+    let location = match &frame.location {
+        Some(meta) => meta_to_exp(meta),
+        None => Expression::make_nil(),
+    };
+    let args: Vec<Expression> = frame
+        .args
+        .iter()
+        .map(|arg| Expression::alloc_data(ExpEnum::String(arg.clone().into(), None)))
+        .collect();
+    let args = if args.is_empty() {
+        Expression::make_nil()
+    } else {
+        Expression::with_list(args)
+    };
+    Expression::with_list(vec![name, location, args])
+}
+
+/// Build the (:error msg backtrace kind data location) list for err (see get-error).
+pub fn error_to_exp(environment: &mut Environment, err: &LispError) -> Expression {
+    let err_sym = Expression::alloc_data(ExpEnum::Symbol(
+        environment.interner.intern(":error"),
+        SymLoc::None,
+    ));
+    let msg = format!("{}", err);
+    let err_msg = Expression::alloc_data(ExpEnum::String(msg.into(), None));
+    let backtrace = Expression::with_list(
+        err.backtrace
+            .iter()
+            .filter(|frame| frame.name.is_some() || frame.location.is_some())
+            .map(frame_to_exp)
+            .collect(),
+    );
+    let kind = Expression::alloc_data(ExpEnum::Symbol(
+        environment.interner.intern(err.kind.unwrap_or(":generic")),
+        SymLoc::None,
+    ));
+    let data = err.data.clone().unwrap_or_else(Expression::make_nil);
+    let location = match &err.location {
+        Some(meta) => meta_to_exp(meta),
+        None => Expression::make_nil(),
+    };
+    let res = vec![err_sym, err_msg, backtrace, kind, data, location];
+    Expression::cons_from_vec(&res, None)
+}
+
 fn builtin_get_error(
     environment: &mut Environment,
     args: &mut dyn Iterator<Item = Expression>,
 ) -> Result<Expression, LispError> {
+    // get-error handles any condition so handlers outside it are not called.
+    let id = environment.next_frame_id();
+    let handlers_len = environment.handlers.len();
+    environment.handlers.push(Handler {
+        kind: ":error",
+        action: HandlerAction::Unwind(id),
+    });
     let mut ret = None;
+    let mut result = Ok(());
     for arg in args {
         match eval(environment, arg) {
             Ok(exp) => ret = Some(exp),
             Err(err) => {
-                let err_sym = Expression::alloc_data(ExpEnum::Symbol(
-                    environment.interner.intern(":error"),
-                    SymLoc::None,
-                ));
-                let msg = format!("{}", err);
-                let err_msg = Expression::alloc_data(ExpEnum::String(msg.into(), None));
-                let res = if let Some(backtrace) = err.backtrace {
-                    vec![err_sym, err_msg, Expression::with_list(backtrace)]
-                } else {
-                    vec![err_sym, err_msg, Expression::make_nil()]
-                };
-                return Ok(Expression::cons_from_vec(&res, None));
+                result = Err(err);
+                break;
             }
         }
     }
-    let ok = Expression::alloc_data(ExpEnum::Symbol(
-        environment.interner.intern(":ok"),
-        SymLoc::None,
-    ));
-    Ok(Expression::alloc_data(ExpEnum::Pair(
-        ok,
-        ret.unwrap_or_else(Expression::make_nil),
-    )))
+    environment.handlers.truncate(handlers_len);
+    match result {
+        // Unwinding to an outer frame (restart or handler-case) so keep going.
+        Err(err) if err.unwind_to.is_some() && err.unwind_to != Some(id) => Err(err),
+        Err(err) => Ok(error_to_exp(environment, &err)),
+        Ok(()) => {
+            let ok = Expression::alloc_data(ExpEnum::Symbol(
+                environment.interner.intern(":ok"),
+                SymLoc::None,
+            ));
+            Ok(Expression::alloc_data(ExpEnum::Pair(
+                ok,
+                ret.unwrap_or_else(Expression::make_nil),
+            )))
+        }
+    }
 }
 
 fn add_usage(doc_str: &mut String, sym: &str, exp: &Expression) {
-    let exp_d = exp.get();
-    let p_iter = match &exp_d.data {
-        ExpEnum::Lambda(f) => f.params.iter(),
-        ExpEnum::Macro(m) => m.params.iter(),
+    let params = match &exp.get().data {
+        ExpEnum::Lambda(l) | ExpEnum::Macro(l) => params_to_string(l),
         _ => return,
     };
     doc_str.push_str("\n\nUsage: (");
     doc_str.push_str(sym);
-    for arg in p_iter {
+    if params != "()" {
         doc_str.push(' ');
-        doc_str.push_str(arg);
+        doc_str.push_str(&params[1..]);
+    } else {
+        doc_str.push(')');
     }
-    doc_str.push(')');
 }
 
 fn make_doc(
@@ -1183,6 +1326,68 @@ pub fn builtin_intern_stats(
     }
 }
 
+fn builtin_gc(
+    _environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    params_done(args, "gc")?;
+    Ok(Expression::alloc_data(ExpEnum::Int(collect() as i64)))
+}
+
+fn builtin_gc_stats(
+    _environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    params_done(args, "gc-stats")?;
+    let stats = gc_stats();
+    let mut map = HashMap::new();
+    for (key, val) in &[
+        (":live", stats.live),
+        (":collections", stats.collections),
+        (":reclaimed", stats.reclaimed),
+        (":cycles", stats.cycles),
+    ] {
+        map.insert(
+            HashKey::Symbol(key),
+            Expression::alloc_data(ExpEnum::Int(*val as i64)),
+        );
+    }
+    Ok(Expression::alloc_data(ExpEnum::HashMap(map)))
+}
+
+fn builtin_disassemble(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    let lambda = param_eval(environment, args, "disassemble")?;
+    params_done(args, "disassemble")?;
+    let lambda_d = lambda.get();
+    let lambda = match &lambda_d.data {
+        ExpEnum::Lambda(l) | ExpEnum::Macro(l) => l,
+        _ => {
+            return Err(LispError::with_kind(
+                ":type",
+                "disassemble: requires a lambda or macro",
+            ))
+        }
+    };
+    let chunk = match &*lambda.code.borrow() {
+        LambdaCode::Compiled(chunk) => chunk.clone(),
+        LambdaCode::TreeWalk => {
+            return Ok(Expression::alloc_data(ExpEnum::String(
+                "tree-walker\n".into(),
+                None,
+            )))
+        }
+        LambdaCode::Pending => Rc::new(compile_lambda(lambda)?),
+    };
+    lambda.code.replace(LambdaCode::Compiled(chunk.clone()));
+    Ok(Expression::alloc_data(ExpEnum::String(
+        disassemble(&chunk).into(),
+        None,
+    )))
+}
+
 pub fn builtin_meta_line_no(
     environment: &mut Environment,
     args: &mut dyn Iterator<Item = Expression>,
@@ -1342,6 +1547,12 @@ pub fn builtin_meta_tag_set(
                             return Ok(Expression::make_true());
                         }
                     }
+                    // Struct instances also have the tags of their type.
+                    if let ExpEnum::Struct(stype, _) = &exp_d.data {
+                        if stype.tags.contains(s) {
+                            return Ok(Expression::make_true());
+                        }
+                    }
                 } else {
                     return Err(LispError::new(
                         "meta-tag?: Takes an expression and a tag (symbol)",
@@ -1377,6 +1588,19 @@ macro_rules! ensure_tonicity {
     }};
 }
 
+// Numbers compared exactly, only if one of the args is a bignum or rational
+// (otherwise compare as ints or floats).
+fn exact_args(args: &[Expression]) -> Option<Vec<Number>> {
+    if args
+        .iter()
+        .any(|a| matches!(a.get().data, ExpEnum::BigInt(_) | ExpEnum::Rational(_)))
+    {
+        parse_list_of_numbers(args)
+    } else {
+        None
+    }
+}
+
 macro_rules! ensure_tonicity_all {
     ($environment:expr, $args:expr, $check_fn:expr) => {{
         let mut list: Vec<Expression> = Vec::new();
@@ -1385,6 +1609,8 @@ macro_rules! ensure_tonicity_all {
         }
         if let Ok(ints) = parse_list_of_ints($environment, &mut list) {
             ensure_tonicity!($check_fn, ints, &i64, i64)
+        } else if let Some(nums) = exact_args(&list) {
+            ensure_tonicity!($check_fn, nums, &Number, Number)
         } else if let Ok(floats) = parse_list_of_floats($environment, &mut list) {
             ensure_tonicity!($check_fn, floats, &f64, f64)
         } else {
@@ -1402,8 +1628,62 @@ pub fn builtin_equal(
     for a in parts {
         args.push(eval(environment, a)?);
     }
+    equal_values(environment, args)
+}
+
+// Struct instances are equal if they have the same type and equal fields.
+fn struct_equal(
+    environment: &mut Environment,
+    a: &Expression,
+    b: &Expression,
+) -> Result<bool, LispError> {
+    match (&a.get().data, &b.get().data) {
+        (ExpEnum::Struct(a_type, a_slots), ExpEnum::Struct(b_type, b_slots)) => {
+            if !Rc::ptr_eq(a_type, b_type) {
+                return Ok(false);
+            }
+            for (a, b) in a_slots.iter().zip(b_slots.iter()) {
+                if equal_values(environment, vec![a.get(), b.get()])?.is_nil() {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+fn equal_values(
+    environment: &mut Environment,
+    mut args: Vec<Expression>,
+) -> Result<Expression, LispError> {
+    if args
+        .iter()
+        .any(|a| matches!(a.get().data, ExpEnum::Struct(_, _)))
+    {
+        for pair in args.windows(2) {
+            if !struct_equal(environment, &pair[0], &pair[1])? {
+                return Ok(Expression::make_nil());
+            }
+        }
+        return Ok(Expression::make_true());
+    }
+    if args.iter().any(|a| matches!(a.get().data, ExpEnum::Set(_))) {
+        for pair in args.windows(2) {
+            let equal = match (&pair[0].get().data, &pair[1].get().data) {
+                (ExpEnum::Set(a), ExpEnum::Set(b)) => a == b,
+                _ => false,
+            };
+            if !equal {
+                return Ok(Expression::make_nil());
+            }
+        }
+        return Ok(Expression::make_true());
+    }
     if let Ok(ints) = parse_list_of_ints(environment, &mut args) {
         ensure_tonicity!(|a, b| a == b, ints, &i64, i64)
+    } else if let Some(nums) = exact_args(&args) {
+        ensure_tonicity!(|a, b| a == b, nums, &Number, Number)
     } else if let Ok(floats) = parse_list_of_floats(environment, &mut args) {
         ensure_tonicity!(|a, b| ((a - b) as f64).abs() < 0.000_001, floats, &f64, f64)
     } else {
@@ -1486,7 +1766,7 @@ Example:
     );
     data.insert(
         interner.intern("unwind-protect"),
-        Expression::make_function(
+        Expression::make_context_function(
             builtin_unwind_protect,
             "Usage: (unwind-protect protected cleanup*) -> [protected result]
 
@@ -1523,16 +1803,29 @@ Example:
         interner.intern("err"),
         Expression::make_function(
             builtin_err,
-            "Usage: (err string) -> raises an error
+            "Usage: (err string) or (err :kind string data?) or (err error) -> raises an error
 
 Raise an error with the supplied string.
 
+With a leading keyword the error gets that kind (builtin kinds are :io, :type,
+:arity and :process, any other keyword is fine for user errors) and an optional
+data expression.  Errors without a kind have the kind :generic.  Passing an
+error returned by get-error will raise it again (keeping kind, data and location).
+
 Section: core
 
 Example:
 (def test-err-err (get-error (err \"Test Error\")))
 (test::assert-equal :error (car test-err-err))
 (test::assert-equal \"Test Error\" (cadr test-err-err))
+(test::assert-equal :generic (error-kind test-err-err))
+(def test-err-err (get-error (err :deploy \"Deploy failed\" '(host-a host-b))))
+(test::assert-equal \"Deploy failed\" (error-message test-err-err))
+(test::assert-equal :deploy (error-kind test-err-err))
+(test::assert-equal '(host-a host-b) (error-data test-err-err))
+(def test-err-err2 (get-error (err test-err-err)))
+(test::assert-equal :deploy (error-kind test-err-err2))
+(test::assert-equal (error-location test-err-err) (error-location test-err-err2))
 ",
         ),
     );
@@ -1748,10 +2041,20 @@ Example:
     data.insert(
         interner.intern("fn"),
         Expression::make_special_fn(
-            "Usage: (fn (param*) expr*) -> exprN
+            "Usage: (fn (param* [&optional opt*] [&rest rest] [&key key*]) expr*) -> exprN
 
 Create a function (lambda).
 
+Params are bound to the arguments in order.  After &optional the params may be
+left out, each opt is a symbol or (symbol default supplied-p): default is
+evaluated (after the earlier params are bound) when it is left out (else nil)
+and supplied-p is bound to true if it was passed and false if not.  &rest binds
+the one param after it to a list of the remaining arguments.  &key params are
+passed after the others as :name value pairs in any order, they take the same
+forms as opt.  Unknown keywords are an error unless there is also a &rest
+param (which gets the keywords and values).  A param (or rest) can be a
+destructuring pattern like (a b & more) or {:keys (x y)}, see let.
+
 Section: core
 
 Example:
@@ -1774,6 +2077,17 @@ Example:
 (test::assert-equal 21 test-fn2)
 (test::assert-equal 30 test-fn3)
 (test::assert-equal 63 ((fn (x y z) (set! test-fn1 x)(set! test-fn2 y)(set! test-fn3 z)(+ x y z)) 12 21 30))
+(def test-fn-opt (fn (a &optional b (c (+ a 1) c?)) (list a b c c?)))
+(test::assert-equal '(1 nil 2 #f) (test-fn-opt 1))
+(test::assert-equal '(1 2 3 #t) (test-fn-opt 1 2 3))
+(test::assert-equal :arity (error-kind (get-error (test-fn-opt 1 2 3 4))))
+(def test-fn-key (fn (a &key (b 2) c) (list a b c)))
+(test::assert-equal '(1 2 nil) (test-fn-key 1))
+(test::assert-equal '(1 3 4) (test-fn-key 1 :c 4 :b 3))
+(test::assert-error (test-fn-key 1 :d 4))
+(def test-fn-pat (fn ((a (b c)) {:keys (d)}) (list a b c d)))
+(test::assert-equal '(1 2 3 4) (test-fn-pat '(1 (2 3)) (hash-map :d 4)))
+(test::assert-equal :arity (error-kind (get-error (test-fn-pat '(1 (2)) nil))))
 ",
         ),
     );
@@ -1870,7 +2184,7 @@ Example:
     );
     data.insert(
         interner.intern("not"),
-        Expression::make_function(
+        Expression::make_context_function(
             builtin_not,
             "Usage: (not expression)
 
@@ -1888,7 +2202,7 @@ Example:
     );
     data.insert(
         interner.intern("null"),
-        Expression::make_function(
+        Expression::make_context_function(
             builtin_not,
             "Usage: (null expression)
 
@@ -2141,15 +2455,22 @@ Example:
     );
     data.insert(
         interner.intern("get-error"),
-        Expression::make_function(
+        Expression::make_context_function(
             builtin_get_error,
             "Usage: (get-error exp0 ... expN) -> pair
 
-Evaluate each form (like do) but on error return (:error msg backtrace) instead of aborting.
+Evaluate each form (like do) but on error return
+(:error msg backtrace kind data location) instead of aborting.
 On success return (:ok . expN-result).
 
 If there is no error will return the value of the last expression as the cdr of
 the pair.  Always returns a pair with the first value either being :ok or :error.
+The kind is a keyword (:io, :type, :arity, :process, :generic or user defined
+with err), data is the expression given to err (or nil), location is a
+vector #(file line column) where the error was raised (or nil) and backtrace is
+the function calls the error unwound through (see error-backtrace).  Use
+error-message, error-kind, error-data, error-location and error-backtrace to
+access the parts.
 
 Section: core
 
@@ -2158,6 +2479,10 @@ Example:
 (test::assert-equal :error (car get-error-t1)) 
 (test::assert-equal \"Some Error\" (cadr get-error-t1)) 
 (test::assert-true (vec? (caddr get-error-t1)))
+(test::assert-equal :generic (error-kind get-error-t1))
+(test::assert-equal :arity (error-kind (get-error ((fn (a) a)))))
+(test::assert-equal :type (error-kind (get-error (+ 1 \"x\"))))
+(test::assert-equal :io (error-kind (get-error (open \"/does/not/exist/get-error\" :read))))
 (test::assert-equal '(:ok . \"Some String\") (get-error \"Some String\"))
 (test::assert-equal '(:ok . \"Some Other String\") (get-error (def test-get-error \"Some \") (str test-get-error \"Other String\")))
 "
@@ -2247,6 +2572,77 @@ Example:
         ),
     );
 
+    data.insert(
+        interner.intern("gc"),
+        Expression::make_function(
+            builtin_gc,
+            "Usage: (gc)
+
+Run the cycle collector now, returns the number of objects it reclaimed.
+
+Objects are freed as soon as nothing refers to them, the collector finds groups
+of objects that only refer to each other (a lambda that captured its own binding
+for instance) and frees them.  It also runs automatically as allocations grow.
+
+Section: core
+
+Example:
+(let ((f nil)) (set! f (fn () f)) nil)
+(test::assert-true (> (gc) 0))
+",
+        ),
+    );
+
+    data.insert(
+        interner.intern("gc-stats"),
+        Expression::make_function(
+            builtin_gc_stats,
+            "Usage: (gc-stats)
+
+Returns a hash map of cycle collector stats:
+:live objects being tracked after the last collection,
+:collections run, total objects :reclaimed and total garbage :cycles found.
+
+Section: core
+
+Example:
+(def test-gc-stats (gc-stats))
+(test::assert-true (int? (hash-get test-gc-stats :live)))
+(test::assert-true (int? (hash-get test-gc-stats :collections)))
+(test::assert-true (int? (hash-get test-gc-stats :reclaimed)))
+(test::assert-true (int? (hash-get test-gc-stats :cycles)))
+(gc)
+(test::assert-true (> (hash-get (gc-stats) :collections) (hash-get test-gc-stats :collections)))
+",
+        ),
+    );
+
+    data.insert(
+        interner.intern("disassemble"),
+        Expression::make_function(
+            builtin_disassemble,
+            "Usage: (disassemble lambda) -> string
+
+Returns a listing of the bytecode a lambda or macro body is compiled to
+(compiling it if it has not been called yet).  Each line is an offset, the op,
+its arguments and what it refers to after the ;.  Forms the compiler leaves to
+the tree-walker show up as eval ops, a lambda first called with *bytecode* set
+to nil is always run by the tree-walker.
+
+Section: core
+
+Example:
+(defn disassemble-test (x) (if (> x 0) (+ x 1) x))
+(def disassemble-listing (disassemble disassemble-test))
+(test::assert-true (str-contains \"guard\" disassemble-listing))
+(test::assert-true (str-contains \"tail-call\" disassemble-listing))
+(test::assert-true (str-contains \"; x\" disassemble-listing))
+(test::assert-equal 3 (disassemble-test 2))
+(test::assert-equal -1 (disassemble-test -1))
+",
+        ),
+    );
+
     data.insert(
         interner.intern("intern-stats"),
         Expression::make_special(
@@ -114,6 +114,21 @@ pub(crate) fn builtin_var(
     do_set(environment, args, "var", true)
 }
 
+// Name an unnamed lambda or macro for backtraces.
+fn name_lambda(environment: &mut Environment, namespace: &str, key: &str, val: &Expression) {
+    if let ExpEnum::Lambda(l) | ExpEnum::Macro(l) = &mut val.get_mut().data {
+        if l.name.is_none() {
+            l.name = Some(if namespace == "root" || namespace == "user" {
+                environment.interner.intern(key)
+            } else {
+                environment
+                    .interner
+                    .intern(&format!("{}::{}", namespace, key))
+            });
+        }
+    }
+}
+
 pub(crate) fn builtin_def(
     environment: &mut Environment,
     args: &mut dyn Iterator<Item = Expression>,
@@ -137,6 +152,7 @@ pub(crate) fn builtin_def(
                     let name = in_scope.borrow().name();
                     if name == namespace {
                         let val = eval(environment, val)?;
+                        name_lambda(environment, namespace, key, &val);
                         in_scope
                             .borrow_mut()
                             .insert_with_doc(key, val.clone(), doc_string);
@@ -153,6 +169,8 @@ pub(crate) fn builtin_def(
         Err(LispError::new(msg))
     } else {
         let val = eval(environment, val)?;
+        let namespace = current_namespace(environment);
+        name_lambda(environment, namespace, key, &val);
         environment
             .namespace
             .borrow_mut()
@@ -0,0 +1,416 @@
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+use std::iter;
+
+use crate::builtins::error_to_exp;
+use crate::builtins_util::*;
+use crate::environment::*;
+use crate::eval::*;
+use crate::interner::*;
+use crate::types::*;
+
+// :error handles any error but not conditions from signal.
+fn kind_matches(handler_kind: &str, err: &LispError, is_error: bool) -> bool {
+    (is_error && handler_kind == ":error") || handler_kind == err.kind.unwrap_or(":generic")
+}
+
+/// Run the condition handlers for err (innermost first) before the stack unwinds.
+/// A handler-bind handler that returns declines, a handler-case (or get-error)
+/// stops the search and err will unwind to it.  A handler that raises an error
+/// or invokes a restart replaces err.  Returns true if err is now unwinding to a
+/// frame or was replaced.
+pub fn signal_error(environment: &mut Environment, err: &mut LispError) -> bool {
+    run_handlers(environment, err, true)
+}
+
+fn run_handlers(environment: &mut Environment, err: &mut LispError, is_error: bool) -> bool {
+    if err.signalled {
+        return false;
+    }
+    err.signalled = true;
+    if err.unwind_to.is_some() {
+        return true;
+    }
+    let mut idx = environment.handlers.len();
+    while idx > 0 {
+        idx -= 1;
+        let handler = environment.handlers[idx].clone();
+        if !kind_matches(handler.kind, err, is_error) {
+            continue;
+        }
+        match handler.action {
+            HandlerAction::Unwind(id) => {
+                err.unwind_to = Some(id);
+                return true;
+            }
+            HandlerAction::Call(lambda) => {
+                // The handler (and anything it signals) only sees the handlers
+                // established outside of it.
+                let saved = environment.handlers.split_off(idx);
+                let condition = error_to_exp(environment, err);
+                let res = call_lambda(environment, lambda, &mut iter::once(condition), false)
+                    .and_then(|res| res.resolve(environment));
+                environment.handlers.extend(saved);
+                if let Err(new_err) = res {
+                    *err = new_err;
+                    err.signalled = true;
+                    return true;
+                }
+            }
+        }
+    }
+    false
+}
+
+fn make_kind(
+    environment: &mut Environment,
+    exp: &Expression,
+    form: &str,
+) -> Result<&'static str, LispError> {
+    if let ExpEnum::Symbol(sym, _) = &exp.get().data {
+        if sym.starts_with(':') {
+            return Ok(environment.interner.intern(sym));
+        }
+    }
+    Err(LispError::with_kind(
+        ":type",
+        format!("{}: condition kind must be a keyword, got {}", form, exp),
+    ))
+}
+
+fn builtin_handler_bind(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    let bindings = if let Some(bindings) = args.next() {
+        bindings
+    } else {
+        return Err(LispError::with_kind(
+            ":arity",
+            "handler-bind: Missing required argument, see (doc 'handler-bind) for usage.",
+        ));
+    };
+    let mut handlers = Vec::new();
+    for binding in bindings.iter() {
+        let mut parts = binding.iter();
+        let kind = param_eval(environment, &mut parts, "handler-bind")?;
+        let kind = make_kind(environment, &kind, "handler-bind")?;
+        let handler = param_eval(environment, &mut parts, "handler-bind")?;
+        params_done(&mut parts, "handler-bind")?;
+        if !matches!(&handler.get().data, ExpEnum::Lambda(_)) {
+            return Err(LispError::with_kind(
+                ":type",
+                format!("handler-bind: handler for {} must be a lambda", kind),
+            ));
+        }
+        handlers.push(Handler {
+            kind,
+            action: HandlerAction::Call(handler),
+        });
+    }
+    let handlers_len = environment.handlers.len();
+    // Push in reverse so the first binding is checked first.
+    environment.handlers.extend(handlers.into_iter().rev());
+    let mut ret = Ok(Expression::make_nil());
+    for arg in args {
+        ret = eval(environment, arg);
+        if ret.is_err() {
+            break;
+        }
+    }
+    environment.handlers.truncate(handlers_len);
+    ret
+}
+
+fn builtin_handler_case_fn(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    let thunk = param_eval(environment, args, "handler-case-fn")?;
+    let mut clauses = Vec::new();
+    while let Some(kind) = args.next() {
+        let kind = eval(environment, kind)?;
+        let kind = make_kind(environment, &kind, "handler-case-fn")?;
+        let clause = param_eval(environment, args, "handler-case-fn")?;
+        clauses.push((kind, clause, environment.next_frame_id()));
+    }
+    let handlers_len = environment.handlers.len();
+    for (kind, _, id) in clauses.iter().rev() {
+        environment.handlers.push(Handler {
+            kind,
+            action: HandlerAction::Unwind(*id),
+        });
+    }
+    let res = call_lambda(environment, thunk, &mut iter::empty(), false)
+        .and_then(|res| res.resolve(environment));
+    environment.handlers.truncate(handlers_len);
+    match res {
+        Err(err) => {
+            if let Some((_, clause, _)) =
+                clauses.iter().find(|(_, _, id)| err.unwind_to == Some(*id))
+            {
+                let condition = error_to_exp(environment, &err);
+                call_lambda(
+                    environment,
+                    clause.clone(),
+                    &mut iter::once(condition),
+                    false,
+                )?
+                .resolve(environment)
+            } else {
+                Err(err)
+            }
+        }
+        ok => ok,
+    }
+}
+
+fn builtin_signal(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    let kind = param_eval(environment, args, "signal")?;
+    let kind = make_kind(environment, &kind, "signal")?;
+    let msg = param_eval(environment, args, "signal")?;
+    let mut err = LispError::with_kind(kind, msg.as_string(environment)?);
+    if let Some(data) = args.next() {
+        err.data = Some(eval(environment, data)?);
+    }
+    params_done(args, "signal")?;
+    err.location = environment.last_meta;
+    if run_handlers(environment, &mut err, false) {
+        // A handler-case caught it, a restart was invoked or a handler raised an error.
+        Err(err)
+    } else {
+        Ok(Expression::make_nil())
+    }
+}
+
+fn builtin_restart_case_fn(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    let thunk = param_eval(environment, args, "restart-case-fn")?;
+    let mut clauses = Vec::new();
+    while let Some(name) = args.next() {
+        let name = eval(environment, name)?;
+        let name = if let ExpEnum::Symbol(sym, _) = &name.get().data {
+            environment.interner.intern(sym)
+        } else {
+            return Err(LispError::with_kind(
+                ":type",
+                format!(
+                    "restart-case-fn: restart name must be a symbol, got {}",
+                    name
+                ),
+            ));
+        };
+        let clause = param_eval(environment, args, "restart-case-fn")?;
+        clauses.push((name, clause, environment.next_frame_id()));
+    }
+    let restarts_len = environment.restarts.len();
+    for (name, _, id) in clauses.iter().rev() {
+        environment.restarts.push(Restart { name, id: *id });
+    }
+    let res = call_lambda(environment, thunk, &mut iter::empty(), false)
+        .and_then(|res| res.resolve(environment));
+    environment.restarts.truncate(restarts_len);
+    match res {
+        Err(err) => {
+            if let Some((_, clause, _)) =
+                clauses.iter().find(|(_, _, id)| err.unwind_to == Some(*id))
+            {
+                let args: Vec<Expression> = match &err.data {
+                    Some(data) => data.iter().collect(),
+                    None => Vec::new(),
+                };
+                call_lambda(environment, clause.clone(), &mut args.into_iter(), false)?
+                    .resolve(environment)
+            } else {
+                Err(err)
+            }
+        }
+        ok => ok,
+    }
+}
+
+fn builtin_invoke_restart(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    let name = param_eval(environment, args, "invoke-restart")?;
+    let name = if let ExpEnum::Symbol(sym, _) = &name.get().data {
+        *sym
+    } else {
+        return Err(LispError::with_kind(
+            ":type",
+            format!(
+                "invoke-restart: restart name must be a symbol, got {}",
+                name
+            ),
+        ));
+    };
+    let mut restart_args = Vec::new();
+    for arg in args {
+        restart_args.push(eval(environment, arg)?);
+    }
+    if let Some(restart) = environment.restarts.iter().rev().find(|r| r.name == name) {
+        let mut err = LispError::with_kind(
+            ":restart",
+            format!("invoke-restart: restart {} not reached", name),
+        )
+        .with_data(Expression::with_list(restart_args));
+        err.signalled = true;
+        err.unwind_to = Some(restart.id);
+        Err(err)
+    } else {
+        Err(LispError::with_kind(
+            ":control-error",
+            format!("invoke-restart: no restart named {} is active", name),
+        ))
+    }
+}
+
+fn builtin_compute_restarts(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    params_done(args, "compute-restarts")?;
+    let names: Vec<Expression> = environment
+        .restarts
+        .iter()
+        .rev()
+        .map(|r| Expression::alloc_data(ExpEnum::Symbol(r.name, SymLoc::None)))
+        .collect();
+    Ok(Expression::cons_from_vec(&names, None))
+}
+
+pub fn add_condition_builtins<S: BuildHasher>(
+    interner: &mut Interner,
+    data: &mut HashMap<&'static str, (Expression, String), S>,
+) {
+    data.insert(
+        interner.intern("handler-bind"),
+        Expression::make_special(
+            builtin_handler_bind,
+            r#"Usage: (handler-bind ((:kind handler)*) body*) -> [last body result]
+
+Evaluate body (like do) with condition handlers established.  When an error (or
+signal) of kind is raised in body the handler is called with the condition (the
+same list get-error returns) BEFORE the stack is unwound.  The handler can
+invoke a restart (see restart-case) to recover, raise a new error or return to
+decline (the next handler is tried, then the error continues as normal).
+
+A kind of :error handles every kind of error (but not conditions from signal).
+While a handler runs only the handlers outside of its handler-bind are active.
+
+Section: conditions
+
+Example:
+(def handler-bind-test nil)
+(test::assert-equal :error (car (get-error
+    (handler-bind ((:oops (fn (c) (set! handler-bind-test (error-message c)))))
+        (err :oops "Oops!")))))
+(test::assert-equal "Oops!" handler-bind-test)
+(test::assert-equal 10 (restart-case
+    (handler-bind ((:type (fn (c) (invoke-restart 'use-value 10))))
+        (+ 1 "one"))
+    (use-value (v) v)))
+"#,
+        ),
+    );
+    data.insert(
+        interner.intern("handler-case-fn"),
+        Expression::make_function(
+            builtin_handler_case_fn,
+            r#"Usage: (handler-case-fn thunk :kind handler*) -> [thunk or handler result]
+
+Function behind handler-case.  Call thunk (a lambda with no parameters) and if a
+condition of kind is raised unwind to here and return the result of calling the
+matching handler with the condition.
+
+Section: conditions
+
+Example:
+(test::assert-equal :type (handler-case-fn (fn () (+ 1 "one")) :io (fn (c) :io) :type (fn (c) (error-kind c))))
+(test::assert-equal 3 (handler-case-fn (fn () (+ 1 2)) :error (fn (c) 0)))
+"#,
+        ),
+    );
+    data.insert(
+        interner.intern("signal"),
+        Expression::make_function(
+            builtin_signal,
+            r#"Usage: (signal :kind string data?) -> nil
+
+Signal a condition.  Like err this runs the handlers for kind (see handler-bind)
+but if none of them handle it (handler-case or a restart) signal returns nil
+instead of raising an error.  Handlers for :error (and get-error) do not handle
+signals, only handlers for kind do.
+
+Section: conditions
+
+Example:
+(def signal-test nil)
+(test::assert-false (signal :note "Just so you know"))
+(test::assert-false (handler-bind ((:note (fn (c) (set! signal-test (error-data c)))))
+    (signal :note "Just so you know" 42)))
+(test::assert-equal 42 signal-test)
+(test::assert-equal "Stop" (handler-case (signal :stop "Stop") (:stop (c) (error-message c))))
+"#,
+        ),
+    );
+    data.insert(
+        interner.intern("restart-case-fn"),
+        Expression::make_function(
+            builtin_restart_case_fn,
+            r#"Usage: (restart-case-fn thunk 'name restart*) -> [thunk or restart result]
+
+Function behind restart-case.  Call thunk (a lambda with no parameters) with the
+named restarts available, invoking one (invoke-restart) unwinds to here and
+returns the result of calling the restart with the invoke-restart arguments.
+
+Section: conditions
+
+Example:
+(test::assert-equal 5 (restart-case-fn (fn () (invoke-restart 'five)) 'five (fn () 5)))
+(test::assert-equal 7 (restart-case-fn (fn () (+ 3 4)) 'five (fn () 5)))
+"#,
+        ),
+    );
+    data.insert(
+        interner.intern("invoke-restart"),
+        Expression::make_function(
+            builtin_invoke_restart,
+            r#"Usage: (invoke-restart 'name arg*) -> [does not return]
+
+Unwind to the innermost active restart named name (see restart-case) and call it
+with args.  Raises a :control-error if no such restart is active.
+
+Section: conditions
+
+Example:
+(test::assert-equal '(1 2) (restart-case (invoke-restart 'both 1 2) (both (a b) (list a b))))
+(test::assert-equal :control-error (error-kind (get-error (invoke-restart 'no-such-restart))))
+"#,
+        ),
+    );
+    data.insert(
+        interner.intern("compute-restarts"),
+        Expression::make_function(
+            builtin_compute_restarts,
+            r#"Usage: (compute-restarts) -> list
+
+Return the names of the active restarts, innermost first.
+
+Section: conditions
+
+Example:
+(test::assert-false (compute-restarts))
+(test::assert-equal '(inner retry outer) (restart-case
+    (restart-case (compute-restarts) (inner () nil) (retry () nil))
+    (outer () nil)))
+"#,
+        ),
+    );
+}
@@ -0,0 +1,661 @@
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+use std::io::{self, Write};
+
+use crate::builtins_util::*;
+use crate::environment::*;
+use crate::eval::*;
+use crate::interner::*;
+use crate::reader::read;
+use crate::types::*;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Step {
+    Off,
+    // Pause at the next form.
+    Into,
+    // Pause at the next form at or above eval level or outside of the frame.
+    Over(u32, usize),
+    // Pause at the next form after the frame returns.
+    Out(usize),
+}
+
+/// State for the debugger (break, debug, break-at).
+#[derive(Debug)]
+pub struct DebugState {
+    /// True if eval needs to call the debug hooks (stepping, breakpoints or
+    /// debugged functions), false while in the debugger itself.
+    pub active: bool,
+    step: Step,
+    breakpoints: Vec<(String, usize)>,
+    debug_fns: Vec<Expression>,
+    // Where the last pause was (file, line, eval level), forms on the same line
+    // inside it do not hit the breakpoint again.
+    last_break: Option<(&'static str, usize, u32)>,
+    in_repl: bool,
+}
+
+impl DebugState {
+    pub fn new() -> Self {
+        DebugState {
+            active: false,
+            step: Step::Off,
+            breakpoints: Vec::new(),
+            debug_fns: Vec::new(),
+            last_break: None,
+            in_repl: false,
+        }
+    }
+
+    fn update_active(&mut self) {
+        self.active = !self.in_repl
+            && (self.step != Step::Off
+                || !self.breakpoints.is_empty()
+                || !self.debug_fns.is_empty());
+    }
+
+    fn breakpoint_at(&self, meta: &ExpMeta) -> bool {
+        self.breakpoints.iter().any(|(file, line)| {
+            *line == meta.line
+                && (meta.file == file
+                    || (meta.file.ends_with(file.as_str())
+                        && meta.file[..meta.file.len() - file.len()].ends_with('/')))
+        })
+    }
+}
+
+impl Default for DebugState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn is_block(expression: &Expression) -> bool {
+    if let Some(command) = expression.iter().next() {
+        matches!(
+            command.get().data,
+            ExpEnum::Symbol("block", _) | ExpEnum::Symbol("do", _)
+        )
+    } else {
+        false
+    }
+}
+
+/// Called by eval before evaluating expression when the debugger is active,
+/// pauses if stepping or expression is on a breakpoint.
+pub fn debug_eval_hook(
+    environment: &mut Environment,
+    expression: &Expression,
+) -> Result<(), LispError> {
+    if !matches!(
+        expression.get().data,
+        ExpEnum::Pair(_, _) | ExpEnum::Vector(_)
+    ) {
+        return Ok(());
+    }
+    let level = environment.eval_level;
+    let frames = environment.stack_frames.len();
+    let meta = expression.meta();
+    let debugger = &environment.debugger;
+    let step_hit = match debugger.step {
+        Step::Off => false,
+        Step::Into => true,
+        Step::Over(step_level, step_frames) => level <= step_level || frames < step_frames,
+        Step::Out(step_frames) => frames < step_frames,
+    };
+    let break_hit = if let Some(meta) = &meta {
+        debugger.breakpoint_at(meta)
+            && !matches!(debugger.last_break,
+                Some((file, line, break_level)) if file == meta.file && line == meta.line && level > break_level)
+    } else {
+        false
+    };
+    if step_hit && !break_hit && is_block(expression) {
+        // Step into block and do (defn bodies are blocks) rather than over them.
+        if let Step::Over(_, _) = environment.debugger.step {
+            environment.debugger.step = Step::Over(level + 1, frames);
+        } else {
+            environment.debugger.step = Step::Into;
+        }
+        return Ok(());
+    }
+    if step_hit || break_hit {
+        environment.debugger.last_break = meta.map(|meta| (meta.file, meta.line, level));
+        let reason = if break_hit { "Breakpoint" } else { "Step" };
+        debug_repl(environment, reason, Some(expression), level)?;
+    }
+    Ok(())
+}
+
+/// Called when a lambda is entered (its frame is set up) when the debugger is
+/// active, pauses if it is being debugged.
+pub fn debug_call_hook(
+    environment: &mut Environment,
+    lambda: &Expression,
+) -> Result<(), LispError> {
+    if environment
+        .debugger
+        .debug_fns
+        .iter()
+        .any(|exp| exp.ptr_eq(lambda))
+    {
+        // The body forms are evaluated one level down, next stops at the first.
+        let level = environment.eval_level + 1;
+        debug_repl(environment, "Entered", None, level)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn debug_write(environment: &mut Environment, text: &str) -> Result<(), LispError> {
+    if let Some(out) = lookup_expression(environment, "*stdout*") {
+        if let ExpEnum::File(f) = &out.get().data {
+            match &mut *f.borrow_mut() {
+                FileState::Stdout => {
+                    let stdout = io::stdout();
+                    let mut out = stdout.lock();
+                    out.write_all(text.as_bytes())?;
+                    out.flush()?;
+                }
+                FileState::Stderr => io::stderr().write_all(text.as_bytes())?,
+                FileState::Write(f) => f.write_all(text.as_bytes())?,
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+fn debug_read_line(environment: &mut Environment) -> Result<Option<String>, LispError> {
+    let mut line = String::new();
+    if let Some(input) = lookup_expression(environment, "*stdin*") {
+        if let ExpEnum::File(f) = &input.get().data {
+            match &mut *f.borrow_mut() {
+                FileState::Stdin => {
+                    if io::stdin().read_line(&mut line)? == 0 {
+                        return Ok(None);
+                    }
+                }
+                FileState::Read(Some(f_iter), _) => {
+                    for ch in f_iter {
+                        if ch == "\n" {
+                            return Ok(Some(line));
+                        }
+                        line.push_str(&ch);
+                    }
+                    if line.is_empty() {
+                        return Ok(None);
+                    }
+                }
+                _ => return Ok(None),
+            }
+        }
+    }
+    Ok(Some(line))
+}
+
+fn where_str(environment: &Environment, reason: &str, form: Option<&Expression>) -> String {
+    let name = environment
+        .stack_frames
+        .last()
+        .and_then(|frame| frame.name)
+        .map(|name| format!(": {}", name))
+        .unwrap_or_default();
+    let meta = form.and_then(|form| form.meta()).or(environment.last_meta);
+    let location = meta
+        .map(|meta| format!(" ({}:{}:{})", meta.file, meta.line, meta.col))
+        .unwrap_or_default();
+    let form = form
+        .map(|form| format!("\n  {}", form.to_short_string(70)))
+        .unwrap_or_default();
+    format!("{}{}{}{}\n", reason, name, location, form)
+}
+
+fn locals_str(environment: &Environment) -> String {
+    let mut out = String::new();
+    if let Some(frame) = environment.stack_frames.last() {
+        let mut locals: Vec<(&'static str, usize)> = frame
+            .symbols
+            .data
+            .borrow()
+            .syms
+            .iter()
+            .map(|(name, idx)| (*name, *idx))
+            .collect();
+        locals.sort_by_key(|(_, idx)| *idx);
+        for (name, idx) in locals {
+            if name == "this-fn" {
+                continue;
+            }
+            if let Some(binding) = environment.stack.get(frame.index + idx) {
+                out.push_str(&format!(
+                    "  {} = {}\n",
+                    name,
+                    binding.get().to_short_string(70)
+                ));
+            }
+        }
+    }
+    out
+}
+
+fn backtrace_str(environment: &Environment) -> String {
+    let mut out = String::new();
+    for (i, frame) in environment.stack_frames.iter().rev().enumerate() {
+        out.push_str(&format!("  #{} {}\n", i, frame.name.unwrap_or("(fn)")));
+    }
+    out
+}
+
+const DEBUG_HELP: &str = "Commands:
+  c, continue    continue running
+  s, step        step into the next form
+  n, next        step over to the next form in this frame
+  o, out         step out of this frame
+  l, locals      print the locals of this frame
+  bt, backtrace  print the function calls
+  w, where       print where evaluation is paused
+  q, quit        abort the evaluation with an error
+  h, help        print this help
+Anything else is evaluated as a form in the paused frame.
+";
+
+// Patch symbols in form that are locals in the paused frame so they are not
+// taken for globals of the same name.
+fn patch_locals(environment: &Environment, form: &Expression) {
+    let mut form_d = form.get_mut();
+    match &mut form_d.data {
+        ExpEnum::Symbol(name, loc @ SymLoc::None) => {
+            if let Some(binding) = lookup_in_stack(environment, name) {
+                *loc = SymLoc::Ref(binding);
+            }
+        }
+        ExpEnum::Pair(_, _) | ExpEnum::Vector(_) => {
+            drop(form_d);
+            for exp in form.iter() {
+                patch_locals(environment, &exp);
+            }
+        }
+        _ => {}
+    }
+}
+
+// Evaluate input in the paused frame, errors are printed (like get-error outer
+// handlers do not see them) unless they are unwinding to an outer frame.
+fn debug_eval(environment: &mut Environment, input: &str) -> Result<String, LispError> {
+    let form = match read(environment, input, None, false) {
+        Ok(form) => form,
+        Err(err) => return Ok(format!("Read error: {}\n", err.reason)),
+    };
+    patch_locals(environment, &form);
+    let id = environment.next_frame_id();
+    let handlers_len = environment.handlers.len();
+    environment.handlers.push(Handler {
+        kind: ":error",
+        action: HandlerAction::Unwind(id),
+    });
+    let result = eval(environment, &form);
+    environment.handlers.truncate(handlers_len);
+    match result {
+        Ok(exp) => Ok(format!("{}\n", exp)),
+        Err(err) if err.unwind_to.is_some() && err.unwind_to != Some(id) => Err(err),
+        Err(err) => Ok(format!("Error: {}\n", err)),
+    }
+}
+
+fn debug_repl(
+    environment: &mut Environment,
+    reason: &str,
+    form: Option<&Expression>,
+    level: u32,
+) -> Result<(), LispError> {
+    let frames = environment.stack_frames.len();
+    environment.debugger.step = Step::Off;
+    environment.debugger.in_repl = true;
+    environment.debugger.update_active();
+    let mut result = debug_write(environment, &where_str(environment, reason, form));
+    while result.is_ok() && environment.exit_code.is_none() {
+        if let Err(err) = debug_write(environment, "debug> ") {
+            result = Err(err);
+            break;
+        }
+        let line = match debug_read_line(environment) {
+            Ok(Some(line)) => line,
+            Ok(None) => break,
+            Err(err) => {
+                result = Err(err);
+                break;
+            }
+        };
+        let output = match line.trim() {
+            "" => continue,
+            "c" | "continue" => break,
+            "s" | "step" => {
+                environment.debugger.step = Step::Into;
+                break;
+            }
+            "n" | "next" => {
+                environment.debugger.step = Step::Over(level, frames);
+                break;
+            }
+            "o" | "out" => {
+                environment.debugger.step = Step::Out(frames);
+                break;
+            }
+            "l" | "locals" => Ok(locals_str(environment)),
+            "bt" | "backtrace" => Ok(backtrace_str(environment)),
+            "w" | "where" => Ok(where_str(environment, reason, form)),
+            "q" | "quit" => {
+                result = Err(LispError::new("Debugger quit."));
+                break;
+            }
+            "h" | "help" => Ok(DEBUG_HELP.to_string()),
+            input => debug_eval(environment, input),
+        };
+        result = output.and_then(|output| debug_write(environment, &output));
+    }
+    environment.debugger.in_repl = false;
+    environment.debugger.update_active();
+    result
+}
+
+fn builtin_break(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    params_done(args, "break")?;
+    if !environment.debugger.in_repl {
+        let level = environment.eval_level;
+        debug_repl(environment, "Break", None, level)?;
+    }
+    Ok(Expression::make_nil())
+}
+
+fn debug_lambda(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+    name: &str,
+) -> Result<Expression, LispError> {
+    let lambda = param_eval(environment, args, name)?;
+    params_done(args, name)?;
+    let is_lambda = matches!(lambda.get().data, ExpEnum::Lambda(_));
+    if is_lambda {
+        Ok(lambda)
+    } else {
+        Err(LispError::with_kind(
+            ":type",
+            format!("{}: requires a lambda", name),
+        ))
+    }
+}
+
+fn builtin_debug(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    let lambda = debug_lambda(environment, args, "debug")?;
+    let debugger = &mut environment.debugger;
+    if !debugger.debug_fns.iter().any(|exp| exp.ptr_eq(&lambda)) {
+        debugger.debug_fns.push(lambda.clone());
+    }
+    debugger.update_active();
+    Ok(lambda)
+}
+
+fn builtin_undebug(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    let lambda = debug_lambda(environment, args, "undebug")?;
+    let debugger = &mut environment.debugger;
+    debugger.debug_fns.retain(|exp| !exp.ptr_eq(&lambda));
+    debugger.update_active();
+    Ok(lambda)
+}
+
+fn breakpoint_params(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+    name: &str,
+) -> Result<(String, usize), LispError> {
+    let file = param_eval(environment, args, name)?;
+    let line = param_eval(environment, args, name)?;
+    params_done(args, name)?;
+    let file = if let ExpEnum::String(file, _) = &file.get().data {
+        file.to_string()
+    } else {
+        return Err(LispError::with_kind(
+            ":type",
+            format!("{}: file must be a string", name),
+        ));
+    };
+    let line = match &line.get().data {
+        ExpEnum::Int(line) if *line >= 1 => *line as usize,
+        ExpEnum::Int(line) => {
+            return Err(LispError::with_kind(
+                ":type",
+                format!("{}: line must be 1 or more, got {}", name, line),
+            ))
+        }
+        _ => {
+            return Err(LispError::with_kind(
+                ":type",
+                format!("{}: line must be an int", name),
+            ))
+        }
+    };
+    Ok((file, line))
+}
+
+fn builtin_break_at(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    let breakpoint = breakpoint_params(environment, args, "break-at")?;
+    let debugger = &mut environment.debugger;
+    if !debugger.breakpoints.contains(&breakpoint) {
+        debugger.breakpoints.push(breakpoint);
+    }
+    debugger.update_active();
+    Ok(Expression::make_true())
+}
+
+fn builtin_break_remove(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    let breakpoint = breakpoint_params(environment, args, "break-remove")?;
+    let debugger = &mut environment.debugger;
+    let len = debugger.breakpoints.len();
+    debugger.breakpoints.retain(|b| *b != breakpoint);
+    let removed = len != debugger.breakpoints.len();
+    debugger.update_active();
+    Ok(if removed {
+        Expression::make_true()
+    } else {
+        Expression::make_false()
+    })
+}
+
+fn builtin_breakpoints(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    params_done(args, "breakpoints")?;
+    let breakpoints = environment
+        .debugger
+        .breakpoints
+        .iter()
+        .map(|(file, line)| {
+            Expression::with_list(vec![
+                Expression::alloc_data(ExpEnum::String(file.clone().into(), None)),
+                Expression::alloc_data(ExpEnum::Int(*line as i64)),
+            ])
+        })
+        .collect();
+    Ok(Expression::with_list(breakpoints))
+}
+
+pub fn add_debug_builtins<S: BuildHasher>(
+    interner: &mut Interner,
+    data: &mut HashMap<&'static str, (Expression, String), S>,
+) {
+    data.insert(
+        interner.intern("break"),
+        Expression::make_function(
+            builtin_break,
+            r#"Usage: (break) -> nil
+
+Pause evaluation and enter the debugger.  The debugger reads commands from
+*stdin* (and writes to *stdout*), enter help for a list.  Any input that is not
+a command is evaluated in the paused frame (so can use or set! the locals of the
+function break was called from).  Use continue to resume, step, next and out to
+resume and pause again at the next form.
+
+Section: debug
+
+Example:
+(def break-test-in "$(temp-dir)/break-test.in")
+(def break-test-out "$(temp-dir)/break-test.out")
+(let ((topen (open break-test-in :create :truncate)))
+    (write-line topen "locals")
+    (write-line topen "(set! x (* x 10))")
+    (write-line topen "continue")
+    (close topen))
+(defn break-test (x) (break) (+ x 1))
+(test::assert-equal 21 (dyn *stdin* (open break-test-in :read)
+    (out> break-test-out (break-test 2))))
+(let ((topen (open break-test-out :read)))
+    (test::assert-true (str-starts-with "Break: break-test" (read-line topen)))
+    (test::assert-equal "debug>   x = 2\n" (read-line topen))
+    (test::assert-equal "debug> 20\n" (read-line topen))
+    (close topen))
+"#,
+        ),
+    );
+    data.insert(
+        interner.intern("debug"),
+        Expression::make_function(
+            builtin_debug,
+            r#"Usage: (debug lambda) -> lambda
+
+Enter the debugger (see break) every time lambda is called, it is paused before
+the first form of the body is evaluated.  Use undebug to stop.
+
+Section: debug
+
+Example:
+(def debug-test-in "$(temp-dir)/debug-test.in")
+(def debug-test-out "$(temp-dir)/debug-test.out")
+(let ((topen (open debug-test-in :create :truncate)))
+    (write-line topen "next")
+    (write-line topen "(set! x 5)")
+    (write-line topen "continue")
+    (close topen))
+(defn debug-test (x) (+ x 1))
+(debug debug-test)
+(test::assert-equal 6 (dyn *stdin* (open debug-test-in :read)
+    (out> debug-test-out (debug-test 1))))
+(undebug debug-test)
+(test::assert-equal 2 (debug-test 1))
+(let ((topen (open debug-test-out :read)))
+    (test::assert-true (str-starts-with "Entered: debug-test" (read-line topen)))
+    (test::assert-true (str-starts-with "debug> Step: debug-test" (read-line topen)))
+    (close topen))
+"#,
+        ),
+    );
+    data.insert(
+        interner.intern("undebug"),
+        Expression::make_function(
+            builtin_undebug,
+            r#"Usage: (undebug lambda) -> lambda
+
+Stop entering the debugger when lambda is called (see debug).
+
+Section: debug
+
+Example:
+(defn undebug-test (x) (+ x 1))
+(debug undebug-test)
+(undebug undebug-test)
+(test::assert-equal 2 (undebug-test 1))
+"#,
+        ),
+    );
+    data.insert(
+        interner.intern("break-at"),
+        Expression::make_function(
+            builtin_break_at,
+            r#"Usage: (break-at file line) -> #t
+
+Set a breakpoint, enter the debugger (see break) before evaluating a form that
+starts on line of file.  File can be the end of the path (after a /) of the
+loaded file.
+
+Section: debug
+
+Example:
+(def break-at-src "$(temp-dir)/break-at-test.lisp")
+(def break-at-in "$(temp-dir)/break-at-test.in")
+(def break-at-out "$(temp-dir)/break-at-test.out")
+(let ((topen (open break-at-src :create :truncate)))
+    (write-line topen "(defn break-at-test (x)")
+    (write-line topen "  (+ x 1))")
+    (close topen))
+(let ((topen (open break-at-in :create :truncate)))
+    (write-line topen "x")
+    (write-line topen "continue")
+    (close topen))
+(load break-at-src)
+(break-at "break-at-test.lisp" 2)
+(test::assert-equal '(#("break-at-test.lisp" 2)) (breakpoints))
+(test::assert-equal 4 (dyn *stdin* (open break-at-in :read)
+    (out> break-at-out (break-at-test 3))))
+(test::assert-true (break-remove "break-at-test.lisp" 2))
+(test::assert-false (break-remove "break-at-test.lisp" 2))
+(test::assert-equal :type (error-kind (get-error (break-at "break-at-test.lisp" 0))))
+(test::assert-error (break-at "break-at-test.lisp" -2))
+(let ((topen (open break-at-out :read)))
+    (test::assert-true (str-starts-with "Breakpoint: break-at-test" (read-line topen)))
+    (read-line topen)
+    (test::assert-equal "debug> 3\n" (read-line topen))
+    (close topen))
+"#,
+        ),
+    );
+    data.insert(
+        interner.intern("break-remove"),
+        Expression::make_function(
+            builtin_break_remove,
+            r#"Usage: (break-remove file line) -> #t/#f
+
+Remove a breakpoint set with break-at, false if it was not set.
+
+Section: debug
+
+Example:
+(break-at "break-remove-test.lisp" 1)
+(test::assert-true (break-remove "break-remove-test.lisp" 1))
+(test::assert-false (break-remove "break-remove-test.lisp" 1))
+"#,
+        ),
+    );
+    data.insert(
+        interner.intern("breakpoints"),
+        Expression::make_function(
+            builtin_breakpoints,
+            r#"Usage: (breakpoints) -> vector
+
+Return the breakpoints set with break-at as a vector of #(file line).
+
+Section: debug
+
+Example:
+(break-at "breakpoints-test.lisp" 3)
+(test::assert-true (in? (breakpoints) (vec "breakpoints-test.lisp" 3)))
+(break-remove "breakpoints-test.lisp" 3)
+(test::assert-false (in? (breakpoints) (vec "breakpoints-test.lisp" 3)))
+"#,
+        ),
+    );
+}
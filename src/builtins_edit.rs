@@ -15,7 +15,7 @@ use crate::types::*;
 fn load_repl_settings(repl_settings: &Expression) -> ReplSettings {
     let mut ret = ReplSettings::default();
     if let ExpEnum::HashMap(repl_settings) = &repl_settings.get().data {
-        if let Some(keybindings) = repl_settings.get(":keybindings") {
+        if let Some(keybindings) = hash_get_str(repl_settings, ":keybindings") {
             if let ExpEnum::Symbol(keybindings, _) = &keybindings.get().data {
                 match &keybindings[..] {
                     ":vi" => ret.key_bindings = Keys::Vi,
@@ -24,7 +24,7 @@ fn load_repl_settings(repl_settings: &Expression) -> ReplSettings {
                 }
             };
         }
-        if let Some(max) = repl_settings.get(":max-history") {
+        if let Some(max) = hash_get_str(repl_settings, ":max-history") {
             if let ExpEnum::Int(max) = &max.get().data {
                 if *max >= 0 {
                     ret.max_history = *max as usize;
@@ -35,7 +35,7 @@ fn load_repl_settings(repl_settings: &Expression) -> ReplSettings {
                 eprintln!("Max history must be a positive integer: {}", max);
             };
         }
-        if let Some(vi_esc) = repl_settings.get(":vi_esc_sequence") {
+        if let Some(vi_esc) = hash_get_str(repl_settings, ":vi_esc_sequence") {
             let mut i = vi_esc.iter();
             if let Some(arg0) = i.next() {
                 if let ExpEnum::String(keys, _) = &arg0.get().data {
@@ -68,22 +68,22 @@ fn load_repl_settings(repl_settings: &Expression) -> ReplSettings {
                 );
             }
         }
-        if let Some(prefix) = repl_settings.get(":vi-normal-prompt-prefix") {
+        if let Some(prefix) = hash_get_str(repl_settings, ":vi-normal-prompt-prefix") {
             if let ExpEnum::String(prefix, _) = &prefix.get().data {
                 ret.vi_normal_prompt_prefix = Some(prefix.to_string());
             };
         }
-        if let Some(suffix) = repl_settings.get(":vi-normal-prompt-suffix") {
+        if let Some(suffix) = hash_get_str(repl_settings, ":vi-normal-prompt-suffix") {
             if let ExpEnum::String(suffix, _) = &suffix.get().data {
                 ret.vi_normal_prompt_suffix = Some(suffix.to_string());
             };
         }
-        if let Some(prefix) = repl_settings.get(":vi-insert-prompt-prefix") {
+        if let Some(prefix) = hash_get_str(repl_settings, ":vi-insert-prompt-prefix") {
             if let ExpEnum::String(prefix, _) = &prefix.get().data {
                 ret.vi_insert_prompt_prefix = Some(prefix.to_string());
             };
         }
-        if let Some(suffix) = repl_settings.get(":vi-insert-prompt-suffix") {
+        if let Some(suffix) = hash_get_str(repl_settings, ":vi-insert-prompt-suffix") {
             if let ExpEnum::String(suffix, _) = &suffix.get().data {
                 ret.vi_insert_prompt_suffix = Some(suffix.to_string());
             };
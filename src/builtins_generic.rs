@@ -0,0 +1,385 @@
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+
+use crate::builtins_util::*;
+use crate::environment::*;
+use crate::eval::*;
+use crate::interner::*;
+use crate::types::*;
+
+#[derive(Debug)]
+struct Method {
+    // The dispatch value each required param must have, None matches anything.
+    specializers: Vec<Option<HashKey>>,
+    method: Expression,
+}
+
+#[derive(Debug)]
+struct Generic {
+    name: &'static str,
+    // The lambda defgeneric binds, it calls generic-call.
+    wrapper: Expression,
+    // Number of required params, these are the ones dispatched on.
+    required: usize,
+    dispatch: Option<Expression>,
+    methods: Vec<Method>,
+    // The method picked for each list of dispatch values seen.
+    cache: HashMap<Vec<HashKey>, Expression>,
+}
+
+/// State for generic functions (defgeneric and defmethod).
+#[derive(Debug, Default)]
+pub struct GenericState {
+    // Keyed by namespace::name from when the generic was made.
+    generics: HashMap<&'static str, Generic>,
+}
+
+impl GenericState {
+    pub fn new() -> Self {
+        GenericState {
+            generics: HashMap::new(),
+        }
+    }
+}
+
+// A dispatch value (or specializer) as the key methods are matched on.
+fn dispatch_key(value: &Expression, form: &str) -> Result<HashKey, LispError> {
+    HashKey::from_exp(value).map_err(|mut err| {
+        err.reason = format!("{}: dispatch value {}", form, err.reason);
+        err
+    })
+}
+
+// The type generics dispatch on, the same as the type builtin (so the name of
+// the struct for struct instances).
+fn type_key(environment: &mut Environment, value: &Expression) -> HashKey {
+    let name = value.display_type();
+    HashKey::Symbol(environment.interner.intern(&name))
+}
+
+// True if a is a more specific method than b, the first param specialized in
+// one and not the other decides.
+fn more_specific(a: &[Option<HashKey>], b: &[Option<HashKey>]) -> bool {
+    for (a, b) in a.iter().zip(b.iter()) {
+        match (a, b) {
+            (Some(_), None) => return true,
+            (None, Some(_)) => return false,
+            _ => {}
+        }
+    }
+    false
+}
+
+fn find_method(generic: &Generic, key: &[HashKey]) -> Option<Expression> {
+    let mut best: Option<&Method> = None;
+    for method in &generic.methods {
+        let applies = method
+            .specializers
+            .iter()
+            .enumerate()
+            .all(|(i, spec)| spec.is_none() || key.get(i) == spec.as_ref());
+        if applies {
+            best = match best {
+                Some(best) if !more_specific(&method.specializers, &best.specializers) => {
+                    Some(best)
+                }
+                _ => Some(method),
+            };
+        }
+    }
+    best.map(|method| method.method.clone())
+}
+
+fn builtin_generic_call(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    let id = param_eval(environment, args, "generic-call")?;
+    let call_args = param_eval(environment, args, "generic-call")?;
+    params_done(args, "generic-call")?;
+    let id = if let ExpEnum::Symbol(id, _) = &id.get().data {
+        *id
+    } else {
+        return Err(LispError::new("generic-call: requires a symbol"));
+    };
+    let (name, required, dispatch) = if let Some(generic) = environment.generics.generics.get(id) {
+        (generic.name, generic.required, generic.dispatch.clone())
+    } else {
+        return Err(LispError::new(format!(
+            "generic-call: {} is not a generic function",
+            id
+        )));
+    };
+    let call_args: Vec<Expression> = call_args.iter().collect();
+    if call_args.len() < required {
+        return Err(LispError::with_kind(
+            ":arity",
+            format!(
+                "{}: wrong number of parameters, expected at least {} got {}",
+                name,
+                required,
+                call_args.len()
+            ),
+        ));
+    }
+    let mut key = Vec::with_capacity(required);
+    if let Some(dispatch) = dispatch {
+        let values = call_evaled(environment, &dispatch, call_args.clone())?;
+        if matches!(values.get().data, ExpEnum::Pair(_, _) | ExpEnum::Vector(_)) {
+            for value in values.iter() {
+                key.push(dispatch_key(&value, name)?);
+            }
+        } else {
+            key.push(dispatch_key(&values, name)?);
+        }
+    } else {
+        for arg in &call_args[..required] {
+            key.push(type_key(environment, arg));
+        }
+    }
+    let method = if let Some(generic) = environment.generics.generics.get_mut(id) {
+        if let Some(method) = generic.cache.get(&key) {
+            Some(method.clone())
+        } else {
+            let method = find_method(generic, &key);
+            if let Some(method) = &method {
+                generic.cache.insert(key.clone(), method.clone());
+            }
+            method
+        }
+    } else {
+        None
+    };
+    if let Some(method) = method {
+        call_evaled(environment, &method, call_args)
+    } else {
+        let mut err = LispError::with_kind(
+            ":no-applicable-method",
+            format!(
+                "{}: no applicable method for ({})",
+                name,
+                key.iter()
+                    .map(|k| k.to_string())
+                    .collect::<Vec<String>>()
+                    .join(" ")
+            ),
+        );
+        err.data = Some(Expression::with_list(call_args));
+        Err(err)
+    }
+}
+
+fn builtin_make_generic(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    let name = param_eval(environment, args, "make-generic")?;
+    let params = param_eval(environment, args, "make-generic")?;
+    let dispatch = if let Some(dispatch) = args.next() {
+        let dispatch = eval(environment, dispatch)?;
+        params_done(args, "make-generic")?;
+        let dispatch_d = dispatch.get();
+        match &dispatch_d.data {
+            ExpEnum::Nil => None,
+            ExpEnum::Lambda(_) | ExpEnum::Function(_) => Some(dispatch.clone()),
+            _ => {
+                return Err(LispError::with_kind(
+                    ":type",
+                    "make-generic: dispatch must be a lambda or builtin",
+                ))
+            }
+        }
+    } else {
+        None
+    };
+    let name = if let ExpEnum::Symbol(name, _) = &name.get().data {
+        *name
+    } else {
+        return Err(LispError::with_kind(
+            ":type",
+            "make-generic: name must be a symbol",
+        ));
+    };
+    let required = match &params.get().data {
+        ExpEnum::Pair(_, _) | ExpEnum::Vector(_) | ExpEnum::Nil => params
+            .iter()
+            .take_while(|p| !matches!(p.get().data, ExpEnum::Symbol(s, _) if s.starts_with('&')))
+            .count(),
+        _ => {
+            return Err(LispError::with_kind(
+                ":type",
+                "make-generic: params must be a list",
+            ))
+        }
+    };
+    let id = environment.interner.intern(&format!(
+        "{}::{}",
+        environment.namespace.borrow().name(),
+        name
+    ));
+    // Redefining a generic (reloading a file for example) keeps its methods.
+    if let Some(generic) = environment.generics.generics.get_mut(id) {
+        if generic.required != required && !generic.methods.is_empty() {
+            return Err(LispError::with_kind(
+                ":arity",
+                format!(
+                    "make-generic: {} has {} method(s) with {} required parameters, can not redefine it with {}",
+                    generic.name,
+                    generic.methods.len(),
+                    generic.required,
+                    required
+                ),
+            ));
+        }
+        generic.required = required;
+        generic.dispatch = dispatch;
+        generic.cache.clear();
+        return Ok(generic.wrapper.clone());
+    }
+    let wrapper = make_forwarding_fn(environment, false, "root::generic-call", id)?;
+    if let ExpEnum::Lambda(l) = &mut wrapper.get_mut().data {
+        l.name = Some(name);
+    }
+    environment.generics.generics.insert(
+        id,
+        Generic {
+            name,
+            wrapper: wrapper.clone(),
+            required,
+            dispatch,
+            methods: Vec::new(),
+            cache: HashMap::new(),
+        },
+    );
+    Ok(wrapper)
+}
+
+fn builtin_generic_add_method(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    let generic = param_eval(environment, args, "generic-add-method")?;
+    let specializers = param_eval(environment, args, "generic-add-method")?;
+    let method = param_eval(environment, args, "generic-add-method")?;
+    params_done(args, "generic-add-method")?;
+    if !matches!(method.get().data, ExpEnum::Lambda(_)) {
+        return Err(LispError::with_kind(
+            ":type",
+            "generic-add-method: method must be a lambda",
+        ));
+    }
+    let mut specs = Vec::new();
+    for spec in specializers.iter() {
+        specs.push(if spec.is_nil() {
+            None
+        } else {
+            Some(dispatch_key(&spec, "generic-add-method")?)
+        });
+    }
+    let state = environment
+        .generics
+        .generics
+        .values_mut()
+        .find(|g| g.wrapper.ptr_eq(&generic))
+        .ok_or_else(|| {
+            LispError::with_kind(
+                ":type",
+                format!(
+                    "generic-add-method: {} is not a generic function",
+                    generic.to_short_string(40)
+                ),
+            )
+        })?;
+    if specs.len() != state.required {
+        return Err(LispError::with_kind(
+            ":arity",
+            format!(
+                "defmethod: {} has {} required parameters, method has {}",
+                state.name,
+                state.required,
+                specs.len()
+            ),
+        ));
+    }
+    if let Some(old) = state.methods.iter_mut().find(|m| m.specializers == specs) {
+        old.method = method;
+    } else {
+        state.methods.push(Method {
+            specializers: specs,
+            method,
+        });
+    }
+    state.cache.clear();
+    Ok(generic)
+}
+
+pub fn add_generic_builtins<S: BuildHasher>(
+    interner: &mut Interner,
+    data: &mut HashMap<&'static str, (Expression, String), S>,
+) {
+    data.insert(
+        interner.intern("make-generic"),
+        Expression::make_function(
+            builtin_make_generic,
+            r#"Usage: (make-generic name params [dispatch]) -> lambda
+
+Make a generic function with the parameters params (a list), used by
+defgeneric.  The required params are dispatched on, by their types or the
+values dispatch returns.  Making a generic with the same name in the same
+namespace again returns the existing one (keeping its methods), this is an
+:arity error if it has methods and the number of required params changed.
+
+Section: struct
+
+Example:
+(def make-generic-test (make-generic 'make-generic-test '(x)))
+(generic-add-method make-generic-test '(Int) (fn (x) (+ x 1)))
+(test::assert-equal 2 (make-generic-test 1))
+(test::assert-true (= make-generic-test (make-generic 'make-generic-test '(x))))
+(test::assert-equal 2 (make-generic-test 1))
+(test::assert-equal :arity (error-kind (get-error (make-generic 'make-generic-test '(x y)))))
+(test::assert-equal 2 (make-generic-test 1))
+"#,
+        ),
+    );
+    data.insert(
+        interner.intern("generic-add-method"),
+        Expression::make_function(
+            builtin_generic_add_method,
+            r#"Usage: (generic-add-method generic specializers method) -> generic
+
+Add method (a lambda) to generic for the dispatch values in specializers (a
+list with one item per required param, nil matches anything), replaces a
+method with the same specializers.  Used by defmethod.
+
+Section: struct
+
+Example:
+(def generic-add-method-test (make-generic 'generic-add-method-test '(x y)))
+(generic-add-method generic-add-method-test '(Int nil) (fn (x y) :int-any))
+(generic-add-method generic-add-method-test '(Int String) (fn (x y) :int-string))
+(test::assert-equal :int-string (generic-add-method-test 1 "s"))
+(test::assert-equal :int-any (generic-add-method-test 1 2))
+(test::assert-error (generic-add-method generic-add-method-test '(Int) (fn (x) x)))
+(test::assert-error (generic-add-method (fn (x) x) '(Int) (fn (x) x)))
+"#,
+        ),
+    );
+    data.insert(
+        interner.intern("generic-call"),
+        Expression::make_function(
+            builtin_generic_call,
+            r#"Usage: (generic-call id args) -> result
+
+Used by the lambdas make-generic returns, calls the method of the generic id
+for args (a list) or raises a :no-applicable-method error.
+
+Section: struct
+
+Example:
+(def generic-call-test (make-generic 'generic-call-test '(x)))
+(test::assert-equal :no-applicable-method (error-kind (get-error (generic-call-test 1))))
+"#,
+        ),
+    );
+}
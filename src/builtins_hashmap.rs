@@ -1,4 +1,3 @@
-use std::borrow::Cow;
 use std::collections::HashMap;
 use std::hash::BuildHasher;
 
@@ -7,31 +6,13 @@ use crate::eval::*;
 use crate::interner::*;
 use crate::types::*;
 
-#[allow(clippy::ptr_arg)]
-pub(crate) fn cow_to_ref(environment: &mut Environment, input: &Cow<'static, str>) -> &'static str {
-    match input {
-        Cow::Borrowed(s) => s,
-        Cow::Owned(s) => environment.interner.intern(&s),
-    }
-}
-
 fn build_map(
-    environment: &mut Environment,
-    mut map: HashMap<&'static str, Expression>,
+    mut map: HashMap<HashKey, Expression>,
     assocs: &mut dyn Iterator<Item = Expression>,
 ) -> Result<Expression, LispError> {
     for key_val in assocs {
         if let ExpEnum::Pair(key, val) = &key_val.get().data {
-            match &key.get().data {
-                ExpEnum::Symbol(sym, _) => map.insert(sym, val.clone()),
-                ExpEnum::String(s, _) => map.insert(cow_to_ref(environment, &s), val.clone()),
-                ExpEnum::Char(ch) => map.insert(cow_to_ref(environment, &ch), val.clone()),
-                _ => {
-                    return Err(LispError::new(
-                        "make-hash key can only be a symbol or string",
-                    ))
-                }
-            };
+            map.insert(HashKey::from_exp(key)?, val.clone());
         } else {
             return Err(LispError::new(
                 "make-hash each association must be a pair (key . val)",
@@ -45,15 +26,15 @@ fn builtin_make_hash(
     environment: &mut Environment,
     args: &mut dyn Iterator<Item = Expression>,
 ) -> Result<Expression, LispError> {
-    let map: HashMap<&'static str, Expression> = HashMap::new();
+    let map: HashMap<HashKey, Expression> = HashMap::new();
     if let Some(assocs) = args.next() {
         if args.next().is_none() {
             let assocs = eval(environment, assocs)?;
             let assocs_d = assocs.get();
             match &assocs_d.data {
-                ExpEnum::Pair(_, _) => build_map(environment, map, &mut assocs.iter()),
+                ExpEnum::Pair(_, _) => build_map(map, &mut assocs.iter()),
                 ExpEnum::Nil => Ok(Expression::alloc_data(ExpEnum::HashMap(map))),
-                ExpEnum::Vector(_) => build_map(environment, map, &mut assocs.iter()),
+                ExpEnum::Vector(_) => build_map(map, &mut assocs.iter()),
                 _ => Err(LispError::new("make-hash takes a sequence")),
             }
         } else {
@@ -64,6 +45,23 @@ fn builtin_make_hash(
     }
 }
 
+fn builtin_hash_map(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    let mut map: HashMap<HashKey, Expression> = HashMap::new();
+    while let Some(key) = args.next() {
+        let key = eval(environment, key)?;
+        let val = if let Some(val) = args.next() {
+            eval(environment, val)?
+        } else {
+            return Err(LispError::new("hash-map takes key value pairs"));
+        };
+        map.insert(HashKey::from_exp(&key)?, val);
+    }
+    Ok(Expression::alloc_data(ExpEnum::HashMap(map)))
+}
+
 fn builtin_hash_set(
     environment: &mut Environment,
     args: &mut dyn Iterator<Item = Expression>,
@@ -73,29 +71,12 @@ fn builtin_hash_set(
             if let Some(val) = args.next() {
                 if args.next().is_none() {
                     let exp_map = eval(environment, map)?;
-                    let key = eval(environment, key)?;
+                    let key = HashKey::from_exp(&eval(environment, key)?)?;
                     let val = eval(environment, val)?;
                     let mut exp_map_d = exp_map.get_mut();
                     if let ExpEnum::HashMap(map) = &mut exp_map_d.data {
-                        match &key.get().data {
-                            ExpEnum::Symbol(sym, _) => {
-                                map.insert(*sym, val);
-                                return Ok(exp_map.clone());
-                            }
-                            ExpEnum::String(s, _) => {
-                                map.insert(cow_to_ref(environment, &s), val);
-                                return Ok(exp_map.clone());
-                            }
-                            ExpEnum::Char(ch) => {
-                                map.insert(cow_to_ref(environment, &ch), val);
-                                return Ok(exp_map.clone());
-                            }
-                            _ => {
-                                return Err(LispError::new(
-                                    "hash-set! key can only be a symbol or string",
-                                ));
-                            }
-                        }
+                        map.insert(key, val);
+                        return Ok(exp_map.clone());
                     }
                 }
             }
@@ -108,37 +89,14 @@ fn builtin_hash_remove(
     environment: &mut Environment,
     args: &mut dyn Iterator<Item = Expression>,
 ) -> Result<Expression, LispError> {
-    fn do_rem(map: &mut HashMap<&'static str, Expression>, sym: &str) -> Expression {
-        let old = map.remove(sym);
-        if let Some(old) = old {
-            old
-        } else {
-            Expression::make_nil()
-        }
-    }
     if let Some(map) = args.next() {
         if let Some(key) = args.next() {
             if args.next().is_none() {
                 let map = eval(environment, map)?;
-                let key = eval(environment, key)?;
+                let key = HashKey::from_exp(&eval(environment, key)?)?;
                 let mut map_d = map.get_mut();
                 if let ExpEnum::HashMap(map) = &mut map_d.data {
-                    match &key.get().data {
-                        ExpEnum::Symbol(sym, _) => {
-                            return Ok(do_rem(map, sym));
-                        }
-                        ExpEnum::String(s, _) => {
-                            return Ok(do_rem(map, &s));
-                        }
-                        ExpEnum::Char(ch) => {
-                            return Ok(do_rem(map, &ch));
-                        }
-                        _ => {
-                            return Err(LispError::new(
-                                "hash-remove! key can only be a symbol or string",
-                            ));
-                        }
-                    }
+                    return Ok(map.remove(&key).unwrap_or_else(Expression::make_nil));
                 }
             }
         }
@@ -152,46 +110,24 @@ fn builtin_hash_get(
     environment: &mut Environment,
     args: &mut dyn Iterator<Item = Expression>,
 ) -> Result<Expression, LispError> {
-    fn do_get(
-        environment: &mut Environment,
-        map: &HashMap<&'static str, Expression>,
-        sym: &str,
-        default: Option<Expression>,
-    ) -> Result<Expression, LispError> {
-        let old = map.get(sym);
-        if let Some(old) = old {
-            Ok(old.clone())
-        } else if let Some(exp) = default {
-            eval(environment, exp)
-        } else {
-            Ok(Expression::make_nil())
-        }
-    }
     if let Some(map) = args.next() {
         if let Some(key) = args.next() {
             let default = args.next();
             if args.next().is_none() {
                 let map = eval(environment, map)?;
-                let key = eval(environment, key)?;
-                let map_d = map.get();
-                if let ExpEnum::HashMap(map) = &map_d.data {
-                    match &key.get().data {
-                        ExpEnum::Symbol(sym, _) => {
-                            return do_get(environment, map, sym, default);
-                        }
-                        ExpEnum::String(s, _) => {
-                            return do_get(environment, map, &s, default);
-                        }
-                        ExpEnum::Char(ch) => {
-                            return do_get(environment, map, &ch, default);
-                        }
-                        _ => {
-                            return Err(LispError::new(
-                                "hash-get: key can only be a symbol or string",
-                            ));
-                        }
-                    }
-                }
+                let key = HashKey::from_exp(&eval(environment, key)?)?;
+                let old = if let ExpEnum::HashMap(map) = &map.get().data {
+                    map.get(&key).cloned()
+                } else {
+                    return Err(LispError::new("hash-get: first form must be a hashmap"));
+                };
+                return if let Some(old) = old {
+                    Ok(old)
+                } else if let Some(exp) = default {
+                    eval(environment, exp)
+                } else {
+                    Ok(Expression::make_nil())
+                };
             }
         }
     }
@@ -204,39 +140,18 @@ fn builtin_hash_haskey(
     environment: &mut Environment,
     args: &mut dyn Iterator<Item = Expression>,
 ) -> Result<Expression, LispError> {
-    fn do_has(map: &HashMap<&'static str, Expression>, sym: &str) -> Expression {
-        if map.contains_key(sym) {
-            Expression::make_true()
-        } else {
-            Expression::make_false()
-        }
-    }
     if let Some(map) = args.next() {
         if let Some(key) = args.next() {
             if args.next().is_none() {
                 let map = eval(environment, map)?;
-                let key = eval(environment, key)?;
+                let key = HashKey::from_exp(&eval(environment, key)?)?;
                 let map_d = map.get();
                 if let ExpEnum::HashMap(map) = &map_d.data {
-                    match &key.get().data {
-                        ExpEnum::Symbol(sym, _) => {
-                            return Ok(do_has(map, sym));
-                        }
-                        ExpEnum::String(s, _) => {
-                            return Ok(do_has(map, &s));
-                        }
-                        ExpEnum::Char(ch) => {
-                            return Ok(do_has(map, &ch));
-                        }
-                        _ => {
-                            let msg =
-                                format!("hash-haskey key can only be a symbol or string {:?}", key);
-                            return Err(LispError::new(
-                                msg,
-                                //"hash-haskey key can only be a symbol or string",
-                            ));
-                        }
-                    }
+                    return if map.contains_key(&key) {
+                        Ok(Expression::make_true())
+                    } else {
+                        Ok(Expression::make_false())
+                    };
                 }
             }
         }
@@ -255,13 +170,7 @@ fn builtin_hash_keys(
             let map = eval(environment, map)?;
             let map_d = map.get();
             if let ExpEnum::HashMap(map) = &map_d.data {
-                let mut key_list = Vec::with_capacity(map.len());
-                for key in map.keys() {
-                    key_list.push(Expression::alloc_data(ExpEnum::Symbol(
-                        environment.interner.intern(key),
-                        SymLoc::None,
-                    )));
-                }
+                let key_list = map.keys().map(HashKey::to_exp).collect();
                 return Ok(Expression::with_list(key_list));
             }
         }
@@ -304,6 +213,10 @@ If associations is provided (makes an empty map if not) then it is a list of
 pairs (key . value) that populate the intial map.  Neither key nor value in the
 associations will be evaluated.
 
+Keys can be symbols, keywords, strings, chars, ints or lists and vectors of
+those (compared by value).  A symbol, string or char with the same text is the
+same key.
+
 Section: hashmap
 
 Example:
@@ -330,9 +243,45 @@ Example:
 (test::assert-equal \"val one\" (hash-get tst-hash :keyv1))
 (test::assert-equal \"val two\" (hash-get tst-hash :keyv2))
 (test::assert-equal 'tst-hash-val (hash-get tst-hash :keyv3))
+(def tst-hash (make-hash '((1 . \"one\")((1 2) . \"list\")(#(1 #\\a) . \"vector\"))))
+(test::assert-equal \"one\" (hash-get tst-hash 1))
+(test::assert-equal \"list\" (hash-get tst-hash (list 1 2)))
+(test::assert-equal \"vector\" (hash-get tst-hash (vec 1 #\\a)))
+(test::assert-false (hash-haskey tst-hash (list 1 2 3)))
+(test::assert-error (make-hash '((1.5 . \"float\"))))
 "
         ),
     );
+    data.insert(
+        interner.intern("hash-map"),
+        Expression::make_function(
+            builtin_hash_map,
+            "Usage: (hash-map key value ...)
+
+Make a new hash map from key value pairs, unlike make-hash the keys and values
+are evaluated.  The reader turns {key value ...} into (hash-map key value ...).
+Note this makes {} an empty hashmap in lisp forms so a command needs \"{}\" to get
+a literal {} (find -exec for instance), shell forms ($(...)) still pass {} as is.
+
+Section: hashmap
+
+Example:
+(def tst-hash-val \"some val\")
+(def tst-hash (hash-map :key1 \"val one\" 'key2 (str \"val \" \"two\") \"key3\" tst-hash-val))
+(test::assert-equal 3 (length (hash-keys tst-hash)))
+(test::assert-equal \"val one\" (hash-get tst-hash :key1))
+(test::assert-equal \"val two\" (hash-get tst-hash 'key2))
+(test::assert-equal \"some val\" (hash-get tst-hash \"key3\"))
+(def tst-hash {:a 1 :b (+ 1 1)})
+(test::assert-equal 2 (hash-get tst-hash :b))
+(test::assert-equal 0 (length (hash-keys {})))
+(test::assert-equal \"{}\n\" (str (syscall 'echo \"{}\")))
+(test::assert-equal \"{}\n\" (str $(echo {})))
+(test::assert-error (syscall 'echo {}))
+(test::assert-error (hash-map :a))
+",
+        ),
+    );
     data.insert(
         interner.intern("hash-set!"),
         Expression::make_function(
@@ -462,7 +411,8 @@ Example:
             builtin_hash_keys,
             "Usage: (hash-keys hashmap)
 
-Returns a vector of all the hashmaps keys.  The keys will be unordered.
+Returns a vector of all the hashmaps keys.  The keys will be unordered and are
+returned as they were first added (a string key is returned as a string).
 
 Section: hashmap
 
@@ -471,9 +421,11 @@ Example:
 (test::assert-equal 4 (length (hash-keys tst-hash)))
 (test::assert-true (in? (hash-keys tst-hash) :key1) \" Test :key1\")
 (test::assert-true (in? (hash-keys tst-hash) 'key2) \" Test key2\")
-; Note string or char used as a key will be a symbol in the hash-keys list...
-(test::assert-true (in? (hash-keys tst-hash) 'S) \" Test S\")
-(test::assert-true (in? (hash-keys tst-hash) 'key3) \" Test key3\")
+(test::assert-true (in? (hash-keys tst-hash) #\\S) \" Test S\")
+(test::assert-true (in? (hash-keys tst-hash) \"key3\") \" Test key3\")
+(test::assert-equal \"String\" (type (first (hash-keys (hash-map \"a\" 1)))))
+(test::assert-equal '(1 2) (first (hash-keys (hash-map '(1 2) 1))))
+(test::assert-equal \"Int\" (type (first (hash-keys (hash-map 1 1)))))
 (test::assert-false (in? (hash-keys tst-hash) :key4))
 ",
         ),
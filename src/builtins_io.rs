@@ -116,10 +116,14 @@ fn builtin_open(
                 if error_nil {
                     return Ok(Expression::make_nil());
                 } else {
-                    return Err(LispError::new(format!(
-                        "open: Error opening {}: {}",
-                        file_name, err
-                    )));
+                    return Err(LispError::with_kind(
+                        ":io",
+                        format!("open: Error opening {}: {}", file_name, err),
+                    )
+                    .with_data(Expression::alloc_data(ExpEnum::String(
+                        file_name.into(),
+                        None,
+                    ))));
                 }
             }
         };
@@ -1,12 +1,285 @@
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::hash::BuildHasher;
+use std::ops::{Add, Mul, Neg, Sub};
 
+use crate::bigint::{BigInt, MAX_POW_BITS};
 use crate::builtins_util::*;
 use crate::environment::*;
 use crate::eval::eval;
 use crate::interner::*;
+use crate::rational::Rational;
 use crate::types::*;
 
+/// A number for the math builtins.  Int arithmetic that overflows produces a
+/// Big and dividing ints produces a Rational, a Big that fits in an i64 or a
+/// Rational that is a whole number is always turned back into an Int.
+#[derive(Clone, Debug)]
+pub enum Number {
+    Int(i64),
+    Big(BigInt),
+    Rational(Rational),
+    Float(f64),
+}
+
+impl Number {
+    pub fn from_exp(exp: &Expression) -> Option<Number> {
+        match &exp.get().data {
+            ExpEnum::Int(i) => Some(Number::Int(*i)),
+            ExpEnum::BigInt(b) => Some(Number::Big(b.clone())),
+            ExpEnum::Rational(r) => Some(Number::Rational(r.clone())),
+            ExpEnum::Float(f) => Some(Number::Float(*f)),
+            ExpEnum::Values(v) if !v.is_empty() => Number::from_exp(&v[0]),
+            _ => None,
+        }
+    }
+
+    fn from_big(b: BigInt) -> Number {
+        match b.to_i64() {
+            Some(i) => Number::Int(i),
+            None => Number::Big(b),
+        }
+    }
+
+    pub fn from_rational(r: Rational) -> Number {
+        if r.is_whole() {
+            Number::from_big(r.numer().clone())
+        } else {
+            Number::Rational(r)
+        }
+    }
+
+    pub fn into_data(self) -> ExpEnum {
+        match self {
+            Number::Int(i) => ExpEnum::Int(i),
+            Number::Big(b) => ExpEnum::BigInt(b),
+            Number::Rational(r) => ExpEnum::Rational(r),
+            Number::Float(f) => ExpEnum::Float(f),
+        }
+    }
+
+    pub fn into_exp(self) -> Expression {
+        Expression::alloc_data(self.into_data())
+    }
+
+    fn is_float(&self) -> bool {
+        matches!(self, Number::Float(_))
+    }
+
+    fn is_rational(&self) -> bool {
+        matches!(self, Number::Rational(_))
+    }
+
+    fn is_integer(&self) -> bool {
+        matches!(self, Number::Int(_) | Number::Big(_))
+    }
+
+    fn is_zero(&self) -> bool {
+        match self {
+            Number::Int(i) => *i == 0,
+            Number::Big(b) => b.is_zero(),
+            // Never zero, that would be an Int.
+            Number::Rational(_) => false,
+            Number::Float(f) => *f == 0.0,
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        match self {
+            Number::Int(i) => *i as f64,
+            Number::Big(b) => b.to_f64(),
+            Number::Rational(r) => r.to_f64(),
+            Number::Float(f) => *f,
+        }
+    }
+
+    // Only used when neither number is a float or rational.
+    fn to_big(&self) -> BigInt {
+        match self {
+            Number::Int(i) => BigInt::from_i64(*i),
+            Number::Big(b) => b.clone(),
+            Number::Rational(r) => r.floor(),
+            Number::Float(f) => BigInt::from_i64(*f as i64),
+        }
+    }
+
+    // Only used when neither number is a float.
+    fn to_rational(&self) -> Rational {
+        match self {
+            Number::Rational(r) => r.clone(),
+            _ => Rational::from_big(self.to_big()),
+        }
+    }
+
+    /// Divide, ints divide to an exact rational unless truncate is set (then
+    /// they truncate like i64 division).  None if other is zero.
+    pub fn checked_div(&self, other: &Number, truncate: bool) -> Option<Number> {
+        if other.is_zero() {
+            return None;
+        }
+        Some(match (self, other) {
+            (Number::Int(a), Number::Int(b))
+                if a.checked_rem(*b) == Some(0) || (truncate && a.checked_div(*b).is_some()) =>
+            {
+                Number::Int(a / b)
+            }
+            _ if self.is_float() || other.is_float() => {
+                Number::Float(self.to_f64() / other.to_f64())
+            }
+            _ if truncate && self.is_integer() && other.is_integer() => {
+                Number::from_big(self.to_big().div_rem(&other.to_big())?.0)
+            }
+            _ => Number::from_rational(self.to_rational().checked_div(&other.to_rational())?),
+        })
+    }
+
+    /// Remainder of truncating division, None if other is zero or either is
+    /// not an int.
+    pub fn checked_rem(&self, other: &Number) -> Option<Number> {
+        if other.is_zero() || !self.is_integer() || !other.is_integer() {
+            return None;
+        }
+        Some(match (self, other) {
+            (Number::Int(a), Number::Int(b)) => Number::Int(a.wrapping_rem(*b)),
+            _ => Number::from_big(self.to_big().div_rem(&other.to_big())?.1),
+        })
+    }
+
+    /// Floored division and modulo (the quotient rounds toward negative
+    /// infinity so the modulo has the sign of other), None if other is zero or
+    /// either is not an int.
+    pub fn checked_div_mod_floor(&self, other: &Number) -> Option<(Number, Number)> {
+        if other.is_zero() || !self.is_integer() || !other.is_integer() {
+            return None;
+        }
+        if let (Number::Int(a), Number::Int(b)) = (self, other) {
+            if let Some(quot) = a.checked_div(*b) {
+                let rem = a % b;
+                return Some(if rem != 0 && (rem < 0) != (*b < 0) {
+                    (Number::Int(quot - 1), Number::Int(rem + b))
+                } else {
+                    (Number::Int(quot), Number::Int(rem))
+                });
+            }
+        }
+        let other = other.to_big();
+        let (quot, rem) = self.to_big().div_rem(&other)?;
+        Some(
+            if !rem.is_zero() && rem.is_negative() != other.is_negative() {
+                (
+                    Number::from_big(&quot - &BigInt::from_i64(1)),
+                    Number::from_big(&rem + &other),
+                )
+            } else {
+                (Number::from_big(quot), Number::from_big(rem))
+            },
+        )
+    }
+
+    pub fn abs(&self) -> Number {
+        match self {
+            Number::Int(i) => match i.checked_abs() {
+                Some(i) => Number::Int(i),
+                None => Number::Big(BigInt::from_i64(*i).abs()),
+            },
+            Number::Big(b) => Number::Big(b.abs()),
+            Number::Rational(r) => Number::Rational(r.abs()),
+            Number::Float(f) => Number::Float(f.abs()),
+        }
+    }
+
+    /// Raise to a non-negative int power, exact unless self is a float.  None
+    /// if an exact result would be too large (see BigInt::checked_pow).
+    pub fn checked_pow(&self, power: u32) -> Option<Number> {
+        Some(match self {
+            Number::Int(i) => match i.checked_pow(power) {
+                Some(i) => Number::Int(i),
+                None => Number::from_big(BigInt::from_i64(*i).checked_pow(power)?),
+            },
+            Number::Big(b) => Number::from_big(b.checked_pow(power)?),
+            Number::Rational(r) => Number::from_rational(r.checked_pow(power)?),
+            Number::Float(f) => Number::Float(f.powi(power as i32)),
+        })
+    }
+
+    /// Apply one of the float rounding fns, exact numbers round exactly.
+    fn round_with(&self, float_fn: fn(f64) -> f64, ratio_fn: fn(&Rational) -> BigInt) -> Number {
+        match self {
+            Number::Float(f) => Number::Int(float_fn(*f) as i64),
+            Number::Rational(r) => Number::from_big(ratio_fn(r)),
+            _ => self.clone(),
+        }
+    }
+}
+
+macro_rules! number_op {
+    ($trait:ident, $method:ident, $checked:ident, $op:tt) => {
+        impl $trait for &Number {
+            type Output = Number;
+
+            fn $method(self, other: &Number) -> Number {
+                match (self, other) {
+                    (Number::Int(a), Number::Int(b)) => match a.$checked(*b) {
+                        Some(i) => Number::Int(i),
+                        None => Number::Big(&BigInt::from_i64(*a) $op &BigInt::from_i64(*b)),
+                    },
+                    _ if self.is_float() || other.is_float() => {
+                        Number::Float(self.to_f64() $op other.to_f64())
+                    }
+                    _ if self.is_rational() || other.is_rational() => {
+                        Number::from_rational(&self.to_rational() $op &other.to_rational())
+                    }
+                    _ => Number::from_big(&self.to_big() $op &other.to_big()),
+                }
+            }
+        }
+    };
+}
+
+number_op!(Add, add, checked_add, +);
+number_op!(Sub, sub, checked_sub, -);
+number_op!(Mul, mul, checked_mul, *);
+
+impl Neg for &Number {
+    type Output = Number;
+
+    fn neg(self) -> Number {
+        match self {
+            Number::Int(i) => match i.checked_neg() {
+                Some(i) => Number::Int(i),
+                None => Number::Big(-&BigInt::from_i64(*i)),
+            },
+            Number::Big(b) => Number::from_big(-b),
+            Number::Rational(r) => Number::Rational(-r),
+            Number::Float(f) => Number::Float(-f),
+        }
+    }
+}
+
+impl PartialEq for Number {
+    fn eq(&self, other: &Number) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for Number {
+    fn partial_cmp(&self, other: &Number) -> Option<Ordering> {
+        match (self, other) {
+            (Number::Int(a), Number::Int(b)) => Some(a.cmp(b)),
+            _ if self.is_float() || other.is_float() => self.to_f64().partial_cmp(&other.to_f64()),
+            _ if self.is_rational() || other.is_rational() => {
+                Some(self.to_rational().cmp(&other.to_rational()))
+            }
+            _ => Some(self.to_big().cmp(&other.to_big())),
+        }
+    }
+}
+
+/// Convert each expression to a Number, None if any are not numbers.
+pub fn parse_list_of_numbers(args: &[Expression]) -> Option<Vec<Number>> {
+    args.iter().map(Number::from_exp).collect()
+}
+
 fn norm_value(arg: Expression) -> Expression {
     let arg_d = arg.get();
     if let ExpEnum::Values(v) = &arg_d.data {
@@ -31,38 +304,25 @@ pub fn add_root_math_builtins<S: BuildHasher>(
             |environment: &mut Environment,
              args: &mut dyn Iterator<Item = Expression>|
              -> Result<Expression, LispError> {
-                let mut sum = 0;
-                let mut sum_float = 0.0;
-                let mut is_float = false;
+                let mut sum = Number::Int(0);
                 for arg in args {
                     let a = norm_value(eval(environment, arg)?);
-                    let a_d = a.get();
-                    match &a_d.data {
-                        ExpEnum::Int(i) if is_float => sum_float += *i as f64,
-                        ExpEnum::Int(i) => sum += i,
-                        ExpEnum::Float(f) if is_float => sum_float += f,
-                        ExpEnum::Float(f) => {
-                            is_float = true;
-                            sum_float = (sum as f64) + f;
-                        }
-                        _ => {
-                            return Err(LispError::new(format!(
-                                "Can only add numbers, got {}/{}.",
-                                a.display_type(),
-                                a
-                            )))
+                    match Number::from_exp(&a) {
+                        Some(num) => sum = &sum + &num,
+                        None => {
+                            return Err(LispError::with_kind(
+                                ":type",
+                                format!("Can only add numbers, got {}/{}.", a.display_type(), a),
+                            ))
                         }
                     }
                 }
-                if is_float {
-                    Ok(Expression::alloc_data(ExpEnum::Float(sum_float)))
-                } else {
-                    Ok(Expression::alloc_data(ExpEnum::Int(sum)))
-                }
+                Ok(sum.into_exp())
             },
             "Usage: (+ number*)
 
-Add a sequence of numbers.  (+) will return 0.
+Add a sequence of numbers.  (+) will return 0.  Ints that overflow a 64 bit
+int become bignums (type BigInt) instead of wrapping.
 
 Section: math
 
@@ -77,6 +337,9 @@ Example:
 (test::assert-equal 6 (+ 1 5))
 (test::assert-equal 6.5 (+ 1 5.5))
 (test::assert-equal 7 (+ 1 2 4))
+(test::assert-equal 9223372036854775808 (+ 9223372036854775807 1))
+(test::assert-equal \"BigInt\" (type (+ 9223372036854775807 1)))
+(test::assert-equal \"Int\" (type (+ 9223372036854775807 1 -1)))
 (test::assert-error (+ 1 2 4 \"5\"))
 ",
         ),
@@ -88,45 +351,22 @@ Example:
             |environment: &mut Environment,
              args: &mut dyn Iterator<Item = Expression>|
              -> Result<Expression, LispError> {
-                let mut res = 0;
-                let mut res_float = 0.0;
-                let mut is_float = false;
-                if let Ok(a) = param_eval(environment, args, "multiply") {
-                    match norm_value(a).get().data {
-                        ExpEnum::Int(i) => res = i,
-                        ExpEnum::Float(f) => {
-                            is_float = true;
-                            res_float = f;
-                        }
-                        _ => return Err(LispError::new("Can only multiply numbers.")),
-                    }
-                } else {
-                    // Missing args so return 1.
-                    return Ok(Expression::alloc_data(ExpEnum::Int(1)));
-                }
+                let mut res = Number::Int(1);
                 for a in args {
                     let a = norm_value(eval(environment, a)?);
-                    let a_d = a.get();
-                    match &a_d.data {
-                        ExpEnum::Int(i) if is_float => res_float *= *i as f64,
-                        ExpEnum::Int(i) => res *= i,
-                        ExpEnum::Float(f) if is_float => res_float *= f,
-                        ExpEnum::Float(f) => {
-                            is_float = true;
-                            res_float = (res as f64) * f;
+                    match Number::from_exp(&a) {
+                        Some(num) => res = &res * &num,
+                        None => {
+                            return Err(LispError::with_kind(":type", "Can only multiply numbers."))
                         }
-                        _ => return Err(LispError::new("Can only multiply numbers.")),
                     }
                 }
-                if is_float {
-                    Ok(Expression::alloc_data(ExpEnum::Float(res_float)))
-                } else {
-                    Ok(Expression::alloc_data(ExpEnum::Int(res)))
-                }
+                Ok(res.into_exp())
             },
             "Usage: (* number*)
 
-Multiply a sequence of numbers.  (*) will return 1.
+Multiply a sequence of numbers.  (*) will return 1.  Ints that overflow a 64
+bit int become bignums.
 
 Section: math
 
@@ -144,6 +384,7 @@ Example:
 (test::assert-equal 16.0 (* 2 2.0 4))
 (test::assert-equal 16.0 (* 2.0 2.0 4.0))
 (test::assert-equal 55.0000000001 (* 100 0.55))
+(test::assert-equal 85070591730234615847396907784232501249 (* 9223372036854775807 9223372036854775807))
 (test::assert-error (* 1 2 4 \"5\"))
 ",
         ),
@@ -155,46 +396,28 @@ Example:
             |environment: &mut Environment,
              args: &mut dyn Iterator<Item = Expression>|
              -> Result<Expression, LispError> {
-                let mut res = 0;
-                let mut res_float = 0.0;
-                let mut is_float = false;
-                let mut has_two = false;
-                match norm_value(param_eval(environment, args, "subtract")?)
-                    .get()
-                    .data
-                {
-                    ExpEnum::Int(i) => res = i,
-                    ExpEnum::Float(f) => {
-                        is_float = true;
-                        res_float = f;
+                let first = norm_value(param_eval(environment, args, "subtract")?);
+                let mut res = match Number::from_exp(&first) {
+                    Some(num) => num,
+                    None => {
+                        return Err(LispError::with_kind(":type", "Can only subtract numbers."))
                     }
-                    _ => return Err(LispError::new("Can only subtract numbers.")),
-                }
+                };
+                let mut has_two = false;
                 for a in args {
                     has_two = true;
                     let a = norm_value(eval(environment, a)?);
-                    let a_d = a.get();
-                    match &a_d.data {
-                        ExpEnum::Int(i) if is_float => res_float -= *i as f64,
-                        ExpEnum::Int(i) => res -= i,
-                        ExpEnum::Float(f) if is_float => res_float -= f,
-                        ExpEnum::Float(f) => {
-                            is_float = true;
-                            res_float = (res as f64) - f;
+                    match Number::from_exp(&a) {
+                        Some(num) => res = &res - &num,
+                        None => {
+                            return Err(LispError::with_kind(":type", "Can only subtract numbers."))
                         }
-                        _ => return Err(LispError::new("Can only subtract numbers.")),
                     }
                 }
-                if is_float {
-                    if has_two {
-                        Ok(Expression::alloc_data(ExpEnum::Float(res_float)))
-                    } else {
-                        Ok(Expression::alloc_data(ExpEnum::Float(-res_float)))
-                    }
-                } else if has_two {
-                    Ok(Expression::alloc_data(ExpEnum::Int(res)))
+                if has_two {
+                    Ok(res.into_exp())
                 } else {
-                    Ok(Expression::alloc_data(ExpEnum::Int(-res)))
+                    Ok((-&res).into_exp())
                 }
             },
             "Usage: (- number+)
@@ -213,6 +436,8 @@ Example:
 (test::assert-equal -4.5 (- 1 5.5))
 (test::assert-equal 4 (- 10 2 4))
 (test::assert-equal 4.9 (- 10.9 2 4))
+(test::assert-equal -9223372036854775810 (- -9223372036854775807 3))
+(test::assert-equal 9223372036854775808 (- -9223372036854775808))
 ",
         ),
     );
@@ -223,65 +448,61 @@ Example:
             |environment: &mut Environment,
              args: &mut dyn Iterator<Item = Expression>|
              -> Result<Expression, LispError> {
-                let mut res = 0;
-                let mut res_float = 0.0;
-                let mut is_float = false;
+                let truncate = lookup_expression(environment, "*int-div-truncate*")
+                    .map(|exp| !exp.is_falsy())
+                    .unwrap_or(false);
+                let first = norm_value(param_eval(environment, args, "divide")?);
+                let mut res = match Number::from_exp(&first) {
+                    Some(num) => num,
+                    None => return Err(LispError::with_kind(":type", "Can only divide numbers.")),
+                };
                 let mut has_two = false;
-                match norm_value(param_eval(environment, args, "divide")?)
-                    .get()
-                    .data
-                {
-                    ExpEnum::Int(i) => res = i,
-                    ExpEnum::Float(f) => {
-                        is_float = true;
-                        res_float = f;
-                    }
-                    _ => return Err(LispError::new("Can only divide numbers.")),
-                }
                 for a in args {
                     has_two = true;
                     let a = norm_value(eval(environment, a)?);
-                    let a_d = a.get();
-                    match &a_d.data {
-                        ExpEnum::Int(i) if *i == 0 => {
-                            return Err(LispError::new("Can not divide by 0."))
+                    let num = match Number::from_exp(&a) {
+                        Some(num) => num,
+                        None => {
+                            return Err(LispError::with_kind(":type", "Can only divide numbers."))
                         }
-                        ExpEnum::Int(i) if is_float => res_float /= *i as f64,
-                        ExpEnum::Int(i) => res /= i,
-                        ExpEnum::Float(f) if *f == 0.0 => {
+                    };
+                    res = match res.checked_div(&num, truncate) {
+                        Some(res) => res,
+                        None if num.is_float() => {
                             return Err(LispError::new("Can not divide by 0.0."))
                         }
-                        ExpEnum::Float(f) if is_float => res_float /= f,
-                        ExpEnum::Float(f) => {
-                            is_float = true;
-                            res_float = (res as f64) / f;
-                        }
-                        _ => return Err(LispError::new("Can only divide numbers.")),
-                    }
+                        None => return Err(LispError::new("Can not divide by 0.")),
+                    };
                 }
                 if !has_two {
                     Err(LispError::new("divide requires at least two numbers."))
-                } else if is_float {
-                    Ok(Expression::alloc_data(ExpEnum::Float(res_float)))
                 } else {
-                    Ok(Expression::alloc_data(ExpEnum::Int(res)))
+                    Ok(res.into_exp())
                 }
             },
             "Usage: (/ number+)
 
-Divide a sequence of numbers.  Requires at least two numbers.
+Divide a sequence of numbers.  Requires at least two numbers.  Ints that do not
+divide evenly produce an exact rational (1/5 for instance), set
+*int-div-truncate* to true to truncate to an int instead.
 
 Section: math
 Example:
 (ns-import 'math)
 (test::assert-equal 5 (/ 50 10))
 (test::assert-equal 5 (/ 50.0 10.0))
-(test::assert-equal 0 (/ 1 5))
+(test::assert-equal 1/5 (/ 1 5))
+(test::assert-equal \"Rational\" (type (/ 1 5)))
+(test::assert-equal 1 (/ 1/5 1/5))
+(test::assert-equal 3/8 (/ 3/4 2))
+(test::assert-equal 0 (let ((*int-div-truncate* #t)) (/ 1 5)))
 (test::assert-equal .2 (/ 1.0 5))
 (test::assert-equal .2 (/ 1.0 5.0))
 (test::assert-equal 5.5 (/ 5.5 1))
 (test::assert-equal 2 (/ 16 2 4))
 (test::assert-equal 5 (/ 100 2 5 2))
+(test::assert-equal 4294967296 (/ 18446744073709551616 4294967296))
+(test::assert-equal 9223372036854775808 (/ -9223372036854775808 -1))
 (test::assert-error (/))
 (test::assert-error (/ 1))
 (test::assert-error (/ 1 0))
@@ -297,17 +518,22 @@ Example:
             |environment: &mut Environment,
              args: &mut dyn Iterator<Item = Expression>|
              -> Result<Expression, LispError> {
-                let arg1 =
-                    norm_value(param_eval(environment, args, "modulo")?).make_int(environment)?;
-                let arg2 =
-                    norm_value(param_eval(environment, args, "modulo")?).make_int(environment)?;
+                let arg1 = norm_value(param_eval(environment, args, "modulo")?);
+                let arg2 = norm_value(param_eval(environment, args, "modulo")?);
                 params_done(args, "modulo")?;
-                if arg2 == 0 {
-                    Err(LispError::new(
+                match (Number::from_exp(&arg1), Number::from_exp(&arg2)) {
+                    (Some(a), Some(b)) if a.is_integer() && b.is_integer() => {
+                        match a.checked_rem(&b) {
+                            Some(res) => Ok(res.into_exp()),
+                            None => Err(LispError::new(
+                                "modulo: expected two ints, second can not be 0",
+                            )),
+                        }
+                    }
+                    _ => Err(LispError::with_kind(
+                        ":type",
                         "modulo: expected two ints, second can not be 0",
-                    ))
-                } else {
-                    Ok(Expression::alloc_data(ExpEnum::Int(arg1 % arg2)))
+                    )),
                 }
             },
             "Usage: (% int int)
@@ -321,15 +547,159 @@ Example:
 (test::assert-equal 0 (% 50 10))
 (test::assert-equal 5 (% 55 10))
 (test::assert-equal 1 (% 1 2))
+(test::assert-equal -1 (% -18446744073709551617 4294967296))
 (test::assert-error (%))
 (test::assert-error (% 1))
 (test::assert-error (% 1 2 3))
 (test::assert-error (% 1 2.0))
+(test::assert-error (% 7/2 2))
 ",
         ),
     );
 }
 
+// The numerator and denominator of an int or rational.
+fn exact_parts(exp: &Expression, form: &str) -> Result<(BigInt, BigInt), LispError> {
+    match Number::from_exp(exp) {
+        Some(Number::Rational(r)) => Ok((r.numer().clone(), r.denom().clone())),
+        Some(num) if num.is_integer() => Ok((num.to_big(), BigInt::from_i64(1))),
+        _ => Err(LispError::with_kind(
+            ":type",
+            format!(
+                "{}: expected an int or rational, got {}",
+                form,
+                exp.display_type()
+            ),
+        )),
+    }
+}
+
+// Evaluate the next arg, it must be an int (or bignum).
+fn param_integer(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+    form: &str,
+) -> Result<Number, LispError> {
+    let arg = norm_value(param_eval(environment, args, form)?);
+    match Number::from_exp(&arg) {
+        Some(num) if num.is_integer() => Ok(num),
+        _ => Err(LispError::with_kind(
+            ":type",
+            format!("{}: expected an int, got {}", form, arg.display_type()),
+        )),
+    }
+}
+
+// The shift count for shl and shr.
+fn param_shift(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+    form: &str,
+) -> Result<u32, LispError> {
+    let count = param_eval(environment, args, form)?.make_int(environment)?;
+    params_done(args, form)?;
+    if count < 0 || count > u32::MAX as i64 {
+        Err(LispError::new(format!(
+            "{}: shift count must be a non-negative int, got {}",
+            form, count
+        )))
+    } else {
+        Ok(count as u32)
+    }
+}
+
+// Fold the int args with a bitwise op starting with init, bignums act like
+// two's complement ints that are as wide as needed.
+fn fold_bits(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+    form: &str,
+    init: i64,
+    int_fn: fn(i64, i64) -> i64,
+    big_fn: fn(&BigInt, &BigInt) -> BigInt,
+) -> Result<Expression, LispError> {
+    let mut res = Number::Int(init);
+    let mut args = args.peekable();
+    while args.peek().is_some() {
+        res = match (res, param_integer(environment, &mut args, form)?) {
+            (Number::Int(a), Number::Int(b)) => Number::Int(int_fn(a, b)),
+            (a, b) => Number::from_big(big_fn(&a.to_big(), &b.to_big())),
+        };
+    }
+    Ok(res.into_exp())
+}
+
+// Apply int_fn (or big_fn for a bignum) to the one int arg.
+fn int_bits(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+    form: &str,
+    int_fn: fn(i64) -> i64,
+    big_fn: fn(&BigInt) -> Number,
+) -> Result<Expression, LispError> {
+    let num = param_integer(environment, args, form)?;
+    params_done(args, form)?;
+    Ok(match num {
+        Number::Int(i) => Number::Int(int_fn(i)),
+        _ => big_fn(&num.to_big()),
+    }
+    .into_exp())
+}
+
+// Bits in the fewest 64 bit words that hold b as two's complement, popcount
+// and leading-zeros count within these so a bignum acts like a wider int.
+fn word_bits(b: &BigInt) -> u64 {
+    let pos = if b.is_negative() { !b } else { b.clone() };
+    (pos.bits() / 64 + 1) * 64
+}
+
+// Floored division of two int args, returns the quotient and modulo.
+fn div_mod_floor(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+    form: &str,
+) -> Result<(Number, Number), LispError> {
+    let num = param_integer(environment, args, form)?;
+    let div = param_integer(environment, args, form)?;
+    params_done(args, form)?;
+    num.checked_div_mod_floor(&div)
+        .ok_or_else(|| LispError::new(format!("{}: can not divide by 0", form)))
+}
+
+// Fold the int args with f, starting with init.
+fn fold_integers(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+    form: &str,
+    init: i64,
+    f: fn(BigInt, BigInt) -> BigInt,
+) -> Result<Expression, LispError> {
+    let mut res = BigInt::from_i64(init);
+    let mut args = args.peekable();
+    while args.peek().is_some() {
+        res = f(res, param_integer(environment, &mut args, form)?.to_big());
+    }
+    Ok(Number::from_big(res).into_exp())
+}
+
+// Round the one number in args to an int, exact numbers round exactly.
+fn round_number(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+    float_fn: fn(f64) -> f64,
+    ratio_fn: fn(&Rational) -> BigInt,
+) -> Result<Expression, LispError> {
+    let args = make_args(environment, args)?;
+    if args.len() != 1 {
+        Err(LispError::new("expected one number"))
+    } else if let Some(num) = Number::from_exp(&args[0]) {
+        Ok(num.round_with(float_fn, ratio_fn).into_exp())
+    } else {
+        let arg1 = args[0].make_float(environment)?;
+        Ok(Expression::alloc_data(ExpEnum::Int(float_fn(arg1) as i64)))
+    }
+}
+
 pub fn add_math_builtins<S: BuildHasher>(
     interner: &mut Interner,
     data: &mut HashMap<&'static str, (Expression, String), S>,
@@ -400,6 +770,29 @@ Example:
              args: &mut dyn Iterator<Item = Expression>|
              -> Result<Expression, LispError> {
                 let mut args = make_args(environment, args)?;
+                if args.len() == 2 {
+                    // An int or rational to a non-negative int power is exact.
+                    if let (Some(base), Some(Number::Int(power))) =
+                        (Number::from_exp(&args[0]), Number::from_exp(&args[1]))
+                    {
+                        if !base.is_float() && power >= 0 {
+                            // Past u32::MAX only 0, 1 and -1 are in range and
+                            // for those just the parity matters.
+                            let power = if power > u32::MAX as i64 {
+                                u32::MAX - 1 + (power % 2) as u32
+                            } else {
+                                power as u32
+                            };
+                            let res = base.checked_pow(power).ok_or_else(|| {
+                                LispError::new(format!(
+                                    "pow: result would be over {} bits",
+                                    MAX_POW_BITS
+                                ))
+                            })?;
+                            return Ok(res.into_exp());
+                        }
+                    }
+                }
                 let floats = parse_list_of_floats(environment, &mut args)?;
                 if floats.len() != 2 {
                     Err(LispError::new("expected two numbers"))
@@ -411,7 +804,10 @@ Example:
             },
             "Usage: (pow base power)
 
-Raise first argument to power of second argument.
+Raise first argument to power of second argument.  An int or rational raised
+to a non-negative int power is exact (a bignum if it is too large for a 64 bit
+int), otherwise the result is a float.  Like shl it is an error if an exact
+result would be over 2^20 bits (about 300,000 digits).
 
 Section: math
 
@@ -422,6 +818,17 @@ Example:
 (test::assert-equal (pow 8 15) (* (pow 8 10) (pow 8 5)))
 (test::assert-equal (pow 100 3) (/ (pow 100 5) (pow 100 2)))
 (test::assert-equal 1 (pow 85 0))
+(test::assert-equal \"Int\" (type (pow 2 62)))
+(test::assert-equal 18446744073709551616 (pow 2 64))
+(test::assert-equal 0.25 (pow 2 -2))
+(test::assert-equal 6.25 (pow 2.5 2))
+(test::assert-equal 4/9 (pow 2/3 2))
+(test::assert-error (pow 3 4000000000))
+(test::assert-error (pow 2 5000000000))
+(test::assert-equal \"Float\" (type (pow 3.0 4000000000)))
+(test::assert-equal 1 (pow 1 4000000000))
+(test::assert-equal -1 (pow -1 5000000001))
+(test::assert-equal 0 (pow 0 5000000000))
 ",
         ),
     );
@@ -468,12 +875,13 @@ Example:
             |environment: &mut Environment,
              args: &mut dyn Iterator<Item = Expression>|
              -> Result<Expression, LispError> {
-                let mut args = make_args(environment, args)?;
-                let floats = parse_list_of_floats(environment, &mut args)?;
-                if floats.len() != 1 {
+                let args = make_args(environment, args)?;
+                if args.len() != 1 {
                     Err(LispError::new("expected one number"))
+                } else if let Some(num) = Number::from_exp(&args[0]) {
+                    Ok(num.abs().into_exp())
                 } else {
-                    let arg1 = floats.get(0).unwrap();
+                    let arg1 = args[0].make_float(environment)?;
                     Ok(Expression::alloc_data(ExpEnum::Float(arg1.abs())))
                 }
             },
@@ -488,6 +896,7 @@ Example:
 (test::assert-equal 2.0 (abs 2))
 (test::assert-equal 144 (abs -144))
 (test::assert-equal 4.53 (abs -4.53))
+(test::assert-equal 9223372036854775808 (abs -9223372036854775808))
 ",
         ),
     );
@@ -498,14 +907,7 @@ Example:
             |environment: &mut Environment,
              args: &mut dyn Iterator<Item = Expression>|
              -> Result<Expression, LispError> {
-                let mut args = make_args(environment, args)?;
-                let floats = parse_list_of_floats(environment, &mut args)?;
-                if floats.len() != 1 {
-                    Err(LispError::new("expected one number"))
-                } else {
-                    let arg1 = floats.get(0).unwrap();
-                    Ok(Expression::alloc_data(ExpEnum::Int(arg1.floor() as i64)))
-                }
+                round_number(environment, args, f64::floor, Rational::floor)
             },
             "Usage: (floor value)
 
@@ -518,6 +920,8 @@ Example:
 (test::assert-equal 2.0 (floor 2))
 (test::assert-equal 144 (floor 144.444444))
 (test::assert-equal 4 (floor 4.53))
+(test::assert-equal -3 (floor -5/2))
+(test::assert-equal 3 (floor 10/3))
 ",
         ),
     );
@@ -528,14 +932,7 @@ Example:
             |environment: &mut Environment,
              args: &mut dyn Iterator<Item = Expression>|
              -> Result<Expression, LispError> {
-                let mut args = make_args(environment, args)?;
-                let floats = parse_list_of_floats(environment, &mut args)?;
-                if floats.len() != 1 {
-                    Err(LispError::new("expected one number"))
-                } else {
-                    let arg1 = floats.get(0).unwrap();
-                    Ok(Expression::alloc_data(ExpEnum::Int(arg1.ceil() as i64)))
-                }
+                round_number(environment, args, f64::ceil, Rational::ceil)
             },
             "Usage: (ceil value)
 
@@ -548,6 +945,8 @@ Example:
 (test::assert-equal 2.0 (ceil 2))
 (test::assert-equal 145 (ceil 144.444444))
 (test::assert-equal 5 (ceil 4.53))
+(test::assert-equal -2 (ceil -5/2))
+(test::assert-equal 4 (ceil 10/3))
 ",
         ),
     );
@@ -558,18 +957,12 @@ Example:
             |environment: &mut Environment,
              args: &mut dyn Iterator<Item = Expression>|
              -> Result<Expression, LispError> {
-                let mut args = make_args(environment, args)?;
-                let floats = parse_list_of_floats(environment, &mut args)?;
-                if floats.len() != 1 {
-                    Err(LispError::new("expected one number"))
-                } else {
-                    let arg1 = floats.get(0).unwrap();
-                    Ok(Expression::alloc_data(ExpEnum::Int(arg1.round() as i64)))
-                }
+                round_number(environment, args, f64::round, Rational::round)
             },
             "Usage: (round arg)
 
-Round arg to nearest int value.
+Round arg to nearest int value, a rational half way between two ints rounds
+away from zero.
 
 Section: math
 
@@ -578,6 +971,61 @@ Example:
 (test::assert-equal 2.0 (round 2))
 (test::assert-equal 144 (round 144.444444))
 (test::assert-equal 5 (round 4.53))
+(test::assert-equal -3 (round -5/2))
+(test::assert-equal 3 (round 10/3))
+",
+        ),
+    );
+
+    data.insert(
+        interner.intern("numerator"),
+        Expression::make_function(
+            |environment: &mut Environment,
+             args: &mut dyn Iterator<Item = Expression>|
+             -> Result<Expression, LispError> {
+                let arg = param_eval(environment, args, "numerator")?;
+                params_done(args, "numerator")?;
+                Ok(Number::from_big(exact_parts(&arg, "numerator")?.0).into_exp())
+            },
+            "Usage: (numerator number)
+
+Returns the numerator of a rational (in lowest terms) or an int unchanged.
+
+Section: math
+
+Example:
+(ns-import 'math)
+(test::assert-equal 3 (numerator (/ 6 8)))
+(test::assert-equal -1 (numerator (/ -2 6)))
+(test::assert-equal 5 (numerator 5))
+(test::assert-error (numerator 1.5))
+",
+        ),
+    );
+
+    data.insert(
+        interner.intern("denominator"),
+        Expression::make_function(
+            |environment: &mut Environment,
+             args: &mut dyn Iterator<Item = Expression>|
+             -> Result<Expression, LispError> {
+                let arg = param_eval(environment, args, "denominator")?;
+                params_done(args, "denominator")?;
+                Ok(Number::from_big(exact_parts(&arg, "denominator")?.1).into_exp())
+            },
+            "Usage: (denominator number)
+
+Returns the denominator of a rational (in lowest terms, always positive), 1 for
+an int.
+
+Section: math
+
+Example:
+(ns-import 'math)
+(test::assert-equal 4 (denominator (/ 6 8)))
+(test::assert-equal 3 (denominator (/ -2 6)))
+(test::assert-equal 1 (denominator 5))
+(test::assert-error (denominator 1.5))
 ",
         ),
     );
@@ -967,6 +1415,453 @@ Section: math
 Example:
 (ns-import 'math)
 (test::assert-equal 0 (- *pi* (to-radians 180)))
+",
+        ),
+    );
+
+    data.insert(
+        interner.intern("bit-and"),
+        Expression::make_function(
+            |environment: &mut Environment,
+             args: &mut dyn Iterator<Item = Expression>|
+             -> Result<Expression, LispError> {
+                fold_bits(environment, args, "bit-and", -1, |a, b| a & b, |a, b| a & b)
+            },
+            "Usage: (bit-and int*)
+
+Bitwise and of the ints, (bit-and) is -1 (all bits set).  Negative ints are
+two's complement and bignums work like ints as wide as needed.
+
+Section: math
+
+Example:
+(ns-import 'math)
+(test::assert-equal 8 (bit-and 12 10))
+(test::assert-equal 2 (bit-and #b1110 #b0111 #b0011))
+(test::assert-equal #o755 (bit-and #o100755 #o777))
+(test::assert-equal -1 (bit-and))
+(test::assert-equal #xffffffffffffffff (bit-and -1 #xffffffffffffffff))
+(test::assert-equal 1 (bit-and 18446744073709551617 3))
+(test::assert-error (bit-and 1 2.0))
+",
+        ),
+    );
+
+    data.insert(
+        interner.intern("bit-or"),
+        Expression::make_function(
+            |environment: &mut Environment,
+             args: &mut dyn Iterator<Item = Expression>|
+             -> Result<Expression, LispError> {
+                fold_bits(environment, args, "bit-or", 0, |a, b| a | b, |a, b| a | b)
+            },
+            "Usage: (bit-or int*)
+
+Bitwise or of the ints, (bit-or) is 0 (see bit-and for negative ints and
+bignums).
+
+Section: math
+
+Example:
+(ns-import 'math)
+(test::assert-equal 14 (bit-or 12 10))
+(test::assert-equal #b111 (bit-or #b1 #b10 #b100))
+(test::assert-equal 0 (bit-or))
+(test::assert-equal 18446744073709551617 (bit-or (shl 1 64) 1))
+",
+        ),
+    );
+
+    data.insert(
+        interner.intern("bit-xor"),
+        Expression::make_function(
+            |environment: &mut Environment,
+             args: &mut dyn Iterator<Item = Expression>|
+             -> Result<Expression, LispError> {
+                fold_bits(environment, args, "bit-xor", 0, |a, b| a ^ b, |a, b| a ^ b)
+            },
+            "Usage: (bit-xor int*)
+
+Bitwise exclusive or of the ints, (bit-xor) is 0 (see bit-and for negative
+ints and bignums).
+
+Section: math
+
+Example:
+(ns-import 'math)
+(test::assert-equal 6 (bit-xor 12 10))
+(test::assert-equal 0 (bit-xor 5 5))
+(test::assert-equal 0 (bit-xor))
+(test::assert-equal -18446744073709551616 (bit-xor -1 #xffffffffffffffff))
+",
+        ),
+    );
+
+    data.insert(
+        interner.intern("bit-not"),
+        Expression::make_function(
+            |environment: &mut Environment,
+             args: &mut dyn Iterator<Item = Expression>|
+             -> Result<Expression, LispError> {
+                int_bits(
+                    environment,
+                    args,
+                    "bit-not",
+                    |i| !i,
+                    |b| Number::from_big(!b),
+                )
+            },
+            "Usage: (bit-not int)
+
+Bitwise not (flip every bit of the two's complement int), this is
+(- (- int) 1) so it works for bignums too.
+
+Section: math
+
+Example:
+(ns-import 'math)
+(test::assert-equal -1 (bit-not 0))
+(test::assert-equal 5 (bit-not -6))
+(test::assert-equal #o022 (bit-and (bit-not #o755) #o777))
+(test::assert-equal -18446744073709551617 (bit-not (shl 1 64)))
+",
+        ),
+    );
+
+    data.insert(
+        interner.intern("shl"),
+        Expression::make_function(
+            |environment: &mut Environment,
+             args: &mut dyn Iterator<Item = Expression>|
+             -> Result<Expression, LispError> {
+                let num = param_integer(environment, args, "shl")?;
+                let count = param_shift(environment, args, "shl")?;
+                let res = match num {
+                    Number::Int(i) if count < 63 && i.checked_mul(1 << count).is_some() => {
+                        Number::Int(i << count)
+                    }
+                    _ => Number::from_big(num.to_big().checked_shl(count).ok_or_else(|| {
+                        LispError::new(format!("shl: result would be over {} bits", MAX_POW_BITS))
+                    })?),
+                };
+                Ok(res.into_exp())
+            },
+            "Usage: (shl int count)
+
+Shift int left by count bits, this is (* int (pow 2 count)) so bits are never
+lost (the result is a bignum if it is too large for a 64 bit int).  It is an
+error if the result would be over 2^20 bits.
+
+Section: math
+
+Example:
+(ns-import 'math)
+(test::assert-equal 40 (shl 5 3))
+(test::assert-equal -40 (shl -5 3))
+(test::assert-equal 5 (shl 5 0))
+(test::assert-equal 18446744073709551616 (shl 1 64))
+(test::assert-equal -9223372036854775808 (shl -1 63))
+(test::assert-error (shl 1 -1))
+(test::assert-error (shl 1 4000000000))
+",
+        ),
+    );
+
+    data.insert(
+        interner.intern("shr"),
+        Expression::make_function(
+            |environment: &mut Environment,
+             args: &mut dyn Iterator<Item = Expression>|
+             -> Result<Expression, LispError> {
+                let num = param_integer(environment, args, "shr")?;
+                let count = param_shift(environment, args, "shr")?;
+                let res = match num {
+                    Number::Int(i) => Number::Int(i >> count.min(63)),
+                    _ => Number::from_big(num.to_big().shr(count)),
+                };
+                Ok(res.into_exp())
+            },
+            "Usage: (shr int count)
+
+Arithmetic shift int right by count bits (the sign is kept), this is
+(div int (pow 2 count)).
+
+Section: math
+
+Example:
+(ns-import 'math)
+(test::assert-equal 5 (shr 40 3))
+(test::assert-equal 2 (shr 5 1))
+(test::assert-equal -3 (shr -5 1))
+(test::assert-equal 0 (shr 5 100))
+(test::assert-equal -1 (shr -5 100))
+(test::assert-equal 1 (shr 18446744073709551616 64))
+(test::assert-equal -2 (shr -18446744073709551617 64))
+(test::assert-equal 1 (shr (shl 1 100000) 100000))
+(test::assert-equal -1 (shr -18446744073709551617 4000000000))
+",
+        ),
+    );
+
+    data.insert(
+        interner.intern("popcount"),
+        Expression::make_function(
+            |environment: &mut Environment,
+             args: &mut dyn Iterator<Item = Expression>|
+             -> Result<Expression, LispError> {
+                int_bits(
+                    environment,
+                    args,
+                    "popcount",
+                    |i| i.count_ones() as i64,
+                    |b| {
+                        Number::Int(if b.is_negative() {
+                            (word_bits(b) - (!b).count_ones()) as i64
+                        } else {
+                            b.count_ones() as i64
+                        })
+                    },
+                )
+            },
+            "Usage: (popcount int)
+
+Number of bits set in the 64 bit int (a negative int is two's complement).
+A bignum counts within the fewest 64 bit words that hold it.
+
+Section: math
+
+Example:
+(ns-import 'math)
+(test::assert-equal 0 (popcount 0))
+(test::assert-equal 3 (popcount #b10101))
+(test::assert-equal 24 (popcount #xffffff00))
+(test::assert-equal 64 (popcount -1))
+(test::assert-equal 65 (popcount (- (shl 1 65) 1)))
+(test::assert-equal 64 (popcount (- (shl 1 64))))
+",
+        ),
+    );
+
+    data.insert(
+        interner.intern("leading-zeros"),
+        Expression::make_function(
+            |environment: &mut Environment,
+             args: &mut dyn Iterator<Item = Expression>|
+             -> Result<Expression, LispError> {
+                int_bits(
+                    environment,
+                    args,
+                    "leading-zeros",
+                    |i| i.leading_zeros() as i64,
+                    |b| {
+                        Number::Int(if b.is_negative() {
+                            0
+                        } else {
+                            (word_bits(b) - b.bits()) as i64
+                        })
+                    },
+                )
+            },
+            "Usage: (leading-zeros int)
+
+Number of zero bits before the highest set bit of the 64 bit int.  A bignum
+counts within the fewest 64 bit words that hold it (with room for the sign).
+
+Section: math
+
+Example:
+(ns-import 'math)
+(test::assert-equal 64 (leading-zeros 0))
+(test::assert-equal 63 (leading-zeros 1))
+(test::assert-equal 56 (leading-zeros 255))
+(test::assert-equal 0 (leading-zeros -1))
+(test::assert-equal 63 (leading-zeros (shl 1 64)))
+(test::assert-equal 0 (leading-zeros -18446744073709551617))
+",
+        ),
+    );
+
+    data.insert(
+        interner.intern("div"),
+        Expression::make_function(
+            |environment: &mut Environment,
+             args: &mut dyn Iterator<Item = Expression>|
+             -> Result<Expression, LispError> {
+                Ok(div_mod_floor(environment, args, "div")?.0.into_exp())
+            },
+            "Usage: (div int int)
+
+Integer division that rounds toward negative infinity (floor of the exact
+quotient).  (+ (* (div a b) b) (mod a b)) is always a.
+
+Section: math
+
+Example:
+(ns-import 'math)
+(test::assert-equal 3 (div 7 2))
+(test::assert-equal -4 (div -7 2))
+(test::assert-equal -4 (div 7 -2))
+(test::assert-equal 3 (div -7 -2))
+(test::assert-equal 9223372036854775808 (div -9223372036854775808 -1))
+(test::assert-error (div 7 0))
+(test::assert-error (div 7 2.0))
+",
+        ),
+    );
+
+    data.insert(
+        interner.intern("rem"),
+        Expression::make_function(
+            |environment: &mut Environment,
+             args: &mut dyn Iterator<Item = Expression>|
+             -> Result<Expression, LispError> {
+                let num = param_integer(environment, args, "rem")?;
+                let div = param_integer(environment, args, "rem")?;
+                params_done(args, "rem")?;
+                match num.checked_rem(&div) {
+                    Some(res) => Ok(res.into_exp()),
+                    None => Err(LispError::new("rem: can not divide by 0")),
+                }
+            },
+            "Usage: (rem int int)
+
+Remainder of integer division that truncates toward zero (like %), the result
+has the sign of the first int.
+
+Section: math
+
+Example:
+(ns-import 'math)
+(test::assert-equal 1 (rem 7 2))
+(test::assert-equal -1 (rem -7 2))
+(test::assert-equal 1 (rem 7 -2))
+(test::assert-error (rem 7 0))
+",
+        ),
+    );
+
+    data.insert(
+        interner.intern("mod"),
+        Expression::make_function(
+            |environment: &mut Environment,
+             args: &mut dyn Iterator<Item = Expression>|
+             -> Result<Expression, LispError> {
+                Ok(div_mod_floor(environment, args, "mod")?.1.into_exp())
+            },
+            "Usage: (mod int int)
+
+Modulo of floored integer division (see div), the result has the sign of the
+second int.
+
+Section: math
+
+Example:
+(ns-import 'math)
+(test::assert-equal 1 (mod 7 2))
+(test::assert-equal 1 (mod -7 2))
+(test::assert-equal -1 (mod 7 -2))
+(test::assert-equal -1 (mod -7 -2))
+(test::assert-equal 6 (mod -1 7))
+(test::assert-error (mod 7 0))
+",
+        ),
+    );
+
+    data.insert(
+        interner.intern("gcd"),
+        Expression::make_function(
+            |environment: &mut Environment,
+             args: &mut dyn Iterator<Item = Expression>|
+             -> Result<Expression, LispError> {
+                fold_integers(environment, args, "gcd", 0, |a, b| a.gcd(&b))
+            },
+            "Usage: (gcd int*)
+
+Greatest common divisor of the ints, never negative.  (gcd) is 0.
+
+Section: math
+
+Example:
+(ns-import 'math)
+(test::assert-equal 4 (gcd 12 8))
+(test::assert-equal 6 (gcd -12 18 30))
+(test::assert-equal 5 (gcd 5 0))
+(test::assert-equal 0 (gcd))
+(test::assert-equal 4294967296 (gcd 18446744073709551616 4294967296))
+",
+        ),
+    );
+
+    data.insert(
+        interner.intern("lcm"),
+        Expression::make_function(
+            |environment: &mut Environment,
+             args: &mut dyn Iterator<Item = Expression>|
+             -> Result<Expression, LispError> {
+                fold_integers(environment, args, "lcm", 1, |a, b| {
+                    match (&a * &b).abs().div_rem(&a.gcd(&b)) {
+                        Some((lcm, _)) => lcm,
+                        // Both zero.
+                        None => BigInt::from_i64(0),
+                    }
+                })
+            },
+            "Usage: (lcm int*)
+
+Least common multiple of the ints, never negative.  (lcm) is 1.
+
+Section: math
+
+Example:
+(ns-import 'math)
+(test::assert-equal 24 (lcm 12 8))
+(test::assert-equal 60 (lcm 4 -6 10))
+(test::assert-equal 0 (lcm 5 0))
+(test::assert-equal 1 (lcm))
+(test::assert-equal 18446744073709551616 (lcm 18446744073709551616 4294967296))
+",
+        ),
+    );
+
+    data.insert(
+        interner.intern("int->str"),
+        Expression::make_function(
+            |environment: &mut Environment,
+             args: &mut dyn Iterator<Item = Expression>|
+             -> Result<Expression, LispError> {
+                let num = param_integer(environment, args, "int->str")?;
+                let radix = param_eval(environment, args, "int->str")?.make_int(environment)?;
+                params_done(args, "int->str")?;
+                if !(2..=36).contains(&radix) {
+                    return Err(LispError::new(format!(
+                        "int->str: radix must be from 2 to 36, got {}",
+                        radix
+                    )));
+                }
+                Ok(Expression::alloc_data(ExpEnum::String(
+                    num.to_big().to_str_radix(radix as u32).into(),
+                    None,
+                )))
+            },
+            "Usage: (int->str int radix)
+
+Format int as a string of digits in radix (2 to 36, digits past 9 are lower
+case letters).  There is no prefix, the reader reads the result back with #b,
+#o or #x for radix 2, 8 or 16.
+
+Section: math
+
+Example:
+(ns-import 'math)
+(test::assert-equal \"ff\" (int->str 255 16))
+(test::assert-equal \"-101\" (int->str -5 2))
+(test::assert-equal \"755\" (int->str #o755 8))
+(test::assert-equal \"0\" (int->str 0 10))
+(test::assert-equal \"z\" (int->str 35 36))
+(test::assert-equal \"10000000000000000\" (int->str (pow 2 64) 16))
+(test::assert-equal #xff (read (str \"#x\" (int->str 255 16))))
+(test::assert-error (int->str 5 1))
+(test::assert-error (int->str 5.0 10))
 ",
         ),
     );
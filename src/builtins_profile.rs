@@ -0,0 +1,339 @@
+use std::collections::HashMap;
+use std::fs;
+use std::hash::BuildHasher;
+use std::time::{Duration, Instant};
+
+use crate::builtins_debug::debug_write;
+use crate::builtins_util::*;
+use crate::environment::*;
+use crate::eval::*;
+use crate::interner::*;
+use crate::types::*;
+
+// Name of the frame for time spent outside of any lambda.
+const TOP: &str = "(top)";
+// Name of the frame for time spent waiting on child processes.
+const WAIT: &str = "(wait)";
+
+#[derive(Debug)]
+struct ProfileFrame {
+    name: &'static str,
+    start: Instant,
+    // Time spent in called lambdas and waiting on child processes.
+    child: Duration,
+    wait: Duration,
+}
+
+#[derive(Clone, Debug, Default)]
+struct FnProfile {
+    calls: u64,
+    inclusive: Duration,
+    exclusive: Duration,
+    wait: Duration,
+}
+
+/// State for the profiler (profile and --profile), records calls to lambdas
+/// while active.
+#[derive(Debug)]
+pub struct ProfileState {
+    /// True if call_lambda needs to record calls.
+    pub active: bool,
+    frames: Vec<ProfileFrame>,
+    fns: HashMap<&'static str, FnProfile>,
+    // Exclusive time by stack (names joined with ;).
+    folded: HashMap<String, Duration>,
+    // Where to write the folded stacks when profiling ends (from --profile).
+    folded_file: Option<String>,
+}
+
+impl ProfileState {
+    pub fn new() -> Self {
+        ProfileState {
+            active: false,
+            frames: Vec::new(),
+            fns: HashMap::new(),
+            folded: HashMap::new(),
+            folded_file: None,
+        }
+    }
+
+    /// Start profiling, folded_file is where profile-end writes the folded
+    /// stacks.
+    pub fn start(&mut self, folded_file: Option<String>) {
+        self.frames.clear();
+        self.fns.clear();
+        self.folded.clear();
+        self.folded_file = folded_file;
+        self.frames.push(ProfileFrame {
+            name: TOP,
+            start: Instant::now(),
+            child: Duration::default(),
+            wait: Duration::default(),
+        });
+        self.active = true;
+    }
+
+    fn stack_key(&self, leaf: &str) -> String {
+        let mut key = String::new();
+        for frame in &self.frames {
+            key.push_str(frame.name);
+            key.push(';');
+        }
+        key.push_str(leaf);
+        key
+    }
+
+    /// A lambda named name was called.
+    pub fn enter(&mut self, name: &'static str) {
+        self.fns.entry(name).or_default().calls += 1;
+        self.frames.push(ProfileFrame {
+            name,
+            start: Instant::now(),
+            child: Duration::default(),
+            wait: Duration::default(),
+        });
+    }
+
+    /// The lambda from the last enter returned.
+    pub fn exit(&mut self) {
+        let frame = if let Some(frame) = self.frames.pop() {
+            frame
+        } else {
+            return;
+        };
+        let elapsed = frame.start.elapsed();
+        let exclusive = elapsed.checked_sub(frame.child).unwrap_or_default();
+        *self.folded.entry(self.stack_key(frame.name)).or_default() += exclusive;
+        if let Some(parent) = self.frames.last_mut() {
+            parent.child += elapsed;
+            parent.wait += frame.wait;
+        } else {
+            return;
+        }
+        // Only the outermost call of a recursive lambda counts for inclusive time.
+        let recursive = self.frames.iter().any(|f| f.name == frame.name);
+        let stats = self.fns.entry(frame.name).or_default();
+        stats.exclusive += exclusive;
+        if !recursive {
+            stats.inclusive += elapsed;
+            stats.wait += frame.wait;
+        }
+    }
+
+    /// The lambda from the last enter made a tail call to a lambda named name.
+    pub fn tail_call(&mut self, name: &'static str) {
+        self.exit();
+        self.enter(name);
+    }
+
+    /// Time spent waiting on a child process.
+    pub fn wait(&mut self, time: Duration) {
+        let key = self.stack_key(WAIT);
+        if let Some(frame) = self.frames.last_mut() {
+            frame.child += time;
+            frame.wait += time;
+            *self.folded.entry(key).or_default() += time;
+        }
+    }
+
+    /// Stop profiling and return the report, the folded stacks and where they
+    /// should be written.
+    pub fn finish(&mut self) -> (String, String, Option<String>) {
+        let total = self
+            .frames
+            .first()
+            .map(|frame| frame.start.elapsed())
+            .unwrap_or_default();
+        while !self.frames.is_empty() {
+            self.exit();
+        }
+        self.active = false;
+        let wait = self.folded.iter().fold(Duration::default(), |acc, (k, v)| {
+            if k.ends_with(WAIT) {
+                acc + *v
+            } else {
+                acc
+            }
+        });
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        let mut report = format!(
+            "Profile: {:.3} ms total, {:.3} ms waiting on child processes\n",
+            ms(total),
+            ms(wait)
+        );
+        report.push_str(&format!(
+            "{:>8} {:>13} {:>13} {:>10}  function\n",
+            "calls", "inclusive ms", "exclusive ms", "wait ms"
+        ));
+        let mut fns: Vec<(&'static str, FnProfile)> = self.fns.drain().collect();
+        fns.sort_by(|(n1, p1), (n2, p2)| p2.exclusive.cmp(&p1.exclusive).then(n1.cmp(n2)));
+        for (name, p) in fns {
+            report.push_str(&format!(
+                "{:>8} {:>13.3} {:>13.3} {:>10.3}  {}\n",
+                p.calls,
+                ms(p.inclusive),
+                ms(p.exclusive),
+                ms(p.wait),
+                name
+            ));
+        }
+        let mut folded: Vec<String> = self
+            .folded
+            .drain()
+            .filter(|(_, time)| time.as_micros() > 0)
+            .map(|(stack, time)| format!("{} {}\n", stack, time.as_micros()))
+            .collect();
+        folded.sort();
+        (report, folded.concat(), self.folded_file.take())
+    }
+}
+
+impl Default for ProfileState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Name to profile a lambda under, the name it was defined with or (fn file:line)
+/// for an anonymous lambda.
+pub fn profile_name(environment: &mut Environment, lambda: &Lambda) -> &'static str {
+    if let Some(name) = lambda.name {
+        return name;
+    }
+    let meta = match &lambda.body {
+        MultiExpression::None => None,
+        MultiExpression::Single(body) => body.meta(),
+        MultiExpression::Multiple(body) => body.first().and_then(|exp| exp.meta()),
+    };
+    if let Some(meta) = meta {
+        environment
+            .interner
+            .intern(&format!("(fn {}:{})", meta.file, meta.line))
+    } else {
+        "(fn)"
+    }
+}
+
+fn builtin_profile(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    let (folded_file, form) = match (args.next(), args.next(), args.next()) {
+        (Some(form), None, None) => (None, form),
+        (Some(file), Some(form), None) => {
+            let file = eval(environment, file)?;
+            let file = if let ExpEnum::String(file, _) = &file.get().data {
+                file.to_string()
+            } else {
+                return Err(LispError::with_kind(
+                    ":type",
+                    "profile: folded stacks file must be a string",
+                ));
+            };
+            (Some(file), form)
+        }
+        _ => {
+            return Err(LispError::new(
+                "profile: takes an optional folded stacks file and a form",
+            ))
+        }
+    };
+    if environment.profiler.active {
+        // Already profiling (--profile or an outer profile), it gets the calls.
+        return eval(environment, form);
+    }
+    environment.profiler.start(folded_file);
+    let result = eval(environment, form);
+    profile_end(environment, false)?;
+    result
+}
+
+/// Stop profiling, print the report to *stdout* (or stderr) and write the
+/// folded stacks if a file was given.  Does nothing if not profiling.
+pub fn profile_end(environment: &mut Environment, to_stderr: bool) -> Result<(), LispError> {
+    if !environment.profiler.active {
+        return Ok(());
+    }
+    let (report, folded, folded_file) = environment.profiler.finish();
+    if to_stderr {
+        eprint!("{}", report);
+    } else {
+        debug_write(environment, &report)?;
+    }
+    if let Some(file) = folded_file {
+        if let Err(err) = fs::write(&file, folded) {
+            return Err(LispError::with_kind(
+                ":io",
+                format!("profile: unable to write {}: {}", file, err),
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn builtin_profile_end(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    params_done(args, "profile-end")?;
+    profile_end(environment, true)?;
+    Ok(Expression::make_nil())
+}
+
+pub fn add_profile_builtins<S: BuildHasher>(
+    interner: &mut Interner,
+    data: &mut HashMap<&'static str, (Expression, String), S>,
+) {
+    data.insert(
+        interner.intern("profile"),
+        Expression::make_context_function(
+            builtin_profile,
+            r#"Usage: (profile [folded-file] form) -> result
+
+Evaluate form while recording each call of a lambda, print a report to
+*stdout* and return the result of form.  The report has a line for each
+lambda sorted by exclusive time (time not spent in other lambdas or waiting on
+child processes) with the number of calls, inclusive time and time spent
+waiting on child processes.  Lambdas are named by def (or defn), others are
+(fn file:line).  If folded-file is given write the folded stacks (one line of
+names separated by ; and microseconds per stack) to it, these can be turned
+into a flamegraph (with flamegraph.pl or inferno-flamegraph for example).
+Running sl-sh with --profile file profiles a script or loading the shell
+config, see profile-end.
+
+Section: debug
+
+Example:
+(defn profile-test-fib (n) (if (< n 2) n (+ (profile-test-fib (- n 1)) (profile-test-fib (- n 2)))))
+(def profile-test-file (str (temp-dir) "/profile-test-out"))
+(def profile-test-folded (str (temp-dir) "/profile-test-folded"))
+(test::assert-equal 55 (out> profile-test-file (profile profile-test-folded (profile-test-fib 10))))
+(def profile-test-report (str-split "\n" (str (syscall "cat" profile-test-file))))
+(test::assert-true (str-starts-with "Profile: " (vec-nth profile-test-report 0)))
+(def profile-test-fib-line (str-split :whitespace (vec-nth profile-test-report 2)))
+(test::assert-equal "177" (vec-nth profile-test-fib-line 0))
+(test::assert-equal "profile-test-fib" (vec-nth profile-test-fib-line 4))
+(test::assert-true (str-contains "(top);profile-test-fib;profile-test-fib " (str (syscall "cat" profile-test-folded))))
+"#,
+        ),
+    );
+    data.insert(
+        interner.intern("profile-end"),
+        Expression::make_function(
+            builtin_profile_end,
+            r#"Usage: (profile-end)
+
+Stop profiling started with the --profile file option, print the report to
+stderr and write the folded stacks to file (see profile).  The standard library
+calls this before starting the REPL so --profile reports on loading slshrc,
+when running a script it is called when the script ends.  Does nothing if
+--profile was not used.
+
+Section: debug
+
+Example:
+(test::assert-false (profile-end))
+"#,
+        ),
+    );
+}
@@ -0,0 +1,339 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::BuildHasher;
+
+use crate::builtins_util::*;
+use crate::environment::*;
+use crate::eval::*;
+use crate::interner::*;
+use crate::types::*;
+
+fn set_type_error(form: &str, exp: &Expression) -> LispError {
+    LispError::with_kind(
+        ":type",
+        format!(
+            "{}: expected a set, got {} {}",
+            form,
+            exp.display_type(),
+            exp.to_short_string(40)
+        ),
+    )
+}
+
+// Evaluate the next arg and return a copy of it's items, it must be a set.
+fn param_set(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+    form: &str,
+) -> Result<HashSet<HashKey>, LispError> {
+    let set = param_eval(environment, args, form)?;
+    let set_d = set.get();
+    if let ExpEnum::Set(set) = &set_d.data {
+        Ok(set.clone())
+    } else {
+        Err(set_type_error(form, &set))
+    }
+}
+
+// Evaluate the next arg, it must be a set, and update it with f.
+fn update_set(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+    form: &str,
+    f: fn(&mut HashSet<HashKey>, HashKey),
+) -> Result<Expression, LispError> {
+    let set = param_eval(environment, args, form)?;
+    if !matches!(set.get().data, ExpEnum::Set(_)) {
+        return Err(set_type_error(form, &set));
+    }
+    for item in args {
+        let key = HashKey::from_exp(&eval(environment, item)?)?;
+        if let ExpEnum::Set(set) = &mut set.get_mut().data {
+            f(set, key);
+        }
+    }
+    Ok(set)
+}
+
+fn builtin_make_set(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    let mut set = HashSet::new();
+    for item in args {
+        set.insert(HashKey::from_exp(&eval(environment, item)?)?);
+    }
+    Ok(Expression::alloc_data(ExpEnum::Set(set)))
+}
+
+fn builtin_set_add(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    update_set(environment, args, "set-add!", |set, key| {
+        set.insert(key);
+    })
+}
+
+fn builtin_set_remove(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    update_set(environment, args, "set-remove!", |set, key| {
+        set.remove(&key);
+    })
+}
+
+fn builtin_set_contains(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    let set = param_eval(environment, args, "set-contains?")?;
+    let item = HashKey::from_exp(&param_eval(environment, args, "set-contains?")?)?;
+    params_done(args, "set-contains?")?;
+    let set_d = set.get();
+    if let ExpEnum::Set(set) = &set_d.data {
+        if set.contains(&item) {
+            Ok(Expression::make_true())
+        } else {
+            Ok(Expression::make_false())
+        }
+    } else {
+        Err(set_type_error("set-contains?", &set))
+    }
+}
+
+fn builtin_set_items(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    let set = param_set(environment, args, "set-items")?;
+    params_done(args, "set-items")?;
+    Ok(Expression::with_list(
+        set.iter().map(HashKey::to_exp).collect(),
+    ))
+}
+
+fn builtin_union(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    let mut set = param_set(environment, args, "union")?;
+    let mut args = args.peekable();
+    while args.peek().is_some() {
+        set.extend(param_set(environment, &mut args, "union")?);
+    }
+    Ok(Expression::alloc_data(ExpEnum::Set(set)))
+}
+
+fn builtin_intersection(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    let mut set = param_set(environment, args, "intersection")?;
+    let mut args = args.peekable();
+    while args.peek().is_some() {
+        let other = param_set(environment, &mut args, "intersection")?;
+        set.retain(|item| other.contains(item));
+    }
+    Ok(Expression::alloc_data(ExpEnum::Set(set)))
+}
+
+fn builtin_difference(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    let mut set = param_set(environment, args, "difference")?;
+    let mut args = args.peekable();
+    while args.peek().is_some() {
+        let other = param_set(environment, &mut args, "difference")?;
+        set.retain(|item| !other.contains(item));
+    }
+    Ok(Expression::alloc_data(ExpEnum::Set(set)))
+}
+
+fn builtin_is_subset(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    let set = param_set(environment, args, "subset?")?;
+    let other = param_set(environment, args, "subset?")?;
+    params_done(args, "subset?")?;
+    if set.is_subset(&other) {
+        Ok(Expression::make_true())
+    } else {
+        Ok(Expression::make_false())
+    }
+}
+
+pub fn add_set_builtins<S: BuildHasher>(
+    interner: &mut Interner,
+    data: &mut HashMap<&'static str, (Expression, String), S>,
+) {
+    data.insert(
+        interner.intern("make-set"),
+        Expression::make_function(
+            builtin_make_set,
+            r#"Usage: (make-set item ...)
+
+Make a new set containing the items.  Items can be any value that can be a hash
+map key (symbols, keywords, strings, chars, ints or lists and vectors of those).
+The reader turns #{item ...} into (make-set item ...).
+
+Section: set
+
+Example:
+(def tst-set (make-set 1 "two" :three))
+(test::assert-equal 3 (length tst-set))
+(test::assert-true (set-contains? tst-set "two"))
+(test::assert-equal 0 (length (make-set)))
+(test::assert-equal 2 (length #{1 (+ 1 1) 2}))
+(test::assert-true (= #{1 2 3} (make-set 3 2 1)))
+(test::assert-false (= #{1 2 3} #{1 2}))
+(test::assert-error (make-set 1.5))
+"#,
+        ),
+    );
+    data.insert(
+        interner.intern("set-add!"),
+        Expression::make_function(
+            builtin_set_add,
+            r#"Usage: (set-add! set item ...) -> set
+
+Add items to a set.  This is a destructive form!
+
+Section: set
+
+Example:
+(def tst-set (make-set))
+(set-add! tst-set 1 '(2 3))
+(test::assert-true (set-contains? tst-set 1))
+(test::assert-true (set-contains? tst-set (list 2 3)))
+(test::assert-equal 2 (length (set-add! tst-set 1)))
+"#,
+        ),
+    );
+    data.insert(
+        interner.intern("set-remove!"),
+        Expression::make_function(
+            builtin_set_remove,
+            r#"Usage: (set-remove! set item ...) -> set
+
+Remove items from a set, items not in the set are ignored.  This is a
+destructive form!
+
+Section: set
+
+Example:
+(def tst-set #{1 2 3})
+(set-remove! tst-set 1 4)
+(test::assert-false (set-contains? tst-set 1))
+(test::assert-equal 2 (length tst-set))
+"#,
+        ),
+    );
+    data.insert(
+        interner.intern("set-contains?"),
+        Expression::make_function(
+            builtin_set_contains,
+            r#"Usage: (set-contains? set item)
+
+True if item is in the set.  A symbol, string or char with the same text is the
+same item.
+
+Section: set
+
+Example:
+(def tst-set #{:a "b" 3})
+(test::assert-true (set-contains? tst-set :a))
+(test::assert-true (set-contains? tst-set 'b))
+(test::assert-true (set-contains? tst-set 3))
+(test::assert-false (set-contains? tst-set "3"))
+"#,
+        ),
+    );
+    data.insert(
+        interner.intern("set-items"),
+        Expression::make_function(
+            builtin_set_items,
+            r#"Usage: (set-items set)
+
+Returns a vector of the items in a set.  The items will be unordered.
+
+Section: set
+
+Example:
+(test::assert-equal '(1 2 3) (qsort (set-items #{3 1 2})))
+(test::assert-equal 0 (length (set-items (make-set))))
+"#,
+        ),
+    );
+    data.insert(
+        interner.intern("union"),
+        Expression::make_function(
+            builtin_union,
+            r#"Usage: (union set ...) -> set
+
+Returns a new set with the items that are in any of the sets.
+
+Section: set
+
+Example:
+(test::assert-true (= #{1 2 3 4} (union #{1 2} #{2 3} #{4})))
+(test::assert-true (= #{1} (union #{1})))
+(test::assert-error (union #{1} '(2)))
+"#,
+        ),
+    );
+    data.insert(
+        interner.intern("intersection"),
+        Expression::make_function(
+            builtin_intersection,
+            r#"Usage: (intersection set ...) -> set
+
+Returns a new set with the items that are in all of the sets.
+
+Section: set
+
+Example:
+(test::assert-true (= #{2} (intersection #{1 2} #{2 3})))
+(test::assert-true (= (make-set) (intersection #{1 2} #{2 3} #{3})))
+"#,
+        ),
+    );
+    data.insert(
+        interner.intern("difference"),
+        Expression::make_function(
+            builtin_difference,
+            r#"Usage: (difference set ...) -> set
+
+Returns a new set with the items of the first set that are not in any of the
+other sets.
+
+Section: set
+
+Example:
+(test::assert-true (= #{1} (difference #{1 2} #{2 3})))
+(test::assert-true (= #{1 2} (difference #{1 2 3} #{3})))
+(test::assert-true (= (make-set) (difference #{1 2} #{1} #{2})))
+"#,
+        ),
+    );
+    data.insert(
+        interner.intern("subset?"),
+        Expression::make_function(
+            builtin_is_subset,
+            r#"Usage: (subset? set other)
+
+True if every item of set is also in other.
+
+Section: set
+
+Example:
+(test::assert-true (subset? #{1 2} #{1 2 3}))
+(test::assert-true (subset? (make-set) #{1}))
+(test::assert-true (subset? #{1 2} #{1 2}))
+(test::assert-false (subset? #{1 4} #{1 2 3}))
+"#,
+        ),
+    );
+}
@@ -228,17 +228,20 @@ fn builtin_summary_stats(
     let mut stats = SummaryStats::new(&mut floats)?;
     stats.calculate();
 
-    let mut map: HashMap<&'static str, Expression> = HashMap::new();
-
-    map.insert(":mean", float_to_expr(stats.calc_mean()));
-    map.insert(":sd", float_to_expr(stats.calc_std_dev()));
-    map.insert(":mode", floats_to_expr(stats.calc_mode()));
-    map.insert(":min", float_to_expr(stats.calc_min()));
-    map.insert(":q1", float_to_expr(stats.calc_q1()));
-    map.insert(":med", float_to_expr(stats.calc_median()));
-    map.insert(":q3", float_to_expr(stats.calc_q3()));
-    map.insert(":max", float_to_expr(stats.calc_max()));
-    map.insert(":vec", floats_to_expr(Vec::from(stats.vec)));
+    let mut map: HashMap<HashKey, Expression> = HashMap::new();
+
+    map.insert(HashKey::Symbol(":mean"), float_to_expr(stats.calc_mean()));
+    map.insert(HashKey::Symbol(":sd"), float_to_expr(stats.calc_std_dev()));
+    map.insert(HashKey::Symbol(":mode"), floats_to_expr(stats.calc_mode()));
+    map.insert(HashKey::Symbol(":min"), float_to_expr(stats.calc_min()));
+    map.insert(HashKey::Symbol(":q1"), float_to_expr(stats.calc_q1()));
+    map.insert(HashKey::Symbol(":med"), float_to_expr(stats.calc_median()));
+    map.insert(HashKey::Symbol(":q3"), float_to_expr(stats.calc_q3()));
+    map.insert(HashKey::Symbol(":max"), float_to_expr(stats.calc_max()));
+    map.insert(
+        HashKey::Symbol(":vec"),
+        floats_to_expr(Vec::from(stats.vec)),
+    );
 
     Ok(Expression::alloc_data(ExpEnum::HashMap(map)))
 }
@@ -1116,7 +1116,7 @@ Example:
     );
     data.insert(
         interner.intern("str"),
-        Expression::make_function(
+        Expression::make_context_function(
             builtin_str,
             r#"Usage: (str arg0 ... argN) -> string
 
@@ -0,0 +1,573 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::hash::BuildHasher;
+use std::rc::Rc;
+
+use crate::builtins_util::*;
+use crate::environment::*;
+use crate::eval::*;
+use crate::interner::*;
+use crate::symbols::*;
+use crate::types::*;
+
+fn struct_type_error(form: &str, exp: &Expression) -> LispError {
+    LispError::with_kind(
+        ":type",
+        format!(
+            "{}: expected a struct, got {} {}",
+            form,
+            exp.display_type(),
+            exp.to_short_string(40)
+        ),
+    )
+}
+
+// The type and field slots of exp_d, a borrow of the struct instance exp.
+fn get_struct<'a>(
+    form: &str,
+    exp: &Expression,
+    exp_d: &'a ExpObj,
+) -> Result<(&'a Rc<StructType>, &'a [Binding]), LispError> {
+    if let ExpEnum::Struct(stype, slots) = &exp_d.data {
+        Ok((stype, slots))
+    } else {
+        Err(struct_type_error(form, exp))
+    }
+}
+
+// The type of a struct instance or type.
+fn get_struct_type(form: &str, exp: &Expression) -> Result<Rc<StructType>, LispError> {
+    match &exp.get().data {
+        ExpEnum::Struct(stype, _) => Ok(stype.clone()),
+        ExpEnum::StructType(stype) => Ok(stype.clone()),
+        _ => Err(struct_type_error(form, exp)),
+    }
+}
+
+// A field name given as a symbol or keyword.
+fn field_name(form: &str, field: &Expression) -> Result<&'static str, LispError> {
+    if let ExpEnum::Symbol(s, _) = &field.get().data {
+        Ok(s.strip_prefix(':').unwrap_or(s))
+    } else {
+        Err(LispError::with_kind(
+            ":type",
+            format!("{}: field must be a symbol", form),
+        ))
+    }
+}
+
+fn field_slot(form: &str, stype: &StructType, field: &Expression) -> Result<usize, LispError> {
+    let name = field_name(form, field)?;
+    stype.slots.get(name).copied().ok_or_else(|| {
+        LispError::new(format!(
+            "{}: struct {} has no field {}",
+            form, stype.name, name
+        ))
+    })
+}
+
+/// Send a message (the first of the evaluated args) to a struct instance, runs
+/// the method or accessor for it.
+pub fn struct_call(
+    environment: &mut Environment,
+    instance: &Expression,
+    args: Vec<Expression>,
+) -> Result<Expression, LispError> {
+    let instance_d = instance.get();
+    let (stype, slots) = get_struct("struct", instance, &instance_d)?;
+    let mut args = args.into_iter();
+    let msg = if let Some(msg) = args.next() {
+        msg
+    } else {
+        return Err(LispError::with_kind(
+            ":arity",
+            format!("struct {}: called without a message", stype.name),
+        ));
+    };
+    let msg = if let ExpEnum::Symbol(s, _) = &msg.get().data {
+        *s
+    } else {
+        return Err(LispError::with_kind(
+            ":type",
+            format!(
+                "struct {}: message must be a keyword, got {}",
+                stype.name,
+                msg.to_short_string(40)
+            ),
+        ));
+    };
+    // Run a method with its captured fields bound to this instance.
+    let method = stype.methods.borrow().get(msg).map(|(method, fields)| {
+        let bound: Vec<(usize, Binding)> = fields
+            .iter()
+            .map(|(idx, slot)| (*idx, slots[*slot].clone()))
+            .collect();
+        (method.clone(), bound)
+    });
+    if let Some((method, bound)) = method {
+        drop(instance_d);
+        let mut call_args = vec![instance.clone()];
+        call_args.extend(args);
+        return call_lambda_bound(
+            environment,
+            method,
+            &mut call_args.into_iter(),
+            false,
+            &bound,
+        )
+        .and_then(|res| res.resolve(environment));
+    }
+    if let Some((slot, setter)) = stype.messages.get(msg) {
+        let value = args.next();
+        return match (value, args.next(), setter) {
+            (None, None, false) => Ok(slots[*slot].get()),
+            (Some(value), None, true) => {
+                slots[*slot].replace(value.clone());
+                Ok(value)
+            }
+            _ => Err(LispError::with_kind(
+                ":arity",
+                format!(
+                    "struct {}: {} takes {} argument(s)",
+                    stype.name,
+                    msg,
+                    if *setter { 1 } else { 0 }
+                ),
+            )),
+        };
+    }
+    if msg == ":type" {
+        return Ok(Expression::alloc_data(ExpEnum::String(
+            stype.name.into(),
+            None,
+        )));
+    }
+    Err(LispError::new(format!(
+        "Invalid message ({}) to struct: {}",
+        msg, stype.name
+    )))
+}
+
+fn builtin_make_struct_type(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    let name = param_eval(environment, args, "make-struct-type")?;
+    let fields = param_eval(environment, args, "make-struct-type")?;
+    let extra_tags = if let Some(tags) = args.next() {
+        let tags = eval(environment, tags)?;
+        params_done(args, "make-struct-type")?;
+        tags
+    } else {
+        Expression::make_nil()
+    };
+    let name = if let ExpEnum::Symbol(name, _) = &name.get().data {
+        *name
+    } else {
+        return Err(LispError::with_kind(
+            ":type",
+            "make-struct-type: name must be a symbol",
+        ));
+    };
+    let mut field_list = Vec::new();
+    let mut slots = HashMap::new();
+    let mut messages = HashMap::new();
+    let mut tags = HashSet::new();
+    tags.insert(":struct");
+    tags.insert(environment.interner.intern(&format!(":struct-{}", name)));
+    for field in fields.iter() {
+        let (fname, access) = match &field.get().data {
+            ExpEnum::Symbol(s, _) => (*s, ":private"),
+            ExpEnum::Pair(_, _) | ExpEnum::Vector(_) => {
+                let spec: Vec<Expression> = field.iter().collect();
+                match (spec.first(), spec.get(1), spec.len()) {
+                    (Some(fname), Some(access), 2) => {
+                        match (&fname.get().data, &access.get().data) {
+                            (ExpEnum::Symbol(f, _), ExpEnum::Symbol(a, _)) => (*f, *a),
+                            _ => ("", ""),
+                        }
+                    }
+                    _ => ("", ""),
+                }
+            }
+            _ => ("", ""),
+        };
+        let access = match access {
+            ":private" => FieldAccess::Private,
+            ":rw" => FieldAccess::ReadWrite,
+            ":ro" => FieldAccess::ReadOnly,
+            ":wo" => FieldAccess::WriteOnly,
+            _ => {
+                return Err(LispError::new(format!(
+                    "make-struct-type: invalid field {}, expected name or (name [:rw|:ro|:wo|:private])",
+                    field
+                )))
+            }
+        };
+        if fname.is_empty() || fname.starts_with(':') || slots.contains_key(fname) {
+            return Err(LispError::new(format!(
+                "make-struct-type: invalid or duplicate field name {}",
+                field
+            )));
+        }
+        let slot = field_list.len();
+        if access == FieldAccess::ReadWrite || access == FieldAccess::ReadOnly {
+            messages.insert(
+                environment.interner.intern(&format!(":{}", fname)),
+                (slot, false),
+            );
+            tags.insert(environment.interner.intern(&format!(":accessor:{}", fname)));
+        }
+        if access == FieldAccess::ReadWrite || access == FieldAccess::WriteOnly {
+            messages.insert(
+                environment.interner.intern(&format!(":set-{}", fname)),
+                (slot, true),
+            );
+            tags.insert(environment.interner.intern(&format!(":setter:{}", fname)));
+        }
+        slots.insert(fname, slot);
+        field_list.push((fname, access));
+    }
+    for tag in extra_tags.iter() {
+        if let ExpEnum::Symbol(tag, _) = &tag.get().data {
+            tags.insert(tag);
+        } else {
+            return Err(LispError::with_kind(
+                ":type",
+                "make-struct-type: tags must be symbols",
+            ));
+        }
+    }
+    Ok(Expression::alloc_data(ExpEnum::StructType(Rc::new(
+        StructType {
+            name,
+            fields: field_list,
+            slots,
+            messages,
+            methods: RefCell::new(HashMap::new()),
+            tags,
+        },
+    ))))
+}
+
+fn builtin_make_struct(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    let stype = param_eval(environment, args, "make-struct")?;
+    let stype = if let ExpEnum::StructType(stype) = &stype.get().data {
+        stype.clone()
+    } else {
+        return Err(LispError::with_kind(
+            ":type",
+            "make-struct: first argument must be a struct type",
+        ));
+    };
+    let mut slots = Vec::with_capacity(stype.fields.len());
+    for arg in args {
+        slots.push(Binding::with_expression(eval(environment, arg)?));
+    }
+    if slots.len() != stype.fields.len() {
+        return Err(LispError::with_kind(
+            ":arity",
+            format!(
+                "make-struct: struct {} has {} fields, got {} values",
+                stype.name,
+                stype.fields.len(),
+                slots.len()
+            ),
+        ));
+    }
+    Ok(Expression::alloc_data(ExpEnum::Struct(stype, slots)))
+}
+
+fn builtin_struct_add_method(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    let stype_exp = param_eval(environment, args, "struct-add-method")?;
+    let msg = param_eval(environment, args, "struct-add-method")?;
+    let method = param_eval(environment, args, "struct-add-method")?;
+    params_done(args, "struct-add-method")?;
+    let stype = if let ExpEnum::StructType(stype) = &stype_exp.get().data {
+        stype.clone()
+    } else {
+        return Err(LispError::with_kind(
+            ":type",
+            "struct-add-method: first argument must be a struct type",
+        ));
+    };
+    let msg = match &msg.get().data {
+        ExpEnum::Symbol(s, _) if s.starts_with(':') => *s,
+        _ => {
+            return Err(LispError::with_kind(
+                ":type",
+                "struct-add-method: message must be a keyword",
+            ))
+        }
+    };
+    // The stack slot of each captured field and the field's slot.
+    let fields: Vec<(usize, usize)> = if let ExpEnum::Lambda(l) = &mut method.get_mut().data {
+        if l.name.is_none() {
+            l.name = Some(
+                environment
+                    .interner
+                    .intern(&format!("{}{}", stype.name, msg)),
+            );
+        }
+        l.syms
+            .capture_slots()
+            .into_iter()
+            .filter_map(|(name, idx)| stype.slots.get(name).map(|slot| (idx, *slot)))
+            .collect()
+    } else {
+        return Err(LispError::with_kind(
+            ":type",
+            "struct-add-method: method must be a lambda",
+        ));
+    };
+    stype.methods.borrow_mut().insert(msg, (method, fields));
+    Ok(stype_exp)
+}
+
+fn builtin_struct_get(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    let instance = param_eval(environment, args, "struct-get")?;
+    let field = param_eval(environment, args, "struct-get")?;
+    params_done(args, "struct-get")?;
+    let instance_d = instance.get();
+    let (stype, slots) = get_struct("struct-get", &instance, &instance_d)?;
+    let slot = field_slot("struct-get", stype, &field)?;
+    Ok(slots[slot].get())
+}
+
+fn builtin_struct_set(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    let instance = param_eval(environment, args, "struct-set!")?;
+    let field = param_eval(environment, args, "struct-set!")?;
+    let value = param_eval(environment, args, "struct-set!")?;
+    params_done(args, "struct-set!")?;
+    let instance_d = instance.get();
+    let (stype, slots) = get_struct("struct-set!", &instance, &instance_d)?;
+    let slot = field_slot("struct-set!", stype, &field)?;
+    slots[slot].replace(value.clone());
+    Ok(value)
+}
+
+fn builtin_is_struct(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    let arg = param_eval(environment, args, "struct?")?;
+    params_done(args, "struct?")?;
+    let is_struct = matches!(arg.get().data, ExpEnum::Struct(_, _));
+    if is_struct {
+        Ok(Expression::make_true())
+    } else {
+        Ok(Expression::make_false())
+    }
+}
+
+fn builtin_struct_name(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    let arg = param_eval(environment, args, "struct-name")?;
+    params_done(args, "struct-name")?;
+    let stype = get_struct_type("struct-name", &arg)?;
+    Ok(Expression::alloc_data(ExpEnum::Symbol(
+        stype.name,
+        SymLoc::None,
+    )))
+}
+
+fn builtin_struct_fields(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    let arg = param_eval(environment, args, "struct-fields")?;
+    params_done(args, "struct-fields")?;
+    let stype = get_struct_type("struct-fields", &arg)?;
+    let fields: Vec<Expression> = stype
+        .fields
+        .iter()
+        .map(|(name, _)| Expression::alloc_data(ExpEnum::Symbol(name, SymLoc::None)))
+        .collect();
+    Ok(Expression::cons_from_vec(&fields, None))
+}
+
+pub fn add_struct_builtins<S: BuildHasher>(
+    interner: &mut Interner,
+    data: &mut HashMap<&'static str, (Expression, String), S>,
+) {
+    data.insert(
+        interner.intern("make-struct-type"),
+        Expression::make_function(
+            builtin_make_struct_type,
+            r#"Usage: (make-struct-type name fields [tags]) -> struct-type
+
+Make a struct type, used by defstruct.  Each field is a symbol (a private
+field) or (symbol access) where access is :rw, :ro, :wo or :private.  Instances
+answer (instance :field) for :rw and :ro fields and (instance :set-field value)
+for :rw and :wo fields.  Instances have the meta tags :struct, :struct-name,
+:accessor:field and :setter:field as well as the symbols in tags.
+
+Section: struct
+
+Example:
+(def make-struct-type-test (make-struct-type 'point '((x :rw) (y :ro) z)))
+(test::assert-equal "StructType" (type make-struct-type-test))
+(def make-struct-type-pt (make-struct make-struct-type-test 1 2 3))
+(test::assert-equal 2 (make-struct-type-pt :y))
+(test::assert-equal 5 (make-struct-type-pt :set-x 5))
+(test::assert-equal 5 (make-struct-type-pt :x))
+(test::assert-error (make-struct-type-pt :z))
+(test::assert-error (make-struct-type-pt :set-y 1))
+(test::assert-true (meta-tag? make-struct-type-pt :struct-point))
+(test::assert-error (make-struct-type 'point '(x x)))
+(test::assert-error (make-struct-type 'point '((x :rx))))
+"#,
+        ),
+    );
+    data.insert(
+        interner.intern("make-struct"),
+        Expression::make_function(
+            builtin_make_struct,
+            r#"Usage: (make-struct struct-type value*) -> struct
+
+Make an instance of struct-type with a value for each field (in order).
+
+Section: struct
+
+Example:
+(def make-struct-test (make-struct-type 'point '((x :ro) (y :ro))))
+(def make-struct-pt (make-struct make-struct-test 1 2))
+(test::assert-equal "point" (type make-struct-pt))
+(test::assert-equal "(point :x 1 :y 2)" (str make-struct-pt))
+(test::assert-true (= make-struct-pt (make-struct make-struct-test 1 2)))
+(test::assert-false (= make-struct-pt (make-struct make-struct-test 1 3)))
+(test::assert-error (make-struct make-struct-test 1))
+"#,
+        ),
+    );
+    data.insert(
+        interner.intern("struct-add-method"),
+        Expression::make_function(
+            builtin_struct_add_method,
+            r#"Usage: (struct-add-method struct-type message method) -> struct-type
+
+Add method (a lambda) to struct-type for message (a keyword), used by
+defstruct.  (instance message arg*) calls method with instance and the args,
+while it runs the symbols it captured that are fields of the struct are bound to
+the fields of instance.  Methods take precedence over accessors.
+
+Section: struct
+
+Example:
+(def struct-add-method-test (make-struct-type 'counter '(n)))
+(let ((n nil))
+  (struct-add-method struct-add-method-test :inc! (fn (self by) (set! n (+ n by))))
+  (struct-add-method struct-add-method-test :n (fn (self) n)))
+(def struct-add-method-c (make-struct struct-add-method-test 0))
+(struct-add-method-c :inc! 2)
+(test::assert-equal 5 (struct-add-method-c :inc! 3))
+(test::assert-equal 5 (struct-add-method-c :n))
+(test::assert-equal 0 ((make-struct struct-add-method-test 0) :n))
+"#,
+        ),
+    );
+    data.insert(
+        interner.intern("struct-get"),
+        Expression::make_function(
+            builtin_struct_get,
+            r#"Usage: (struct-get struct field) -> value
+
+Return the value of field (a symbol or keyword) in struct, ignores the field's
+access.
+
+Section: struct
+
+Example:
+(def struct-get-test (make-struct (make-struct-type 'point '(x y)) 1 2))
+(test::assert-equal 1 (struct-get struct-get-test 'x))
+(test::assert-equal 2 (struct-get struct-get-test :y))
+(test::assert-error (struct-get struct-get-test 'z))
+(test::assert-error (struct-get 1 'x))
+"#,
+        ),
+    );
+    data.insert(
+        interner.intern("struct-set!"),
+        Expression::make_function(
+            builtin_struct_set,
+            r#"Usage: (struct-set! struct field value) -> value
+
+Set field (a symbol or keyword) in struct to value, ignores the field's access.
+
+Section: struct
+
+Example:
+(def struct-set-test (make-struct (make-struct-type 'point '(x y)) 1 2))
+(test::assert-equal 3 (struct-set! struct-set-test 'x 3))
+(test::assert-equal 3 (struct-get struct-set-test 'x))
+(test::assert-error (struct-set! struct-set-test 'z 1))
+"#,
+        ),
+    );
+    data.insert(
+        interner.intern("struct?"),
+        Expression::make_function(
+            builtin_is_struct,
+            r#"Usage: (struct? expression) -> t/nil
+
+True if expression is a struct instance.
+
+Section: struct
+
+Example:
+(def struct?-type (make-struct-type 'point '(x)))
+(test::assert-true (struct? (make-struct struct?-type 1)))
+(test::assert-false (struct? struct?-type))
+(test::assert-false (struct? {:x 1}))
+"#,
+        ),
+    );
+    data.insert(
+        interner.intern("struct-name"),
+        Expression::make_function(
+            builtin_struct_name,
+            r#"Usage: (struct-name struct) -> symbol
+
+Return the name of a struct instance or type.
+
+Section: struct
+
+Example:
+(def struct-name-type (make-struct-type 'point '(x)))
+(test::assert-equal 'point (struct-name struct-name-type))
+(test::assert-equal 'point (struct-name (make-struct struct-name-type 1)))
+"#,
+        ),
+    );
+    data.insert(
+        interner.intern("struct-fields"),
+        Expression::make_function(
+            builtin_struct_fields,
+            r#"Usage: (struct-fields struct) -> list
+
+Return the field names of a struct instance or type.
+
+Section: struct
+
+Example:
+(def struct-fields-type (make-struct-type 'point '((x :rw) y)))
+(test::assert-equal '(x y) (struct-fields struct-fields-type))
+(test::assert-equal '(x y) (struct-fields (make-struct struct-fields-type 1 2)))
+"#,
+        ),
+    );
+}
@@ -6,9 +6,12 @@ use nix::{
     unistd::{self, Pid},
 };
 use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::env;
+use std::fs::OpenOptions;
 use std::hash::BuildHasher;
 use std::io::{self, BufReader, Read, Write};
+use std::os::unix::io::AsRawFd;
 use std::{thread, time};
 
 use crate::builtins_util::*;
@@ -16,6 +19,7 @@ use crate::environment::*;
 use crate::eval::*;
 use crate::interner::*;
 use crate::process::*;
+use crate::signals::*;
 use crate::types::*;
 use crate::unix::*;
 use std::time::SystemTime;
@@ -175,6 +179,37 @@ fn builtin_unexport(
     ))
 }
 
+// Marker for a job in listings, + is the current job (the default for job
+// specs) and - the previous one.
+fn job_marker(idx: usize, len: usize) -> char {
+    if idx + 1 == len {
+        '+'
+    } else if idx + 2 == len {
+        '-'
+    } else {
+        ' '
+    }
+}
+
+// Print notices for background jobs that are done (and remove them).
+fn report_done_jobs(environment: &mut Environment) {
+    let len = environment.jobs.borrow().len();
+    let markers: Vec<(u32, char)> = environment
+        .jobs
+        .borrow()
+        .iter()
+        .enumerate()
+        .map(|(i, job)| (job.id, job_marker(i, len)))
+        .collect();
+    for job in take_done_jobs(environment) {
+        let marker = markers
+            .iter()
+            .find(|(id, _)| *id == job.id)
+            .map_or(' ', |(_, m)| *m);
+        eprintln!("[{}]{} {}  {}", job.id, marker, job.status, job.command());
+    }
+}
+
 fn builtin_jobs(
     environment: &mut Environment,
     args: &mut dyn Iterator<Item = Expression>,
@@ -184,19 +219,115 @@ fn builtin_jobs(
     } else {
         // Update the list before printing.
         reap_procs(environment)?;
+        let len = environment.jobs.borrow().len();
         for (i, job) in environment.jobs.borrow().iter().enumerate() {
+            if let JobStatus::Done(_) = job.status {
+                // Reported below.
+                continue;
+            }
+            let secs = job.started.elapsed().map_or(0, |e| e.as_secs());
             println!(
-                "[{}]\t{}\t{:?}\t{:?}",
-                i,
+                "[{}]{} {}  {}\t{:?}\t{}s",
+                job.id,
+                job_marker(i, len),
                 job.status.to_string(),
+                job.command(),
                 job.pids,
-                job.names
+                secs
             );
         }
+        report_done_jobs(environment);
         Ok(Expression::alloc_data(ExpEnum::Nil))
     }
 }
 
+// A job spec is unevaluated if it is a %spec symbol otherwise it is evaluated.
+fn eval_job_spec(environment: &mut Environment, arg: Expression) -> Result<Expression, LispError> {
+    if let ExpEnum::Symbol(s, _) = &arg.get().data {
+        if s.starts_with('%') {
+            return Ok(arg.clone());
+        }
+    }
+    eval(environment, arg)
+}
+
+fn job_spec(jobs: &[Job], spec: &str) -> Option<usize> {
+    match &spec[1..] {
+        "" | "%" | "+" => jobs.len().checked_sub(1),
+        "-" => jobs.len().checked_sub(2),
+        spec => {
+            if let Ok(id) = spec.parse::<u32>() {
+                jobs.iter().position(|j| j.id == id)
+            } else if let Some(text) = spec.strip_prefix('?') {
+                jobs.iter().rposition(|j| j.command().contains(text))
+            } else {
+                jobs.iter().rposition(|j| j.command().starts_with(spec))
+            }
+        }
+    }
+}
+
+// Find the index in the jobs list for a job spec:
+// int or %n: job number n
+// % %% or %+: the current job (most recent)
+// %-: the previous job
+// %?str: job with a command containing str
+// %str: job with a command starting with str
+fn find_job(environment: &Environment, spec: &Expression, form: &str) -> Result<usize, LispError> {
+    let jobs = environment.jobs.borrow();
+    let spec_text = if let ExpEnum::String(s, _) = &spec.get().data {
+        s.to_string()
+    } else {
+        spec.to_string()
+    };
+    let idx = match &spec.get().data {
+        ExpEnum::Int(id) => jobs.iter().position(|j| i64::from(j.id) == *id),
+        ExpEnum::Symbol(s, _) if s.starts_with('%') => job_spec(&jobs, s),
+        // From the shell reader args are strings.
+        ExpEnum::String(s, _) if s.starts_with('%') => job_spec(&jobs, s),
+        ExpEnum::String(s, _) if s.parse::<u32>().is_ok() => {
+            let id = s.parse::<u32>().unwrap_or_default();
+            jobs.iter().position(|j| j.id == id)
+        }
+        _ => {
+            let msg = format!(
+                "{}: {} is not a valid job spec, must be a job number or %spec",
+                form, spec_text
+            );
+            return Err(LispError::new(msg));
+        }
+    };
+    if let Some(idx) = idx {
+        Ok(idx)
+    } else {
+        Err(LispError::new(format!(
+            "{}: {}: no such job",
+            form, spec_text
+        )))
+    }
+}
+
+// The process (group leader) for a job spec or for the last stopped job if no spec.
+fn get_job_pid(
+    environment: &mut Environment,
+    arg: Option<Expression>,
+    form: &str,
+) -> Result<Option<u32>, LispError> {
+    let pid = if let Some(arg) = arg {
+        let spec = eval_job_spec(environment, arg)?;
+        let idx = find_job(environment, &spec, form)?;
+        environment.jobs.borrow()[idx].pids[0]
+    } else if let Some(pid) = environment.stopped_procs.borrow_mut().pop() {
+        return Ok(Some(pid));
+    } else if let Some(job) = environment.jobs.borrow().last() {
+        job.pids[0]
+    } else {
+        return Ok(None);
+    };
+    environment.stopped_procs.borrow_mut().retain(|p| *p != pid);
+    Ok(Some(pid))
+}
+
 fn builtin_epoch(
     _environment: &mut Environment,
     args: &mut dyn Iterator<Item = Expression>,
@@ -213,58 +344,20 @@ fn builtin_epoch(
     }
 }
 
-fn get_stopped_pid(environment: &mut Environment, arg: Option<Expression>) -> Option<u32> {
-    if let Some(arg) = arg {
-        if let ExpEnum::Int(ji) = &arg.get().data {
-            let ji = *ji as usize;
-            let jobs = &*environment.jobs.borrow();
-            if ji < jobs.len() {
-                let pid = jobs[ji].pids[0];
-                let mut stop_idx: Option<u32> = None;
-                for (i, sp) in environment.stopped_procs.borrow().iter().enumerate() {
-                    if *sp == pid {
-                        stop_idx = Some(i as u32);
-                        break;
-                    }
-                }
-                if let Some(idx) = stop_idx {
-                    environment.stopped_procs.borrow_mut().remove(idx as usize);
-                }
-                Some(pid)
-            } else {
-                eprintln!("Error job id out of range.");
-                None
-            }
-        } else {
-            eprintln!("Error job id must be integer.");
-            None
-        }
-    } else {
-        environment.stopped_procs.borrow_mut().pop()
-    }
-}
-
 fn builtin_bg(
     environment: &mut Environment,
     args: &mut dyn Iterator<Item = Expression>,
 ) -> Result<Expression, LispError> {
-    let arg = if let Some(arg) = args.next() {
-        if args.next().is_some() {
-            return Err(LispError::new(
-                "bg can only have one optional form (job id)",
-            ));
-        }
-        Some(eval(environment, arg)?)
-    } else {
-        None
-    };
-    let opid = get_stopped_pid(environment, arg);
+    let arg = args.next();
+    params_done(args, "bg")?;
+    let opid = get_job_pid(environment, arg, "bg")?;
     if let Some(pid) = opid {
-        let ppid = Pid::from_raw(pid as i32);
-        if let Err(err) = signal::kill(ppid, Signal::SIGCONT) {
+        let pgid = Pid::from_raw(-(pid as i32));
+        if let Err(err) = signal::kill(pgid, Signal::SIGCONT) {
             eprintln!("Error sending sigcont to wake up process: {}.", err);
         } else {
             mark_job_running(environment, pid);
+            set_job_background(environment, pid, true);
         }
     }
     Ok(Expression::alloc_data(ExpEnum::Nil))
@@ -274,21 +367,13 @@ fn builtin_fg(
     environment: &mut Environment,
     args: &mut dyn Iterator<Item = Expression>,
 ) -> Result<Expression, LispError> {
-    let arg = if let Some(arg) = args.next() {
-        if args.next().is_some() {
-            return Err(LispError::new(
-                "fg can only have one optional form (job id)",
-            ));
-        }
-        Some(eval(environment, arg)?)
-    } else {
-        None
-    };
-    let opid = get_stopped_pid(environment, arg);
+    let arg = args.next();
+    params_done(args, "fg")?;
+    let opid = get_job_pid(environment, arg, "fg")?;
     if let Some(pid) = opid {
         let term_settings = termios::tcgetattr(nix::libc::STDIN_FILENO).unwrap();
         let ppid = Pid::from_raw(pid as i32);
-        if let Err(err) = signal::kill(ppid, Signal::SIGCONT) {
+        if let Err(err) = signal::kill(Pid::from_raw(-(pid as i32)), Signal::SIGCONT) {
             eprintln!("Error sending sigcont to wake up process: {}.", err);
         } else {
             if let Err(err) = unistd::tcsetpgrp(nix::libc::STDIN_FILENO, ppid) {
@@ -296,12 +381,124 @@ fn builtin_fg(
                 eprintln!("{}", msg);
             }
             mark_job_running(environment, pid);
+            set_job_background(environment, pid, false);
             wait_pid(environment, pid, Some(&term_settings));
         }
     }
     Ok(Expression::alloc_data(ExpEnum::Nil))
 }
 
+fn parse_signal(name: &str) -> Option<Signal> {
+    if let Ok(num) = name.parse::<i32>() {
+        return Signal::try_from(num).ok();
+    }
+    let mut name = name.to_uppercase();
+    if !name.starts_with("SIG") {
+        name = format!("SIG{}", name);
+    }
+    name.parse::<Signal>().ok()
+}
+
+// Signal from a keyword (:sigterm), -NAME or -SIGNAME symbol (or string from the
+// shell reader) or negative signal number.  None if arg is not a signal.
+fn kill_signal(arg: &Expression) -> Result<Option<Signal>, LispError> {
+    let name = match &arg.get().data {
+        ExpEnum::Symbol(s, _) if s.starts_with('-') || s.starts_with(':') => s[1..].to_string(),
+        ExpEnum::String(s, _) if s.starts_with('-') => s[1..].to_string(),
+        ExpEnum::Int(num) if *num < 0 => (-num).to_string(),
+        _ => return Ok(None),
+    };
+    if let Some(sig) = parse_signal(&name) {
+        Ok(Some(sig))
+    } else {
+        Err(LispError::new(format!("kill: {} is not a signal", arg)))
+    }
+}
+
+fn builtin_kill(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    let mut args = args.peekable();
+    let sig = if let Some(arg) = args.peek() {
+        let arg = arg.clone();
+        if let Some(sig) = kill_signal(&arg)? {
+            args.next();
+            sig
+        } else {
+            Signal::SIGTERM
+        }
+    } else {
+        Signal::SIGTERM
+    };
+    if args.peek().is_none() {
+        return Err(LispError::new(
+            "kill: Missing required argument, see (doc 'kill) for usage.",
+        ));
+    }
+    for arg in args {
+        let target = eval_job_spec(environment, arg)?;
+        let target_d = target.get();
+        let (pid, stopped) = match &target_d.data {
+            ExpEnum::Int(pid) if *pid > 0 => (Pid::from_raw(*pid as i32), false),
+            ExpEnum::String(pid, _) if pid.parse::<u32>().is_ok() => {
+                (Pid::from_raw(pid.parse::<i32>().unwrap_or_default()), false)
+            }
+            ExpEnum::Process(ProcessState::Running(pid)) => (Pid::from_raw(*pid as i32), false),
+            ExpEnum::Process(ProcessState::Over(pid, _)) => {
+                let msg = format!("kill: process {} is not running", pid);
+                return Err(LispError::new(msg));
+            }
+            _ => {
+                let idx = find_job(environment, &target, "kill")?;
+                let jobs = environment.jobs.borrow();
+                let job = &jobs[idx];
+                let stopped = matches!(job.status, JobStatus::Stopped);
+                // Signal the whole process group of the job.
+                (Pid::from_raw(-(job.pids[0] as i32)), stopped)
+            }
+        };
+        if let Err(err) = signal::kill(pid, sig) {
+            let msg = format!("kill: failed to send {} to {}: {}", sig, target, err);
+            return Err(LispError::new(msg));
+        }
+        if stopped && sig != Signal::SIGKILL && sig != Signal::SIGCONT {
+            // A stopped job will not see the signal until it is continued.
+            let _ = signal::kill(pid, Signal::SIGCONT);
+        }
+    }
+    Ok(Expression::make_nil())
+}
+
+fn builtin_disown(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    let mut idxs = Vec::new();
+    for arg in args {
+        let spec = eval_job_spec(environment, arg)?;
+        idxs.push(find_job(environment, &spec, "disown")?);
+    }
+    if idxs.is_empty() {
+        let len = environment.jobs.borrow().len();
+        if len == 0 {
+            return Err(LispError::new("disown: no current job"));
+        }
+        idxs.push(len - 1);
+    }
+    idxs.sort_unstable();
+    idxs.dedup();
+    // The processes are still reaped, they just are not jobs anymore.
+    for idx in idxs.iter().rev() {
+        let job = environment.jobs.borrow_mut().remove(*idx);
+        environment
+            .stopped_procs
+            .borrow_mut()
+            .retain(|p| !job.pids.contains(p));
+    }
+    Ok(Expression::make_nil())
+}
+
 fn builtin_fork(
     environment: &mut Environment,
     args: &mut dyn Iterator<Item = Expression>,
@@ -309,6 +506,7 @@ fn builtin_fork(
     if let Some(exp) = args.next() {
         if args.next().is_none() {
             let pid = fork(environment, exp, None, None, None)?;
+            set_job_background(environment, pid, true);
             let res_proc = Expression::alloc_data(ExpEnum::Process(ProcessState::Running(pid)));
             add_process(environment, pid, (res_proc.clone(), None));
             return Ok(res_proc);
@@ -388,6 +586,7 @@ fn builtin_reap_jobs(
 ) -> Result<Expression, LispError> {
     params_done(args, "reap-jobs")?;
     reap_procs(environment)?;
+    report_done_jobs(environment);
     Ok(Expression::make_nil())
 }
 
@@ -402,6 +601,48 @@ fn pipe_write_file(pipe_in: i32, writer: &mut dyn Write) -> Result<(), LispError
     Ok(())
 }
 
+// Wait on the forked parts of a pipe then set *pipe-status* to the exit status
+// of each part (nil if it is not a process) and *last-status* to the status of
+// the pipe.  The pipe status is the status of the last part unless *pipefail* is
+// set, then it is the last non-zero status (returned).  None if the pipe has no
+// status (the last part is not a process and nothing failed).
+fn pipe_status(environment: &mut Environment, pids: &[u32], last: &Expression) -> Option<i32> {
+    let mut statuses = Vec::with_capacity(pids.len() + 1);
+    for pid in pids {
+        statuses.push(wait_pid(environment, *pid, None));
+    }
+    statuses.push(match &last.get().data {
+        ExpEnum::Process(ProcessState::Over(_pid, status)) => Some(*status),
+        _ => None,
+    });
+    let failed = if shell_option(environment, "*pipefail*") {
+        statuses
+            .iter()
+            .rev()
+            .find(|s| matches!(s, Some(code) if *code != 0))
+            .copied()
+            .flatten()
+    } else {
+        None
+    };
+    let status = failed.or_else(|| statuses.last().copied().flatten());
+    let status_vec: Vec<Expression> = statuses
+        .iter()
+        .map(|s| match s {
+            Some(code) => Expression::alloc_data(ExpEnum::Int(i64::from(*code))),
+            None => Expression::make_nil(),
+        })
+        .collect();
+    environment.root_scope.borrow_mut().insert(
+        environment.interner.intern("*pipe-status*"),
+        Expression::with_list(status_vec),
+    );
+    if let Some(status) = status {
+        set_last_status(environment, status);
+    }
+    status
+}
+
 fn builtin_pipe(
     environment: &mut Environment,
     args: &mut dyn Iterator<Item = Expression>,
@@ -445,6 +686,7 @@ fn builtin_pipe(
     let mut next_read;
     let mut res = Ok(Expression::make_nil());
     let mut procs = Vec::new();
+    let mut pids = Vec::new();
     let gpo = set_grab_proc_output(environment, false);
     while let Some(p) = pipe {
         let next_pipe = args.next();
@@ -452,7 +694,10 @@ fn builtin_pipe(
             // Last thing in the pipe so do not run in background.
             let _old_stdin = grab_stdin(read)?; // RAII guard for stdin
             gpo.environment.grab_proc_output = gpo.old_grab_proc_output;
+            // For *errexit* the status of the whole pipe is checked below.
+            gpo.environment.in_condition += 1;
             res = eval(gpo.environment, p);
+            gpo.environment.in_condition -= 1;
             // If pipe ended in a file then dump final output into it.
             match &res {
                 Ok(res_in) => {
@@ -490,6 +735,7 @@ fn builtin_pipe(
             let error = if do_error { write } else { None };
             let pid = fork(gpo.environment, p, read, write, error)?;
             last_pid = Some(pid);
+            pids.push(pid);
             let res_proc = Expression::alloc_data(ExpEnum::Process(ProcessState::Running(pid)));
             procs.push(res_proc.clone());
             add_process(gpo.environment, pid, (res_proc, None));
@@ -502,6 +748,9 @@ fn builtin_pipe(
     }
     gpo.environment.pipe_pgid = None;
     if let Ok(res) = res {
+        if let Some(status) = pipe_status(gpo.environment, &pids, &res) {
+            check_errexit(gpo.environment, "pipe", status)?;
+        }
         procs.insert(0, res);
         Ok(Expression::alloc_data(ExpEnum::Values(procs)))
     } else {
@@ -518,6 +767,331 @@ fn builtin_pipe(
     }
 }
 
+fn builtin_fd_redirect(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    fn get_fd(environment: &Environment, exp: &Expression) -> Result<i32, LispError> {
+        match exp.make_int(environment) {
+            Ok(fd) if fd >= 0 && fd <= i64::from(i32::MAX) => Ok(fd as i32),
+            _ => Err(LispError::new(format!(
+                "fd-redirect: {} is not a valid file descriptor",
+                exp
+            ))),
+        }
+    }
+
+    let specs = if let Some(specs) = args.next() {
+        specs
+    } else {
+        return Err(LispError::new(
+            "fd-redirect: Missing required argument, see (doc 'fd-redirect) for usage.",
+        ));
+    };
+    // Keep the opened files until the body is done, dropping them closes the fds.
+    let mut files = Vec::new();
+    let mut redirects = Vec::new();
+    for spec in specs.iter() {
+        let mut parts = spec.iter();
+        let fd = param_eval(environment, &mut parts, "fd-redirect")?;
+        let fd = get_fd(environment, &fd)?;
+        let op = param_eval(environment, &mut parts, "fd-redirect")?;
+        let op = if let ExpEnum::Symbol(op, _) = &op.get().data {
+            *op
+        } else {
+            return Err(LispError::new(format!(
+                "fd-redirect: {} is not a valid redirect, must be :write, :append, :read, :dup or :close",
+                op
+            )));
+        };
+        let redirect = match op {
+            ":close" => FdRedirect::Close(fd),
+            ":dup" => {
+                let source = param_eval(environment, &mut parts, "fd-redirect")?;
+                FdRedirect::Dup(fd, get_fd(environment, &source)?)
+            }
+            ":write" | ":append" | ":read" => {
+                let path = param_eval(environment, &mut parts, "fd-redirect")?;
+                let path = path.as_string(environment)?;
+                let path = match expand_tilde(&path) {
+                    Some(p) => p,
+                    None => path,
+                };
+                let mut opts = OpenOptions::new();
+                match op {
+                    ":write" => opts.write(true).create(true).truncate(true),
+                    ":append" => opts.append(true).create(true),
+                    _ => opts.read(true),
+                };
+                let file = match opts.open(&path) {
+                    Ok(file) => file,
+                    Err(err) => {
+                        let msg = format!("fd-redirect: failed to open {}: {}", path, err);
+                        return Err(LispError::new(msg));
+                    }
+                };
+                let file = fd_to_file(dup_fd_high(file.as_raw_fd())?);
+                let redirect = FdRedirect::Dup(fd, file.as_raw_fd());
+                files.push(file);
+                redirect
+            }
+            _ => {
+                return Err(LispError::new(format!(
+                    "fd-redirect: {} is not a valid redirect, must be :write, :append, :read, :dup or :close",
+                    op
+                )));
+            }
+        };
+        params_done(&mut parts, "fd-redirect")?;
+        redirects.push(redirect);
+    }
+    let old_len = environment.fd_redirects.len();
+    environment.fd_redirects.append(&mut redirects);
+    let mut res = Ok(Expression::make_nil());
+    for exp in args {
+        res = eval(environment, exp).and_then(|r| r.resolve(environment));
+        if res.is_err() {
+            break;
+        }
+    }
+    environment.fd_redirects.truncate(old_len);
+    drop(files);
+    res
+}
+
+fn builtin_with_stdin_str(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    let text = param_eval(environment, args, "with-stdin-str")?;
+    let text = text.as_string(environment)?;
+    let (read_fd, write_fd) = anon_pipe()?;
+    // Feed the pipe from a thread so text bigger than the pipe buffer can not
+    // block, if the reader exits early the write fails and the thread ends.
+    let writer = thread::spawn(move || {
+        let mut file = fd_to_file(write_fd);
+        let _ = file.write_all(text.as_bytes());
+    });
+    let old_stdin = match replace_stdin(read_fd) {
+        Ok(old_stdin) => old_stdin,
+        Err(err) => {
+            let _ = close_fd(read_fd);
+            let _ = writer.join();
+            return Err(err);
+        }
+    };
+    let mut res = Ok(Expression::make_nil());
+    for exp in args {
+        res = eval(environment, exp).and_then(|r| r.resolve(environment));
+        if res.is_err() {
+            break;
+        }
+    }
+    // Restoring stdin closes our read end of the pipe which lets the writer finish.
+    if let Err(err) = dup_stdin(old_stdin) {
+        eprintln!("Error restoring stdin after with-stdin-str: {}", err);
+    }
+    let _ = writer.join();
+    res
+}
+
+fn proc_sub(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+    form: &str,
+    is_in: bool,
+) -> Result<Expression, LispError> {
+    let exp = if let Some(exp) = args.next() {
+        exp
+    } else {
+        let msg = format!(
+            "{}: Missing required argument, see (doc '{}) for usage.",
+            form, form
+        );
+        return Err(LispError::new(msg));
+    };
+    params_done(args, form)?;
+    let (read_fd, write_fd) = anon_pipe()?;
+    let keep_fd = if is_in { read_fd } else { write_fd };
+    // Record our end before forking so the child closes it (see fork).
+    environment.proc_subs.push((keep_fd, 0, is_in));
+    // The substitution is part of the command using it, not a job of its own.
+    let do_job_control = environment.do_job_control;
+    environment.do_job_control = false;
+    let pid = if is_in {
+        fork(environment, exp, None, Some(write_fd), None)
+    } else {
+        fork(environment, exp, Some(read_fd), None, None)
+    };
+    environment.do_job_control = do_job_control;
+    let pid = match pid {
+        Ok(pid) => pid,
+        Err(err) => {
+            environment.proc_subs.pop();
+            let _ = close_fd(read_fd);
+            let _ = close_fd(write_fd);
+            return Err(err);
+        }
+    };
+    if let Some(last) = environment.proc_subs.last_mut() {
+        last.1 = pid;
+    }
+    clear_cloexec(keep_fd)?;
+    Ok(Expression::alloc_data(ExpEnum::String(
+        format!("/dev/fd/{}", keep_fd).into(),
+        None,
+    )))
+}
+
+fn builtin_proc_sub_in(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    proc_sub(environment, args, "proc-sub-in", true)
+}
+
+fn builtin_proc_sub_out(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    proc_sub(environment, args, "proc-sub-out", false)
+}
+
+// Signals that can be trapped and their trap keywords.
+const TRAP_SIGNALS: [(&str, Signal); 3] = [
+    (":sighup", Signal::SIGHUP),
+    (":sigterm", Signal::SIGTERM),
+    (":sigusr1", Signal::SIGUSR1),
+];
+
+fn trap_signal(key: &str) -> Option<Signal> {
+    TRAP_SIGNALS
+        .iter()
+        .find(|(name, _)| *name == key)
+        .map(|(_, sig)| *sig)
+}
+
+fn trap_key(
+    environment: &mut Environment,
+    key: Expression,
+    form: &str,
+) -> Result<&'static str, LispError> {
+    let key = eval(environment, key)?;
+    if let ExpEnum::Symbol(k, _) = &key.get().data {
+        if *k == ":exit" || *k == ":err" || trap_signal(k).is_some() {
+            return Ok(k);
+        }
+    }
+    let msg = format!(
+        "{}: {} is not a valid trap, must be :sigterm, :sighup, :sigusr1, :exit or :err",
+        form, key
+    );
+    Err(LispError::new(msg))
+}
+
+/// Run the trap handler for key (if there is one) with args.  Errors from the
+/// handler are reported here, they do not unwind whatever the trap interrupted.
+pub fn run_trap(environment: &mut Environment, key: &str, args: Vec<Expression>) {
+    if let Some(handler) = environment.traps.get(key).cloned() {
+        let res = call_lambda(environment, handler, &mut args.into_iter(), false)
+            .and_then(|res| res.resolve(environment));
+        if let Err(err) = res {
+            eprintln!("Error running trap {}: {}", key, err);
+        }
+    }
+}
+
+/// Run the handlers for any trapped signals that have come in.
+pub fn run_pending_traps(environment: &mut Environment) {
+    for sig in take_pending_traps() {
+        if let Some((key, _)) = TRAP_SIGNALS.iter().find(|(_, s)| *s == sig) {
+            run_trap(environment, key, Vec::new());
+        }
+    }
+}
+
+/// Run (and remove) the :exit trap, called once when the shell is done.
+pub fn run_exit_trap(environment: &mut Environment) {
+    if environment.traps.contains_key(":exit") {
+        // Evaluation stops once exit is called so hold the code while the trap runs.
+        let exit_code = environment.exit_code.take();
+        run_trap(environment, ":exit", Vec::new());
+        environment.traps.remove(":exit");
+        if environment.exit_code.is_none() {
+            environment.exit_code = exit_code;
+        }
+    }
+}
+
+fn builtin_trap(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    let key = if let Some(key) = args.next() {
+        trap_key(environment, key, "trap")?
+    } else {
+        let mut keys: Vec<&'static str> = environment.traps.keys().copied().collect();
+        keys.sort_unstable();
+        let mut traps = Vec::with_capacity(keys.len());
+        for key in keys {
+            let key_exp = Expression::alloc_data(ExpEnum::Symbol(key, SymLoc::None));
+            traps.push(Expression::alloc_data(ExpEnum::Pair(
+                key_exp,
+                environment.traps[key].clone(),
+            )));
+        }
+        return Ok(Expression::cons_from_vec(&traps, None));
+    };
+    let handler = if let Some(handler) = args.next() {
+        eval(environment, handler)?
+    } else {
+        return Ok(environment
+            .traps
+            .get(key)
+            .cloned()
+            .unwrap_or_else(Expression::make_nil));
+    };
+    params_done(args, "trap")?;
+    if !matches!(handler.get().data, ExpEnum::Lambda(_)) {
+        let msg = format!(
+            "trap: handler must be a lambda, got {}",
+            handler.display_type()
+        );
+        return Err(LispError::new(msg));
+    }
+    if let Some(sig) = trap_signal(key) {
+        if !environment.traps.contains_key(key) && !install_trap_handler(sig) {
+            let msg = format!("trap: failed to install the handler for {}", key);
+            return Err(LispError::new(msg));
+        }
+    }
+    environment.traps.insert(key, handler.clone());
+    Ok(handler)
+}
+
+fn builtin_untrap(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    let key = if let Some(key) = args.next() {
+        trap_key(environment, key, "untrap")?
+    } else {
+        return Err(LispError::new(
+            "untrap: Missing required argument, see (doc 'untrap) for usage.",
+        ));
+    };
+    params_done(args, "untrap")?;
+    if let Some(sig) = trap_signal(key) {
+        if environment.traps.contains_key(key) {
+            remove_trap_handler(sig);
+        }
+    }
+    Ok(environment
+        .traps
+        .remove(key)
+        .unwrap_or_else(Expression::make_nil))
+}
+
 fn builtin_wait(
     environment: &mut Environment,
     args: &mut dyn Iterator<Item = Expression>,
@@ -595,6 +1169,52 @@ pub fn add_system_builtins<S: BuildHasher>(
     interner: &mut Interner,
     data: &mut HashMap<&'static str, (Expression, String), S>,
 ) {
+    data.insert(
+        interner.intern("*errexit*"),
+        (
+            Expression::make_nil(),
+            "Usage: (set! *errexit* #t)
+
+If true then a system command that exits with a non-zero status is an error,
+unless it is part of a condition (the test of an if, not or any but the last
+form of an and or or).  A script that ends with an errexit error exits with the
+status of the failed command.  Set with -e (--errexit) when running a script.
+
+Section: globals
+
+Example:
+(def errexit-test (get-error (dyn *errexit* #t (syscall 'false))))
+(test::assert-equal :error (car errexit-test))
+(test::assert-true (str-contains \"exited with status 1\" (car (cdr errexit-test))))
+(test::assert-equal :ok (car (get-error (dyn *errexit* #t (if (syscall 'false) 1 2)))))
+(test::assert-equal :ok (car (get-error (dyn *errexit* #t (or (syscall 'false) (syscall 'true))))))
+(test::assert-equal :error (car (get-error (dyn *errexit* #t (and (syscall 'true) (syscall 'false))))))
+(test::assert-equal :error (car (get-error (dyn *errexit* #t (pipe (syscall 'true) (syscall 'false))))))
+"
+            .to_string(),
+        ),
+    );
+    data.insert(
+        interner.intern("*xtrace*"),
+        (
+            Expression::make_nil(),
+            "Usage: (set! *xtrace* #t)
+
+If true then print each system command with it's arguments (after expansion) to
+stderr, prefixed with + before running it.  Set with -x (--xtrace) when running
+a script.
+
+Section: globals
+
+Example:
+(def xtrace-file (str (temp-dir) \"/xtrace-test\"))
+(err> xtrace-file (dyn *xtrace* #t (syscall 'true \"a b\" \"c\")))
+(test::assert-equal \"+ true 'a b' c\\n\" (str (syscall 'cat xtrace-file)))
+(syscall 'rm xtrace-file)
+"
+            .to_string(),
+        ),
+    );
     data.insert(
         interner.intern("syscall"),
         Expression::make_function(
@@ -704,7 +1324,10 @@ Example:
             builtin_jobs,
             r#"Usage: (jobs)
 
-Print list of jobs with ids.
+Print list of jobs with ids, status, command, pids and run time.  The current
+job (the default for fg, bg and disown) is marked with + and the previous job
+with -.  Background jobs that are done are printed (once) as Done (or Exit N
+for a non-zero exit status) and removed from the list.
 
 Section: system
 
@@ -716,13 +1339,19 @@ Example:
     );
     data.insert(
         interner.intern("bg"),
-        Expression::make_function(
+        Expression::make_context_function(
             builtin_bg,
-            r#"Usage: (bg job-id?)
+            r#"Usage: (bg job-spec?)
 
 Put a job in the background.
 
-If no job id is specified use the last job.
+If no job is specified use the last stopped job (or the current job).  A job
+spec is a job number or one of:
+- %n job number n
+- % %% or %+ the current job
+- %- the previous job
+- %str the most recent job with a command starting with str
+- %?str the most recent job with a command containing str
 
 Section: system
 
@@ -734,19 +1363,62 @@ Example:
     );
     data.insert(
         interner.intern("fg"),
-        Expression::make_function(
+        Expression::make_context_function(
             builtin_fg,
-            r#"Usage: (fg job-id?)
+            r#"Usage: (fg job-spec?)
 
 Put a job in the foreground.
 
-If no job id is specified use the last job.
+If no job is specified use the last stopped job (or the current job).  See bg
+for job specs.
 
 Section: system
 
 Example:
 ;(fg)
 #t
+"#,
+        ),
+    );
+    data.insert(
+        interner.intern("kill"),
+        Expression::make_context_function(
+            builtin_kill,
+            r#"Usage: (kill signal? target+) -> nil
+
+Send a signal (default SIGTERM) to each target.  A target is a pid, a process
+(from fork for instance) or a job spec (see bg), a job is signalled as a process
+group.  The signal is not evaluated and is a keyword (:sigkill), a symbol like
+-KILL or -SIGKILL or a negative signal number (-9).  A stopped job is also sent
+SIGCONT so it will see the signal.
+
+Section: system
+
+Example:
+(def kill-test (fork (sleep 10000)))
+(def kill-time (time (do (kill :sigkill kill-test)(wait kill-test))))
+(test::assert-true (< kill-time 5.0))
+(def kill-test (fork (sleep 10000)))
+(kill -TERM kill-test)
+(test::assert-equal 143 (wait kill-test))
+(test::assert-error (kill :signope 1))
+(test::assert-error (kill %99))
+"#,
+        ),
+    );
+    data.insert(
+        interner.intern("disown"),
+        Expression::make_context_function(
+            builtin_disown,
+            r#"Usage: (disown job-spec*) -> nil
+
+Remove jobs (default the current job) from the jobs list, see bg for job specs.
+The processes keep running but are no longer jobs.
+
+Section: system
+
+Example:
+(test::assert-error (disown %99))
 "#,
         ),
     );
@@ -808,7 +1480,7 @@ Example:
     );
     data.insert(
         interner.intern("time"),
-        Expression::make_function(
+        Expression::make_context_function(
             builtin_time,
             r#"Usage: (time form) -> eval-time
 
@@ -828,8 +1500,10 @@ Example:
             builtin_reap_jobs,
             r#"Usage: (reap-jobs) -> nil
 
-Reaps any completed jobs.  Only intended to be used by code implemeting the REPL
-loop or something similiar, this is probably not the form you are searching for.
+Reaps any completed jobs and prints a notice for background jobs that are done
+(for instance "[1]+ Done  sleep 10").  Only intended to be used by code
+implemeting the REPL loop or something similiar, this is probably not the form
+you are searching for.
 
 Section: system
 
@@ -841,7 +1515,7 @@ Example:
     );
     data.insert(
         interner.intern("pipe"),
-        Expression::make_function(
+        Expression::make_context_function(
             builtin_pipe,
             r#"Usage: (pipe [expression]+)
 
@@ -869,6 +1543,11 @@ Pipe will return a multiple values, the first/primary is the final form for the
 pipe and the process objects for each part of the pipe are next (first element
 can be found with (values-nth 1 return-val), etc).
 
+Once the final form is done pipe waits for the other parts and sets
+*pipe-status* to a vector of each part's exit status (nil for a part that is
+not a process).  *last-status* is set to the status of the final part, or if
+*pipefail* is true to the last non-zero status of any part.
+
 Section: system
 
 Example:
@@ -921,6 +1600,183 @@ $(rm "${pipe-test-dir}/test1")
   $(rm $file-name)
 )
 $(rmdir $pipe-test-dir)
+(pipe (syscall 'sh "-c" "exit 3")(syscall 'true))
+(test::assert-equal '#(3 0) *pipe-status*)
+(test::assert-equal 0 *last-status*)
+(dyn *pipefail* #t (pipe (syscall 'sh "-c" "exit 3")(syscall 'sh "-c" "exit 2")(syscall 'true)))
+(test::assert-equal '#(3 2 0) *pipe-status*)
+(test::assert-equal 2 *last-status*)
+(pipe (syscall 'true)(syscall 'sh "-c" "exit 4"))
+(test::assert-equal '#(0 4) *pipe-status*)
+(test::assert-equal 4 *last-status*)
+(pipe (syscall 'true)(read-line *stdin*))
+(test::assert-equal '#(0 nil) *pipe-status*)
+(dyn *pipefail* #t (pipe (syscall 'sh "-c" "exit 3")(read-line *stdin*)))
+(test::assert-equal '#(3 nil) *pipe-status*)
+(test::assert-equal 3 *last-status*)
+"#,
+        ),
+    );
+    data.insert(
+        interner.intern("fd-redirect"),
+        Expression::make_context_function(
+            builtin_fd_redirect,
+            r#"Usage: (fd-redirect ((fd op target?)*) body*) -> result
+
+Redirect file descriptors for system commands run in body and return the result
+of the last form.  Each redirect is a list of a file descriptor number, an
+operation and (except for :close) a target, all of which are evaluated.  The
+operations are:
+- :write  open the target file for writing (created and truncated) on fd
+- :append open the target file for appending (created if needed) on fd
+- :read   open the target file for reading on fd
+- :dup    make fd a copy of the target file descriptor
+- :close  close fd
+
+Redirects are applied in order in the new process after its stdin, stdout and
+stderr have been set up (so after any out>, err>, pipe etc) and a nested
+fd-redirect is applied after the outer one.  They only affect the file
+descriptors of system commands, not output from lisp code (see out> and err>
+for that).  Opened files are closed when fd-redirect returns.
+
+The shell reader turns n>file, n>>file, n<file, n>&m, n<&m, n>&- and n<&- (n a
+single digit) into fd-redirect.
+
+Section: system
+
+Example:
+(def fd-redirect-test (str (temp-dir) "/fd-redirect-test"))
+(fd-redirect ((3 :write fd-redirect-test)) (syscall 'sh "-c" "echo three >&3"))
+(test::assert-equal "three\n" (str (syscall 'cat fd-redirect-test)))
+(fd-redirect ((3 :append fd-redirect-test)) (syscall 'sh "-c" "echo four >&3"))
+(test::assert-equal "three\nfour\n" (str (syscall 'cat fd-redirect-test)))
+(test::assert-equal "three\n" (str (fd-redirect ((5 :read fd-redirect-test)) (syscall 'head "-n" "1" "/dev/fd/5"))))
+(test::assert-equal "err\n" (str (fd-redirect ((2 :dup 1)) (syscall 'sh "-c" "echo err >&2"))))
+(test::assert-equal "closed\n" (str (fd-redirect ((0 :close)) (syscall 'sh "-c" "cat 2>/dev/null || echo closed"))))
+(fd-redirect ((4 :write fd-redirect-test)(2 :dup 4)) (syscall 'sh "-c" "echo both >&2"))
+(test::assert-equal "both\n" (str (syscall 'cat fd-redirect-test)))
+(syscall 'rm fd-redirect-test)
+"#,
+        ),
+    );
+    data.insert(
+        interner.intern("with-stdin-str"),
+        Expression::make_context_function(
+            builtin_with_stdin_str,
+            r#"Usage: (with-stdin-str string body*) -> result
+
+Evaluate body with stdin reading string (through a pipe) and return the result
+of the last form.  System commands started in body read the string on their
+stdin, stdin is restored when with-stdin-str returns.
+
+The shell reader uses this for here-documents (cmd <<EOF) and here-strings
+(cmd <<< word).
+
+Section: system
+
+Example:
+(test::assert-equal "3" (str-trim (str (with-stdin-str "one\ntwo\nthree\n" (syscall 'wc "-l")))))
+(test::assert-equal "ONE\n" (str (with-stdin-str "one\n" (syscall 'tr "a-z" "A-Z"))))
+(test::assert-equal "100000" (str-trim (str (with-stdin-str (str-cat-list "" (make-vec 100000 "x")) (syscall 'wc "-c")))))
+"#,
+        ),
+    );
+    data.insert(
+        interner.intern("proc-sub-in"),
+        Expression::make_context_function(
+            builtin_proc_sub_in,
+            r#"Usage: (proc-sub-in form) -> "/dev/fd/N"
+
+Fork form into a new process with it's stdout connected to a pipe and return a
+path (/dev/fd/N) to read that output from, this is process substitution.  The
+path is intended as an argument to a system command, once the command using it
+is done the pipe is closed and the process is reaped.
+
+The shell reader turns <(cmd args) into proc-sub-in.
+
+Section: system
+
+Example:
+(test::assert-equal "one\ntwo\n" (str (syscall 'cat (proc-sub-in (syscall 'echo "one")) (proc-sub-in (syscall 'echo "two")))))
+(test::assert-equal "" (str (syscall 'diff (proc-sub-in (syscall 'echo "same")) (proc-sub-in (syscall 'echo "same")))))
+(test::assert-true (str-starts-with "/dev/fd/" (str (syscall 'echo (proc-sub-in (syscall 'true))))))
+"#,
+        ),
+    );
+    data.insert(
+        interner.intern("proc-sub-out"),
+        Expression::make_context_function(
+            builtin_proc_sub_out,
+            r#"Usage: (proc-sub-out form) -> "/dev/fd/N"
+
+Fork form into a new process with it's stdin connected to a pipe and return a
+path (/dev/fd/N) to write to that pipe, this is process substitution.  The path
+is intended as an argument to a system command, once the command using it is
+done the pipe is closed and the process is reaped (so it is done as well).
+
+The shell reader turns >(cmd args) into proc-sub-out.
+
+Section: system
+
+Example:
+(def proc-sub-test (str (temp-dir) "/proc-sub-out-test"))
+(syscall 'sh "-c" "echo hi > \$1" "sh" (proc-sub-out (out> proc-sub-test (syscall 'tr "a-z" "A-Z"))))
+(test::assert-equal "HI\n" (str (syscall 'cat proc-sub-test)))
+(syscall 'rm proc-sub-test)
+"#,
+        ),
+    );
+    data.insert(
+        interner.intern("trap"),
+        Expression::make_function(
+            builtin_trap,
+            r#"Usage: (trap) -> ((:trap . handler)*)
+(trap :trap) -> handler
+(trap :trap handler) -> handler
+
+Set the handler for a trap, handler is a lambda.  With only a trap return it's
+handler (nil if not set) and with no arguments return a list of all the traps
+and handlers as pairs.  Traps are:
+- :sigterm, :sighup, :sigusr1 run when the signal is received, the signal is
+noted when it arrives and the handler runs before the next form is evaluated.
+Trapping a signal replaces it's default action (for instance SIGTERM will not
+terminate the shell, call exit from the handler for that).
+- :exit run when the shell exits (script is done, exit called or end of the REPL).
+- :err run when an error ends a script or REPL line, called with the error
+(result of get-error).
+Handlers (except :err) take no arguments.  Forked processes do not inherit traps.
+
+Section: system
+
+Example:
+(def trap-test nil)
+(trap :sigusr1 (fn () (set! trap-test "usr1")))
+(test::assert-true (lambda? (trap :sigusr1)))
+(test::assert-equal :sigusr1 (car (car (trap))))
+(syscall 'sh "-c" "kill -USR1 \$PPID")
+(test::assert-equal "usr1" trap-test)
+(untrap :sigusr1)
+(test::assert-false (trap :sigusr1))
+(test::assert-error (trap :sigwhat (fn () nil)))
+"#,
+        ),
+    );
+    data.insert(
+        interner.intern("untrap"),
+        Expression::make_function(
+            builtin_untrap,
+            r#"Usage: (untrap :trap) -> handler
+
+Remove the handler for trap (see trap), a signal goes back to it's default
+action.  Returns the removed handler or nil if it was not set.
+
+Section: system
+
+Example:
+(trap :sigterm (fn () (exit 1)))
+(test::assert-true (lambda? (untrap :sigterm)))
+(test::assert-false (untrap :sigterm))
+(test::assert-false (trap :sigterm))
 "#,
         ),
     );
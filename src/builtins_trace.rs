@@ -0,0 +1,330 @@
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::hash::BuildHasher;
+use std::io::{self, Write};
+
+use crate::builtins_util::*;
+use crate::environment::*;
+use crate::eval::*;
+use crate::interner::*;
+use crate::pretty_print::pretty_printf;
+use crate::symbols::Binding;
+use crate::types::*;
+
+#[derive(Debug)]
+struct Trace {
+    name: &'static str,
+    // The namespace slot the wrapper was put in, untrace puts original back.
+    binding: Binding,
+    original: Expression,
+    wrapper: Expression,
+    // Only calls this returns true for (called with the args) are printed.
+    pred: Option<Expression>,
+    // Print to this file instead of stderr.
+    file: Option<File>,
+}
+
+/// State for trace and untrace.
+#[derive(Debug, Default)]
+pub struct TraceState {
+    traces: Vec<Trace>,
+    // Number of printed traced calls in progress (for indenting).
+    depth: usize,
+}
+
+impl TraceState {
+    pub fn new() -> Self {
+        TraceState {
+            traces: Vec::new(),
+            depth: 0,
+        }
+    }
+}
+
+// Put the original back if the wrapper is still bound (it was not redefined).
+fn restore(trace: Trace) {
+    if trace.binding.get().ptr_eq(&trace.wrapper) {
+        trace.binding.replace(trace.original);
+    }
+}
+
+fn pretty_string(environment: &mut Environment, exp: &Expression, indent: &str) -> String {
+    // The body of a lambda (an iterator for example) is mostly noise.
+    if matches!(exp.get().data, ExpEnum::Lambda(_) | ExpEnum::Macro(_)) {
+        return exp.to_short_string(40);
+    }
+    let mut out: Vec<u8> = Vec::new();
+    if pretty_printf(exp, environment, &mut out).is_err() {
+        return exp.to_string();
+    }
+    let text = String::from_utf8_lossy(&out);
+    text.trim_end().replace('\n', &format!("\n{}", indent))
+}
+
+fn trace_write(environment: &mut Environment, idx: usize, text: &str) -> Result<(), LispError> {
+    if let Some(file) = &mut environment.tracer.traces[idx].file {
+        file.write_all(text.as_bytes())?;
+    } else {
+        io::stderr().write_all(text.as_bytes())?;
+    }
+    Ok(())
+}
+
+fn builtin_trace_call(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    let name = param_eval(environment, args, "trace-call")?;
+    let call_args = param_eval(environment, args, "trace-call")?;
+    params_done(args, "trace-call")?;
+    let idx = if let ExpEnum::Symbol(name, _) = &name.get().data {
+        environment
+            .tracer
+            .traces
+            .iter()
+            .position(|trace| trace.name == *name)
+            .ok_or_else(|| LispError::new(format!("trace-call: {} is not traced", name)))?
+    } else {
+        return Err(LispError::new("trace-call: requires a symbol"));
+    };
+    let call_args: Vec<Expression> = call_args.iter().collect();
+    let trace = &environment.tracer.traces[idx];
+    let (name, original, pred) = (trace.name, trace.original.clone(), trace.pred.clone());
+    if let Some(pred) = pred {
+        if call_evaled(environment, &pred, call_args.clone())?.is_falsy() {
+            return call_evaled(environment, &original, call_args);
+        }
+    }
+    let indent = "  ".repeat(environment.tracer.depth);
+    let mut line = format!("{}({}", indent, name);
+    for arg in &call_args {
+        line.push(' ');
+        line.push_str(&pretty_string(environment, arg, &indent));
+    }
+    line.push_str(")\n");
+    trace_write(environment, idx, &line)?;
+    environment.tracer.depth += 1;
+    let result = call_evaled(environment, &original, call_args);
+    environment.tracer.depth -= 1;
+    // The trace may have been removed (or replaced) by the call.
+    let idx = environment
+        .tracer
+        .traces
+        .iter()
+        .position(|trace| trace.name == name);
+    if let Some(idx) = idx {
+        let line = match &result {
+            Ok(exp) => format!(
+                "{}=> {}\n",
+                indent,
+                pretty_string(environment, exp, &indent)
+            ),
+            Err(err) => format!("{}!! {}\n", indent, err),
+        };
+        trace_write(environment, idx, &line)?;
+    }
+    result
+}
+
+fn untrace(environment: &mut Environment, name: &str) -> bool {
+    if let Some(idx) = environment
+        .tracer
+        .traces
+        .iter()
+        .position(|trace| trace.name == name)
+    {
+        restore(environment.tracer.traces.remove(idx));
+        true
+    } else {
+        false
+    }
+}
+
+fn builtin_trace(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    let sym = param_eval(environment, args, "trace")?;
+    let name = if let ExpEnum::Symbol(name, _) = &sym.get().data {
+        *name
+    } else {
+        return Err(LispError::with_kind(
+            ":type",
+            "trace: first form must be a symbol",
+        ));
+    };
+    let mut pred = None;
+    let mut file = None;
+    while let Some(key) = args.next() {
+        let key = eval(environment, key)?;
+        let value = param_eval(environment, args, "trace")?;
+        let key_d = key.get();
+        match &key_d.data {
+            ExpEnum::Symbol(":when", _) => pred = Some(value),
+            ExpEnum::Symbol(":file", _) => {
+                let path = if let ExpEnum::String(path, _) = &value.get().data {
+                    path.to_string()
+                } else {
+                    return Err(LispError::with_kind(
+                        ":type",
+                        "trace: :file must be a string",
+                    ));
+                };
+                file = Some(OpenOptions::new().append(true).create(true).open(path)?);
+            }
+            _ => {
+                return Err(LispError::new(format!(
+                    "trace: unknown option {}, expected :when or :file",
+                    key
+                )))
+            }
+        }
+    }
+    untrace(environment, name);
+    let binding = lookup_in_namespace(environment, name)
+        .ok_or_else(|| LispError::new(format!("trace: symbol {} not found", name)))?;
+    let original = binding.get();
+    let is_macro = match &original.get().data {
+        ExpEnum::Lambda(_) => false,
+        ExpEnum::Macro(_) => true,
+        ExpEnum::Function(c) if !c.args_in_context => false,
+        _ => {
+            return Err(LispError::with_kind(
+                ":type",
+                format!(
+                    "trace: {} must be a lambda, macro or builtin that evaluates its args",
+                    name
+                ),
+            ))
+        }
+    };
+    let wrapper = make_forwarding_fn(environment, is_macro, "root::trace-call", name)?;
+    if let ExpEnum::Lambda(l) | ExpEnum::Macro(l) = &mut wrapper.get_mut().data {
+        l.name = Some(environment.interner.intern(&format!("(trace {})", name)));
+    }
+    binding.replace(wrapper.clone());
+    environment.tracer.traces.push(Trace {
+        name,
+        binding,
+        original,
+        wrapper,
+        pred,
+        file,
+    });
+    Ok(sym)
+}
+
+fn builtin_untrace(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    if let Some(sym) = args.next() {
+        let sym = eval(environment, sym)?;
+        params_done(args, "untrace")?;
+        let name = if let ExpEnum::Symbol(name, _) = &sym.get().data {
+            Some(*name)
+        } else {
+            None
+        };
+        if let Some(name) = name {
+            Ok(if untrace(environment, name) {
+                Expression::make_true()
+            } else {
+                Expression::make_false()
+            })
+        } else {
+            Err(LispError::with_kind(":type", "untrace: requires a symbol"))
+        }
+    } else {
+        for trace in environment.tracer.traces.drain(..) {
+            restore(trace);
+        }
+        Ok(Expression::make_nil())
+    }
+}
+
+pub fn add_trace_builtins<S: BuildHasher>(
+    interner: &mut Interner,
+    data: &mut HashMap<&'static str, (Expression, String), S>,
+) {
+    data.insert(
+        interner.intern("trace"),
+        Expression::make_function(
+            builtin_trace,
+            r#"Usage: (trace symbol [:when predicate] [:file path]) -> symbol
+
+Trace calls to the lambda, macro or builtin bound to symbol.  Each call prints
+the name and arguments (indented by the depth of traced calls in progress) and
+then => and the return value (or !! and the error).  For a macro the arguments
+are the unevaluated forms and the return value is the expansion.  If predicate
+is given it is called with the arguments and only calls it returns true for
+are printed.  Output goes to stderr unless :file is given, then it is appended
+to path.  Tracing replaces the binding of symbol with a wrapper, use untrace to
+put the original back.  Builtins that evaluate their own arguments (special
+forms like if or let and builtins like kill that look at them unevaluated) can
+not be traced.
+
+Section: debug
+
+Example:
+(defn trace-test-fact (n) (if (< n 2) 1 (* n (trace-test-fact (- n 1)))))
+(def trace-test-file (str (temp-dir) "/trace-test-out"))
+(syscall "rm" "-f" trace-test-file)
+(trace 'trace-test-fact :file trace-test-file)
+(test::assert-equal 6 (trace-test-fact 3))
+(test::assert-equal "(trace-test-fact 3)\n  (trace-test-fact 2)\n    (trace-test-fact 1)\n    => 1\n  => 2\n=> 6\n" (str (syscall "cat" trace-test-file)))
+(syscall "rm" "-f" trace-test-file)
+(trace 'str-upper :when (fn (s) (= s "b")) :file trace-test-file)
+(test::assert-equal "ABC" (str (str-upper "a") (str-upper "b") (str-upper "c")))
+(test::assert-equal "(str-upper \"b\")\n=> \"B\"\n" (str (syscall "cat" trace-test-file)))
+(untrace)
+(test::assert-error (trace 'kill))
+(test::assert-error (trace 'if))
+"#,
+        ),
+    );
+    data.insert(
+        interner.intern("untrace"),
+        Expression::make_function(
+            builtin_untrace,
+            r#"Usage: (untrace [symbol]) -> t/nil
+
+Stop tracing symbol (see trace) and put its original value back in the
+binding, returns true if symbol was traced.  With no symbol stop tracing
+everything.  If symbol was redefined while traced the new value is kept.
+
+Section: debug
+
+Example:
+(defn untrace-test (x) (+ x 1))
+(def untrace-test-orig untrace-test)
+(trace 'untrace-test :file "/dev/null")
+(test::assert-false (= untrace-test-orig untrace-test))
+(test::assert-equal 2 (untrace-test 1))
+(test::assert-true (untrace 'untrace-test))
+(test::assert-true (= untrace-test-orig untrace-test))
+(test::assert-false (untrace 'untrace-test))
+"#,
+        ),
+    );
+    data.insert(
+        interner.intern("trace-call"),
+        Expression::make_function(
+            builtin_trace_call,
+            r#"Usage: (trace-call symbol args) -> result
+
+Used by the wrappers trace installs, calls the original value of the traced
+symbol with args (a list) and prints the call.
+
+Section: debug
+
+Example:
+(defn trace-call-test (x) (* x 2))
+(trace 'trace-call-test :file "/dev/null")
+(test::assert-equal 4 (trace-call 'trace-call-test '(2)))
+(untrace 'trace-call-test)
+(test::assert-error (trace-call 'trace-call-test '(2)))
+"#,
+        ),
+    );
+}
@@ -2,6 +2,7 @@ use std::collections::HashMap;
 use std::hash::BuildHasher;
 use std::num::{ParseFloatError, ParseIntError};
 
+use crate::bigint::BigInt;
 use crate::builtins_util::*;
 use crate::environment::*;
 use crate::eval::*;
@@ -158,6 +159,23 @@ fn builtin_is_float(
     Err(LispError::new("float? needs one form"))
 }
 
+fn builtin_is_rational(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    if let Some(arg) = args.next() {
+        if args.next().is_none() {
+            let arg = eval_no_values(environment, arg)?;
+            return if let ExpEnum::Rational(_) = arg.get().data {
+                Ok(Expression::make_true())
+            } else {
+                Ok(Expression::make_false())
+            };
+        }
+    }
+    Err(LispError::new("rational? needs one form"))
+}
+
 fn builtin_is_int(
     environment: &mut Environment,
     args: &mut dyn Iterator<Item = Expression>,
@@ -165,7 +183,7 @@ fn builtin_is_int(
     if let Some(arg) = args.next() {
         if args.next().is_none() {
             let arg = eval_no_values(environment, arg)?;
-            return if let ExpEnum::Int(_) = arg.get().data {
+            return if let ExpEnum::Int(_) | ExpEnum::BigInt(_) = arg.get().data {
                 Ok(Expression::make_true())
             } else {
                 Ok(Expression::make_false())
@@ -366,6 +384,23 @@ fn builtin_is_hash(
     Err(LispError::new("hash? needs one form"))
 }
 
+fn builtin_is_set(
+    environment: &mut Environment,
+    args: &mut dyn Iterator<Item = Expression>,
+) -> Result<Expression, LispError> {
+    if let Some(arg) = args.next() {
+        if args.next().is_none() {
+            let arg = eval_no_values(environment, arg)?;
+            return if let ExpEnum::Set(_) = arg.get().data {
+                Ok(Expression::make_true())
+            } else {
+                Ok(Expression::make_false())
+            };
+        }
+    }
+    Err(LispError::new("set? needs one form"))
+}
+
 fn builtin_is_list(
     environment: &mut Environment,
     args: &mut dyn Iterator<Item = Expression>,
@@ -393,7 +428,10 @@ fn builtin_str_to_int(
                 let potential_int: Result<i64, ParseIntError> = istr.parse();
                 return match potential_int {
                     Ok(v) => Ok(Expression::alloc_data(ExpEnum::Int(v))),
-                    Err(_) => Err(LispError::new("str->int: string is not a valid integer")),
+                    Err(_) => match BigInt::parse(istr, 10) {
+                        Some(big) => Ok(Expression::alloc_data(ExpEnum::BigInt(big))),
+                        None => Err(LispError::new("str->int: string is not a valid integer")),
+                    },
                 };
             }
         }
@@ -511,6 +549,8 @@ Types are:
     False
     Float
     Int
+    BigInt
+    Rational
     Symbol
     String
     Char
@@ -523,7 +563,11 @@ Types are:
     Pair
     Nil
     HashMap
+    Set
     File
+    StructType
+
+For a struct instance (see struct::defstruct) the type is the name of the struct.
 
 Section: type
 
@@ -532,6 +576,8 @@ Example:
 (test::assert-equal "False" (type #f))
 (test::assert-equal "Float" (type 1.1))
 (test::assert-equal "Int" (type 1))
+(test::assert-equal "BigInt" (type 12345678901234567890))
+(test::assert-equal "Rational" (type 3/4))
 (test::assert-equal "Symbol" (type 'symbol))
 (def type-sym 'symbol)
 (test::assert-equal "Symbol" (type type-sym))
@@ -550,7 +596,10 @@ Example:
 (test::assert-equal "Nil" (type nil))
 (test::assert-equal "Nil" (type '()))
 (test::assert-equal "HashMap" (type (make-hash)))
+(test::assert-equal "Set" (type #{1 2}))
 (test::assert-equal "File" (type (open :stdin)))
+(struct::defstruct type-struct (a 1 :ro))
+(test::assert-equal "type-struct" (type (type-struct)))
 "#,
         ),
     );
@@ -714,13 +763,36 @@ Example:
             builtin_is_int,
             r#"Usage: (int? expression)
 
-True if the expression is an int, false otherwise.
+True if the expression is an int (including a bignum), false otherwise.
 
 Section: type
 
 Example:
 (test::assert-true (int? 1))
+(test::assert-true (int? 12345678901234567890))
 (test::assert-false (int? 1.5))
+(test::assert-false (int? 1/2))
+"#,
+        ),
+    );
+    data.insert(
+        interner.intern("rational?"),
+        Expression::make_function(
+            builtin_is_rational,
+            r#"Usage: (rational? expression)
+
+True if the expression is a rational (an exact fraction like 3/4), false
+otherwise.  A whole number is always an int, never a rational.  Only a fraction
+written in lowest terms reads as a rational, 6/8 or 2024/10 are symbols.
+
+Section: type
+
+Example:
+(test::assert-true (rational? 3/4))
+(test::assert-true (rational? (/ 1 3)))
+(test::assert-false (rational? (/ 4 2)))
+(test::assert-false (rational? 0.75))
+(test::assert-false (rational? '6/8))
 "#,
         ),
     );
@@ -921,6 +993,24 @@ Example:
 (test::assert-false (hash? '(1 2 3)))
 (test::assert-false (hash? (list)))
 (test::assert-false (hash? (vec)))
+"#,
+        ),
+    );
+    data.insert(
+        interner.intern("set?"),
+        Expression::make_function(
+            builtin_is_set,
+            r#"Usage: (set? expression)
+
+True if the expression is a set, false otherwise.
+
+Section: type
+
+Example:
+(test::assert-true (set? (make-set)))
+(test::assert-true (set? #{1 2}))
+(test::assert-false (set? (make-hash)))
+(test::assert-false (set? '(1 2 3)))
 "#,
         ),
     );
@@ -950,7 +1040,8 @@ Example:
             builtin_str_to_int,
             r#"Usage: (str->int string) -> int
 
-If string is a valid representation of an integer return that int.  Error if not.
+If string is a valid representation of an integer return that int (a bignum if
+it is too large for a 64 bit int).  Error if not.
 
 Section: type
 
@@ -958,6 +1049,8 @@ Example:
 (test::assert-equal 0 (str->int "0"))
 (test::assert-equal 101 (str->int "101"))
 (test::assert-equal -101 (str->int "-101"))
+(test::assert-equal "BigInt" (type (str->int "123456789012345678901234567890")))
+(test::assert-equal 123456789012345678901234567891 (+ 1 (str->int "123456789012345678901234567890")))
 (test::assert-error (str->int "not int"))
 (test::assert-error (str->int "10.0"))
 (test::assert-error (str->int "--10"))
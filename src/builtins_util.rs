@@ -16,7 +16,7 @@ pub fn param_eval(
             "{}: Missing required argument, see (doc '{}) for usage.",
             form, form
         );
-        Err(LispError::new(msg))
+        Err(LispError::with_kind(":arity", msg))
     }
 }
 
@@ -31,7 +31,7 @@ pub fn params_done(
             "{}: Too many arguments, see (doc '{}) for usage.",
             form, form
         );
-        Err(LispError::new(msg))
+        Err(LispError::with_kind(":arity", msg))
     }
 }
 
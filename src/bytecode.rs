@@ -0,0 +1,502 @@
+//! Compile analyzed lambda bodies to bytecode for the vm.
+//!
+//! The compiler works on the forms after analyze has run (macros expanded and
+//! symbols patched with their locations) so locals become stack slots and
+//! globals become bindings.  Calls to lambdas and functions (builtins that
+//! evaluate all their arguments) have their arguments compiled, if, do, and, or,
+//! block and quote are compiled inline and anything else (other special forms,
+//! unpatched symbols, etc) is left as a form for the tree-walker to evaluate.  Calls check
+//! what they are calling when they run and fall back to the tree-walker if it is
+//! not a lambda or function anymore.
+
+use std::cell::RefCell;
+use std::fmt::Write;
+use std::rc::Rc;
+
+use crate::environment::*;
+use crate::symbols::*;
+use crate::types::*;
+
+#[derive(Clone, Copy, Debug)]
+pub enum Op {
+    // Push constant idx.
+    Const(usize),
+    // Push the value in stack slot idx of the current frame.
+    Stack(usize, &'static str),
+    // Push the value of binding idx.
+    Ref(usize),
+    // Push the (resolved) result of evaluating form idx with the tree-walker.
+    Eval(usize),
+    // Push the result of evaluating form idx, it may be a tail call to resolve.
+    EvalTail(usize),
+    Pop,
+    Jump(usize),
+    // Pop the top value and jump if it is false or nil.
+    JumpIfFalse(usize),
+    // If the top value is false or nil replace it with false and jump.
+    And(usize),
+    // If the top value is true jump else pop it.
+    Or(usize),
+    // Values between these are conditions (so failed commands are not errors).
+    CondEnter,
+    CondExit,
+    // If binding idx no longer holds constant idx jump.
+    Guard(usize, usize, usize),
+    // Start a block, a return-from it leaves its value on the stack and jumps.
+    Block(&'static str, usize),
+    BlockEnd,
+    // Call the evaluated command on the stack with the unevaluated args of form
+    // idx (the tree-walker way), true if a tail call.
+    EvalCommand(usize, bool),
+    // If the top value is not a lambda or function jump.
+    Callable(usize),
+    // Call the function under argc arguments, form idx is the source of the call.
+    Call(usize, usize),
+    // Call in tail position, the result may be a tail call to resolve.
+    TailCall(usize, usize),
+    Return,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Chunk {
+    pub code: Vec<Op>,
+    pub consts: Vec<Expression>,
+    pub refs: Vec<(&'static str, Binding)>,
+    pub forms: Vec<Expression>,
+}
+
+/// Compiled state of a lambda, shared by all the closures made from one fn form.
+#[derive(Clone, Debug)]
+pub enum LambdaCode {
+    // Not called yet.
+    Pending,
+    Compiled(Rc<Chunk>),
+    // Run by the tree-walker (*bytecode* was off when first called).
+    TreeWalk,
+}
+
+impl LambdaCode {
+    pub fn new_shared() -> Rc<RefCell<LambdaCode>> {
+        Rc::new(RefCell::new(LambdaCode::Pending))
+    }
+}
+
+struct Compiler {
+    chunk: Chunk,
+}
+
+impl Compiler {
+    fn emit(&mut self, op: Op) -> usize {
+        self.chunk.code.push(op);
+        self.chunk.code.len() - 1
+    }
+
+    fn here(&self) -> usize {
+        self.chunk.code.len()
+    }
+
+    // Point the jump at idx to the current position.
+    fn patch(&mut self, idx: usize) -> Result<(), LispError> {
+        let target = self.here();
+        match &mut self.chunk.code[idx] {
+            Op::Jump(t) | Op::JumpIfFalse(t) | Op::And(t) | Op::Or(t) | Op::Callable(t) => {
+                *t = target
+            }
+            Op::Guard(_, _, t) | Op::Block(_, t) => *t = target,
+            op => {
+                return Err(LispError::new(format!(
+                    "Compiler: patched {:?}, it is not a jump.",
+                    op
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    fn add_const(&mut self, exp: Expression) -> usize {
+        self.chunk.consts.push(exp);
+        self.chunk.consts.len() - 1
+    }
+
+    fn add_ref(&mut self, name: &'static str, binding: &Binding) -> usize {
+        self.chunk.refs.push((name, binding.clone()));
+        self.chunk.refs.len() - 1
+    }
+
+    fn add_form(&mut self, exp: &Expression) -> usize {
+        self.chunk.forms.push(exp.clone());
+        self.chunk.forms.len() - 1
+    }
+
+    fn eval_form(&mut self, exp: &Expression, tail: bool) {
+        let idx = self.add_form(exp);
+        if tail {
+            self.emit(Op::EvalTail(idx));
+        } else {
+            self.emit(Op::Eval(idx));
+        }
+    }
+
+    fn compile(&mut self, exp: &Expression, tail: bool) -> Result<(), LispError> {
+        let exp_d = exp.get();
+        if !*exp_d.analyzed.borrow() {
+            drop(exp_d);
+            self.eval_form(exp, tail);
+            return Ok(());
+        }
+        match &exp_d.data {
+            ExpEnum::Nil
+            | ExpEnum::True
+            | ExpEnum::False
+            | ExpEnum::Int(_)
+            | ExpEnum::BigInt(_)
+            | ExpEnum::Rational(_)
+            | ExpEnum::Float(_)
+            | ExpEnum::Char(_)
+            | ExpEnum::CodePoint(_)
+            | ExpEnum::String(_, _)
+            | ExpEnum::HashMap(_)
+            | ExpEnum::Set(_)
+            | ExpEnum::Lambda(_)
+            | ExpEnum::Macro(_)
+            | ExpEnum::Process(_) => {
+                drop(exp_d);
+                let idx = self.add_const(exp.clone());
+                self.emit(Op::Const(idx));
+            }
+            ExpEnum::Symbol(name, SymLoc::Stack(idx)) => {
+                self.emit(Op::Stack(*idx, name));
+            }
+            ExpEnum::Symbol(name, SymLoc::Ref(binding)) => {
+                let idx = self.add_ref(name, binding);
+                self.emit(Op::Ref(idx));
+            }
+            ExpEnum::Symbol(name, SymLoc::None) if name.starts_with(':') => {
+                drop(exp_d);
+                let idx = self.add_const(exp.clone());
+                self.emit(Op::Const(idx));
+            }
+            ExpEnum::Pair(_, _) | ExpEnum::Vector(_) => {
+                drop(exp_d);
+                self.compile_call(exp, tail)?;
+            }
+            _ => {
+                drop(exp_d);
+                self.eval_form(exp, tail);
+            }
+        }
+        Ok(())
+    }
+
+    fn compile_call(&mut self, form: &Expression, tail: bool) -> Result<(), LispError> {
+        let mut parts = form.iter();
+        let command = if let Some(command) = parts.next() {
+            command
+        } else {
+            self.eval_form(form, tail);
+            return Ok(());
+        };
+        let args: Vec<Expression> = parts.collect();
+        let command_d = command.get();
+        match &command_d.data {
+            ExpEnum::Symbol(name, SymLoc::Ref(binding)) => {
+                let value = binding.get();
+                let inline = match &value.get().data {
+                    ExpEnum::Function(c) if c.is_special_form => match *name {
+                        "do" | "and" | "or" => true,
+                        "if" => !args.is_empty(),
+                        "block" => block_name(&args).is_some(),
+                        _ => false,
+                    },
+                    ExpEnum::Quote => args.len() == 1,
+                    _ => false,
+                };
+                if inline {
+                    let binding_idx = self.add_ref(name, binding);
+                    let value_idx = self.add_const(value);
+                    let guard = self.emit(Op::Guard(binding_idx, value_idx, 0));
+                    match *name {
+                        "if" => self.compile_if(&args, tail)?,
+                        "do" => self.compile_do(&args, tail)?,
+                        "and" => self.compile_and(&args)?,
+                        "or" => self.compile_or(&args)?,
+                        "block" => self.compile_block(&args, tail)?,
+                        _ => {
+                            let idx = self.add_const(args[0].clone());
+                            self.emit(Op::Const(idx));
+                        }
+                    }
+                    let done = self.emit(Op::Jump(0));
+                    self.patch(guard)?;
+                    drop(command_d);
+                    self.eval_form(form, tail);
+                    self.patch(done)?;
+                } else {
+                    let idx = self.add_ref(name, binding);
+                    drop(command_d);
+                    self.emit(Op::Ref(idx));
+                    self.compile_args(form, &args, tail)?;
+                }
+            }
+            ExpEnum::Symbol(name, SymLoc::Stack(idx)) => {
+                self.emit(Op::Stack(*idx, name));
+                drop(command_d);
+                self.compile_args(form, &args, tail)?;
+            }
+            ExpEnum::Wrapper(_) | ExpEnum::Pair(_, _) | ExpEnum::Vector(_) => {
+                // A fn in the call position (what let expands to) or a form
+                // that evaluates to the command.
+                drop(command_d);
+                let idx = self.add_form(&command);
+                self.emit(Op::Eval(idx));
+                self.compile_args(form, &args, tail)?;
+            }
+            _ => {
+                drop(command_d);
+                self.eval_form(form, tail);
+            }
+        }
+        Ok(())
+    }
+
+    // The command is on the stack, compile the args and call it.  If it is not
+    // a lambda or function a symbol command is left to the tree-walker (for
+    // its errors and special forms), otherwise the evaluated command is called
+    // with the unevaluated args.
+    fn compile_args(
+        &mut self,
+        form: &Expression,
+        args: &[Expression],
+        tail: bool,
+    ) -> Result<(), LispError> {
+        let symbol_command = form
+            .iter()
+            .next()
+            .map(|c| matches!(c.get().data, ExpEnum::Symbol(_, _)))
+            .unwrap_or(false);
+        let callable = self.emit(Op::Callable(0));
+        for arg in args {
+            self.compile(arg, false)?;
+        }
+        let form_idx = self.add_form(form);
+        if tail {
+            self.emit(Op::TailCall(args.len(), form_idx));
+        } else {
+            self.emit(Op::Call(args.len(), form_idx));
+        }
+        let done = self.emit(Op::Jump(0));
+        self.patch(callable)?;
+        if symbol_command {
+            self.emit(Op::Pop);
+            self.eval_form(form, tail);
+        } else {
+            let form_idx = self.add_form(form);
+            self.emit(Op::EvalCommand(form_idx, tail));
+        }
+        self.patch(done)?;
+        Ok(())
+    }
+
+    fn compile_condition(&mut self, exp: &Expression) -> Result<(), LispError> {
+        self.emit(Op::CondEnter);
+        self.compile(exp, false)?;
+        self.emit(Op::CondExit);
+        Ok(())
+    }
+
+    fn compile_if(&mut self, args: &[Expression], tail: bool) -> Result<(), LispError> {
+        let mut dones = Vec::new();
+        let mut args = args.iter().peekable();
+        while let Some(arg) = args.next() {
+            if let Some(then) = args.next() {
+                self.compile_condition(arg)?;
+                let next = self.emit(Op::JumpIfFalse(0));
+                self.compile(then, tail)?;
+                dones.push(self.emit(Op::Jump(0)));
+                self.patch(next)?;
+                if args.peek().is_none() {
+                    let idx = self.add_const(Expression::make_false());
+                    self.emit(Op::Const(idx));
+                }
+            } else {
+                self.compile(arg, tail)?;
+            }
+        }
+        for done in dones {
+            self.patch(done)?;
+        }
+        Ok(())
+    }
+
+    fn compile_do(&mut self, args: &[Expression], tail: bool) -> Result<(), LispError> {
+        if let Some((last, init)) = args.split_last() {
+            for arg in init {
+                self.compile(arg, false)?;
+                self.emit(Op::Pop);
+            }
+            self.compile(last, tail)?;
+        } else {
+            let idx = self.add_const(Expression::make_nil());
+            self.emit(Op::Const(idx));
+        }
+        Ok(())
+    }
+
+    fn compile_block(&mut self, args: &[Expression], tail: bool) -> Result<(), LispError> {
+        let name =
+            block_name(args).ok_or_else(|| LispError::new("Compiler: block requires a name."))?;
+        let block = self.emit(Op::Block(name, 0));
+        self.compile_do(&args[1..], tail)?;
+        self.emit(Op::BlockEnd);
+        self.patch(block)?;
+        Ok(())
+    }
+
+    fn compile_and(&mut self, args: &[Expression]) -> Result<(), LispError> {
+        if let Some((last, init)) = args.split_last() {
+            let mut dones = Vec::new();
+            for arg in init {
+                self.compile_condition(arg)?;
+                dones.push(self.emit(Op::And(0)));
+                self.emit(Op::Pop);
+            }
+            self.compile(last, false)?;
+            dones.push(self.emit(Op::And(0)));
+            for done in dones {
+                self.patch(done)?;
+            }
+        } else {
+            let idx = self.add_const(Expression::make_true());
+            self.emit(Op::Const(idx));
+        }
+        Ok(())
+    }
+
+    fn compile_or(&mut self, args: &[Expression]) -> Result<(), LispError> {
+        let mut dones = Vec::new();
+        let mut args = args.iter().peekable();
+        while let Some(arg) = args.next() {
+            if args.peek().is_some() {
+                self.compile_condition(arg)?;
+            } else {
+                self.compile(arg, false)?;
+            }
+            dones.push(self.emit(Op::Or(0)));
+        }
+        let idx = self.add_const(Expression::make_false());
+        self.emit(Op::Const(idx));
+        for done in dones {
+            self.patch(done)?;
+        }
+        Ok(())
+    }
+}
+
+fn block_name(args: &[Expression]) -> Option<&'static str> {
+    if let ExpEnum::Symbol(name, _) = &args.first()?.get().data {
+        Some(name)
+    } else {
+        None
+    }
+}
+
+/// Compile the body of lambda.
+pub fn compile_lambda(lambda: &Lambda) -> Result<Chunk, LispError> {
+    let mut compiler = Compiler {
+        chunk: Chunk::default(),
+    };
+    match &lambda.body {
+        MultiExpression::None => {
+            let idx = compiler.add_const(Expression::make_nil());
+            compiler.emit(Op::Const(idx));
+        }
+        MultiExpression::Single(exp) => compiler.compile(exp, true)?,
+        MultiExpression::Multiple(body) => compiler.compile_do(body, true)?,
+    }
+    compiler.emit(Op::Return);
+    Ok(compiler.chunk)
+}
+
+/// Returns the compiled body of lambda (compiling it on first use), None if it
+/// should be run by the tree-walker.
+pub fn lambda_chunk(
+    environment: &Environment,
+    lambda: &Lambda,
+) -> Result<Option<Rc<Chunk>>, LispError> {
+    if environment.debugger.active {
+        // The debugger hooks into the tree-walker.
+        return Ok(None);
+    }
+    let code = match &*lambda.code.borrow() {
+        LambdaCode::Compiled(chunk) => return Ok(Some(chunk.clone())),
+        LambdaCode::TreeWalk => return Ok(None),
+        LambdaCode::Pending => {
+            let enabled = lookup_expression(environment, "*bytecode*")
+                .map(|exp| !exp.is_falsy())
+                .unwrap_or(true);
+            if enabled {
+                LambdaCode::Compiled(Rc::new(compile_lambda(lambda)?))
+            } else {
+                LambdaCode::TreeWalk
+            }
+        }
+    };
+    lambda.code.replace(code);
+    lambda_chunk(environment, lambda)
+}
+
+fn short_form(exp: &Expression) -> String {
+    exp.to_short_string(40)
+}
+
+/// Human readable listing of chunk.
+pub fn disassemble(chunk: &Chunk) -> String {
+    let mut out = String::new();
+    for (i, op) in chunk.code.iter().enumerate() {
+        let (name, args, note) = match op {
+            Op::Const(idx) => ("const", idx.to_string(), short_form(&chunk.consts[*idx])),
+            Op::Stack(idx, name) => ("stack", idx.to_string(), name.to_string()),
+            Op::Ref(idx) => ("ref", idx.to_string(), chunk.refs[*idx].0.to_string()),
+            Op::Eval(idx) => ("eval", idx.to_string(), short_form(&chunk.forms[*idx])),
+            Op::EvalTail(idx) => ("eval-tail", idx.to_string(), short_form(&chunk.forms[*idx])),
+            Op::EvalCommand(idx, tail) => (
+                if *tail { "eval-cmd-tail" } else { "eval-cmd" },
+                idx.to_string(),
+                short_form(&chunk.forms[*idx]),
+            ),
+            Op::Pop => ("pop", String::new(), String::new()),
+            Op::Jump(t) => ("jump", t.to_string(), String::new()),
+            Op::JumpIfFalse(t) => ("jump-if-false", t.to_string(), String::new()),
+            Op::And(t) => ("and", t.to_string(), String::new()),
+            Op::Or(t) => ("or", t.to_string(), String::new()),
+            Op::CondEnter => ("cond-enter", String::new(), String::new()),
+            Op::CondExit => ("cond-exit", String::new(), String::new()),
+            Op::Guard(binding, _, t) => (
+                "guard",
+                format!("{} {}", binding, t),
+                chunk.refs[*binding].0.to_string(),
+            ),
+            Op::Block(name, t) => ("block", t.to_string(), name.to_string()),
+            Op::BlockEnd => ("block-end", String::new(), String::new()),
+            Op::Callable(t) => ("callable", t.to_string(), String::new()),
+            Op::Call(argc, idx) => (
+                "call",
+                format!("{} {}", argc, idx),
+                short_form(&chunk.forms[*idx]),
+            ),
+            Op::TailCall(argc, idx) => (
+                "tail-call",
+                format!("{} {}", argc, idx),
+                short_form(&chunk.forms[*idx]),
+            ),
+            Op::Return => ("return", String::new(), String::new()),
+        };
+        let line = format!("{:4}  {:<14}{:<8}", i, name, args);
+        if note.is_empty() {
+            writeln!(out, "{}", line.trim_end()).unwrap();
+        } else {
+            writeln!(out, "{}; {}", line, note).unwrap();
+        }
+    }
+    out
+}
@@ -5,6 +5,9 @@ pub struct Config {
     pub command: Option<String>,
     pub script: Option<String>,
     pub args: Vec<String>,
+    pub errexit: bool,
+    pub xtrace: bool,
+    pub profile: Option<String>,
 }
 
 pub const VERSION_STRING: &str = env!("VERSION_STRING");
@@ -18,9 +21,13 @@ USAGE:
 FLAGS:
     -v, --version  Print the version, platform and revision of sl-sh then exit.
     -h, --help     Print help (this) and exit.
+    -e, --errexit  Set *errexit*, a command that fails is an error (ends a script).
+    -x, --xtrace   Set *xtrace*, print system commands to stderr before running them.
 
 OPTIONS:
-    -c             Command to run instead of entering the REPL.
+    -c                Command to run instead of entering the REPL.
+    --profile <file>  Profile running the script (or loading slshrc), print a report
+                      to stderr and write folded stacks (for a flamegraph) to file.
 
 ARGS:
     <args>...      Script to run with arguments."#;
@@ -47,6 +54,9 @@ pub fn get_config() -> Option<Config> {
     let mut command: Option<String> = None;
     let mut script: Option<String> = None;
     let mut command_args: Vec<String> = Vec::new();
+    let mut errexit = false;
+    let mut xtrace = false;
+    let mut profile: Option<String> = None;
 
     let mut args: Vec<OsString> = env::args_os().collect();
 
@@ -72,6 +82,12 @@ pub fn get_config() -> Option<Config> {
                         help(&exe_name);
                         return None;
                     }
+                    // After the script these are args for the script.
+                    "-e" | "--errexit" if script.is_none() => errexit = true,
+                    "-x" | "--xtrace" if script.is_none() => xtrace = true,
+                    "--profile" if script.is_none() => {
+                        profile = Some(get_arg(&exe_name, &mut args)?);
+                    }
                     _ => {
                         if command.is_none() && script.is_none() {
                             script = Some(arg);
@@ -90,5 +106,8 @@ pub fn get_config() -> Option<Config> {
         command,
         script,
         args: command_args,
+        errexit,
+        xtrace,
+        profile,
     })
 }
@@ -2,16 +2,23 @@ use std::cell::RefCell;
 use std::collections::HashMap;
 use std::fmt;
 use std::io;
+use std::num::NonZeroUsize;
 use std::rc::Rc;
+use std::time::SystemTime;
 
 use sl_liner::Context;
 
+use crate::builtins_debug::DebugState;
+use crate::builtins_generic::GenericState;
+use crate::builtins_profile::ProfileState;
+use crate::builtins_trace::TraceState;
 use crate::interner::*;
 use crate::process::*;
 use crate::reader::ReaderState;
+use crate::signals::test_clear_sigchld;
 use crate::symbols::*;
 use crate::types::*;
-use crate::unix::cvt;
+use crate::unix::{cvt, FdRedirect};
 use crate::{add_math_builtins, add_stats_builtins};
 
 const ROOT_NS: &str = "root";
@@ -53,6 +60,8 @@ impl Default for ReplSettings {
 pub enum JobStatus {
     Running,
     Stopped,
+    // Exit status of the last process in the job, None if unknown (killed for instance).
+    Done(Option<i32>),
 }
 
 impl fmt::Display for JobStatus {
@@ -60,21 +69,78 @@ impl fmt::Display for JobStatus {
         match self {
             JobStatus::Running => write!(f, "Running"),
             JobStatus::Stopped => write!(f, "Stopped"),
+            JobStatus::Done(Some(0)) => write!(f, "Done"),
+            JobStatus::Done(Some(code)) => write!(f, "Exit {}", code),
+            JobStatus::Done(None) => write!(f, "Terminated"),
         }
     }
 }
 
 #[derive(Clone, Debug)]
 pub struct Job {
+    // Job number used by job specs (%1), not reused until the job is gone.
+    pub id: u32,
     pub pids: Vec<u32>,
+    // Command text for each process in the job.
     pub names: Vec<String>,
     pub status: JobStatus,
+    // Pids that have exited with their status, the job is done once they all have.
+    pub exited: Vec<(u32, Option<i32>)>,
+    // Background jobs (fork/& or bg) report when they are done.
+    pub background: bool,
+    pub started: SystemTime,
+}
+
+impl Job {
+    pub fn new(id: u32) -> Job {
+        Job {
+            id,
+            pids: Vec::new(),
+            names: Vec::new(),
+            status: JobStatus::Running,
+            exited: Vec::new(),
+            background: false,
+            started: SystemTime::now(),
+        }
+    }
+
+    /// The command text for the whole job (a pipe is joined with |).
+    pub fn command(&self) -> String {
+        self.names.join(" | ")
+    }
+}
+
+// Id of a handler-case, restart-case or get-error frame (see next_frame_id).
+pub type FrameId = NonZeroUsize;
+
+#[derive(Clone, Debug)]
+pub enum HandlerAction {
+    // Call the handler (handler-bind) without unwinding.
+    Call(Expression),
+    // Unwind to the frame with this id (handler-case, get-error).
+    Unwind(FrameId),
+}
+
+#[derive(Clone, Debug)]
+pub struct Handler {
+    // Condition kind this handles (:error handles all kinds).
+    pub kind: &'static str,
+    pub action: HandlerAction,
+}
+
+#[derive(Clone, Debug)]
+pub struct Restart {
+    pub name: &'static str,
+    // Id of the restart-case clause to unwind to.
+    pub id: FrameId,
 }
 
 #[derive(Clone, Debug)]
 pub struct StackFrame {
     pub index: usize,
     pub symbols: Symbols,
+    // Name of the lambda this is a call to (if it has one).
+    pub name: Option<&'static str>,
 }
 
 pub struct GrabProcOutput<'a> {
@@ -101,6 +167,8 @@ impl<'a> Drop for GrabProcOutput<'a> {
 }
 
 pub type ProcessMap = Rc<RefCell<HashMap<u32, (Expression, Option<i32>)>>>;
+// A process substitution (fd, pid, is_in), see proc_subs.
+pub type ProcSub = (i32, u32, bool);
 
 //#[derive(Clone, Debug)]
 pub struct Environment {
@@ -144,9 +212,40 @@ pub struct Environment {
     pub terminal_fd: i32,
     pub grab_proc_output: bool,
     pub in_fork: bool,
+    // File descriptor redirects (fd-redirect) to apply to system commands.
+    pub fd_redirects: Vec<FdRedirect>,
+    // Open process substitutions (fd, pid, is_in), closed and reaped after the command using
+    // them.  is_in is true for <(...) (the process writes to the fd we keep).
+    pub proc_subs: Vec<ProcSub>,
+    // Process substitutions of a command that did not exit (it was stopped), key is the
+    // command's pid.  They are closed and reaped once it exits (see try_wait_pid).
+    pub held_proc_subs: RefCell<HashMap<u32, Vec<ProcSub>>>,
+    // Trap handlers (trap), key is the trap keyword (:sigterm, :exit, etc).
+    pub traps: HashMap<&'static str, Expression>,
+    // Depth of conditions (if tests, and, or, not) being evaluated, *errexit* is off in them.
+    pub in_condition: u32,
+    // Active condition handlers (handler-bind, handler-case, get-error), innermost last.
+    pub handlers: Vec<Handler>,
+    // Active restarts (restart-case), innermost last.
+    pub restarts: Vec<Restart>,
+    // Last id given to a handler-case/restart-case/get-error frame.
+    pub last_frame_id: usize,
+    // Value stack and active blocks (name, end, stack depth) for running bytecode.
+    pub vm_stack: Vec<Expression>,
+    pub vm_blocks: Vec<(&'static str, usize, usize)>,
+    pub debugger: DebugState,
+    pub profiler: ProfileState,
+    pub tracer: TraceState,
+    pub generics: GenericState,
 }
 
 impl Environment {
+    /// A new unique id for a handler-case, restart-case or get-error frame.
+    pub fn next_frame_id(&mut self) -> FrameId {
+        self.last_frame_id += 1;
+        FrameId::new(self.last_frame_id).expect("frame ids start at 1")
+    }
+
     pub fn insert_into_root_scope(&mut self, symbol: &'static str, data: Expression) {
         self.root_scope.borrow_mut().insert(symbol, data);
     }
@@ -209,6 +308,20 @@ pub fn build_default_environment() -> Environment {
         terminal_fd,
         grab_proc_output: false,
         in_fork: false,
+        fd_redirects: Vec::new(),
+        proc_subs: Vec::new(),
+        held_proc_subs: RefCell::new(HashMap::new()),
+        traps: HashMap::new(),
+        in_condition: 0,
+        handlers: Vec::new(),
+        restarts: Vec::new(),
+        last_frame_id: 0,
+        vm_stack: Vec::new(),
+        vm_blocks: Vec::new(),
+        debugger: DebugState::new(),
+        profiler: ProfileState::new(),
+        tracer: TraceState::new(),
+        generics: GenericState::new(),
     }
 }
 
@@ -240,7 +353,7 @@ pub fn get_from_namespace(environment: &Environment, key: &str) -> Option<Expres
     None
 }
 
-fn lookup_in_stack(environment: &Environment, key: &str) -> Option<Binding> {
+pub(crate) fn lookup_in_stack(environment: &Environment, key: &str) -> Option<Binding> {
     if let Some(current_frame) = environment.stack_frames.last() {
         let lex_id = current_frame.symbols.lex_id();
         let lex_depth = current_frame.symbols.lex_depth();
@@ -269,7 +382,7 @@ fn lookup_in_stack(environment: &Environment, key: &str) -> Option<Binding> {
     None
 }
 
-fn lookup_in_namespace(environment: &Environment, key: &str) -> Option<Binding> {
+pub(crate) fn lookup_in_namespace(environment: &Environment, key: &str) -> Option<Binding> {
     // Check for namespaced symbols.
     if key.contains("::") {
         // namespace reference.
@@ -369,36 +482,72 @@ pub fn get_namespace(environment: &Environment, name: &str) -> Option<Rc<RefCell
     }
 }
 
+/// Id for a new job, one more than the highest id in use.
+pub fn next_job_id(environment: &Environment) -> u32 {
+    environment
+        .jobs
+        .borrow()
+        .iter()
+        .map(|j| j.id)
+        .max()
+        .unwrap_or(0)
+        + 1
+}
+
 pub fn mark_job_stopped(environment: &Environment, pid: u32) {
-    'outer: for mut j in environment.jobs.borrow_mut().iter_mut() {
-        for p in &j.pids {
-            if *p == pid {
-                j.status = JobStatus::Stopped;
-                break 'outer;
-            }
+    for j in environment.jobs.borrow_mut().iter_mut() {
+        if j.pids.contains(&pid) {
+            j.status = JobStatus::Stopped;
+            // A stopped job is no longer in the foreground.
+            j.background = true;
+            break;
         }
     }
 }
 
 pub fn mark_job_running(environment: &Environment, pid: u32) {
-    'outer: for mut j in environment.jobs.borrow_mut().iter_mut() {
-        for p in &j.pids {
-            if *p == pid {
-                j.status = JobStatus::Running;
-                break 'outer;
-            }
+    for j in environment.jobs.borrow_mut().iter_mut() {
+        if j.pids.contains(&pid) {
+            j.status = JobStatus::Running;
+            break;
+        }
+    }
+}
+
+pub fn set_job_background(environment: &Environment, pid: u32, background: bool) {
+    for j in environment.jobs.borrow_mut().iter_mut() {
+        if j.pids.contains(&pid) {
+            j.background = background;
+            break;
         }
     }
 }
 
-pub fn remove_job(environment: &Environment, pid: u32) {
+/// Record that pid has exited with status.  Once all of a job's processes are
+/// gone a foreground job is removed and a background job is marked done (it is
+/// removed once reported, see take_done_jobs).
+pub fn mark_job_exited(environment: &Environment, pid: u32, status: Option<i32>) {
     let mut idx: Option<usize> = None;
-    'outer: for (i, j) in environment.jobs.borrow_mut().iter_mut().enumerate() {
-        for p in &j.pids {
-            if *p == pid {
-                idx = Some(i);
-                break 'outer;
+    for (i, j) in environment.jobs.borrow_mut().iter_mut().enumerate() {
+        if j.pids.contains(&pid) {
+            if !j.exited.iter().any(|(p, _)| *p == pid) {
+                j.exited.push((pid, status));
             }
+            if j.exited.len() >= j.pids.len() {
+                if j.background {
+                    // Like a pipe the job's status is the status of it's last process.
+                    let last_pid = j.pids[j.pids.len() - 1];
+                    let status = j
+                        .exited
+                        .iter()
+                        .find(|(p, _)| *p == last_pid)
+                        .and_then(|(_, status)| *status);
+                    j.status = JobStatus::Done(status);
+                } else {
+                    idx = Some(i);
+                }
+            }
+            break;
         }
     }
     if let Some(i) = idx {
@@ -406,11 +555,30 @@ pub fn remove_job(environment: &Environment, pid: u32) {
     }
 }
 
+/// Remove and return the jobs that are done.
+pub fn take_done_jobs(environment: &Environment) -> Vec<Job> {
+    let mut jobs = environment.jobs.borrow_mut();
+    let mut done = Vec::new();
+    let mut i = 0;
+    while i < jobs.len() {
+        if let JobStatus::Done(_) = jobs[i].status {
+            done.push(jobs.remove(i));
+        } else {
+            i += 1;
+        }
+    }
+    done
+}
+
 pub fn add_process(environment: &Environment, pid: u32, val: (Expression, Option<i32>)) {
     environment.procs.borrow_mut().insert(pid, val);
 }
 
 pub fn reap_procs(environment: &Environment) -> io::Result<()> {
+    // Nothing to do unless a child changed state (SIGCHLD) since the last reap.
+    if !test_clear_sigchld() {
+        return Ok(());
+    }
     let procs = environment.procs.borrow_mut();
     if !procs.is_empty() {
         let keys: Vec<u32> = procs.keys().copied().collect();
@@ -495,6 +663,7 @@ mod tests {
         environment.stack_frames.push(StackFrame {
             index: 0,
             symbols: syms.clone(),
+            name: None,
         });
         assert!(lookup_expression(&mut environment, "XXXX").is_none());
         assert_lookup(&environment, "XXX", 222);
@@ -510,6 +679,7 @@ mod tests {
         environment.stack_frames.push(StackFrame {
             index: syms.len(),
             symbols: syms2.clone(),
+            name: None,
         });
         environment.stack_frame_base = syms.len();
         environment
@@ -527,6 +697,7 @@ mod tests {
         environment.stack_frames.push(StackFrame {
             index: (syms.len() + syms2.len()),
             symbols: syms3.clone(),
+            name: None,
         });
         environment.stack_frame_base = syms.len() + syms2.len();
         environment
@@ -605,6 +776,7 @@ mod tests {
         environment.stack_frames.push(StackFrame {
             index: 0,
             symbols: syms4.clone(),
+            name: None,
         });
         environment.stack_frame_base = 0;
         assert!(lookup_expression(&mut environment, "a1").is_none());
@@ -642,6 +814,7 @@ mod tests {
         environment.stack_frames.push(StackFrame {
             index: 0,
             symbols: syms.clone(),
+            name: None,
         });
         //assert!(
         //    get_expression(&mut environment, ExpEnum::Symbol("NA", SymLoc::None).into()).is_none()
@@ -662,6 +835,7 @@ mod tests {
         environment.stack_frames.push(StackFrame {
             index: syms.len(),
             symbols: syms2.clone(),
+            name: None,
         });
         environment.stack_frame_base = syms.len();
         environment
@@ -3,10 +3,18 @@ use std::cell::RefCell;
 use crate::analyze::*;
 use crate::builtins::{builtin_bquote, builtin_quote};
 use crate::builtins_bind::{builtin_def, builtin_var};
+use crate::builtins_conditions::signal_error;
+use crate::builtins_debug::{debug_call_hook, debug_eval_hook};
+use crate::builtins_profile::profile_name;
+use crate::builtins_struct::struct_call;
+use crate::builtins_system::run_pending_traps;
+use crate::bytecode::lambda_chunk;
 use crate::environment::*;
-use crate::signals::test_clear_sigint;
+use crate::gc::{collect, gc_wanted};
+use crate::signals::{test_clear_sigint, traps_pending};
 use crate::symbols::*;
 use crate::types::*;
+use crate::vm::run_chunk;
 
 fn setup_args(
     environment: &mut Environment,
@@ -26,16 +34,22 @@ fn setup_args(
         }
     }
     if has_rest && params < (num_params - 1) {
-        return Err(LispError::new(format!(
-            "wrong number of parameters, expected at least {} got {}",
-            (num_params - 1),
-            params,
-        )));
+        return Err(LispError::with_kind(
+            ":arity",
+            format!(
+                "wrong number of parameters, expected at least {} got {}",
+                (num_params - 1),
+                params,
+            ),
+        ));
     } else if !has_rest && params != num_params {
-        return Err(LispError::new(format!(
-            "wrong number of parameters, expected {} got {}",
-            num_params, params,
-        )));
+        return Err(LispError::with_kind(
+            ":arity",
+            format!(
+                "wrong number of parameters, expected {} got {}",
+                num_params, params,
+            ),
+        ));
     }
     if let Some(rest_data) = rest_data {
         if rest_data.is_empty() {
@@ -51,18 +65,255 @@ fn setup_args(
     Ok(())
 }
 
+fn push_opt_param(
+    environment: &mut Environment,
+    param: &OptParam,
+    arg: Option<Expression>,
+    defaults: &mut Vec<(usize, Expression)>,
+) {
+    let supplied = arg.is_some();
+    if let Some(arg) = arg {
+        environment.stack.push(Binding::with_expression(arg));
+    } else {
+        if let Some(default) = &param.default {
+            defaults.push((environment.stack.len(), default.clone()));
+        }
+        environment
+            .stack
+            .push(Binding::with_expression(Expression::make_nil()));
+    }
+    if param.supplied.is_some() {
+        environment
+            .stack
+            .push(Binding::with_expression(if supplied {
+                Expression::make_true()
+            } else {
+                Expression::make_false()
+            }));
+    }
+}
+
+// setup_args for a lambda with &optional or &key params, returns the stack
+// slots that need their default evaluated (once the frame is set up).
+fn setup_opt_args(
+    environment: &mut Environment,
+    lambda: &Lambda,
+    vars: &mut dyn Iterator<Item = Expression>,
+) -> Result<Vec<(usize, Expression)>, LispError> {
+    let mut args = Vec::new();
+    for v in vars {
+        args.push(v.resolve(environment)?);
+    }
+    let required = lambda.num_params - if lambda.has_rest { 1 } else { 0 };
+    let max = required + lambda.optional.len();
+    if args.len() < required || (!lambda.has_rest && lambda.keys.is_empty() && args.len() > max) {
+        let expected = if lambda.has_rest || !lambda.keys.is_empty() {
+            format!("at least {}", required)
+        } else if lambda.optional.is_empty() {
+            format!("{}", required)
+        } else {
+            format!("{} to {}", required, max)
+        };
+        return Err(LispError::with_kind(
+            ":arity",
+            format!(
+                "wrong number of parameters, expected {} got {}",
+                expected,
+                args.len(),
+            ),
+        ));
+    }
+    let mut args = args.drain(..);
+    let mut defaults = Vec::new();
+    for _ in 0..required {
+        if let Some(arg) = args.next() {
+            environment.stack.push(Binding::with_expression(arg));
+        }
+    }
+    for param in &lambda.optional {
+        push_opt_param(environment, param, args.next(), &mut defaults);
+    }
+    let rest: Vec<Expression> = args.collect();
+    if lambda.has_rest {
+        environment
+            .stack
+            .push(Binding::with_expression(if rest.is_empty() {
+                Expression::make_nil()
+            } else {
+                Expression::with_list(rest.clone())
+            }));
+    }
+    if !lambda.keys.is_empty() {
+        if rest.len() % 2 == 1 {
+            return Err(LispError::with_kind(
+                ":arity",
+                "wrong number of parameters, &key arguments must be pairs of keyword and value",
+            ));
+        }
+        let mut key_args: Vec<Option<Expression>> = vec![None; lambda.keys.len()];
+        for pair in rest.chunks(2) {
+            let idx = if let ExpEnum::Symbol(key, _) = &pair[0].get().data {
+                lambda
+                    .keys
+                    .iter()
+                    .position(|param| key.starts_with(':') && param.name == &key[1..])
+            } else {
+                None
+            };
+            if let Some(idx) = idx {
+                // Like common lisp the first of a repeated key wins.
+                if key_args[idx].is_none() {
+                    key_args[idx] = Some(pair[1].clone());
+                }
+            } else if !lambda.has_rest {
+                return Err(LispError::with_kind(
+                    ":arity",
+                    format!("unknown keyword argument {}", pair[0]),
+                ));
+            }
+        }
+        for (param, arg) in lambda.keys.iter().zip(key_args) {
+            push_opt_param(environment, param, arg, &mut defaults);
+        }
+    }
+    Ok(defaults)
+}
+
+fn destructure_arity(
+    text: &Expression,
+    at_least: bool,
+    expected: usize,
+    value: &Expression,
+) -> LispError {
+    LispError::with_kind(
+        ":arity",
+        format!(
+            "wrong number of elements to destructure {} into {}, expected {}{} got {}",
+            value.to_short_string(40),
+            text,
+            if at_least { "at least " } else { "" },
+            expected,
+            value.iter().count()
+        ),
+    )
+}
+
+// Bind the parts of value to the slots in pattern (relative to the frame at
+// index), text is the param's pattern for errors.
+fn destructure(
+    environment: &mut Environment,
+    pattern: &Pattern,
+    value: Expression,
+    index: usize,
+    text: &Expression,
+) -> Result<(), LispError> {
+    match pattern {
+        Pattern::Bind(slot) => {
+            environment.stack[index + slot].replace(value);
+        }
+        Pattern::Seq(items, rest) => {
+            let value_d = value.get();
+            let (parts, tail) = match &value_d.data {
+                ExpEnum::Vector(v) => {
+                    let len = items.len().min(v.len());
+                    let tail = if v.len() > len {
+                        Expression::with_list(v[len..].to_vec())
+                    } else {
+                        Expression::make_nil()
+                    };
+                    (v[..len].to_vec(), tail)
+                }
+                ExpEnum::Pair(_, _) | ExpEnum::Nil => {
+                    let mut parts = Vec::new();
+                    let mut tail = value.clone();
+                    while parts.len() < items.len() {
+                        let next = if let ExpEnum::Pair(car, cdr) = &tail.get().data {
+                            parts.push(car.clone());
+                            cdr.clone()
+                        } else {
+                            break;
+                        };
+                        tail = next;
+                    }
+                    (parts, tail)
+                }
+                _ => {
+                    return Err(LispError::with_kind(
+                        ":type",
+                        format!(
+                            "can not destructure {} into {}, expected a list or vector",
+                            value.display_type(),
+                            text
+                        ),
+                    ))
+                }
+            };
+            drop(value_d);
+            if parts.len() < items.len() || (rest.is_none() && !tail.is_nil()) {
+                return Err(destructure_arity(text, rest.is_some(), items.len(), &value));
+            }
+            for (item, part) in items.iter().zip(parts) {
+                destructure(environment, item, part, index, text)?;
+            }
+            if let Some(rest) = rest {
+                destructure(environment, rest, tail, index, text)?;
+            }
+        }
+        Pattern::Map(entries, as_slot) => {
+            if !value.is_nil() && !matches!(value.get().data, ExpEnum::HashMap(_)) {
+                return Err(LispError::with_kind(
+                    ":type",
+                    format!(
+                        "can not destructure {} into {}, expected a hashmap",
+                        value.display_type(),
+                        text
+                    ),
+                ));
+            }
+            for entry in entries {
+                let found = if let ExpEnum::HashMap(map) = &value.get().data {
+                    entry
+                        .keys
+                        .iter()
+                        .find_map(|key| hash_get_str(map, key).cloned())
+                } else {
+                    None
+                };
+                let part = match (found, &entry.default) {
+                    (Some(part), _) => part,
+                    (None, Some(default)) => eval(environment, default)?,
+                    (None, None) => Expression::make_nil(),
+                };
+                destructure(environment, &entry.pattern, part, index, text)?;
+            }
+            if let Some(slot) = as_slot {
+                environment.stack[index + slot].replace(value);
+            }
+        }
+    }
+    Ok(())
+}
+
 fn prep_stack(
     environment: &mut Environment,
     vars: &mut dyn Iterator<Item = Expression>,
     lambda: &Lambda,
     lambda_exp: Expression,
+    bound: &[(usize, Binding)],
 ) -> Result<(), LispError> {
     let index = environment.stack.len();
-    setup_args(environment, lambda.num_params, lambda.has_rest, vars)?;
+    let defaults = if lambda.optional.is_empty() && lambda.keys.is_empty() {
+        setup_args(environment, lambda.num_params, lambda.has_rest, vars)?;
+        Vec::new()
+    } else {
+        setup_opt_args(environment, lambda, vars)?
+    };
     let symbols = lambda.syms.clone();
     if !lambda.no_recur {
         // Push the 'this-fn' value.
-        environment.stack.push(Binding::with_expression(lambda_exp));
+        environment
+            .stack
+            .push(Binding::with_expression(lambda_exp.clone()));
     }
     let mut i = 0;
     let extras = symbols.len() - (environment.stack.len() - index);
@@ -71,18 +322,67 @@ fn prep_stack(
         i += 1;
     }
     symbols.stack_captures(environment, index);
-    environment.stack_frames.push(StackFrame { index, symbols });
+    for (idx, binding) in bound {
+        environment.stack[index + idx] = binding.clone();
+    }
+    environment.stack_frames.push(StackFrame {
+        index,
+        symbols,
+        name: lambda.name,
+    });
     environment.stack_frame_base = index;
+    // Defaults are evaluated in the new frame so they can use earlier params.
+    for (slot, default) in defaults {
+        let value = eval(environment, default)?;
+        environment.stack[slot].replace(value);
+    }
+    for (slot, text, pattern) in &lambda.patterns {
+        let value = environment.stack[index + slot].get();
+        destructure(environment, pattern, value, index, text)?;
+    }
+    if environment.debugger.active {
+        debug_call_hook(environment, &lambda_exp)?;
+    }
     Ok(())
 }
 
+fn eval_body(
+    environment: &mut Environment,
+    lambda: &Lambda,
+    body: &MultiExpression,
+) -> Result<Expression, LispError> {
+    if let Some(chunk) = lambda_chunk(environment, lambda)? {
+        return run_chunk(environment, &chunk);
+    }
+    let mut tmp_eval: Option<Expression> = None;
+    Ok(match body {
+        MultiExpression::None => Expression::make_nil(),
+        MultiExpression::Single(body) => eval_nr(environment, &body)?,
+        MultiExpression::Multiple(body) => {
+            for arg in body {
+                if let Some(ret) = tmp_eval {
+                    ret.resolve(environment)?;
+                }
+                tmp_eval = Some(eval_nr(environment, arg.clone())?);
+            }
+            if let Some(exp) = tmp_eval {
+                exp
+            } else {
+                Expression::make_nil()
+            }
+        }
+    })
+}
+
 fn call_lambda_int(
     environment: &mut Environment,
     lambda_exp: Expression,
     lambda: Lambda,
     args: &mut dyn Iterator<Item = Expression>,
     eval_args: bool,
+    bound: &[(usize, Binding)],
 ) -> Result<Expression, LispError> {
+    let mut bound = bound;
     let mut lambda_int = lambda;
     let mut lambda: &mut Lambda = &mut lambda_int;
     let mut body: &MultiExpression = &lambda.body;
@@ -96,33 +396,23 @@ fn call_lambda_int(
             tvars.push(eval(environment, &v)?);
         }
         let ib = &mut tvars.iter().cloned();
-        prep_stack(environment, ib, &lambda, lambda_current.clone())?;
+        prep_stack(environment, ib, &lambda, lambda_current.clone(), bound)?;
     } else {
-        prep_stack(environment, args, &lambda, lambda_current.clone())?;
+        prep_stack(environment, args, &lambda, lambda_current.clone(), bound)?;
     }
 
     let mut llast_eval: Option<Expression> = None;
+    let mut tail_calls: Vec<&'static str> = Vec::new();
     let mut looping = true;
     while looping {
         if test_clear_sigint() {
             return Err(LispError::new("Lambda interupted by SIGINT."));
         }
-        let mut tmp_eval: Option<Expression> = None;
-        let last_eval = match body {
-            MultiExpression::None => Expression::make_nil(),
-            MultiExpression::Single(body) => eval_nr(environment, &body)?,
-            MultiExpression::Multiple(body) => {
-                for arg in body {
-                    if let Some(ret) = tmp_eval {
-                        ret.resolve(environment)?;
-                    }
-                    tmp_eval = Some(eval_nr(environment, arg.clone())?);
-                }
-                if let Some(exp) = tmp_eval {
-                    exp
-                } else {
-                    Expression::make_nil()
-                }
+        let last_eval = match eval_body(environment, lambda, body) {
+            Ok(last_eval) => last_eval,
+            Err(mut err) => {
+                lambda_error_frame(environment, lambda, &tail_calls, &mut err);
+                return Err(err);
             }
         };
         looping = environment.recur_num_args.is_some() && environment.exit_code.is_none();
@@ -144,11 +434,17 @@ fn call_lambda_int(
                     &mut last_eval.iter(),
                     &lambda,
                     lambda_current.clone(),
+                    bound,
                 )?;
             }
         } else if environment.exit_code.is_none() {
             // This will detect a normal tail call and optimize it.
             if let ExpEnum::LazyFn(lam, parts) = &last_eval.get().data {
+                if let Some(name) = lambda.name {
+                    if tail_calls.last() != Some(&name) && tail_calls.len() < 16 {
+                        tail_calls.push(name);
+                    }
+                }
                 lambda_current = lam.clone();
                 let lam_d = lambda_current.get();
                 if let ExpEnum::Lambda(lam) = &lam_d.data {
@@ -156,13 +452,18 @@ fn call_lambda_int(
                     drop(lam_d);
                     lambda = &mut lambda_int;
                     body = &lambda.body;
+                    bound = &[];
                     looping = true;
+                    if environment.profiler.active {
+                        let name = profile_name(environment, lambda);
+                        environment.profiler.tail_call(name);
+                    }
                     environment.namespace = lambda.syms.namespace().clone();
                     environment.stack.truncate(stack_len);
                     environment.stack_frames.truncate(stack_frames_len);
                     environment.stack_frame_base = stack_base;
                     let ib = &mut parts.iter().cloned();
-                    prep_stack(environment, ib, &lambda, lambda_current.clone())?;
+                    prep_stack(environment, ib, &lambda, lambda_current.clone(), bound)?;
                 }
             }
         }
@@ -182,6 +483,18 @@ pub fn call_lambda(
     lambda_exp: Expression,
     args: &mut dyn Iterator<Item = Expression>,
     eval_args: bool,
+) -> Result<Expression, LispError> {
+    call_lambda_bound(environment, lambda_exp, args, eval_args, &[])
+}
+
+/// Like call_lambda but the stack slots in bound are set to their bindings
+/// (after the captures), runs a struct method against the fields of an instance.
+pub fn call_lambda_bound(
+    environment: &mut Environment,
+    lambda_exp: Expression,
+    args: &mut dyn Iterator<Item = Expression>,
+    eval_args: bool,
+    bound: &[(usize, Binding)],
 ) -> Result<Expression, LispError> {
     let lambda = if let ExpEnum::Lambda(l) = &lambda_exp.get().data {
         l.clone()
@@ -199,7 +512,15 @@ pub fn call_lambda(
     let stack_len = environment.stack.len();
     let stack_frames_len = environment.stack_frames.len();
     let old_base = environment.stack_frame_base;
-    let ret = call_lambda_int(environment, lambda_exp, lambda, args, eval_args);
+    let profiling = environment.profiler.active;
+    if profiling {
+        let name = profile_name(environment, &lambda);
+        environment.profiler.enter(name);
+    }
+    let ret = call_lambda_int(environment, lambda_exp, lambda, args, eval_args, bound);
+    if profiling && environment.profiler.active {
+        environment.profiler.exit();
+    }
     environment.stack.truncate(stack_len);
     environment.stack_frames.truncate(stack_frames_len);
     environment.stack_frame_base = old_base;
@@ -207,6 +528,87 @@ pub fn call_lambda(
     ret
 }
 
+// An already evaluated value as the form (quote value), marked analyzed so
+// evaluating it just returns the value.
+fn quote_evaled(value: Expression) -> Expression {
+    let form = Expression::cons_from_vec(&[ExpEnum::Quote.into(), value], None);
+    form.get().analyzed.replace(true);
+    form
+}
+
+/// Call a builtin with args that are already evaluated.  Each arg is passed
+/// quoted so the builtin's eval of it gives back the value.
+pub fn call_builtin_evaled(
+    environment: &mut Environment,
+    func: CallFunc,
+    args: Vec<Expression>,
+) -> Result<Expression, LispError> {
+    func(environment, &mut args.into_iter().map(quote_evaled))
+}
+
+/// Call a lambda, macro or builtin with args that are already evaluated.
+pub fn call_evaled(
+    environment: &mut Environment,
+    command: &Expression,
+    args: Vec<Expression>,
+) -> Result<Expression, LispError> {
+    let command_d = command.get();
+    match &command_d.data {
+        ExpEnum::Lambda(_) | ExpEnum::Macro(_) => {
+            drop(command_d);
+            call_lambda(environment, command.clone(), &mut args.into_iter(), false)?
+                .resolve(environment)
+        }
+        ExpEnum::Function(c) if !c.args_in_context => {
+            let func = c.func;
+            drop(command_d);
+            call_builtin_evaled(environment, func, args)
+        }
+        _ => Err(LispError::new(format!(
+            "Can not call {} {} with evaluated args.",
+            command.display_type(),
+            command
+        ))),
+    }
+}
+
+fn make_symbol(environment: &mut Environment, name: &str) -> Expression {
+    let name = environment.interner.intern(name);
+    Expression::alloc_data(ExpEnum::Symbol(name, SymLoc::None))
+}
+
+/// Make the wrapper (fn (&rest args) (target 'name args)), a macro if is_macro.
+/// Used by trace and defgeneric to forward calls to a builtin.
+pub fn make_forwarding_fn(
+    environment: &mut Environment,
+    is_macro: bool,
+    target: &str,
+    name: &'static str,
+) -> Result<Expression, LispError> {
+    let params = Expression::cons_from_vec(
+        &[
+            make_symbol(environment, "&rest"),
+            make_symbol(environment, "args"),
+        ],
+        None,
+    );
+    let body = Expression::cons_from_vec(
+        &[
+            make_symbol(environment, target),
+            quote_evaled(make_symbol(environment, name)),
+            make_symbol(environment, "args"),
+        ],
+        None,
+    );
+    let lambda = make_fn(environment, &mut vec![params, body].into_iter(), &None)?;
+    Ok(if is_macro {
+        ExpEnum::Macro(lambda)
+    } else {
+        ExpEnum::Lambda(lambda)
+    }
+    .into())
+}
+
 fn make_lazy(
     environment: &mut Environment,
     lambda: Expression,
@@ -224,7 +626,7 @@ fn make_lazy(
     }))
 }
 
-fn eval_command(
+pub(crate) fn eval_command(
     environment: &mut Environment,
     com_exp: &Expression,
     parts: &mut dyn Iterator<Item = Expression>,
@@ -245,6 +647,14 @@ fn eval_command(
         ExpEnum::DeclareVar => builtin_var(environment, &mut *parts),
         ExpEnum::Quote => builtin_quote(environment, &mut *parts),
         ExpEnum::BackQuote => builtin_bquote(environment, &mut *parts),
+        ExpEnum::Struct(_, _) => {
+            drop(com_exp_d);
+            let mut args = Vec::new();
+            for p in parts {
+                args.push(eval(environment, p)?);
+            }
+            struct_call(environment, com_exp, args)
+        }
         _ => {
             let msg = format!(
                 "Not a valid command {}, type {}.",
@@ -287,6 +697,10 @@ fn fn_eval_lazy(
             drop(command_d);
             let form = get_expression(environment, command.clone());
             if let Some(exp) = form {
+                let is_struct = matches!(exp.get().data, ExpEnum::Struct(_, _));
+                if is_struct {
+                    return eval_command(environment, &exp, &mut parts);
+                }
                 match &exp.get().data {
                     ExpEnum::Function(c) => (c.func)(environment, &mut parts),
                     ExpEnum::DeclareDef => builtin_def(environment, &mut parts),
@@ -337,6 +751,10 @@ fn fn_eval_lazy(
             let com_exp = eval(environment, &command)?;
             eval_command(environment, &com_exp, &mut parts)
         }
+        ExpEnum::Struct(_, _) => {
+            drop(command_d);
+            eval_command(environment, &command, &mut parts)
+        }
         _ => {
             let msg = format!(
                 "Not a valid command {}, type {}.",
@@ -348,16 +766,28 @@ fn fn_eval_lazy(
     }
 }
 
+// Checks to make before evaluating a form, handle a SIGINT, run pending traps and
+// the cycle collector.  Returns false if exit was called.
+pub(crate) fn eval_checks(environment: &mut Environment) -> Result<bool, LispError> {
+    if test_clear_sigint() {
+        return Err(LispError::new("Script interupted by SIGINT."));
+    }
+    if traps_pending() {
+        run_pending_traps(environment);
+    }
+    if gc_wanted() {
+        collect();
+    }
+    Ok(environment.exit_code.is_none())
+}
+
 fn internal_eval(
     environment: &mut Environment,
     expression_in: &Expression,
 ) -> Result<Expression, LispError> {
     let expression = expression_in.clone();
-    if test_clear_sigint() {
-        return Err(LispError::new("Script interupted by SIGINT."));
-    }
     // exit was called so just return nil to unwind.
-    if environment.exit_code.is_some() {
+    if !eval_checks(environment)? {
         return Ok(Expression::alloc_data(ExpEnum::Nil));
     }
     let in_recur = environment.recur_num_args.is_some();
@@ -425,11 +855,14 @@ fn internal_eval(
             }
         }
         ExpEnum::HashMap(_) => Ok(expression.clone()),
+        ExpEnum::Set(_) => Ok(expression.clone()),
         ExpEnum::String(_, _) => Ok(expression.clone()),
         ExpEnum::True => Ok(expression.clone()),
         ExpEnum::False => Ok(expression.clone()),
         ExpEnum::Float(_) => Ok(expression.clone()),
         ExpEnum::Int(_) => Ok(expression.clone()),
+        ExpEnum::BigInt(_) => Ok(expression.clone()),
+        ExpEnum::Rational(_) => Ok(expression.clone()),
         ExpEnum::Char(_) => Ok(expression.clone()),
         ExpEnum::CodePoint(_) => Ok(expression.clone()),
         ExpEnum::Lambda(_) => Ok(expression.clone()),
@@ -441,6 +874,8 @@ fn internal_eval(
         ExpEnum::BackQuote => Ok(Expression::alloc_data(ExpEnum::Nil)),
         ExpEnum::Process(_) => Ok(expression.clone()),
         ExpEnum::File(_) => Ok(Expression::alloc_data(ExpEnum::Nil)),
+        ExpEnum::StructType(_) => Ok(expression.clone()),
+        ExpEnum::Struct(_, _) => Ok(expression.clone()),
         ExpEnum::LazyFn(_, _) => {
             let int_exp = expression.clone().resolve(environment)?;
             eval(environment, int_exp)
@@ -456,10 +891,15 @@ fn internal_eval(
                         params: p,
                         num_params: l.num_params,
                         has_rest: l.has_rest,
+                        optional: l.optional.clone(),
+                        keys: l.keys.clone(),
+                        patterns: l.patterns.clone(),
                         body: l.body.clone(),
                         syms,
                         namespace: environment.namespace.clone(),
                         no_recur: l.no_recur,
+                        code: l.code.clone(),
+                        name: None,
                     })))
                 }
                 ExpEnum::Macro(l) => {
@@ -470,10 +910,15 @@ fn internal_eval(
                         params: p,
                         num_params: l.num_params,
                         has_rest: l.has_rest,
+                        optional: l.optional.clone(),
+                        keys: l.keys.clone(),
+                        patterns: l.patterns.clone(),
                         body: l.body.clone(),
                         syms,
                         namespace: environment.namespace.clone(),
                         no_recur: l.no_recur,
+                        code: l.code.clone(),
+                        name: None,
                     })))
                 }
                 _ => {
@@ -491,6 +936,87 @@ fn internal_eval(
     ret
 }
 
+// The frame for the lambda call err is currently unwinding through.
+fn current_error_frame(err: &mut LispError) -> &mut ErrorFrame {
+    if err.backtrace.is_empty() {
+        err.backtrace.push(ErrorFrame {
+            name: None,
+            location: None,
+            args: Vec::new(),
+        });
+    }
+    let last = err.backtrace.len() - 1;
+    &mut err.backtrace[last]
+}
+
+// err is leaving a call to lambda, finish its frame and start one for the caller.
+// tail_calls are the named lambdas that tail called into this one (most recent
+// last), they have no frames of their own left so get one without a location.
+fn lambda_error_frame(
+    environment: &Environment,
+    lambda: &Lambda,
+    tail_calls: &[&'static str],
+    err: &mut LispError,
+) {
+    let base = environment.stack_frame_base;
+    let num_args = lambda.param_slots().min(environment.stack.len() - base);
+    let args = environment.stack[base..base + num_args]
+        .iter()
+        .map(|arg| arg.get().to_short_string(40))
+        .collect();
+    let frame = current_error_frame(err);
+    frame.name = lambda.name;
+    frame.args = args;
+    for name in tail_calls.iter().rev() {
+        if Some(*name) != lambda.name {
+            err.backtrace.push(ErrorFrame {
+                name: Some(name),
+                location: None,
+                args: Vec::new(),
+            });
+        }
+    }
+    err.backtrace.push(ErrorFrame {
+        name: None,
+        location: None,
+        args: Vec::new(),
+    });
+}
+
+// Record where err is in the current call, the first call form with meta data
+// it passes through.
+fn error_location(expression: &Expression, err: &mut LispError) {
+    if let Some(meta) = expression.meta() {
+        let frame = current_error_frame(err);
+        if frame.location.is_none() {
+            frame.location = Some(meta);
+        }
+    }
+}
+
+// Record err passing through expression.  The first call form with meta data in
+// each lambda call is where the error happened in it, the first call form err
+// passes through is where it was raised so also run the condition handlers.
+pub(crate) fn error_frame(
+    environment: &mut Environment,
+    expression: &Expression,
+    err: &mut LispError,
+) {
+    let is_call = matches!(
+        &expression.get().data,
+        ExpEnum::Pair(_, _) | ExpEnum::Vector(_)
+    );
+    if is_call {
+        error_location(expression, err);
+    }
+    if is_call && !err.signalled {
+        if err.location.is_none() {
+            err.location = expression.meta();
+        }
+        signal_error(environment, err);
+    }
+}
+
 pub fn eval_nr(
     environment: &mut Environment,
     expression: impl AsRef<Expression>,
@@ -507,15 +1033,15 @@ pub fn eval_nr(
     }
     environment.eval_level += 1;
     if let Err(mut err) = analyze(environment, expression, &mut None) {
-        if err.backtrace.is_none() {
-            err.backtrace = Some(Vec::new());
-        }
-        if let Some(backtrace) = &mut err.backtrace {
-            backtrace.push(expression.clone());
-        }
+        error_frame(environment, expression, &mut err);
         return Err(err);
     }
-    let tres = internal_eval(environment, &expression);
+    let tres = if environment.debugger.active {
+        debug_eval_hook(environment, expression)
+            .and_then(|_| internal_eval(environment, expression))
+    } else {
+        internal_eval(environment, &expression)
+    };
     let mut result = if environment.eval_level == 1 && environment.return_val.is_some() {
         environment.return_val = None;
         Err(LispError::new("Return without matching block."))
@@ -523,12 +1049,7 @@ pub fn eval_nr(
         tres
     };
     if let Err(err) = &mut result {
-        if err.backtrace.is_none() {
-            err.backtrace = Some(Vec::new());
-        }
-        if let Some(backtrace) = &mut err.backtrace {
-            backtrace.push(expression.clone());
-        }
+        error_frame(environment, expression, err);
     }
     environment.eval_level -= 1;
     environment.last_meta = None;
@@ -542,12 +1063,8 @@ pub fn eval(
     let expression = expression.as_ref();
     let mut result = eval_nr(environment, expression)?.resolve(environment);
     if let Err(err) = &mut result {
-        if err.backtrace.is_none() {
-            err.backtrace = Some(Vec::new());
-        }
-        if let Some(backtrace) = &mut err.backtrace {
-            backtrace.push(expression.clone());
-        }
+        // A tail call that failed when resolved.
+        error_location(expression, err);
     }
     result
 }
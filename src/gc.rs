@@ -0,0 +1,367 @@
+//! Cycle collector for Expressions.
+//!
+//! Expressions are reference counted so a cycle (a lambda that captured its own
+//! binding, a hash map that contains itself, etc) is never freed.  This finds
+//! them by trial deletion: starting from the tracked container allocations work
+//! out how many references to each object come from other objects in the graph,
+//! anything with more references than that is held from outside (a namespace,
+//! the stack, a Rust local) and is live along with everything it reaches.  What
+//! is left is only referenced by itself and is garbage, clearing the data of
+//! those Expressions breaks the cycles so reference counting frees them.
+//!
+//! Objects that can not be examined (they are mutably borrowed for instance)
+//! are treated as live, so a collection can only miss garbage, never free
+//! something in use.
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+
+use crate::symbols::*;
+use crate::types::*;
+
+// Never collect automatically before this many container allocations.
+const MIN_THRESHOLD: usize = 100_000;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GcStats {
+    // Tracked objects alive after the last collection.
+    pub live: usize,
+    // Number of collections run.
+    pub collections: usize,
+    // Total objects freed by breaking cycles.
+    pub reclaimed: usize,
+    // Total garbage cycles (groups of objects only referencing each other) freed.
+    pub cycles: usize,
+}
+
+struct GcState {
+    tracked: RefCell<Vec<Weak<RefCell<ExpObj>>>>,
+    allocs: Cell<usize>,
+    threshold: Cell<usize>,
+    stats: Cell<GcStats>,
+}
+
+thread_local! {
+    static GC: GcState = GcState {
+        tracked: RefCell::new(Vec::new()),
+        allocs: Cell::new(0),
+        threshold: Cell::new(MIN_THRESHOLD),
+        stats: Cell::new(GcStats::default()),
+    };
+}
+
+/// True if data can hold references to other objects (so can be part of a cycle).
+pub fn can_cycle(data: &ExpEnum) -> bool {
+    matches!(
+        data,
+        ExpEnum::Pair(_, _)
+            | ExpEnum::Vector(_)
+            | ExpEnum::Values(_)
+            | ExpEnum::HashMap(_)
+            | ExpEnum::Lambda(_)
+            | ExpEnum::Macro(_)
+            | ExpEnum::LazyFn(_, _)
+            | ExpEnum::Wrapper(_)
+            | ExpEnum::StructType(_)
+            | ExpEnum::Struct(_, _)
+            | ExpEnum::Symbol(_, SymLoc::Ref(_))
+    )
+}
+
+/// Remember a new allocation so the collector can start from it.
+pub fn track(obj: &Rc<RefCell<ExpObj>>) {
+    GC.with(|gc| {
+        gc.tracked.borrow_mut().push(Rc::downgrade(obj));
+        gc.allocs.set(gc.allocs.get() + 1);
+    });
+}
+
+/// True when enough has been allocated since the last collection to run another.
+pub fn gc_wanted() -> bool {
+    GC.with(|gc| gc.allocs.get() > gc.threshold.get())
+}
+
+pub fn gc_stats() -> GcStats {
+    GC.with(|gc| gc.stats.get())
+}
+
+// A reference counted object in the graph, identified by its allocation.
+enum Node {
+    Exp(Rc<RefCell<ExpObj>>),
+    Binding(Rc<RefCell<Expression>>),
+    Captures(Captures),
+    Symbols(Rc<RefCell<Symbols>>),
+    // Shared by every instance, holds the method lambdas.
+    StructType(Rc<StructType>),
+}
+
+impl Node {
+    fn key(&self) -> *const () {
+        match self {
+            Node::Exp(rc) => Rc::as_ptr(rc) as *const (),
+            Node::Binding(rc) => Rc::as_ptr(rc) as *const (),
+            Node::Captures(rc) => Rc::as_ptr(rc) as *const (),
+            Node::Symbols(rc) => Rc::as_ptr(rc) as *const (),
+            Node::StructType(rc) => Rc::as_ptr(rc) as *const (),
+        }
+    }
+
+    fn strong_count(&self) -> usize {
+        match self {
+            Node::Exp(rc) => Rc::strong_count(rc),
+            Node::Binding(rc) => Rc::strong_count(rc),
+            Node::Captures(rc) => Rc::strong_count(rc),
+            Node::Symbols(rc) => Rc::strong_count(rc),
+            Node::StructType(rc) => Rc::strong_count(rc),
+        }
+    }
+
+    // Push the objects this one holds a strong reference to, false if it could
+    // not be examined.
+    fn children(&self, out: &mut Vec<Node>) -> bool {
+        match self {
+            Node::Exp(rc) => match rc.try_borrow() {
+                Ok(obj) => {
+                    exp_children(&obj.data, out);
+                    true
+                }
+                Err(_) => false,
+            },
+            Node::Binding(rc) => match rc.try_borrow() {
+                Ok(exp) => {
+                    push_exp(exp.rc(), out);
+                    true
+                }
+                Err(_) => false,
+            },
+            Node::Captures(rc) => match rc.try_borrow() {
+                Ok(captures) => {
+                    for (_, _, binding) in captures.iter() {
+                        out.push(Node::Binding(binding.rc().clone()));
+                    }
+                    true
+                }
+                Err(_) => false,
+            },
+            Node::Symbols(rc) => match rc.try_borrow() {
+                Ok(syms) => {
+                    symbols_children(&syms, out);
+                    true
+                }
+                Err(_) => false,
+            },
+            Node::StructType(rc) => match rc.methods.try_borrow() {
+                Ok(methods) => {
+                    for (method, _) in methods.values() {
+                        push_exp(method.rc(), out);
+                    }
+                    true
+                }
+                Err(_) => false,
+            },
+        }
+    }
+}
+
+// Expressions that can not hold references are skipped, they can not be in a cycle.
+fn push_exp(rc: &Rc<RefCell<ExpObj>>, out: &mut Vec<Node>) {
+    let skip = match rc.try_borrow() {
+        Ok(obj) => !can_cycle(&obj.data),
+        Err(_) => false,
+    };
+    if !skip {
+        out.push(Node::Exp(rc.clone()));
+    }
+}
+
+fn symbols_children(syms: &Symbols, out: &mut Vec<Node>) {
+    out.push(Node::Captures(syms.captures_rc().clone()));
+    if let Some(outer) = syms.outer_rc() {
+        out.push(Node::Symbols(outer.clone()));
+    }
+}
+
+fn exp_children(data: &ExpEnum, out: &mut Vec<Node>) {
+    match data {
+        ExpEnum::Pair(car, cdr) => {
+            push_exp(car.rc(), out);
+            push_exp(cdr.rc(), out);
+        }
+        ExpEnum::Vector(list) | ExpEnum::Values(list) => {
+            for exp in list {
+                push_exp(exp.rc(), out);
+            }
+        }
+        ExpEnum::HashMap(map) => {
+            for exp in map.values() {
+                push_exp(exp.rc(), out);
+            }
+        }
+        ExpEnum::Lambda(lambda) | ExpEnum::Macro(lambda) => {
+            match &lambda.body {
+                MultiExpression::None => {}
+                MultiExpression::Single(exp) => push_exp(exp.rc(), out),
+                MultiExpression::Multiple(exps) => {
+                    for exp in exps {
+                        push_exp(exp.rc(), out);
+                    }
+                }
+            }
+            symbols_children(&lambda.syms, out);
+        }
+        ExpEnum::LazyFn(lambda, args) => {
+            push_exp(lambda.rc(), out);
+            for exp in args {
+                push_exp(exp.rc(), out);
+            }
+        }
+        ExpEnum::Wrapper(exp) => push_exp(exp.rc(), out),
+        ExpEnum::StructType(stype) => out.push(Node::StructType(stype.clone())),
+        ExpEnum::Struct(stype, slots) => {
+            out.push(Node::StructType(stype.clone()));
+            for binding in slots {
+                out.push(Node::Binding(binding.rc().clone()));
+            }
+        }
+        ExpEnum::Symbol(_, SymLoc::Ref(binding)) => out.push(Node::Binding(binding.rc().clone())),
+        _ => {}
+    }
+}
+
+// Union find root of idx.
+fn find(group: &mut [usize], idx: usize) -> usize {
+    let mut root = idx;
+    while group[root] != root {
+        root = group[root];
+    }
+    let mut idx = idx;
+    while group[idx] != root {
+        let next = group[idx];
+        group[idx] = root;
+        idx = next;
+    }
+    root
+}
+
+/// Run a collection, returns the number of objects reclaimed.
+pub fn collect() -> usize {
+    let seeds: Vec<Rc<RefCell<ExpObj>>> = GC.with(|gc| {
+        let mut tracked = gc.tracked.borrow_mut();
+        tracked.retain(|weak| weak.strong_count() > 0);
+        tracked.iter().filter_map(|weak| weak.upgrade()).collect()
+    });
+
+    // Find every object reachable from the tracked ones along with the
+    // references between them.
+    let mut nodes: Vec<Node> = Vec::with_capacity(seeds.len());
+    let mut index: HashMap<*const (), usize> = HashMap::with_capacity(seeds.len());
+    for seed in seeds {
+        let node = Node::Exp(seed);
+        index.entry(node.key()).or_insert_with(|| {
+            nodes.push(node);
+            nodes.len() - 1
+        });
+    }
+    let mut edges: Vec<Vec<usize>> = Vec::with_capacity(nodes.len());
+    let mut opaque: Vec<bool> = Vec::with_capacity(nodes.len());
+    let mut children = Vec::new();
+    let mut i = 0;
+    while i < nodes.len() {
+        children.clear();
+        opaque.push(!nodes[i].children(&mut children));
+        let mut node_edges = Vec::with_capacity(children.len());
+        for child in children.drain(..) {
+            let idx = *index.entry(child.key()).or_insert_with(|| {
+                nodes.push(child);
+                nodes.len() - 1
+            });
+            node_edges.push(idx);
+        }
+        edges.push(node_edges);
+        i += 1;
+    }
+
+    // Trial deletion, remove the internal references from each count.  Anything
+    // left over is a reference from outside the graph (the one held in nodes
+    // does not count).
+    let mut internal = vec![0; nodes.len()];
+    for node_edges in &edges {
+        for idx in node_edges {
+            internal[*idx] += 1;
+        }
+    }
+    let mut live = vec![false; nodes.len()];
+    let mut stack = Vec::new();
+    for (idx, node) in nodes.iter().enumerate() {
+        if opaque[idx] || node.strong_count() - 1 > internal[idx] {
+            live[idx] = true;
+            stack.push(idx);
+        }
+    }
+    while let Some(idx) = stack.pop() {
+        for child in &edges[idx] {
+            if !live[*child] {
+                live[*child] = true;
+                stack.push(*child);
+            }
+        }
+    }
+
+    // Count the separate groups of garbage (each holds at least one cycle).
+    let mut group: Vec<usize> = (0..nodes.len()).collect();
+    for (idx, node_edges) in edges.iter().enumerate() {
+        if live[idx] {
+            continue;
+        }
+        for child in node_edges {
+            if !live[*child] {
+                let a = find(&mut group, idx);
+                let b = find(&mut group, *child);
+                group[a] = b;
+            }
+        }
+    }
+    let mut cycles = 0;
+    let mut reclaimed = 0;
+    for idx in 0..nodes.len() {
+        if !live[idx] {
+            if find(&mut group, idx) == idx {
+                cycles += 1;
+            }
+            if let Node::Exp(_) = nodes[idx] {
+                reclaimed += 1;
+            }
+        }
+    }
+
+    // Break the cycles, dropping nodes then frees them.
+    for (idx, node) in nodes.iter().enumerate() {
+        if !live[idx] {
+            if let Node::Exp(rc) = node {
+                let data = if let Ok(mut obj) = rc.try_borrow_mut() {
+                    std::mem::replace(&mut obj.data, ExpEnum::Nil)
+                } else {
+                    continue;
+                };
+                drop(data);
+            }
+        }
+    }
+    drop(nodes);
+
+    GC.with(|gc| {
+        let mut tracked = gc.tracked.borrow_mut();
+        tracked.retain(|weak| weak.strong_count() > 0);
+        let live = tracked.len();
+        gc.allocs.set(0);
+        gc.threshold.set(MIN_THRESHOLD.max(live));
+        let mut stats = gc.stats.get();
+        stats.live = live;
+        stats.collections += 1;
+        stats.reclaimed += reclaimed;
+        stats.cycles += cycles;
+        gc.stats.set(stats);
+    });
+    reclaimed
+}
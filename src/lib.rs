@@ -6,6 +6,11 @@ extern crate sl_liner;
 pub mod types;
 pub use crate::types::*;
 
+pub mod bigint;
+pub use crate::bigint::*;
+pub mod rational;
+pub use crate::rational::*;
+
 pub mod environment;
 pub use crate::environment::*;
 
@@ -75,6 +80,22 @@ pub use crate::builtins_edit::*;
 pub mod builtins_bind;
 pub use crate::builtins_bind::*;
 
+pub mod builtins_conditions;
+pub use crate::builtins_conditions::*;
+
+pub mod builtins_debug;
+pub use crate::builtins_debug::*;
+pub mod builtins_profile;
+pub use crate::builtins_profile::*;
+pub mod builtins_trace;
+pub use crate::builtins_trace::*;
+pub mod builtins_generic;
+pub use crate::builtins_generic::*;
+pub mod builtins_set;
+pub use crate::builtins_set::*;
+pub mod builtins_struct;
+pub use crate::builtins_struct::*;
+
 pub mod pretty_print;
 pub use crate::pretty_print::*;
 
@@ -87,12 +108,21 @@ pub use crate::interner::*;
 pub mod analyze;
 pub use crate::analyze::*;
 
+pub mod bytecode;
+pub use crate::bytecode::*;
+
+pub mod vm;
+pub use crate::vm::*;
+
 pub mod symbols;
 pub use crate::symbols::*;
 
 pub mod backquote;
 pub use crate::backquote::*;
 
+pub mod gc;
+pub use crate::gc::*;
+
 pub mod signals;
 pub use crate::signals::*;
 
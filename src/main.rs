@@ -15,6 +15,9 @@ use ::sl_sh::types::LispError;
 
 fn main() -> Result<(), LispError> {
     if let Some(config) = get_config() {
+        if !install_sigchld_handler() {
+            std::process::exit(1)
+        }
         if config.command.is_none() && config.script.is_none() {
             /* See if we are running interactively.  */
             let shell_terminal = nix::libc::STDIN_FILENO;
@@ -52,11 +55,11 @@ fn main() -> Result<(), LispError> {
                     std::process::exit(1)
                 }
 
-                let code = start_interactive(true);
+                let code = start_interactive(true, config.profile.as_deref());
                 std::process::exit(code);
             } else {
                 // No tty, just read stdin and do something with it..
-                let code = start_interactive(false);
+                let code = start_interactive(false, config.profile.as_deref());
                 std::process::exit(code);
             }
         } else if config.command.is_some() {
@@ -67,7 +70,13 @@ fn main() -> Result<(), LispError> {
             }
         } else if config.script.is_some() {
             let script = config.script.unwrap();
-            let code = run_one_script(&script, &config.args);
+            let code = run_one_script(
+                &script,
+                &config.args,
+                config.errexit,
+                config.xtrace,
+                config.profile.as_deref(),
+            );
             std::process::exit(code);
         }
     }
@@ -5,19 +5,45 @@ use crate::builtins_util::is_proper_list;
 use crate::environment::*;
 use crate::types::*;
 
-fn params_to_string(params: &[&'static str]) -> String {
-    let mut pstr = "(".to_string();
-    let mut first = true;
-    for p in params {
-        if first {
-            first = false;
+fn opt_param_to_string(param: &OptParam) -> String {
+    match (&param.default, param.supplied) {
+        (None, None) => param.name.to_string(),
+        (Some(default), None) => format!("({} {})", param.name, default),
+        (Some(default), Some(supplied)) => format!("({} {} {})", param.name, default, supplied),
+        (None, Some(supplied)) => format!("({} nil {})", param.name, supplied),
+    }
+}
+
+/// The lambda list of l, (a b &optional (c 1) &rest d &key e).
+pub fn params_to_string(l: &Lambda) -> String {
+    let rest_idx = l
+        .params
+        .iter()
+        .position(|p| *p == "&rest")
+        .unwrap_or(l.params.len());
+    // A destructured param is written as its pattern.
+    let mut patterns = l.patterns.iter().map(|(_, text, _)| text.to_string());
+    let mut param_to_string = |p: &&str| {
+        if p.is_empty() {
+            patterns.next().unwrap_or_default()
         } else {
-            pstr.push(' ');
+            p.to_string()
         }
-        pstr.push_str(p);
+    };
+    let mut parts: Vec<String> = l.params[..rest_idx]
+        .iter()
+        .map(&mut param_to_string)
+        .collect();
+    if !l.optional.is_empty() {
+        parts.push("&optional".to_string());
+        parts.extend(l.optional.iter().map(opt_param_to_string));
+    }
+    parts.extend(l.params[rest_idx..].iter().map(&mut param_to_string));
+    if !l.keys.is_empty() {
+        parts.push("&key".to_string());
+        parts.extend(l.keys.iter().map(opt_param_to_string));
     }
-    pstr.push(')');
-    pstr
+    format!("({})", parts.join(" "))
 }
 
 impl fmt::Display for Expression {
@@ -35,9 +61,9 @@ impl fmt::Display for Expression {
         }
         fn lambda_out(f: &mut fmt::Formatter, l: &Lambda) -> fmt::Result {
             if l.no_recur {
-                write!(f, "(fn :no-recur {}", params_to_string(&l.params),)?;
+                write!(f, "(fn :no-recur {}", params_to_string(l),)?;
             } else {
-                write!(f, "(fn {}", params_to_string(&l.params),)?;
+                write!(f, "(fn {}", params_to_string(l),)?;
             }
             match &l.body {
                 MultiExpression::None => {}
@@ -56,6 +82,8 @@ impl fmt::Display for Expression {
             ExpEnum::False => write!(f, "false"),
             ExpEnum::Float(n) => write!(f, "{}", n),
             ExpEnum::Int(i) => write!(f, "{}", i),
+            ExpEnum::BigInt(b) => write!(f, "{}", b),
+            ExpEnum::Rational(r) => write!(f, "{}", r),
             ExpEnum::Symbol(s, _) => write!(f, "{}", s),
             ExpEnum::String(s, _) => write!(f, "\"{}\"", s),
             ExpEnum::Char(c) => write!(f, "#\\{}", c),
@@ -153,6 +181,20 @@ impl fmt::Display for Expression {
                 res.push_str("))");
                 write!(f, "{}", res)
             }
+            ExpEnum::Set(set) => {
+                let mut res = String::new();
+                res.push_str("#{");
+                let mut first = true;
+                for item in set.iter() {
+                    if !first {
+                        res.push(' ');
+                    }
+                    first = false;
+                    res.push_str(&item.to_string());
+                }
+                res.push('}');
+                write!(f, "{}", res)
+            }
             ExpEnum::File(file) => match &*file.borrow() {
                 FileState::Stdout => write!(f, "#<STDOUT>"),
                 FileState::Stderr => write!(f, "#<STDERR>"),
@@ -162,6 +204,17 @@ impl fmt::Display for Expression {
                 FileState::ReadBinary(_file) => write!(f, "#<READ (BIN) FILE>"),
                 FileState::Write(_file) => write!(f, "#<WRITE FILE>"),
             },
+            ExpEnum::StructType(t) => write!(f, "#<StructType {}>", t.name),
+            ExpEnum::Struct(t, slots) => {
+                let mut res = String::new();
+                res.push('(');
+                res.push_str(t.name);
+                for ((field, _), slot) in t.fields.iter().zip(slots.iter()) {
+                    res.push_str(&format!(" :{} {}", field, slot.get()));
+                }
+                res.push(')');
+                write!(f, "{}", res)
+            }
             ExpEnum::LazyFn(_, args) => {
                 let mut res = String::new();
                 res.push_str("#<LAZYFN<");
@@ -262,6 +315,20 @@ fn pretty_print_int(
                 write!(writer, "))")?;
             }
         }
+        ExpEnum::Set(set) => {
+            init_space(indent, writer)?;
+            let a_str = expression.to_string();
+            if a_str.len() < 40 {
+                writer.write_all(a_str.as_bytes())?;
+            } else {
+                writer.write_all(b"#{")?;
+                for item in set.iter() {
+                    init_space(indent + 1, writer)?;
+                    write!(writer, "{}", item)?;
+                }
+                write!(writer, "}}")?;
+            }
+        }
         ExpEnum::String(_, _) => {
             write!(writer, "{}", expression.to_string())?;
         }
@@ -273,9 +340,9 @@ fn pretty_print_int(
         }
         ExpEnum::Lambda(l) => {
             if l.no_recur {
-                write!(writer, "(fn :no-recur {}", params_to_string(&l.params))?;
+                write!(writer, "(fn :no-recur {}", params_to_string(l))?;
             } else {
-                write!(writer, "(fn {}", params_to_string(&l.params))?;
+                write!(writer, "(fn {}", params_to_string(l))?;
             }
             match &l.body {
                 MultiExpression::None => {}
@@ -291,7 +358,7 @@ fn pretty_print_int(
             writer.write_all(b")")?;
         }
         ExpEnum::Macro(m) => {
-            write!(writer, "(macro {}", params_to_string(&m.params))?;
+            write!(writer, "(macro {}", params_to_string(m))?;
             match &m.body {
                 MultiExpression::None => {}
                 MultiExpression::Single(body) => {
@@ -312,11 +379,15 @@ fn pretty_print_int(
         ExpEnum::False => expression.writef(environment, writer)?,
         ExpEnum::Float(_) => expression.writef(environment, writer)?,
         ExpEnum::Int(_) => expression.writef(environment, writer)?,
+        ExpEnum::BigInt(_) => expression.writef(environment, writer)?,
+        ExpEnum::Rational(_) => expression.writef(environment, writer)?,
         ExpEnum::Symbol(_, _) => expression.writef(environment, writer)?,
         ExpEnum::Function(_) => expression.writef(environment, writer)?,
         ExpEnum::LazyFn(_, _) => expression.writef(environment, writer)?,
         ExpEnum::Process(_) => expression.writef(environment, writer)?,
         ExpEnum::File(_) => expression.writef(environment, writer)?,
+        ExpEnum::StructType(_) => expression.writef(environment, writer)?,
+        ExpEnum::Struct(_, _) => expression.writef(environment, writer)?,
         ExpEnum::DeclareDef => expression.writef(environment, writer)?,
         ExpEnum::DeclareVar => expression.writef(environment, writer)?,
         ExpEnum::DeclareFn => expression.writef(environment, writer)?,
@@ -1,6 +1,7 @@
 use std::env;
-use std::io;
+use std::io::{self, Write};
 use std::os::unix::io::AsRawFd;
+use std::time::Instant;
 
 use nix::{
     sys::{
@@ -13,7 +14,7 @@ use nix::{
 
 use crate::environment::*;
 use crate::eval::*;
-use crate::signals::test_clear_sigint;
+use crate::signals::{test_clear_sigint, wait_for_signal};
 use crate::types::*;
 use crate::unix::*;
 
@@ -30,13 +31,15 @@ pub fn try_wait_pid(environment: &Environment, pid: u32) -> (bool, Option<i32>)
                     code = Some(status)
                 }
             }
-            remove_job(environment, pid);
+            mark_job_exited(environment, pid, code);
+            end_held_proc_subs(environment, pid);
             (true, code)
         }
         Err(err) => {
             eprintln!("Error waiting for pid {}, {}", pid, err);
             environment.procs.borrow_mut().remove(&pid);
-            remove_job(environment, pid);
+            mark_job_exited(environment, pid, None);
+            end_held_proc_subs(environment, pid);
             (true, None)
         }
         Ok(WaitStatus::Exited(_, status)) => {
@@ -46,7 +49,21 @@ pub fn try_wait_pid(environment: &Environment, pid: u32) -> (bool, Option<i32>)
                     .data
                     .replace(ExpEnum::Process(ProcessState::Over(pid, status)));
             }
-            remove_job(environment, pid);
+            mark_job_exited(environment, pid, Some(status));
+            end_held_proc_subs(environment, pid);
+            (true, Some(status))
+        }
+        Ok(WaitStatus::Signaled(_, sig, _)) => {
+            // Killed by a signal, like other shells the status is 128 + the signal number.
+            let status = 128 + sig as i32;
+            if let Some(pval) = environment.procs.borrow_mut().remove(&pid) {
+                pval.0
+                    .get_mut()
+                    .data
+                    .replace(ExpEnum::Process(ProcessState::Over(pid, status)));
+            }
+            mark_job_exited(environment, pid, None);
+            end_held_proc_subs(environment, pid);
             (true, Some(status))
         }
         Ok(WaitStatus::Stopped(..)) => {
@@ -59,6 +76,17 @@ pub fn try_wait_pid(environment: &Environment, pid: u32) -> (bool, Option<i32>)
     }
 }
 
+/// Set *last-status* (and $LAST_STATUS) unless saving the exit status is turned off.
+pub fn set_last_status(environment: &mut Environment, status: i32) {
+    if environment.save_exit_status {
+        env::set_var("LAST_STATUS".to_string(), format!("{}", status));
+        environment.root_scope.borrow_mut().insert_exp_data(
+            environment.interner.intern("*last-status*"),
+            ExpEnum::Int(i64::from(status)),
+        );
+    }
+}
+
 pub fn wait_pid(
     environment: &mut Environment,
     pid: u32,
@@ -66,6 +94,7 @@ pub fn wait_pid(
 ) -> Option<i32> {
     let result: Option<i32>;
     let mut int_cnt = 0;
+    let start = Instant::now();
     loop {
         if test_clear_sigint() {
             if int_cnt == 0 {
@@ -85,17 +114,14 @@ pub fn wait_pid(
         if stop {
             result = status;
             if let Some(status) = status {
-                if environment.save_exit_status {
-                    env::set_var("LAST_STATUS".to_string(), format!("{}", status));
-                    environment.root_scope.borrow_mut().insert_exp_data(
-                        environment.interner.intern("*last-status*"),
-                        ExpEnum::Int(i64::from(status)),
-                    );
-                }
+                set_last_status(environment, status);
             }
             break;
         }
-        std::thread::sleep(std::time::Duration::from_millis(100));
+        wait_for_signal();
+    }
+    if environment.profiler.active {
+        environment.profiler.wait(start.elapsed());
     }
     // If we were given terminal settings restore them.
     if let Some(settings) = term_settings {
@@ -120,11 +146,79 @@ pub fn wait_pid(
     result
 }
 
+/// True if the shell option global (*errexit* for instance) is set to a true value.
+pub fn shell_option(environment: &Environment, name: &str) -> bool {
+    lookup_expression(environment, name)
+        .map(|exp| !exp.is_falsy())
+        .unwrap_or(false)
+}
+
+/// With *errexit* set a failed foreground command (non-zero status) is an error,
+/// unless it is part of a condition (if test, and, or, not).
+pub fn check_errexit(
+    environment: &Environment,
+    command: &str,
+    status: i32,
+) -> Result<(), LispError> {
+    if status != 0 && environment.in_condition == 0 && shell_option(environment, "*errexit*") {
+        let msg = format!("errexit: {} exited with status {}", command, status);
+        Err(LispError::with_kind(":process", msg)
+            .with_data(Expression::alloc_data(ExpEnum::Int(status as i64))))
+    } else {
+        Ok(())
+    }
+}
+
+// Quote an xtrace argument if the shell would need it to read it back.
+fn xtrace_arg(arg: &str) -> String {
+    let plain = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|ch| ch.is_alphanumeric() || "-_./=:,+@%^".contains(ch));
+    if plain {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+// Print the command for *xtrace* to *stderr* (so it follows err> for instance).
+fn xtrace(environment: &Environment, command: &str, args: &[String]) {
+    let mut line = format!("+ {}", xtrace_arg(command));
+    for arg in args {
+        line.push(' ');
+        line.push_str(&xtrace_arg(arg));
+    }
+    line.push('\n');
+    if let Some(exp) = lookup_expression(environment, "*stderr*") {
+        if let ExpEnum::File(file) = &exp.get().data {
+            match &mut *file.borrow_mut() {
+                FileState::Write(f) => {
+                    if let Err(err) = f.write_all(line.as_bytes()).and_then(|_| f.flush()) {
+                        eprintln!("Error writing xtrace: {}", err);
+                    }
+                    return;
+                }
+                FileState::Stdout => {
+                    print!("{}", line);
+                    return;
+                }
+                _ => {}
+            }
+        }
+    }
+    eprint!("{}", line);
+}
+
 fn run_command(
     environment: &mut Environment,
     command: &str,
     args: Vec<String>,
+    proc_sub_base: usize,
 ) -> Result<Expression, LispError> {
+    if shell_option(environment, "*xtrace*") {
+        xtrace(environment, command, &args);
+    }
     if environment.in_fork && environment.eval_level == 1 {
         // We are the top level of a new fork so no need to fork again, just exec here.
         // On success exec will not return.
@@ -134,6 +228,7 @@ fn run_command(
         }
         // If we still have procs running then maybe don't orphin them (at least not yet).
         if environment.procs.borrow().is_empty() {
+            apply_fd_redirects(&environment.fd_redirects)?;
             return Err(exec(&command, &args).into());
         }
     }
@@ -173,6 +268,13 @@ fn run_command(
                 None => Expression::alloc_data(ExpEnum::Nil),
             };
             add_process(environment, proc, (result.clone(), pipe_read));
+            if status.is_none() {
+                // Stopped, not done with its process substitutions until it exits.
+                hold_proc_subs(environment, proc, proc_sub_base);
+            }
+            if let Some(code) = status {
+                check_errexit(environment, command, code)?;
+            }
             Ok(result)
         }
         Err(e) => {
@@ -203,7 +305,11 @@ fn run_command(
                     environment.is_tty = false;
                 }
             }
-            Err(LispError::new(err_msg))
+            Err(
+                LispError::with_kind(":process", err_msg).with_data(Expression::alloc_data(
+                    ExpEnum::String(command.to_string().into(), None),
+                )),
+            )
         }
     }
 }
@@ -263,13 +369,74 @@ pub fn do_command(
                     add_arg_s(args, a)?;
                 }
             }
+            ExpEnum::HashMap(_) => return Err(LispError::new("Sys command arguments can not be hashmaps ({} reads as an empty hashmap, use \"{}\" to pass {} to a command).")),
             _ => return Err(LispError::new("Sys command arguments need to be string (or symbols or lists that reduce to strings).")),
         }
         Ok(())
     }
+    // Any process substitutions in the args are done once the command is.
+    let proc_sub_base = environment.proc_subs.len();
     let mut args = Vec::new();
+    let mut result = Ok(());
     for a_exp in parts {
-        add_arg_s(&mut args, eval(environment, a_exp)?)?;
+        result = eval(environment, a_exp).and_then(|arg| add_arg_s(&mut args, arg));
+        if result.is_err() {
+            break;
+        }
+    }
+    let result = result.and_then(|_| run_command(environment, command, args, proc_sub_base));
+    end_proc_subs(environment, proc_sub_base);
+    result
+}
+
+/// Close the process substitutions started after base and reap their processes.
+pub fn end_proc_subs(environment: &mut Environment, base: usize) {
+    if base < environment.proc_subs.len() {
+        close_proc_subs(environment.proc_subs.drain(base..).collect());
+    }
+}
+
+// Give the process substitutions started after base to pid, they are ended
+// once it exits.
+fn hold_proc_subs(environment: &mut Environment, pid: u32, base: usize) {
+    if base < environment.proc_subs.len() {
+        let proc_subs: Vec<ProcSub> = environment.proc_subs.drain(base..).collect();
+        environment
+            .held_proc_subs
+            .borrow_mut()
+            .entry(pid)
+            .or_default()
+            .extend(proc_subs);
+    }
+}
+
+// pid is gone so end any process substitutions it was holding.
+fn end_held_proc_subs(environment: &Environment, pid: u32) {
+    let proc_subs = environment.held_proc_subs.borrow_mut().remove(&pid);
+    if let Some(proc_subs) = proc_subs {
+        close_proc_subs(proc_subs);
+    }
+}
+
+fn close_proc_subs(proc_subs: Vec<ProcSub>) {
+    for (fd, pid, is_in) in proc_subs {
+        // Closing our end first lets the process see EOF (or a closed pipe) and exit.
+        if let Err(err) = close_fd(fd) {
+            eprintln!("Error closing process substitution fd {}: {}", fd, err);
+        }
+        let pid = Pid::from_raw(pid as i32);
+        // A <(...) that is still running (tail -f for instance) may never write
+        // again to notice the closed pipe so kill it, nothing will read its output.
+        // A >(...) got EOF and is finishing with what it read so wait for it.
+        if is_in {
+            if let Ok(WaitStatus::StillAlive) = wait::waitpid(pid, Some(WaitPidFlag::WNOHANG)) {
+                let _ = kill(pid, Signal::SIGTERM);
+            } else {
+                continue;
+            }
+        }
+        if let Err(err) = wait::waitpid(pid, None) {
+            eprintln!("Error waiting on process substitution {}: {}", pid, err);
+        }
     }
-    run_command(environment, command, args)
 }
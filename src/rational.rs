@@ -0,0 +1,246 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, Mul, Neg, Sub};
+
+use crate::bigint::BigInt;
+
+/// An exact fraction, always in lowest terms with a positive denominator.  A
+/// whole number has a denominator of one, these are turned back into ints
+/// before they become a lisp value.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Rational {
+    numer: BigInt,
+    denom: BigInt,
+}
+
+impl Rational {
+    /// numer/denom in lowest terms, None if denom is zero.
+    pub fn new(numer: BigInt, denom: BigInt) -> Option<Rational> {
+        if denom.is_zero() {
+            return None;
+        }
+        let gcd = numer.gcd(&denom);
+        let (mut numer, mut denom) = (numer.div_rem(&gcd)?.0, denom.div_rem(&gcd)?.0);
+        if denom.is_negative() {
+            numer = -&numer;
+            denom = -&denom;
+        }
+        Some(Rational { numer, denom })
+    }
+
+    pub fn from_big(numer: BigInt) -> Rational {
+        Rational {
+            numer,
+            denom: BigInt::from_i64(1),
+        }
+    }
+
+    /// Parse numer/denom (for instance 3/4 or -1_000/3), None if not valid.
+    pub fn parse(s: &str) -> Option<Rational> {
+        let (numer, denom) = s.split_once('/')?;
+        if denom.starts_with('-') || denom.starts_with('+') {
+            return None;
+        }
+        let numer = BigInt::parse(&numer.replace('_', ""), 10)?;
+        let denom = BigInt::parse(&denom.replace('_', ""), 10)?;
+        Rational::new(numer, denom)
+    }
+
+    pub fn numer(&self) -> &BigInt {
+        &self.numer
+    }
+
+    pub fn denom(&self) -> &BigInt {
+        &self.denom
+    }
+
+    pub fn is_whole(&self) -> bool {
+        self.denom == BigInt::from_i64(1)
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.numer.to_f64() / self.denom.to_f64()
+    }
+
+    pub fn abs(&self) -> Rational {
+        Rational {
+            numer: self.numer.abs(),
+            denom: self.denom.clone(),
+        }
+    }
+
+    /// None if other is zero.
+    pub fn checked_div(&self, other: &Rational) -> Option<Rational> {
+        Rational::new(&self.numer * &other.denom, &self.denom * &other.numer)
+    }
+
+    pub fn pow(&self, power: u32) -> Rational {
+        Rational {
+            numer: self.numer.pow(power),
+            denom: self.denom.pow(power),
+        }
+    }
+
+    /// Like pow but None if the numerator or denominator would be too large
+    /// (see BigInt::checked_pow).
+    pub fn checked_pow(&self, power: u32) -> Option<Rational> {
+        Some(Rational {
+            numer: self.numer.checked_pow(power)?,
+            denom: self.denom.checked_pow(power)?,
+        })
+    }
+
+    /// Largest whole number less than or equal to self.
+    pub fn floor(&self) -> BigInt {
+        let (quot, rem) = self.split();
+        if rem.is_negative() {
+            &quot - &BigInt::from_i64(1)
+        } else {
+            quot
+        }
+    }
+
+    /// Smallest whole number greater than or equal to self.
+    pub fn ceil(&self) -> BigInt {
+        let (quot, rem) = self.split();
+        if !rem.is_negative() && !rem.is_zero() {
+            &quot + &BigInt::from_i64(1)
+        } else {
+            quot
+        }
+    }
+
+    /// Nearest whole number, halves round away from zero.
+    pub fn round(&self) -> BigInt {
+        let half = Rational {
+            numer: BigInt::from_i64(1),
+            denom: BigInt::from_i64(2),
+        };
+        if self.numer.is_negative() {
+            -&(&self.abs() + &half).floor()
+        } else {
+            (self + &half).floor()
+        }
+    }
+
+    // Truncated whole part and remainder.
+    fn split(&self) -> (BigInt, BigInt) {
+        self.numer
+            .div_rem(&self.denom)
+            .unwrap_or_else(|| (BigInt::from_i64(0), BigInt::from_i64(0)))
+    }
+}
+
+impl Ord for Rational {
+    fn cmp(&self, other: &Rational) -> Ordering {
+        // Denominators are positive so cross multiplying keeps the order.
+        (&self.numer * &other.denom).cmp(&(&other.numer * &self.denom))
+    }
+}
+
+impl PartialOrd for Rational {
+    fn partial_cmp(&self, other: &Rational) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Add for &Rational {
+    type Output = Rational;
+
+    fn add(self, other: &Rational) -> Rational {
+        let numer = &(&self.numer * &other.denom) + &(&other.numer * &self.denom);
+        Rational::new(numer, &self.denom * &other.denom).unwrap_or_else(|| self.clone())
+    }
+}
+
+impl Sub for &Rational {
+    type Output = Rational;
+
+    fn sub(self, other: &Rational) -> Rational {
+        self + &-other
+    }
+}
+
+impl Mul for &Rational {
+    type Output = Rational;
+
+    fn mul(self, other: &Rational) -> Rational {
+        Rational::new(&self.numer * &other.numer, &self.denom * &other.denom)
+            .unwrap_or_else(|| self.clone())
+    }
+}
+
+impl Neg for &Rational {
+    type Output = Rational;
+
+    fn neg(self) -> Rational {
+        Rational {
+            numer: -&self.numer,
+            denom: self.denom.clone(),
+        }
+    }
+}
+
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_whole() {
+            write!(f, "{}", self.numer)
+        } else {
+            write!(f, "{}/{}", self.numer, self.denom)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ratio(s: &str) -> Rational {
+        Rational::parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_parse_reduce() {
+        assert_eq!(ratio("6/8").to_string(), "3/4");
+        assert_eq!(ratio("-6/8").to_string(), "-3/4");
+        assert_eq!(ratio("4/2").to_string(), "2");
+        assert_eq!(ratio("0/5").to_string(), "0");
+        assert!(Rational::parse("1/0").is_none());
+        assert!(Rational::parse("1/-2").is_none());
+        assert!(Rational::parse("1/2/3").is_none());
+        assert!(Rational::parse("a/2").is_none());
+    }
+
+    #[test]
+    fn test_arith() {
+        assert_eq!((&ratio("1/3") + &ratio("1/6")).to_string(), "1/2");
+        assert_eq!((&ratio("1/3") - &ratio("1/2")).to_string(), "-1/6");
+        assert_eq!((&ratio("2/3") * &ratio("3/4")).to_string(), "1/2");
+        assert_eq!(
+            ratio("2/3")
+                .checked_div(&ratio("-4/3"))
+                .unwrap()
+                .to_string(),
+            "-1/2"
+        );
+        assert!(ratio("2/3").checked_div(&ratio("0/3")).is_none());
+        assert!(ratio("1/3") < ratio("1/2"));
+        assert!(ratio("-1/2") < ratio("-1/3"));
+    }
+
+    #[test]
+    fn test_rounding() {
+        for (s, floor, ceil, round) in &[
+            ("7/2", 3, 4, 4),
+            ("-7/2", -4, -3, -4),
+            ("10/3", 3, 4, 3),
+            ("-10/3", -4, -3, -3),
+            ("5/1", 5, 5, 5),
+        ] {
+            let r = ratio(s);
+            assert_eq!(r.floor(), BigInt::from_i64(*floor));
+            assert_eq!(r.ceil(), BigInt::from_i64(*ceil));
+            assert_eq!(r.round(), BigInt::from_i64(*round));
+        }
+    }
+}
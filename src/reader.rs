@@ -7,8 +7,10 @@ use std::num::{ParseFloatError, ParseIntError};
 
 use unicode_segmentation::UnicodeSegmentation;
 
+use crate::bigint::BigInt;
 use crate::environment::*;
 use crate::eval::eval;
+use crate::rational::Rational;
 use crate::types::*;
 
 #[derive(Clone, Debug)]
@@ -151,8 +153,8 @@ fn consume_block_comment(chars: &mut CharIter, reader_state: &mut ReaderState) {
     }
 }
 
-fn end_symbol(ch: &str, read_table_term: &HashMap<&'static str, Expression>) -> bool {
-    if is_whitespace(ch) || read_table_term.contains_key(ch) {
+fn end_symbol(ch: &str, read_table_term: &HashMap<HashKey, Expression>) -> bool {
+    if is_whitespace(ch) || hash_get_str(read_table_term, ch).is_some() {
         true
     } else {
         matches!(ch, "(" | ")" | "#" | "\"" | "," | "'" | "`")
@@ -310,7 +312,7 @@ fn read_string(
     environment: &mut Environment,
     mut chars: CharIter,
     symbol: &mut String,
-    read_table: &HashMap<&'static str, Expression>,
+    read_table: &HashMap<HashKey, Expression>,
 ) -> Result<(Expression, CharIter), (ReadError, CharIter)> {
     symbol.clear();
     let mut last_ch_escape = false;
@@ -330,7 +332,7 @@ fn read_string(
         }
         if last_ch_escape {
             let mut do_match = true;
-            if read_table.contains_key(&*ch) {
+            if hash_get_str(read_table, &ch).is_some() {
                 do_match = false;
                 symbol.push_str(&ch);
             }
@@ -375,9 +377,9 @@ fn read_string(
                 break;
             }
             let mut proc_ch = true;
-            if read_table.contains_key(&*ch) {
+            if hash_get_str(read_table, &ch).is_some() {
                 proc_ch = false;
-                if let ExpEnum::Symbol(s, _) = read_table.get(&*ch).unwrap().get().data {
+                if let ExpEnum::Symbol(s, _) = hash_get_str(read_table, &ch).unwrap().get().data {
                     let res = prep_reader_macro(environment, chars, s, &ch);
                     match res {
                         Ok((None, ichars)) => {
@@ -501,6 +503,17 @@ fn read_string_literal(
     ))
 }
 
+// A rational written the way it prints (3/4 or -1/2), anything else with a /
+// (paths and dates like 4/2 or 2024/10) stays a symbol.
+fn rational_literal(symbol: &str) -> Option<Rational> {
+    let ratio = Rational::parse(symbol)?;
+    if !ratio.is_whole() && ratio.to_string() == symbol {
+        Some(ratio)
+    } else {
+        None
+    }
+}
+
 fn do_atom(
     environment: &mut Environment,
     symbol: &str,
@@ -514,6 +527,10 @@ fn do_atom(
         match potential_int {
             Ok(v) => Expression::alloc_data(ExpEnum::Int(v)),
             Err(_) => {
+                // Too big for an i64 so make a bignum.
+                if let Some(big) = BigInt::parse(&num_str, 10) {
+                    return make_exp(ExpEnum::BigInt(big), meta);
+                }
                 let potential_float: Result<f64, ParseFloatError> = num_str.parse();
                 match potential_float {
                     Ok(v) => make_exp(ExpEnum::Float(v), meta),
@@ -530,6 +547,8 @@ fn do_atom(
         }
         if symbol == "nil" {
             make_exp(ExpEnum::Nil, meta)
+        } else if let Some(ratio) = rational_literal(symbol) {
+            make_exp(ExpEnum::Rational(ratio), meta)
         } else {
             make_exp(
                 ExpEnum::Symbol(environment.interner.intern(symbol), SymLoc::None),
@@ -545,7 +564,7 @@ fn read_symbol(
     reader_state: &mut ReaderState,
     for_ch: bool,
     skip_underscore: bool,
-    read_table_term: &HashMap<&'static str, Expression>,
+    read_table_term: &HashMap<HashKey, Expression>,
 ) -> bool {
     fn maybe_number(ch: &str, has_e: &mut bool, last_e: &mut bool, has_decimal: &mut bool) -> bool {
         if ch == "." {
@@ -575,8 +594,10 @@ fn read_symbol(
     let mut has_decimal = buffer.len() == 1 && &buffer[..] == ".";
     let mut has_e = false;
     let mut last_e = false;
+    // A } ends a symbol (for {} maps) but not a char (#\} or #\u{a}).
+    let ends = |ch: &str| end_symbol(ch, read_table_term) || (!for_ch && ch == "}");
     if let Some(ch) = chars.peek() {
-        if end_symbol(&ch, read_table_term) && !for_ch {
+        if ends(&ch) && !for_ch {
             return buffer.len() == 1 && is_digit(&buffer[..]);
         }
     };
@@ -611,7 +632,7 @@ fn read_symbol(
             }
             buffer.push_str(&next_ch);
             push_next = false;
-        } else if end_symbol(peek_ch, read_table_term) {
+        } else if ends(peek_ch) {
             break;
         }
         next_ch = chars.next();
@@ -781,7 +802,7 @@ fn read_num_radix(
     buffer: &mut String,
     radix: u32,
     meta: Option<ExpMeta>,
-    read_table_term: &HashMap<&'static str, Expression>,
+    read_table_term: &HashMap<HashKey, Expression>,
 ) -> Result<(Expression, CharIter), (ReadError, CharIter)> {
     buffer.clear();
     read_symbol(
@@ -794,12 +815,16 @@ fn read_num_radix(
     );
     match i64::from_str_radix(buffer, radix) {
         Ok(n) => Ok((make_exp(ExpEnum::Int(n), meta), chars)),
-        Err(e) => Err((
-            ReadError {
-                reason: e.to_string(),
-            },
-            chars,
-        )),
+        Err(e) => match BigInt::parse(buffer, radix) {
+            // Too big for an i64 so make a bignum.
+            Some(big) => Ok((make_exp(ExpEnum::BigInt(big), meta), chars)),
+            None => Err((
+                ReadError {
+                    reason: e.to_string(),
+                },
+                chars,
+            )),
+        },
     }
 }
 
@@ -821,8 +846,14 @@ fn read_vector(
         let (exp, mut ichars) = match read_inner(environment, chars, buffer, in_back_quote, true) {
             Ok((exp, ichars)) => {
                 if let Some(exp) = &exp {
-                    if let ExpEnum::Symbol(")", _) = exp.get().data {
-                        return Ok((make_exp(ExpEnum::Vector(v), meta), ichars));
+                    match exp.get().data {
+                        ExpEnum::Symbol(")", _) => {
+                            return Ok((make_exp(ExpEnum::Vector(v), meta), ichars));
+                        }
+                        ExpEnum::Symbol("}", _) => {
+                            return Err((unexpected_close(environment, "}"), ichars));
+                        }
+                        _ => {}
                     }
                 }
                 (exp, ichars)
@@ -847,6 +878,89 @@ fn read_vector(
     ))
 }
 
+fn unexpected_close(environment: &Environment, close: &str) -> ReadError {
+    ReadError {
+        reason: format!(
+            "Unexpected '{}': {} line {} col {}",
+            close,
+            environment.reader_state.file_name.unwrap_or(""),
+            environment.reader_state.line,
+            environment.reader_state.column
+        ),
+    }
+}
+
+// Read {key val ...} as the form (hash-map key val ...) or #{item ...} (when
+// is_set) as (make-set item ...).
+fn read_map(
+    environment: &mut Environment,
+    mut chars: CharIter, // Pass ownership in and out for reader macro support.
+    buffer: &mut String,
+    in_back_quote: bool,
+    is_set: bool,
+) -> Result<(Expression, CharIter), (ReadError, CharIter)> {
+    let head = if is_set { "make-set" } else { "hash-map" };
+    let mut v: Vec<Expression> = vec![Expression::alloc_data(ExpEnum::Symbol(
+        environment.interner.intern(head),
+        SymLoc::None,
+    ))];
+    let meta = get_meta(
+        environment.reader_state.file_name,
+        environment.reader_state.line,
+        environment.reader_state.column,
+    );
+    let mut cont = true;
+
+    while cont {
+        let (exp, mut ichars) = match read_inner(environment, chars, buffer, in_back_quote, true) {
+            Ok((exp, ichars)) => {
+                if let Some(exp) = &exp {
+                    match exp.get().data {
+                        ExpEnum::Symbol("}", _) => {
+                            // v starts with hash-map so pairs leave it odd.
+                            if is_set || v.len() % 2 == 1 {
+                                return Ok((Expression::cons_from_vec(&v, meta), ichars));
+                            }
+                            return Err((
+                                ReadError {
+                                    reason: "Map literal must have key value pairs".to_string(),
+                                },
+                                ichars,
+                            ));
+                        }
+                        ExpEnum::Symbol(")", _) => {
+                            return Err((unexpected_close(environment, ")"), ichars));
+                        }
+                        _ => {}
+                    }
+                }
+                (exp, ichars)
+            }
+            Err((err, ichars)) => {
+                return Err((err, ichars));
+            }
+        };
+        let pch = ichars.peek();
+        if let Some(exp) = exp {
+            v.push(exp);
+        } else if pch.is_none() {
+            cont = false;
+        }
+        chars = ichars;
+    }
+    let reason = if is_set {
+        "Unclosed set"
+    } else {
+        "Unclosed map"
+    };
+    Err((
+        ReadError {
+            reason: reason.to_string(),
+        },
+        chars,
+    ))
+}
+
 fn get_unquote_lst(exp: &Expression) -> Option<Expression> {
     let exp_d = exp.get();
     if let ExpEnum::Pair(car, cdr) = &exp_d.data {
@@ -904,6 +1018,8 @@ fn read_list(
                 if let Some(exp) = &exp {
                     if let ExpEnum::Symbol(")", _) = exp.get().data {
                         return Ok((make_exp(head, meta), ichars));
+                    } else if let ExpEnum::Symbol("}", _) = exp.get().data {
+                        return Err((unexpected_close(environment, "}"), ichars));
                     } else if let ExpEnum::Symbol(".", _) = exp.get().data {
                         dot = true;
                         chars = ichars;
@@ -1057,8 +1173,8 @@ fn read_inner(
 
     while let Some((ch, peek_ch)) = next2(&mut chars) {
         environment.reader_state.column += 1;
-        if read_table.contains_key(&*ch) {
-            if let ExpEnum::Symbol(s, _) = read_table.get(&*ch).unwrap().get().data {
+        if hash_get_str(read_table, &ch).is_some() {
+            if let ExpEnum::Symbol(s, _) = hash_get_str(read_table, &ch).unwrap().get().data {
                 let res = prep_reader_macro(environment, chars, s, &ch);
                 match res {
                     Ok((None, ichars)) => {
@@ -1068,8 +1184,8 @@ fn read_inner(
                     _ => return res,
                 }
             }
-        } else if read_table_term.contains_key(&*ch) {
-            if let ExpEnum::Symbol(s, _) = read_table_term.get(&*ch).unwrap().get().data {
+        } else if hash_get_str(read_table_term, &ch).is_some() {
+            if let ExpEnum::Symbol(s, _) = hash_get_str(read_table_term, &ch).unwrap().get().data {
                 let res = prep_reader_macro(environment, chars, s, &ch);
                 match res {
                     Ok((None, ichars)) => {
@@ -1227,6 +1343,11 @@ fn read_inner(
                         let (exp, chars) = read_vector(environment, chars, buffer, in_back_quote)?;
                         return Ok((Some(exp), chars));
                     }
+                    "{" => {
+                        let (exp, chars) =
+                            read_map(environment, chars, buffer, in_back_quote, true)?;
+                        return Ok((Some(exp), chars));
+                    }
                     "t" => {
                         return Ok((Some(Expression::make_true()), chars));
                     }
@@ -1279,6 +1400,20 @@ fn read_inner(
                 let (exp, chars) = read_list(environment, chars, buffer, in_back_quote)?;
                 return Ok((Some(exp), chars));
             }
+            "{" => {
+                let (exp, chars) = read_map(environment, chars, buffer, in_back_quote, false)?;
+                return Ok((Some(exp), chars));
+            }
+            "}" => {
+                if return_close_paren {
+                    return Ok((
+                        Some(Expression::alloc_data(ExpEnum::Symbol("}", SymLoc::None))),
+                        chars,
+                    ));
+                } else {
+                    return Err((unexpected_close(environment, "}"), chars));
+                }
+            }
             ")" => {
                 if return_close_paren {
                     return Ok((
@@ -1960,6 +2095,53 @@ two""#
         assert!(tokens[7] == ")");
     }
 
+    #[test]
+    fn test_tok_maps() {
+        let mut environment = build_def_env();
+        let input = "{:a 1 b 2} #\\}";
+        let tokens = tokenize(&mut environment, input, None);
+        assert!(tokens.len() == 10);
+        assert!(tokens[0] == "#(");
+        assert!(tokens[1] == "(");
+        assert!(tokens[2] == "Symbol:hash-map");
+        assert!(tokens[3] == "Symbol::a");
+        assert!(tokens[4] == "Int:1");
+        assert!(tokens[5] == "Symbol:b");
+        assert!(tokens[6] == "Int:2");
+        assert!(tokens[7] == ")");
+        assert!(tokens[8] == "Char:#\\}");
+        assert!(tokens[9] == ")");
+        let input = "{:a}";
+        tokenize_err(&mut environment, input, None);
+        let input = "(a })";
+        tokenize_err(&mut environment, input, None);
+        let input = "{a )";
+        tokenize_err(&mut environment, input, None);
+    }
+
+    #[test]
+    fn test_tok_sets() {
+        let mut environment = build_def_env();
+        let input = "#{:a 1 \"b\"} #{}";
+        let tokens = tokenize(&mut environment, input, None);
+        assert!(tokens.len() == 11);
+        assert!(tokens[0] == "#(");
+        assert!(tokens[1] == "(");
+        assert!(tokens[2] == "Symbol:make-set");
+        assert!(tokens[3] == "Symbol::a");
+        assert!(tokens[4] == "Int:1");
+        assert!(tokens[5] == "String:\"b\"");
+        assert!(tokens[6] == ")");
+        assert!(tokens[7] == "(");
+        assert!(tokens[8] == "Symbol:make-set");
+        assert!(tokens[9] == ")");
+        assert!(tokens[10] == ")");
+        let input = "#{:a";
+        tokenize_err(&mut environment, input, None);
+        let input = "#{a )";
+        tokenize_err(&mut environment, input, None);
+    }
+
     #[test]
     fn test_tok_ints() {
         let mut environment = build_def_env();
@@ -1986,6 +2168,35 @@ two""#
         tokenize_err(&mut environment, input, None);
         let input = "#o80";
         tokenize_err(&mut environment, input, None);
+        let input = "9223372036854775807 9_223_372_036_854_775_808 -9223372036854775809 #x1_0000_0000_0000_0000 1e20";
+        let tokens = tokenize(&mut environment, input, None);
+        assert!(tokens.len() == 7);
+        assert!(tokens[1] == "Int:9223372036854775807");
+        assert!(tokens[2] == "BigInt:9223372036854775808");
+        assert!(tokens[3] == "BigInt:-9223372036854775809");
+        assert!(tokens[4] == "BigInt:18446744073709551616");
+        assert!(tokens[5] == "Float:100000000000000000000");
+    }
+
+    #[test]
+    fn test_tok_rationals() {
+        let mut environment = build_def_env();
+        let input = "3/4 -1/2 18446744073709551616/3 6/8 4/2 2024/10 1_000/3 03/4 1/0 1/-2 a/2 /";
+        let tokens = tokenize(&mut environment, input, None);
+        assert!(tokens.len() == 14);
+        assert!(tokens[1] == "Rational:3/4");
+        assert!(tokens[2] == "Rational:-1/2");
+        assert!(tokens[3] == "Rational:18446744073709551616/3");
+        // Only the printed form is a rational, path like tokens are symbols.
+        assert!(tokens[4] == "Symbol:6/8");
+        assert!(tokens[5] == "Symbol:4/2");
+        assert!(tokens[6] == "Symbol:2024/10");
+        assert!(tokens[7] == "Symbol:1_000/3");
+        assert!(tokens[8] == "Symbol:03/4");
+        assert!(tokens[9] == "Symbol:1/0");
+        assert!(tokens[10] == "Symbol:1/-2");
+        assert!(tokens[11] == "Symbol:a/2");
+        assert!(tokens[12] == "Symbol:/");
     }
 
     #[test]
@@ -10,6 +10,8 @@ use nix::sys::signal::{self, SigHandler, Signal};
 use nix::unistd::{gethostname, Uid};
 
 use crate::builtins::load;
+use crate::builtins_profile::profile_end;
+use crate::builtins_system::run_exit_trap;
 use crate::environment::*;
 use crate::types::*;
 
@@ -71,7 +73,19 @@ t
     }
 }
 
-pub fn start_interactive(is_tty: bool) -> i32 {
+// Set the shell option globals from the command line (they default to nil).
+fn set_shell_options(environment: &mut Environment, errexit: bool, xtrace: bool) {
+    for (name, on) in &[("*errexit*", errexit), ("*xtrace*", xtrace)] {
+        if *on {
+            environment
+                .root_scope
+                .borrow_mut()
+                .insert(environment.interner.intern(name), Expression::make_true());
+        }
+    }
+}
+
+pub fn start_interactive(is_tty: bool, profile: Option<&str>) -> i32 {
     // Initialize the HOST variable
     let mut hostname = [0_u8; 512];
     env::set_var(
@@ -116,7 +130,11 @@ pub fn start_interactive(is_tty: bool) -> i32 {
             Expression::make_true(),
         );
     }
+    if let Some(file) = profile {
+        environment.profiler.start(Some(file.to_string()));
+    }
     load_user_env(&mut environment, &home, true, true);
+    run_exit_trap(&mut environment);
     if environment.exit_code.is_some() {
         environment.exit_code.unwrap()
     } else {
@@ -198,7 +216,13 @@ pub fn run_one_command(command: &str, args: &[String]) -> Result<(), LispError>
     Ok(())
 }
 
-pub fn run_one_script(command: &str, args: &[String]) -> i32 {
+pub fn run_one_script(
+    command: &str,
+    args: &[String],
+    errexit: bool,
+    xtrace: bool,
+    profile: Option<&str>,
+) -> i32 {
     let mut environment = build_default_environment();
     environment.do_job_control = false;
     let mut home = match env::var("HOME") {
@@ -228,7 +252,15 @@ pub fn run_one_script(command: &str, args: &[String]) -> i32 {
         .root_scope
         .borrow_mut()
         .insert(environment.interner.intern("args"), data);
+    set_shell_options(&mut environment, errexit, xtrace);
+    if let Some(file) = profile {
+        environment.profiler.start(Some(file.to_string()));
+    }
     load_user_env(&mut environment, &home, false, false);
+    if let Err(err) = profile_end(&mut environment, true) {
+        eprintln!("{}", err);
+    }
+    run_exit_trap(&mut environment);
     if let Err(err) = reap_procs(&environment) {
         eprintln!("Error reaping procs after running {}: {}", command, err);
     }
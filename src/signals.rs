@@ -1,14 +1,74 @@
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::convert::TryFrom;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU64, AtomicUsize, Ordering};
 
 use nix::{
     sys::signal::{self, sigaction, SigHandler, Signal},
     Error,
 };
 
+use crate::unix::anon_pipe;
+
 static SIG_INT: AtomicBool = AtomicBool::new(false);
+// Count of SIGCHLDs received and the count when children were last reaped.
+static SIG_CHLD: AtomicUsize = AtomicUsize::new(0);
+static REAPED_CHLD: AtomicUsize = AtomicUsize::new(0);
+// Bit sets (by signal number) of signals with a trap handler installed and of
+// trapped signals received but not yet handled.
+static TRAPS_INSTALLED: AtomicU64 = AtomicU64::new(0);
+static TRAPS_PENDING: AtomicU64 = AtomicU64::new(0);
+// Self pipe, the signal handlers write a byte to it to wake up wait_for_signal.
+static WAKE_READ: AtomicI32 = AtomicI32::new(-1);
+static WAKE_WRITE: AtomicI32 = AtomicI32::new(-1);
+
+fn errno_location() -> *mut libc::c_int {
+    cfg_if::cfg_if! {
+        if #[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))] {
+            unsafe { libc::__error() }
+        } else if #[cfg(any(target_os = "android", target_os = "netbsd", target_os = "openbsd"))] {
+            unsafe { libc::__errno() }
+        } else {
+            unsafe { libc::__errno_location() }
+        }
+    }
+}
+
+fn wake() {
+    let fd = WAKE_WRITE.load(Ordering::Relaxed);
+    if fd >= 0 {
+        let buf = [1_u8];
+        // The handlers can interrupt code between a failed call and its check
+        // of errno so save and restore it around the write.  If the pipe is
+        // full then a wake up is already pending so ignore errors.
+        unsafe {
+            let errno = *errno_location();
+            libc::write(fd, buf.as_ptr() as *const libc::c_void, 1);
+            *errno_location() = errno;
+        }
+    }
+}
 
 extern "C" fn sig_int_handle(_: libc::c_int, _: *mut libc::siginfo_t, _: *mut libc::c_void) {
     SIG_INT.store(true, Ordering::Relaxed);
+    wake();
+}
+
+extern "C" fn sig_chld_handle(_: libc::c_int, _: *mut libc::siginfo_t, _: *mut libc::c_void) {
+    SIG_CHLD.fetch_add(1, Ordering::Relaxed);
+    wake();
+}
+
+extern "C" fn sig_trap_handle(sig: libc::c_int, _: *mut libc::siginfo_t, _: *mut libc::c_void) {
+    TRAPS_PENDING.fetch_or(1 << sig, Ordering::Relaxed);
+    wake();
+}
+
+fn report_install_error(name: &str, err: Error) {
+    eprint!("ERROR Failed to install {} handler due to: ", name);
+
+    match err {
+        Error::Sys(errno) => eprintln!("{} ({}).", errno.desc(), errno),
+        _ => eprintln!("unexpected cause."),
+    };
 }
 
 pub fn install_sigint_handler() -> bool {
@@ -22,18 +82,156 @@ pub fn install_sigint_handler() -> bool {
     };
 
     if let Err(err) = result {
-        eprint!("ERROR Failed to install SIGINT handler due to: ");
+        report_install_error("SIGINT", err);
+        false
+    } else {
+        true
+    }
+}
+
+/// Install the trap handler for sig, the trap itself is run later by the
+/// evaluator (see take_pending_traps).
+pub fn install_trap_handler(sig: Signal) -> bool {
+    let result = unsafe {
+        let sig_action = signal::SigAction::new(
+            signal::SigHandler::SigAction(sig_trap_handle),
+            signal::SaFlags::SA_RESTART,
+            signal::SigSet::empty(),
+        );
+        sigaction(sig, &sig_action)
+    };
+
+    if let Err(err) = result {
+        report_install_error(sig.as_str(), err);
+        false
+    } else {
+        TRAPS_INSTALLED.fetch_or(1 << sig as i32, Ordering::Relaxed);
+        true
+    }
+}
+
+/// Put sig back to the default handling and drop any pending trap for it.
+pub fn remove_trap_handler(sig: Signal) {
+    let mask = !(1 << sig as i32);
+    TRAPS_INSTALLED.fetch_and(mask, Ordering::Relaxed);
+    unsafe {
+        if let Err(err) = signal::signal(sig, SigHandler::SigDfl) {
+            report_install_error(sig.as_str(), err);
+        }
+    }
+    TRAPS_PENDING.fetch_and(mask, Ordering::Relaxed);
+}
+
+/// Remove all the trap handlers, a forked child does not run its parent's traps.
+pub fn reset_trap_handlers() {
+    let installed = TRAPS_INSTALLED.load(Ordering::Relaxed);
+    for signum in 1..64 {
+        if installed & (1 << signum) != 0 {
+            if let Ok(sig) = Signal::try_from(signum) {
+                remove_trap_handler(sig);
+            }
+        }
+    }
+    TRAPS_PENDING.store(0, Ordering::Relaxed);
+}
+
+/// Returns true if any trapped signals are waiting to be handled.
+pub fn traps_pending() -> bool {
+    TRAPS_PENDING.load(Ordering::Relaxed) != 0
+}
+
+/// Returns the trapped signals received since the last call (in signal number order).
+pub fn take_pending_traps() -> Vec<Signal> {
+    let pending = TRAPS_PENDING.swap(0, Ordering::Relaxed);
+    let mut sigs = Vec::new();
+    if pending != 0 {
+        for signum in 1..64 {
+            if pending & (1 << signum) != 0 {
+                if let Ok(sig) = Signal::try_from(signum) {
+                    sigs.push(sig);
+                }
+            }
+        }
+    }
+    sigs
+}
+
+fn open_wake_pipe() -> bool {
+    match anon_pipe() {
+        Ok((read_fd, write_fd)) => {
+            unsafe {
+                libc::fcntl(read_fd, libc::F_SETFL, libc::O_NONBLOCK);
+                libc::fcntl(write_fd, libc::F_SETFL, libc::O_NONBLOCK);
+            }
+            WAKE_READ.store(read_fd, Ordering::Relaxed);
+            WAKE_WRITE.store(write_fd, Ordering::Relaxed);
+            true
+        }
+        Err(err) => {
+            eprintln!("ERROR Failed to create the SIGCHLD pipe: {}", err);
+            false
+        }
+    }
+}
+
+pub fn install_sigchld_handler() -> bool {
+    if WAKE_READ.load(Ordering::Relaxed) < 0 && !open_wake_pipe() {
+        return false;
+    }
+    let result = unsafe {
+        // Restart interrupted system calls, children can exit at any time and
+        // that should not break a read on stdin or a pipe.
+        let sig_action = signal::SigAction::new(
+            signal::SigHandler::SigAction(sig_chld_handle),
+            signal::SaFlags::SA_RESTART,
+            signal::SigSet::empty(),
+        );
+        sigaction(signal::SIGCHLD, &sig_action)
+    };
 
-        match err {
-            Error::Sys(errno) => eprintln!("{} ({}).", errno.desc(), errno),
-            _ => eprintln!("unexpected cause."),
-        };
+    if let Err(err) = result {
+        report_install_error("SIGCHLD", err);
         false
     } else {
         true
     }
 }
 
+/// Replace the SIGCHLD self pipe, a forked child must not share it with its parent.
+pub fn reset_sigchld_pipe() {
+    let read_fd = WAKE_READ.swap(-1, Ordering::Relaxed);
+    let write_fd = WAKE_WRITE.swap(-1, Ordering::Relaxed);
+    if read_fd >= 0 {
+        unsafe {
+            libc::close(read_fd);
+            libc::close(write_fd);
+        }
+        open_wake_pipe();
+    }
+}
+
+/// Block until a SIGCHLD or SIGINT is received.  If the SIGCHLD handler is not
+/// installed then this falls back to sleeping for a short time.
+pub fn wait_for_signal() {
+    let read_fd = WAKE_READ.load(Ordering::Relaxed);
+    if read_fd < 0 {
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        return;
+    }
+    let mut poll_fd = libc::pollfd {
+        fd: read_fd,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    // The timeout is only a safety net, a wake up should always arrive through the pipe.
+    // EINTR is fine, it means a signal came in and that is what we are waiting for.
+    unsafe {
+        libc::poll(&mut poll_fd, 1, 1000);
+    }
+    let mut buf = [0_u8; 64];
+    while unsafe { libc::read(read_fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) } > 0 {}
+}
+
 pub fn mask_signals() {
     /* Ignore interactive and job-control signals.  */
     unsafe {
@@ -51,3 +249,13 @@ pub fn mask_signals() {
 pub fn test_clear_sigint() -> bool {
     SIG_INT.swap(false, Ordering::Relaxed)
 }
+
+/// Returns true if a child has changed state since the last call (children need
+/// reaping).  Always true if the SIGCHLD handler is not installed.
+pub fn test_clear_sigchld() -> bool {
+    if WAKE_READ.load(Ordering::Relaxed) < 0 {
+        return true;
+    }
+    let count = SIG_CHLD.load(Ordering::Relaxed);
+    REAPED_CHLD.swap(count, Ordering::Relaxed) != count
+}
@@ -4,17 +4,24 @@ use std::rc::Rc;
 
 use crate::builtins::add_builtins;
 use crate::builtins_bind::add_bind_builtins;
+use crate::builtins_conditions::add_condition_builtins;
+use crate::builtins_debug::add_debug_builtins;
 use crate::builtins_edit::add_edit_builtins;
 use crate::builtins_file::add_file_builtins;
+use crate::builtins_generic::add_generic_builtins;
 use crate::builtins_hashmap::add_hash_builtins;
 use crate::builtins_io::add_io_builtins;
 use crate::builtins_math::*;
 use crate::builtins_namespace::add_namespace_builtins;
 use crate::builtins_pair::add_pair_builtins;
+use crate::builtins_profile::add_profile_builtins;
 use crate::builtins_rand::add_rand_builtins;
+use crate::builtins_set::add_set_builtins;
 use crate::builtins_stats::add_stats_builtins;
 use crate::builtins_str::add_str_builtins;
+use crate::builtins_struct::add_struct_builtins;
 use crate::builtins_system::add_system_builtins;
+use crate::builtins_trace::add_trace_builtins;
 use crate::builtins_types::add_type_builtins;
 use crate::builtins_values::add_values_builtins;
 use crate::builtins_vector::add_vec_builtins;
@@ -43,6 +50,11 @@ impl Binding {
         self.expression.borrow().clone()
     }
 
+    // The shared binding, used by the cycle collector.
+    pub(crate) fn rc(&self) -> &Rc<RefCell<Expression>> {
+        &self.expression
+    }
+
     pub fn replace(&self, exp: Expression) -> Expression {
         self.expression.replace(exp)
     }
@@ -64,9 +76,11 @@ impl Default for Binding {
 pub struct SymbolsInt {
     pub syms: HashMap<&'static str, usize>,
     count: usize,
+    // Slots with no symbol (see insert_anon).
+    anon: usize,
 }
 
-type Captures = Rc<RefCell<Vec<(&'static str, usize, Binding)>>>;
+pub(crate) type Captures = Rc<RefCell<Vec<(&'static str, usize, Binding)>>>;
 
 #[derive(Clone, Debug)]
 pub struct Symbols {
@@ -83,6 +97,7 @@ impl Symbols {
         let data = Rc::new(RefCell::new(SymbolsInt {
             syms: HashMap::new(),
             count: 0,
+            anon: 0,
         }));
         let (lex_id, lex_depth, namespace, outer) = if let Some(lex_syms) = syms {
             (
@@ -106,6 +121,15 @@ impl Symbols {
         }
     }
 
+    // The captures and outer scope, used by the cycle collector.
+    pub(crate) fn captures_rc(&self) -> &Captures {
+        &self.captures
+    }
+
+    pub(crate) fn outer_rc(&self) -> Option<&Rc<RefCell<Symbols>>> {
+        self.outer.as_ref()
+    }
+
     pub fn dup(&self) -> Symbols {
         let self_captures = self.captures.borrow();
         let mut captures = Vec::with_capacity(self_captures.len());
@@ -122,12 +146,22 @@ impl Symbols {
         }
     }
 
+    /// The name and stack slot of each capture.
+    pub fn capture_slots(&self) -> Vec<(&'static str, usize)> {
+        self.captures
+            .borrow()
+            .iter()
+            .map(|(name, idx, _)| (*name, *idx))
+            .collect()
+    }
+
     pub fn is_empty(&self) -> bool {
-        self.data.borrow().syms.is_empty()
+        self.len() == 0
     }
 
     pub fn len(&self) -> usize {
-        self.data.borrow().syms.len()
+        let data = self.data.borrow();
+        data.syms.len() + data.anon
     }
 
     pub fn lex_id(&self) -> usize {
@@ -183,6 +217,15 @@ impl Symbols {
         count
     }
 
+    /// A stack slot with no symbol (a destructured param for instance).
+    pub fn insert_anon(&mut self) -> usize {
+        let mut data = self.data.borrow_mut();
+        let count = data.count;
+        data.count += 1;
+        data.anon += 1;
+        count
+    }
+
     pub fn insert_capture(&self, key: &'static str, environment: &mut Environment) -> usize {
         let mut data = self.data.borrow_mut();
         let count = data.count;
@@ -277,6 +320,13 @@ impl Namespace {
         add_type_builtins(interner, &mut data);
         add_namespace_builtins(interner, &mut data);
         add_bind_builtins(interner, &mut data);
+        add_condition_builtins(interner, &mut data);
+        add_debug_builtins(interner, &mut data);
+        add_profile_builtins(interner, &mut data);
+        add_trace_builtins(interner, &mut data);
+        add_generic_builtins(interner, &mut data);
+        add_set_builtins(interner, &mut data);
+        add_struct_builtins(interner, &mut data);
         data.insert(
             interner.intern("*stdin*"),
             (
@@ -4,21 +4,48 @@ use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::fmt;
 use std::fs::File;
+use std::hash::{Hash, Hasher};
 use std::io::{self, BufReader, BufWriter, Read, Write};
 use std::iter;
 use std::num::{ParseFloatError, ParseIntError};
 use std::rc::Rc;
 
+use crate::bigint::BigInt;
+use crate::bytecode::LambdaCode;
 use crate::environment::*;
 use crate::eval::call_lambda;
+use crate::gc::{self, can_cycle};
 use crate::process::*;
+use crate::rational::Rational;
 use crate::symbols::*;
 use crate::unix::fd_to_file;
 
+/// A lambda call an error unwound through, see get-error.
+#[derive(Clone, Debug)]
+pub struct ErrorFrame {
+    /// Name the lambda was defined with, None for the top level or anonymous fns.
+    pub name: Option<&'static str>,
+    /// The innermost form (with meta data) in the call the error passed through.
+    pub location: Option<ExpMeta>,
+    /// The lambda's arguments (printed and truncated).
+    pub args: Vec<String>,
+}
+
 #[derive(Clone, Debug)]
 pub struct LispError {
     pub reason: String,
-    pub backtrace: Option<Vec<Expression>>,
+    /// Calls the error unwound through, innermost first.
+    pub backtrace: Vec<ErrorFrame>,
+    /// Keyword naming the kind of error (:io, :type, :arity, :process or user defined).
+    pub kind: Option<&'static str>,
+    /// Optional expression carried along with the error (see err).
+    pub data: Option<Expression>,
+    /// Where the error was raised (the innermost expression with meta data).
+    pub location: Option<ExpMeta>,
+    /// True once the condition handlers have seen this error (see handler-bind).
+    pub signalled: bool,
+    /// Id of the handler-case/restart-case/get-error frame this error is unwinding to.
+    pub unwind_to: Option<FrameId>,
 }
 
 impl Error for LispError {}
@@ -33,7 +60,12 @@ impl From<io::Error> for LispError {
     fn from(item: io::Error) -> Self {
         LispError {
             reason: item.to_string(),
-            backtrace: None,
+            backtrace: Vec::new(),
+            kind: Some(":io"),
+            data: None,
+            location: None,
+            signalled: false,
+            unwind_to: None,
         }
     }
 }
@@ -42,9 +74,39 @@ impl LispError {
     pub fn new<S: Into<String>>(reason: S) -> LispError {
         LispError {
             reason: reason.into(),
-            backtrace: None,
+            backtrace: Vec::new(),
+            kind: None,
+            data: None,
+            location: None,
+            signalled: false,
+            unwind_to: None,
+        }
+    }
+
+    pub fn with_kind<S: Into<String>>(kind: &'static str, reason: S) -> LispError {
+        LispError {
+            reason: reason.into(),
+            backtrace: Vec::new(),
+            kind: Some(kind),
+            data: None,
+            location: None,
+            signalled: false,
+            unwind_to: None,
         }
     }
+
+    pub fn with_data(mut self, data: Expression) -> LispError {
+        self.data = Some(data);
+        self
+    }
+
+    /// Set the kind unless the error already has one.
+    pub fn or_kind(mut self, kind: &'static str) -> LispError {
+        if self.kind.is_none() {
+            self.kind = Some(kind);
+        }
+        self
+    }
 }
 
 pub trait PeekableIterator: std::iter::Iterator {
@@ -83,15 +145,52 @@ impl MultiExpression {
     }
 }
 
+/// An &optional or &key parameter.  If it is not passed default (or nil) is
+/// evaluated for it and the supplied symbol (if any) is bound to false.
+#[derive(Clone, Debug)]
+pub struct OptParam {
+    pub name: &'static str,
+    pub default: Option<Expression>,
+    pub supplied: Option<&'static str>,
+}
+
+/// A destructuring pattern for a parameter, binds parts of the argument to
+/// the stack slots of the symbols in the pattern.
+#[derive(Clone, Debug)]
+pub enum Pattern {
+    Bind(usize),
+    /// A list or vector, the element patterns and the pattern after &.
+    Seq(Vec<Pattern>, Option<Box<Pattern>>),
+    /// A hashmap ({} in a parameter list), the entries and the :as slot.
+    Map(Vec<MapPattern>, Option<usize>),
+}
+
+/// The value of the first key found (or default or nil) is bound to pattern.
+#[derive(Clone, Debug)]
+pub struct MapPattern {
+    pub pattern: Pattern,
+    pub keys: Vec<&'static str>,
+    pub default: Option<Expression>,
+}
+
 #[derive(Clone, Debug)]
 pub struct Lambda {
+    // The required params and &rest (not &optional or &key), a destructured
+    // param is "" (see patterns).
     pub params: Vec<&'static str>,
     pub num_params: usize,
     pub has_rest: bool,
+    pub optional: Vec<OptParam>,
+    pub keys: Vec<OptParam>,
+    // Destructured params, the param's slot, the pattern as written and the pattern.
+    pub patterns: Vec<(usize, Expression, Pattern)>,
     pub body: MultiExpression,
     pub syms: Symbols,
     pub namespace: Rc<RefCell<Namespace>>,
     pub no_recur: bool,
+    pub code: Rc<RefCell<LambdaCode>>,
+    // Set when the lambda is bound with def (for backtraces).
+    pub name: Option<&'static str>,
 }
 
 impl Lambda {
@@ -100,14 +199,249 @@ impl Lambda {
             params: self.params.iter().copied().collect(),
             num_params: self.num_params,
             has_rest: self.has_rest,
+            optional: self.optional.clone(),
+            keys: self.keys.clone(),
+            patterns: self.patterns.clone(),
             body: self.body.copy(),
             syms: self.syms.clone(), // XXX TODO deep?
             namespace: self.namespace.clone(),
             no_recur: self.no_recur,
+            code: LambdaCode::new_shared(),
+            name: self.name,
+        }
+    }
+
+    /// Number of stack slots the params (including supplied flags) use.
+    pub fn param_slots(&self) -> usize {
+        self.optional
+            .iter()
+            .chain(self.keys.iter())
+            .fold(self.num_params, |slots, param| {
+                slots + if param.supplied.is_some() { 2 } else { 1 }
+            })
+    }
+}
+
+/// How a struct field is reached with messages, (instance :field) for a reader
+/// and (instance :set-field value) for a writer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FieldAccess {
+    Private,
+    ReadWrite,
+    ReadOnly,
+    WriteOnly,
+}
+
+/// A struct method and the stack slot and field slot of each field it captures.
+pub type StructMethod = (Expression, Vec<(usize, usize)>);
+
+/// The type of a struct (see defstruct), shared by all the instances.
+#[derive(Debug)]
+pub struct StructType {
+    pub name: &'static str,
+    pub fields: Vec<(&'static str, FieldAccess)>,
+    // Field name to slot.
+    pub slots: HashMap<&'static str, usize>,
+    // The accessor messages, the slot and true for a setter.
+    pub messages: HashMap<&'static str, (usize, bool)>,
+    // Lambdas by message, they capture the field names and are run with those
+    // bound to the slots of the instance.
+    pub methods: RefCell<HashMap<&'static str, StructMethod>>,
+    pub tags: HashSet<&'static str>,
+}
+
+/// A hash map key.  Symbols (and keywords), strings and chars with the same
+/// text are the same key, lists and vectors are keys if their items are (and
+/// are copied so changing the list later does not change the key).
+#[derive(Clone, Debug)]
+pub enum HashKey {
+    Symbol(&'static str),
+    String(Cow<'static, str>),
+    Char(Cow<'static, str>),
+    Int(i64),
+    BigInt(BigInt),
+    Rational(Rational),
+    List(Vec<HashKey>),
+    Vector(Vec<HashKey>),
+}
+
+impl HashKey {
+    pub fn from_exp(exp: &Expression) -> Result<HashKey, LispError> {
+        match &exp.get().data {
+            ExpEnum::Symbol(s, _) => Ok(HashKey::Symbol(s)),
+            ExpEnum::String(s, _) => Ok(HashKey::String(s.clone())),
+            ExpEnum::Char(c) => Ok(HashKey::Char(c.clone())),
+            ExpEnum::Int(i) => Ok(HashKey::Int(*i)),
+            ExpEnum::BigInt(b) => Ok(HashKey::BigInt(b.clone())),
+            ExpEnum::Rational(r) => Ok(HashKey::Rational(r.clone())),
+            ExpEnum::Nil => Ok(HashKey::List(Vec::new())),
+            ExpEnum::Pair(_, _) => {
+                let mut items = Vec::new();
+                let mut tail = exp.clone();
+                while let ExpEnum::Pair(car, cdr) = &tail.clone().get().data {
+                    items.push(HashKey::from_exp(car)?);
+                    tail = cdr.clone();
+                }
+                if tail.is_nil() {
+                    Ok(HashKey::List(items))
+                } else {
+                    Err(LispError::with_kind(
+                        ":type",
+                        "hash key can not be an improper list",
+                    ))
+                }
+            }
+            ExpEnum::Vector(v) => Ok(HashKey::Vector(
+                v.iter().map(HashKey::from_exp).collect::<Result<_, _>>()?,
+            )),
+            ExpEnum::Values(v) if !v.is_empty() => HashKey::from_exp(&v[0]),
+            _ => Err(LispError::with_kind(
+                ":type",
+                format!(
+                    "hash key must be a symbol, string, char, int or list of those, got {}",
+                    exp.display_type()
+                ),
+            )),
+        }
+    }
+
+    pub fn to_exp(&self) -> Expression {
+        match self {
+            HashKey::Symbol(s) => Expression::alloc_data(ExpEnum::Symbol(s, SymLoc::None)),
+            HashKey::String(s) => Expression::alloc_data(ExpEnum::String(s.clone(), None)),
+            HashKey::Char(c) => Expression::alloc_data(ExpEnum::Char(c.clone())),
+            HashKey::Int(i) => Expression::alloc_data(ExpEnum::Int(*i)),
+            HashKey::BigInt(b) => Expression::alloc_data(ExpEnum::BigInt(b.clone())),
+            HashKey::Rational(r) => Expression::alloc_data(ExpEnum::Rational(r.clone())),
+            HashKey::List(items) => {
+                let items: Vec<Expression> = items.iter().map(HashKey::to_exp).collect();
+                Expression::cons_from_vec(&items, None)
+            }
+            HashKey::Vector(items) => {
+                Expression::with_list(items.iter().map(HashKey::to_exp).collect())
+            }
+        }
+    }
+}
+
+impl fmt::Display for HashKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_exp())
+    }
+}
+
+/// Lets a map keyed by HashKey be searched with a &str (the same key as a
+/// symbol or string with that text) without making a HashKey.
+pub trait HashKeyLookup {
+    // The text of a symbol, string or char key.
+    fn text(&self) -> Option<&str>;
+    fn key(&self) -> Option<&HashKey>;
+}
+
+impl HashKeyLookup for HashKey {
+    fn text(&self) -> Option<&str> {
+        match self {
+            HashKey::Symbol(s) => Some(s),
+            HashKey::String(s) => Some(s),
+            HashKey::Char(c) => Some(c),
+            _ => None,
+        }
+    }
+
+    fn key(&self) -> Option<&HashKey> {
+        Some(self)
+    }
+}
+
+impl HashKeyLookup for &str {
+    fn text(&self) -> Option<&str> {
+        Some(self)
+    }
+
+    fn key(&self) -> Option<&HashKey> {
+        None
+    }
+}
+
+impl<'a> std::borrow::Borrow<dyn HashKeyLookup + 'a> for HashKey {
+    fn borrow(&self) -> &(dyn HashKeyLookup + 'a) {
+        self
+    }
+}
+
+impl Hash for dyn HashKeyLookup + '_ {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match (self.text(), self.key()) {
+            (Some(text), _) => {
+                0u8.hash(state);
+                text.hash(state);
+            }
+            (None, Some(HashKey::Int(i))) => {
+                1u8.hash(state);
+                i.hash(state);
+            }
+            (None, Some(HashKey::List(items))) => {
+                2u8.hash(state);
+                items.hash(state);
+            }
+            (None, Some(HashKey::Vector(items))) => {
+                3u8.hash(state);
+                items.hash(state);
+            }
+            (None, Some(HashKey::BigInt(b))) => {
+                4u8.hash(state);
+                b.hash(state);
+            }
+            (None, Some(HashKey::Rational(r))) => {
+                5u8.hash(state);
+                r.hash(state);
+            }
+            _ => {}
         }
     }
 }
 
+impl PartialEq for dyn HashKeyLookup + '_ {
+    fn eq(&self, other: &Self) -> bool {
+        match (self.text(), other.text()) {
+            (Some(a), Some(b)) => a == b,
+            (None, None) => match (self.key(), other.key()) {
+                (Some(HashKey::Int(a)), Some(HashKey::Int(b))) => a == b,
+                (Some(HashKey::BigInt(a)), Some(HashKey::BigInt(b))) => a == b,
+                (Some(HashKey::Rational(a)), Some(HashKey::Rational(b))) => a == b,
+                (Some(HashKey::List(a)), Some(HashKey::List(b))) => a == b,
+                (Some(HashKey::Vector(a)), Some(HashKey::Vector(b))) => a == b,
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+}
+
+impl Eq for dyn HashKeyLookup + '_ {}
+
+impl Hash for HashKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (self as &dyn HashKeyLookup).hash(state)
+    }
+}
+
+impl PartialEq for HashKey {
+    fn eq(&self, other: &Self) -> bool {
+        (self as &dyn HashKeyLookup) == (other as &dyn HashKeyLookup)
+    }
+}
+
+impl Eq for HashKey {}
+
+/// Get the value for the symbol or string key in a hash map.
+pub fn hash_get_str<'a>(
+    map: &'a HashMap<HashKey, Expression>,
+    key: &str,
+) -> Option<&'a Expression> {
+    map.get(&key as &dyn HashKeyLookup)
+}
+
 #[derive(Clone, Copy)]
 pub enum ProcessState {
     Running(u32),   // pid
@@ -199,13 +533,17 @@ impl Iterator for ListIter {
     }
 }
 
-type CallFunc =
+pub type CallFunc =
     fn(&mut Environment, &mut dyn Iterator<Item = Expression>) -> Result<Expression, LispError>;
 
 #[derive(Clone)]
 pub struct Callable {
     pub func: CallFunc,
     pub is_special_form: bool,
+    // Evaluates its arguments in a context it sets up (grabbing process output,
+    // redirecting, catching errors, etc) or looks at them unevaluated (job
+    // specs like %1) so they can not be evaluated first.
+    pub args_in_context: bool,
 }
 
 impl Callable {
@@ -213,6 +551,15 @@ impl Callable {
         Callable {
             func,
             is_special_form,
+            args_in_context: is_special_form,
+        }
+    }
+
+    pub fn with_context(func: CallFunc) -> Callable {
+        Callable {
+            func,
+            is_special_form: false,
+            args_in_context: true,
         }
     }
 }
@@ -245,6 +592,8 @@ pub enum ExpEnum {
     Nil,
     Float(f64),
     Int(i64),
+    BigInt(BigInt),
+    Rational(Rational),
     Symbol(&'static str, SymLoc),
     // NOTE: String has an invarent to maintain, if Cow ever changes then the iterator must be set
     // to None if it is Some.
@@ -262,7 +611,8 @@ pub enum ExpEnum {
     Vector(Vec<Expression>),
     Values(Vec<Expression>), // Used for multi value returns
     Pair(Expression, Expression),
-    HashMap(HashMap<&'static str, Expression>),
+    HashMap(HashMap<HashKey, Expression>),
+    Set(HashSet<HashKey>),
 
     // Represents a running or completed system process
     Process(ProcessState),
@@ -270,6 +620,10 @@ pub enum ExpEnum {
     // A file
     File(Rc<RefCell<FileState>>),
 
+    // A struct type and an instance of one (a binding for each field).
+    StructType(Rc<StructType>),
+    Struct(Rc<StructType>, Vec<Binding>),
+
     // Used as part of analyzer (a wrapped thing has already been 'prepped' so
     // when evaluated just unwrap it).
     Wrapper(Expression),
@@ -323,6 +677,8 @@ impl ExpEnum {
             ExpEnum::Nil => ExpEnum::Nil,
             ExpEnum::Float(n) => ExpEnum::Float(*n),
             ExpEnum::Int(i) => ExpEnum::Int(*i),
+            ExpEnum::BigInt(b) => ExpEnum::BigInt(b.clone()),
+            ExpEnum::Rational(r) => ExpEnum::Rational(r.clone()),
             ExpEnum::Symbol(s, _) => ExpEnum::Symbol(s, SymLoc::None),
             // XXX TODO- make a new Cow (next two)?
             ExpEnum::String(s, _) => ExpEnum::String(s.clone(), None),
@@ -338,8 +694,17 @@ impl ExpEnum {
             ExpEnum::Values(v) => ExpEnum::Values(v.iter().map(|h| copy_handle(h)).collect()),
             ExpEnum::Pair(car, cdr) => ExpEnum::Pair(copy_handle(car), copy_handle(cdr)),
             ExpEnum::HashMap(map) => ExpEnum::HashMap(map.clone()), //XXX TODO- deep copy
+            ExpEnum::Set(set) => ExpEnum::Set(set.clone()),
             ExpEnum::Process(p) => ExpEnum::Process(*p),
             ExpEnum::File(f) => ExpEnum::File(f.clone()),
+            ExpEnum::StructType(t) => ExpEnum::StructType(t.clone()),
+            ExpEnum::Struct(t, slots) => ExpEnum::Struct(
+                t.clone(),
+                slots
+                    .iter()
+                    .map(|b| Binding::with_expression(copy_handle(&b.get())))
+                    .collect(),
+            ),
             ExpEnum::Wrapper(h) => ExpEnum::Wrapper(copy_handle(h)),
             ExpEnum::DeclareDef => ExpEnum::DeclareDef,
             ExpEnum::DeclareVar => ExpEnum::DeclareVar,
@@ -360,6 +725,8 @@ impl Clone for ExpEnum {
             ExpEnum::Nil => ExpEnum::Nil,
             ExpEnum::Float(n) => ExpEnum::Float(*n),
             ExpEnum::Int(i) => ExpEnum::Int(*i),
+            ExpEnum::BigInt(b) => ExpEnum::BigInt(b.clone()),
+            ExpEnum::Rational(r) => ExpEnum::Rational(r.clone()),
             ExpEnum::Symbol(s, l) => ExpEnum::Symbol(s, l.clone()),
             ExpEnum::String(s, _) => ExpEnum::String(s.clone(), None),
             ExpEnum::Char(c) => ExpEnum::Char(c.clone()),
@@ -372,8 +739,11 @@ impl Clone for ExpEnum {
             ExpEnum::Values(v) => ExpEnum::Values(v.clone()),
             ExpEnum::Pair(car, cdr) => ExpEnum::Pair(car.clone(), cdr.clone()),
             ExpEnum::HashMap(map) => ExpEnum::HashMap(map.clone()),
+            ExpEnum::Set(set) => ExpEnum::Set(set.clone()),
             ExpEnum::Process(p) => ExpEnum::Process(*p),
             ExpEnum::File(f) => ExpEnum::File(f.clone()),
+            ExpEnum::StructType(t) => ExpEnum::StructType(t.clone()),
+            ExpEnum::Struct(t, slots) => ExpEnum::Struct(t.clone(), slots.clone()),
             ExpEnum::Wrapper(h) => ExpEnum::Wrapper(h.clone()),
             ExpEnum::DeclareDef => ExpEnum::DeclareDef,
             ExpEnum::DeclareVar => ExpEnum::DeclareVar,
@@ -393,6 +763,8 @@ impl fmt::Debug for ExpEnum {
             ExpEnum::False => write!(f, "ExpEnum::False"),
             ExpEnum::Float(n) => write!(f, "ExpEnum::Float({})", n),
             ExpEnum::Int(i) => write!(f, "ExpEnum::Int({})", i),
+            ExpEnum::BigInt(b) => write!(f, "ExpEnum::BigInt({})", b),
+            ExpEnum::Rational(r) => write!(f, "ExpEnum::Rational({})", r),
             ExpEnum::Symbol(s, loc) => write!(f, "ExpEnum::Symbol({}, {:?})", s, loc),
             ExpEnum::String(s, _) => write!(f, "ExpEnum::String(\"{}\")", s),
             ExpEnum::Char(c) => write!(f, "ExpEnum::Char(#\\{})", c),
@@ -409,6 +781,7 @@ impl fmt::Debug for ExpEnum {
             ExpEnum::Values(v) => write!(f, "ExpEnum::Vector({:?})", v),
             ExpEnum::Pair(e1, e2) => write!(f, "ExpEnum::Pair({:?} . {:?})", e1, e2),
             ExpEnum::HashMap(map) => write!(f, "ExpEnum::HashMap({:?})", map),
+            ExpEnum::Set(set) => write!(f, "ExpEnum::Set({:?})", set),
             ExpEnum::Function(_) => write!(f, "ExpEnum::Function(_)"),
             ExpEnum::Process(ProcessState::Running(pid)) => {
                 write!(f, "ExpEnum::Process(ProcessStats::Running({}))", pid)
@@ -419,6 +792,8 @@ impl fmt::Debug for ExpEnum {
                 pid, exit_status
             ),
             ExpEnum::File(_) => write!(f, "ExpEnum::File(_)"),
+            ExpEnum::StructType(t) => write!(f, "ExpEnum::StructType({})", t.name),
+            ExpEnum::Struct(t, _) => write!(f, "ExpEnum::Struct({})", t.name),
             ExpEnum::LazyFn(_, exp) => write!(f, "ExpEnum::LazyFn({:?})", exp),
             ExpEnum::Wrapper(exp) => write!(f, "ExpEnum::Wrapper({:?})", exp),
             ExpEnum::Nil => write!(f, "ExpEnum::Nil"),
@@ -517,15 +892,21 @@ pub struct Expression {
 
 impl Expression {
     pub fn copy(&self) -> Expression {
-        Expression {
-            data: Rc::new(RefCell::new(self.data.borrow().copy())),
-        }
+        Expression::alloc(self.data.borrow().copy())
     }
 
     pub fn alloc(obj: ExpObj) -> Expression {
-        Expression {
-            data: Rc::new(RefCell::new(obj)),
+        let track = can_cycle(&obj.data);
+        let data = Rc::new(RefCell::new(obj));
+        if track {
+            gc::track(&data);
         }
+        Expression { data }
+    }
+
+    // The shared object, used by the cycle collector.
+    pub(crate) fn rc(&self) -> &Rc<RefCell<ExpObj>> {
+        &self.data
     }
 
     pub fn alloc_data(data: ExpEnum) -> Expression {
@@ -601,6 +982,11 @@ impl Expression {
         }
     }
 
+    /// True if self and other are the same object.
+    pub fn ptr_eq(&self, other: &Expression) -> bool {
+        Rc::ptr_eq(&self.data, &other.data)
+    }
+
     pub fn meta(&self) -> Option<ExpMeta> {
         self.get().meta
     }
@@ -610,6 +996,10 @@ impl Expression {
         match &data.data {
             ExpEnum::Pair(_, _) => Box::new(PairIter::new(self.clone())),
             ExpEnum::Vector(_) => Box::new(ListIter::new(self.clone())),
+            ExpEnum::Set(set) => {
+                let items: Vec<Expression> = set.iter().map(HashKey::to_exp).collect();
+                Box::new(items.into_iter())
+            }
             _ => Box::new(iter::empty()),
         }
     }
@@ -645,6 +1035,13 @@ impl Expression {
         )
     }
 
+    pub fn make_context_function(func: CallFunc, doc_str: &str) -> (Expression, String) {
+        (
+            ExpEnum::Function(Callable::with_context(func)).into(),
+            doc_str.to_string(),
+        )
+    }
+
     pub fn make_special(func: CallFunc, doc_str: &str) -> (Expression, String) {
         (
             ExpEnum::Function(Callable::new(func, true)).into(),
@@ -719,12 +1116,24 @@ impl Expression {
         })
     }
 
+    /// The printed expression on one line, truncated to max chars.
+    pub fn to_short_string(&self, max: usize) -> String {
+        let mut form = self.to_string().replace('\n', " ");
+        if form.chars().count() > max {
+            form = form.chars().take(max - 3).collect();
+            form.push_str("...");
+        }
+        form
+    }
+
     pub fn display_type(&self) -> String {
         match &self.get().data {
             ExpEnum::True => "True".to_string(),
             ExpEnum::False => "False".to_string(),
             ExpEnum::Float(_) => "Float".to_string(),
             ExpEnum::Int(_) => "Int".to_string(),
+            ExpEnum::BigInt(_) => "BigInt".to_string(),
+            ExpEnum::Rational(_) => "Rational".to_string(),
             ExpEnum::Symbol(_, _) => "Symbol".to_string(),
             ExpEnum::String(_, _) => "String".to_string(),
             ExpEnum::Char(_) => "Char".to_string(),
@@ -750,7 +1159,10 @@ impl Expression {
             }
             ExpEnum::Pair(_, _) => "Pair".to_string(),
             ExpEnum::HashMap(_) => "HashMap".to_string(),
+            ExpEnum::Set(_) => "Set".to_string(),
             ExpEnum::File(_) => "File".to_string(),
+            ExpEnum::StructType(_) => "StructType".to_string(),
+            ExpEnum::Struct(t, _) => t.name.to_string(),
             ExpEnum::LazyFn(_, _) => "Lambda".to_string(),
             ExpEnum::Wrapper(exp) => {
                 let exp: Expression = exp.clone();
@@ -851,9 +1263,16 @@ impl Expression {
     }
 
     pub fn make_float(&self, environment: &Environment) -> Result<f64, LispError> {
+        self.make_float_inner(environment)
+            .map_err(|err| err.or_kind(":type"))
+    }
+
+    fn make_float_inner(&self, environment: &Environment) -> Result<f64, LispError> {
         match &self.get().data {
             ExpEnum::Float(f) => Ok(*f),
             ExpEnum::Int(i) => Ok(*i as f64),
+            ExpEnum::BigInt(b) => Ok(b.to_f64()),
+            ExpEnum::Rational(r) => Ok(r.to_f64()),
             ExpEnum::Process(ProcessState::Running(_pid)) => {
                 Err(LispError::new("Not a number (process still running!)"))
             }
@@ -877,6 +1296,7 @@ impl Expression {
             ExpEnum::Pair(_, _) => Err(LispError::new("Pair not a number")),
             ExpEnum::Nil => Err(LispError::new("Nil not a number")),
             ExpEnum::HashMap(_) => Err(LispError::new("Map not a number")),
+            ExpEnum::Set(_) => Err(LispError::new("Set not a number")),
             ExpEnum::File(_) => Err(LispError::new("File not a number")),
             ExpEnum::LazyFn(_, _) => Err(LispError::new("Fn call not a number")),
             ExpEnum::Wrapper(_) => Err(LispError::new("Not a number")),
@@ -891,8 +1311,15 @@ impl Expression {
     }
 
     pub fn make_int(&self, environment: &Environment) -> Result<i64, LispError> {
+        self.make_int_inner(environment)
+            .map_err(|err| err.or_kind(":type"))
+    }
+
+    fn make_int_inner(&self, environment: &Environment) -> Result<i64, LispError> {
         match &self.get().data {
             ExpEnum::Int(i) => Ok(*i),
+            ExpEnum::BigInt(_) => Err(LispError::new("Integer too large for a 64 bit int")),
+            ExpEnum::Rational(_) => Err(LispError::new("Not an integer (rational)")),
             ExpEnum::Process(ProcessState::Running(_pid)) => {
                 Err(LispError::new("Not an integer (process still running!)"))
             }
@@ -916,6 +1343,7 @@ impl Expression {
             ExpEnum::Pair(_, _) => Err(LispError::new("Pair not an integer")),
             ExpEnum::Nil => Err(LispError::new("Nil not an integer")),
             ExpEnum::HashMap(_) => Err(LispError::new("Map not an integer")),
+            ExpEnum::Set(_) => Err(LispError::new("Set not an integer")),
             ExpEnum::File(_) => Err(LispError::new("File not an integer")),
             ExpEnum::LazyFn(_, _) => Err(LispError::new("Fn call not an integer")),
             ExpEnum::Wrapper(_) => Err(LispError::new("Not an integer")),
@@ -977,6 +1405,7 @@ impl Expression {
             ExpEnum::Pair(_, _) => write!(writer, "{}", self.to_string())?,
             ExpEnum::Nil => write!(writer, "{}", self.to_string())?,
             ExpEnum::HashMap(_map) => write!(writer, "{}", self.to_string())?,
+            ExpEnum::Set(_set) => write!(writer, "{}", self)?,
             ExpEnum::File(file) => {
                 let mut file_d = file.try_borrow_mut().map_err(|_| {
                     LispError::new("Invalid file, are you trying to read and write the same file?")
@@ -13,6 +13,7 @@ use nix::unistd::{self, Pid};
 
 use crate::environment::*;
 use crate::eval::*;
+use crate::signals::{reset_sigchld_pipe, reset_trap_handlers};
 use crate::types::*;
 
 pub trait IsMinusOne {
@@ -62,40 +63,95 @@ pub fn anon_pipe() -> Result<(i32, i32), LispError> {
     }
 }
 
+/// A file descriptor redirect to apply in a child before it execs.
+#[derive(Clone, Copy, Debug)]
+pub enum FdRedirect {
+    /// Make the first fd a copy of the second (the second is left alone).
+    Dup(i32, i32),
+    /// Close the fd.
+    Close(i32),
+}
+
+/// Duplicate fd to the lowest free fd at or above 10 with CLOEXEC set.  Files
+/// opened for a redirect are moved out of the way so that redirects onto low fds
+/// can not clobber them before they are used.
+pub fn dup_fd_high(fd: i32) -> Result<i32, LispError> {
+    Ok(unsafe { cvt(libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, 10))? })
+}
+
+/// Clear the CLOEXEC flag so fd is inherited by exec'ed commands.
+pub fn clear_cloexec(fd: i32) -> Result<(), LispError> {
+    unsafe {
+        let flags = cvt(libc::fcntl(fd, libc::F_GETFD))?;
+        cvt(libc::fcntl(fd, libc::F_SETFD, flags & !libc::FD_CLOEXEC))?;
+    }
+    Ok(())
+}
+
+/// Apply redirects in order, only call this in a child that is about to exec.
+pub fn apply_fd_redirects(redirects: &[FdRedirect]) -> Result<(), LispError> {
+    for redirect in redirects {
+        match *redirect {
+            FdRedirect::Dup(fd, source) if fd == source => {
+                // dup2 is a no-op here, make sure the fd survives the exec.
+                clear_cloexec(fd)?;
+            }
+            FdRedirect::Dup(fd, source) => unsafe {
+                cvt(libc::dup2(source, fd))?;
+            },
+            FdRedirect::Close(fd) => unsafe {
+                // Closing an fd that is not open is not an error (same as n>&-).
+                libc::close(fd);
+            },
+        }
+    }
+    Ok(())
+}
+
+fn job_name_part(exp: &Expression) -> String {
+    if let ExpEnum::String(s, _) = &exp.get().data {
+        s.to_string()
+    } else {
+        exp.to_string()
+    }
+}
+
 fn fork_job_name(environment: &mut Environment, exp: &Expression) -> Result<String, LispError> {
     let res = match &exp.get().data {
-        ExpEnum::Pair(car, cdr) => {
-            if car.to_string() == "syscall" {
-                if let ExpEnum::Pair(cadr, _) = &cdr.get().data {
-                    eval(environment, cadr)?.to_string()
-                } else {
-                    fork_job_name(environment, &cdr)?
+        ExpEnum::Pair(_, _) | ExpEnum::Vector(_) => {
+            let mut parts = exp.iter();
+            let mut name = match parts.next() {
+                Some(command) if command.to_string() == "syscall" => {
+                    if let Some(command) = parts.next() {
+                        job_name_part(&eval(environment, command)?)
+                    } else {
+                        command.to_string()
+                    }
                 }
-            } else {
-                car.to_string()
-            }
-        }
-        ExpEnum::Vector(v) if !v.is_empty() => {
-            if v[0].to_string() == "syscall" {
-                if let Some(n) = v.get(1) {
-                    eval(environment, n)?.to_string()
-                } else {
-                    v[0].to_string()
+                // The shell reader calls lisp functions with fncall.
+                Some(command) if command.to_string() == "shell-read::fncall" => {
+                    if let Some(command) = parts.next() {
+                        command.to_string()
+                    } else {
+                        command.to_string()
+                    }
                 }
-            } else {
-                v[0].to_string()
-            }
-        }
-        _ => {
-            let exp_name = exp.to_string();
-            if exp_name.len() > 30 {
-                exp_name[0..30].to_string()
-            } else {
-                exp_name
+                Some(command) => command.to_string(),
+                None => exp.to_string(),
+            };
+            for part in parts {
+                name.push(' ');
+                name.push_str(&job_name_part(&part));
             }
+            name
         }
+        _ => exp.to_string(),
     };
-    Ok(res)
+    if res.chars().count() > 60 {
+        Ok(format!("{}...", res.chars().take(60).collect::<String>()))
+    } else {
+        Ok(res)
+    }
 }
 
 fn setup_job(environment: &mut Environment, proc: u32, command: &str) {
@@ -107,11 +163,7 @@ fn setup_job(environment: &mut Environment, proc: u32, command: &str) {
             None => Pid::from_raw(proc as i32),
         };
         if pgid.is_none() {
-            let mut job = Job {
-                pids: Vec::new(),
-                names: Vec::new(),
-                status: JobStatus::Running,
-            };
+            let mut job = Job::new(next_job_id(environment));
             job.pids.push(proc);
             job.names.push(command.to_string());
             environment.jobs.borrow_mut().push(job);
@@ -146,6 +198,18 @@ pub fn fork(
     let pid = unsafe {
         match result {
             0 => {
+                reset_sigchld_pipe();
+                reset_trap_handlers();
+                environment.traps.clear();
+                // Process substitutions belong to the parent's commands.
+                for (fd, _pid, _is_in) in environment.proc_subs.drain(..) {
+                    libc::close(fd);
+                }
+                for (_pid, proc_subs) in environment.held_proc_subs.get_mut().drain() {
+                    for (fd, _pid, _is_in) in proc_subs {
+                        libc::close(fd);
+                    }
+                }
                 if let Some(stdin) = stdin {
                     if let Err(err) = cvt(libc::dup2(stdin, 0)) {
                         eprintln!("Error setting up stdin (dup) in pipe: {}", err);
@@ -425,10 +489,17 @@ pub fn fork_exec<I, S>(
     args: I,
 ) -> Result<u32, LispError>
 where
-    I: IntoIterator<Item = S>,
+    I: IntoIterator<Item = S> + Clone,
     S: AsRef<OsStr>,
 {
     const CLOEXEC_MSG_FOOTER: [u8; 4] = *b"NOEX";
+    let mut command_text = program.to_string();
+    if environment.do_job_control {
+        for arg in args.clone() {
+            command_text.push(' ');
+            command_text.push_str(&arg.as_ref().to_string_lossy());
+        }
+    }
     let (input, output) = anon_pipe()?;
     let result = unsafe { cvt(libc::fork())? };
 
@@ -477,6 +548,10 @@ where
                         libc::_exit(10);
                     }
                 }
+                if let Err(err) = apply_fd_redirects(&environment.fd_redirects) {
+                    eprintln!("Error setting up file descriptor redirects: {}", err);
+                    libc::_exit(10);
+                }
                 if environment.do_job_control {
                     let pid = unistd::getpid();
                     let pgid = match environment.pipe_pgid {
@@ -529,7 +604,7 @@ where
     loop {
         match input.read(&mut bytes) {
             Ok(0) => {
-                setup_job(environment, pid, program);
+                setup_job(environment, pid, &command_text);
                 return Ok(pid);
             }
             Ok(8) => {
@@ -0,0 +1,296 @@
+//! Run bytecode compiled by bytecode.rs.
+//!
+//! A chunk is the body of one lambda, it runs with the lambda's frame already
+//! set up on the environment stack (by call_lambda) and returns what the
+//! tree-walker would have, so a tail call is returned as a lazy fn for
+//! call_lambda to loop on.
+
+use std::cell::RefCell;
+
+use crate::builtins_debug::debug_eval_hook;
+use crate::bytecode::*;
+use crate::environment::*;
+use crate::eval::*;
+use crate::types::*;
+
+fn call_command(
+    environment: &mut Environment,
+    command: Expression,
+    args: Vec<Expression>,
+    tail: bool,
+) -> Result<Expression, LispError> {
+    if !eval_checks(environment)? {
+        return Ok(Expression::make_nil());
+    }
+    let command_d = command.get();
+    let result = match &command_d.data {
+        ExpEnum::Lambda(l) => {
+            if tail && environment.allow_lazy_fn && !l.no_recur {
+                drop(command_d);
+                return Ok(Expression::alloc(ExpObj {
+                    data: ExpEnum::LazyFn(command, args),
+                    meta: None,
+                    meta_tags: None,
+                    analyzed: RefCell::new(true),
+                }));
+            }
+            drop(command_d);
+            call_lambda(environment, command, &mut args.into_iter(), false)?
+        }
+        ExpEnum::Function(c) => {
+            let func = c.func;
+            drop(command_d);
+            call_builtin_evaled(environment, func, args)?
+        }
+        _ => {
+            return Err(LispError::new(format!(
+                "Not a valid command {}, type {}.",
+                command,
+                command.display_type()
+            )))
+        }
+    };
+    if tail {
+        Ok(result)
+    } else {
+        result.resolve(environment)
+    }
+}
+
+// Do what eval_nr does around a call form: limit the depth, track the source and
+// record it in the backtrace of an error.
+fn call_form(
+    environment: &mut Environment,
+    form: &Expression,
+    command: Expression,
+    args: Vec<Expression>,
+    tail: bool,
+) -> Result<Expression, LispError> {
+    if environment.eval_level > 500 {
+        return Err(LispError::new("Eval calls to deep."));
+    }
+    environment.eval_level += 1;
+    environment.last_meta = form.meta();
+    let mut result = if environment.debugger.active {
+        debug_eval_hook(environment, form)
+            .and_then(|_| call_command(environment, command, args, tail))
+    } else {
+        call_command(environment, command, args, tail)
+    };
+    if let Err(err) = &mut result {
+        error_frame(environment, form, err);
+    }
+    environment.eval_level -= 1;
+    environment.last_meta = None;
+    result
+}
+
+// The command was evaluated but is not a lambda or function, let the
+// tree-walker call it with the unevaluated args from form.
+fn eval_command_form(
+    environment: &mut Environment,
+    form: &Expression,
+    command: Expression,
+    tail: bool,
+) -> Result<Expression, LispError> {
+    environment.eval_level += 1;
+    environment.last_meta = form.meta();
+    let mut parts = form.iter();
+    parts.next();
+    let mut result = eval_command(environment, &command, &mut parts);
+    if !tail {
+        result = result.and_then(|exp| exp.resolve(environment));
+    }
+    if let Err(err) = &mut result {
+        error_frame(environment, form, err);
+    }
+    environment.eval_level -= 1;
+    environment.last_meta = None;
+    result
+}
+
+fn is_callable(exp: &Expression) -> bool {
+    match &exp.get().data {
+        ExpEnum::Lambda(_) => true,
+        ExpEnum::Function(c) => !c.args_in_context,
+        _ => false,
+    }
+}
+
+// A return-from or exit is unwinding.
+fn unwinding(environment: &Environment) -> bool {
+    environment.return_val.is_some() || environment.exit_code.is_some()
+}
+
+// Find the block a return-from is for, leave its value on the stack and return
+// where to continue.  None if it is not in this chunk (or exit was called), the
+// tree-walker would evaluate everything left to nil so just return.
+fn catch_return(environment: &mut Environment, blocks_base: usize) -> Option<usize> {
+    if environment.exit_code.is_some() {
+        return None;
+    }
+    let ret_name = environment.return_val.as_ref()?.0;
+    let idx = environment.vm_blocks[blocks_base..]
+        .iter()
+        .rposition(|(name, _, _)| ret_name.map(|ret| ret == *name).unwrap_or(true))?
+        + blocks_base;
+    let (_, value) = environment.return_val.take()?;
+    let (_, end, depth) = environment.vm_blocks[idx];
+    environment.vm_stack.truncate(depth);
+    environment.vm_stack.push(value);
+    environment.vm_blocks.truncate(idx);
+    Some(end)
+}
+
+fn pop(environment: &mut Environment) -> Result<Expression, LispError> {
+    environment
+        .vm_stack
+        .pop()
+        .ok_or_else(|| LispError::new("Bytecode popped an empty stack."))
+}
+
+fn run_int(
+    environment: &mut Environment,
+    chunk: &Chunk,
+    conditions: &mut u32,
+    blocks_base: usize,
+) -> Result<Expression, LispError> {
+    let mut ip = 0;
+    loop {
+        let op = chunk.code[ip];
+        ip += 1;
+        match op {
+            Op::Const(idx) => environment.vm_stack.push(chunk.consts[idx].clone()),
+            Op::Stack(idx, _) => {
+                if let Some(exp) = get_expression_stack(environment, idx) {
+                    environment.vm_stack.push(exp);
+                } else {
+                    return Err(LispError::new(format!(
+                        "Bytecode referenced invalid stack slot {}.",
+                        idx
+                    )));
+                }
+            }
+            Op::Ref(idx) => environment.vm_stack.push(chunk.refs[idx].1.get()),
+            Op::Eval(idx) => {
+                let result = eval(environment, &chunk.forms[idx])?;
+                environment.vm_stack.push(result);
+            }
+            Op::EvalTail(idx) => {
+                let result = eval_nr(environment, &chunk.forms[idx])?;
+                environment.vm_stack.push(result);
+            }
+            Op::EvalCommand(idx, tail) => {
+                let command = pop(environment)?;
+                let result = eval_command_form(environment, &chunk.forms[idx], command, tail)?;
+                environment.vm_stack.push(result);
+            }
+            Op::Pop => {
+                pop(environment)?;
+            }
+            Op::Jump(target) => ip = target,
+            Op::JumpIfFalse(target) => {
+                if pop(environment)?.is_falsy() {
+                    ip = target;
+                }
+            }
+            Op::And(target) => {
+                if let Some(top) = environment.vm_stack.last_mut() {
+                    if top.is_falsy() {
+                        *top = Expression::make_false();
+                        ip = target;
+                    }
+                }
+            }
+            Op::Or(target) => {
+                if environment
+                    .vm_stack
+                    .last()
+                    .map(|exp| !exp.is_falsy())
+                    .unwrap_or(false)
+                {
+                    ip = target;
+                } else {
+                    pop(environment)?;
+                }
+            }
+            Op::CondEnter => {
+                environment.in_condition += 1;
+                *conditions += 1;
+            }
+            Op::CondExit => {
+                environment.in_condition -= 1;
+                *conditions -= 1;
+            }
+            Op::Guard(binding, value, target) => {
+                if !chunk.refs[binding].1.get().ptr_eq(&chunk.consts[value]) {
+                    ip = target;
+                }
+            }
+            Op::Block(name, end) => {
+                let depth = environment.vm_stack.len();
+                environment.vm_blocks.push((name, end, depth));
+            }
+            Op::BlockEnd => {
+                environment.vm_blocks.pop();
+            }
+            Op::Callable(target) => {
+                if !environment
+                    .vm_stack
+                    .last()
+                    .map(is_callable)
+                    .unwrap_or(false)
+                {
+                    ip = target;
+                }
+            }
+            Op::Call(argc, form) | Op::TailCall(argc, form) => {
+                let tail = matches!(op, Op::TailCall(_, _));
+                let args_start = environment
+                    .vm_stack
+                    .len()
+                    .checked_sub(argc)
+                    .ok_or_else(|| LispError::new("Bytecode popped an empty stack."))?;
+                let args = environment.vm_stack.split_off(args_start);
+                let command = pop(environment)?;
+                let result = call_form(environment, &chunk.forms[form], command, args, tail)?;
+                if !tail && environment.recur_num_args.is_some() {
+                    environment.recur_num_args = None;
+                    return Err(LispError::new("Called recur in a non-tail position."));
+                }
+                environment.vm_stack.push(result);
+            }
+            Op::Return => return pop(environment),
+        }
+        if matches!(
+            op,
+            Op::Eval(_)
+                | Op::EvalTail(_)
+                | Op::EvalCommand(_, _)
+                | Op::Call(_, _)
+                | Op::TailCall(_, _)
+        ) && unwinding(environment)
+        {
+            if let Some(end) = catch_return(environment, blocks_base) {
+                ip = end;
+            } else {
+                return Ok(Expression::make_nil());
+            }
+        }
+    }
+}
+
+/// Run chunk (the body of the lambda whose frame is on top of the stack).
+pub fn run_chunk(environment: &mut Environment, chunk: &Chunk) -> Result<Expression, LispError> {
+    if unwinding(environment) {
+        return Ok(Expression::make_nil());
+    }
+    let stack_base = environment.vm_stack.len();
+    let blocks_base = environment.vm_blocks.len();
+    let mut conditions = 0;
+    let result = run_int(environment, chunk, &mut conditions, blocks_base);
+    environment.in_condition -= conditions;
+    environment.vm_stack.truncate(stack_base);
+    environment.vm_blocks.truncate(blocks_base);
+    result
+}
@@ -0,0 +1,30 @@
+; Ints overflow into bignums and come back when they fit.
+(defn bigint-fact (n) (if (< n 2) 1 (* n (bigint-fact (- n 1)))))
+(test::assert-equal 2432902008176640000 (bigint-fact 20))
+(test::assert-equal "Int" (type (bigint-fact 20)))
+(test::assert-equal 51090942171709440000 (bigint-fact 21))
+(test::assert-equal "BigInt" (type (bigint-fact 21)))
+(test::assert-equal 21 (/ (bigint-fact 21) (bigint-fact 20)))
+(test::assert-equal "Int" (type (/ (bigint-fact 21) (bigint-fact 20))))
+
+; Summing sizes past i64 does not wrap.
+(def bigint-total 0)
+(dotimes-i i 4 (set! bigint-total (+ bigint-total 4611686018427387904)))
+(test::assert-equal 18446744073709551616 bigint-total)
+(test::assert-true (> bigint-total 9223372036854775807))
+
+; Comparisons are exact, mixed with floats they compare as floats.
+(test::assert-true (< -9223372036854775809 -9223372036854775808 0 9223372036854775808))
+(test::assert-false (= 18446744073709551617 18446744073709551616))
+(test::assert-true (= 18446744073709551616 (* 4294967296 4294967296)))
+(test::assert-true (< 18446744073709551616 1e20))
+(test::assert-true (>= 18446744073709551616 18446744073709551616 1.5))
+(test::assert-equal '(1 18446744073709551616 36893488147419103232)
+                    (qsort (list 36893488147419103232 1 18446744073709551616)))
+
+; Printing reads back and bignums can be keys.
+(test::assert-equal 18446744073709551616 (read (str 18446744073709551616)))
+(test::assert-equal :big (hash-get {18446744073709551616 :big} (* 4294967296 4294967296)))
+(test::assert-true (set-contains? #{-18446744073709551616} (- 18446744073709551616)))
+(test::assert-equal 18446744073709551616 #x10000000000000000)
+(test::assert-error (% 18446744073709551616 0))
@@ -0,0 +1,35 @@
+; File modes.
+(def bits-mode #o100644)
+(test::assert-equal "644" (math::int->str (math::bit-and bits-mode #o777) 8))
+(test::assert-true (= 0 (math::bit-and bits-mode #o111)))
+(test::assert-equal "755" (math::int->str (math::bit-or #o644 #o111) 8))
+
+; Network masks.
+(defn bits-prefix->mask (prefix)
+  (math::bit-and (math::bit-not (math::shr #xffffffff prefix)) #xffffffff))
+(test::assert-equal #xffffff00 (bits-prefix->mask 24))
+(test::assert-equal 24 (math::popcount (bits-prefix->mask 24)))
+(test::assert-equal 32 (- 64 (math::leading-zeros (bits-prefix->mask 24))))
+(test::assert-equal #xc0a80100 (math::bit-and #xc0a80117 (bits-prefix->mask 24)))
+
+; Flag words.
+(def bits-flags (math::bit-or (math::shl 1 0) (math::shl 1 3)))
+(test::assert-equal 9 bits-flags)
+(test::assert-false (= 0 (math::bit-and bits-flags (math::shl 1 3))))
+(test::assert-equal 1 (math::bit-xor bits-flags (math::shl 1 3)))
+
+; Floored division always puts the dividend back together.
+(for a in '(7 -7 9223372036854775807 -18446744073709551617)
+  (for b in '(2 -2 3 -5 4294967296)
+    (do
+      (test::assert-equal a (+ (* (math::div a b) b) (math::mod a b)))
+      (test::assert-equal a (+ (* (let ((*int-div-truncate* #t)) (/ a b)) b) (math::rem a b))))))
+(test::assert-equal 12 (math::lcm (math::gcd 12 18) 4))
+
+; Masks wider than 64 bits.
+(def bits-wide (math::bit-or (math::shl 1 100) #xff))
+(test::assert-equal #xff (math::bit-and bits-wide #xffff))
+(test::assert-equal (math::shl 1 100) (math::bit-and bits-wide (math::bit-not #xff)))
+(test::assert-equal 9 (math::popcount bits-wide))
+(test::assert-equal 27 (math::leading-zeros bits-wide))
+(test::assert-equal 0 (math::bit-xor bits-wide bits-wide))
@@ -0,0 +1,59 @@
+; Lambdas are compiled to bytecode on their first call, check it does what the
+; tree-walker does.
+(ns-import 'iterator)
+(defn bc-sum-to (n acc) (if (= n 0) acc (recur (- n 1) (+ acc n))))
+(test::assert-equal 50005000 (bc-sum-to 10000 0))
+(def bc-odd? nil)
+(defn bc-even? (n) (if (= n 0) #t (bc-odd? (- n 1))))
+(defn bc-odd? (n) (if (= n 0) #f (bc-even? (- n 1))))
+(test::assert-true (bc-even? 10000))
+(test::assert-true (str-contains "tail-call" (disassemble bc-even?)))
+(test::assert-error-msg ((fn () (+ 1 (recur)))) "Called recur in a non-tail position.")
+
+(defn bc-and-or (a b) (list (and) (and a b) (or) (or a b) (or nil nil) (if a 1) (if a 1 b 2 3)))
+(test::assert-equal '(#t 2 #f 1 #f 1 1) (bc-and-or 1 2))
+(test::assert-equal '(#t #f #f 2 #f #f 2) (bc-and-or nil 2))
+(test::assert-equal '(#t #f #f #f #f #f 3) (bc-and-or nil nil))
+(defn bc-do (v) (do (vec-push! v 1) (vec-push! v 2) (do)))
+(def bc-v (vec))
+(test::assert-false (bc-do bc-v))
+(test::assert-equal '#(1 2) bc-v)
+
+; Blocks and return-from.
+(defn bc-find (item items) (for i in items (when (= i item) (return-from bc-find i))) :none)
+(test::assert-equal 3 (bc-find 3 '(1 2 3 4)))
+(test::assert-equal :none (bc-find 5 '(1 2 3 4)))
+(defn bc-nested (x)
+  (+ 1 (block outer (+ 10 (block inner (if x (return-from outer 100) (return-from inner 1)))))))
+(test::assert-equal 101 (bc-nested #t))
+(test::assert-equal 12 (bc-nested nil))
+
+; Commands that are forms, fns and builtins that eval in their own context.
+(defn bc-iter (items) (((iter items) :map (fn (x) (* x 2))) :collect))
+(test::assert-equal '(2 4 6) (bc-iter '(1 2 3)))
+(defn bc-let (x) (let ((y (+ x 1))) (* y 2)))
+(test::assert-equal 8 (bc-let 3))
+(defn bc-apply (items) (apply + (collect (map (fn (x) (+ x 1)) items))))
+(test::assert-equal 9 (bc-apply '(1 2 3)))
+(defn bc-str-map (s) (str-map (fn (ch) (if (= ch #\a) #\b ch)) s))
+(test::assert-equal "bbc" (bc-str-map "abc"))
+(defn bc-not (x) (not (= x 1)))
+(test::assert-true (bc-not 2))
+(defn bc-error (x) (get-error (err x)))
+(test::assert-equal :error (car (bc-error "oops")))
+(defn bc-kill (proc) (kill -TERM proc))
+(def bc-proc (fork (sleep 10000)))
+(bc-kill bc-proc)
+(test::assert-equal 143 (wait bc-proc))
+
+; A redefined special form is not inlined.
+(defn bc-shadow () (let ((if (fn (a b c) c))) (if #t 1 2)))
+(test::assert-equal 2 (bc-shadow))
+
+; With *bytecode* false new lambdas stay on the tree-walker.
+(def *bytecode* nil)
+(defn bc-tree (n acc) (if (= n 0) acc (recur (- n 1) (+ acc n))))
+(test::assert-equal 50005000 (bc-tree 10000 0))
+(test::assert-equal "tree-walker\n" (disassemble bc-tree))
+(undef *bytecode*)
+(test::assert-not-equal "tree-walker\n" (disassemble bc-sum-to))
@@ -0,0 +1,52 @@
+; Handlers run innermost first, a handler that returns declines to the next one.
+(def conditions-seen (list))
+(test::assert-equal :error (car (get-error
+    (handler-bind ((:oops (fn (c) (set! conditions-seen (join :outer conditions-seen)))))
+        (handler-bind ((:oops (fn (c) (set! conditions-seen (join :inner conditions-seen))))
+                       (:error (fn (c) (set! conditions-seen (join :any conditions-seen)))))
+            (err :oops "Oops!"))))))
+(test::assert-equal '(:outer :any :inner) conditions-seen)
+
+; get-error handles every error so handlers outside of it are not called.
+(set! conditions-seen nil)
+(handler-bind ((:oops (fn (c) (set! conditions-seen #t))))
+    (get-error (err :oops "Oops!")))
+(test::assert-false conditions-seen)
+
+; A handler only sees the handlers outside of its handler-bind.
+(test::assert-equal :inner-err (handler-case
+    (handler-bind ((:oops (fn (c) (err :inner-err "From handler"))))
+        (handler-bind ((:inner-err (fn (c) (set! conditions-seen :wrong))))
+            (err :oops "Oops!")))
+    (:inner-err (c) (error-kind c))))
+(test::assert-false conditions-seen)
+
+; Handlers run before unwinding (the unwind-protect cleanup happens after).
+(set! conditions-seen (list))
+(handler-case
+    (handler-bind ((:oops (fn (c) (set! conditions-seen (join :handler conditions-seen)))))
+        (unwind-protect (err :oops "Oops!") (set! conditions-seen (join :cleanup conditions-seen))))
+    (:oops () nil))
+(test::assert-equal '(:cleanup :handler) conditions-seen)
+
+; Restarts unwind through get-error and handler-case to their restart-case.
+(test::assert-equal 42 (restart-case
+    (get-error (handler-case (invoke-restart 'use-value 42) (:error () :wrong)))
+    (use-value (v) v)))
+
+; Retry a flaky operation from a handler.
+(def conditions-tries 0)
+(defn conditions-flaky ()
+    (set! conditions-tries (+ conditions-tries 1))
+    (if (< conditions-tries 3) (err :io "Flaky") :done))
+(defn conditions-with-retry (thunk)
+    (restart-case (thunk) (retry () (conditions-with-retry thunk))))
+(test::assert-equal :done (handler-bind ((:io (fn (c) (invoke-restart 'retry))))
+    (conditions-with-retry conditions-flaky)))
+(test::assert-equal 3 conditions-tries)
+
+; signal is not an error, :error handlers and get-error do not see it.
+(set! conditions-seen nil)
+(test::assert-equal '(:ok) (get-error (handler-bind ((:error (fn (c) (set! conditions-seen #t))))
+    (signal :note "Note"))))
+(test::assert-false conditions-seen)
@@ -0,0 +1,67 @@
+; Destructuring patterns in let, fn params and for.
+(let (((a b & rest) '(1 2 3 4)))
+  (test::assert-equal '(1 2 (3 4)) (list a b rest)))
+(let (((a b & rest) (vec 1 2 3 4)))
+  (test::assert-equal '(1 2 #(3 4)) (list a b rest)))
+(let (((a b & rest) '(1 2)))
+  (test::assert-equal '(1 2 nil) (list a b rest)))
+(let (((a (b c) & (d)) '(1 (2 3) 4)))
+  (test::assert-equal '(1 2 3 4) (list a b c d)))
+(let ((#(a b) '(1 2)))
+  (test::assert-equal '(1 2) (list a b)))
+
+; Shape errors.
+(test::assert-equal "wrong number of elements to destructure (1 2 3) into (a b), expected 2 got 3"
+  (error-message (get-error (let (((a b) '(1 2 3))) a))))
+(test::assert-equal "wrong number of elements to destructure (1) into (a b & r), expected at least 2 got 1"
+  (error-message (get-error (let (((a b & r) '(1))) a))))
+(test::assert-equal :arity (error-kind (get-error (let (((a (b c)) '(1 (2)))) a))))
+(test::assert-equal :type (error-kind (get-error (let (((a b) 1)) a))))
+(test::assert-equal :type (error-kind (get-error (let (({:keys (a)} '(1))) a))))
+
+; Maps.
+(def destructure-cfg {:host "localhost" "port" 8080 :user {:name "sls"}})
+(let (({:keys (host port missing)} destructure-cfg))
+  (test::assert-equal '("localhost" 8080 nil) (list host port missing)))
+(let (({h :host p "port" {:keys (name)} :user} destructure-cfg))
+  (test::assert-equal '("localhost" 8080 "sls") (list h p name)))
+(let (({:keys (host timeout) :or {timeout (* 2 5)} :as all} destructure-cfg))
+  (test::assert-equal '("localhost" 10) (list host timeout))
+  (test::assert-true (= all destructure-cfg)))
+(let (({:keys (a)} nil))
+  (test::assert-false a))
+
+; Params, &rest, recur and macros.
+(defn destructure-params ((x y) &rest (z & more)) (list x y z more))
+(test::assert-equal '(1 2 3 #(4)) (destructure-params '(1 2) 3 4))
+(test::assert-equal :arity (error-kind (get-error (destructure-params '(1 2)))))
+(test::assert-equal "wrong number of elements to destructure (1) into (x y), expected 2 got 1"
+  (error-message (get-error (destructure-params '(1) 2))))
+(test::assert-true (str-contains "(fn ((x y) &rest (z & more))" (str destructure-params)))
+(defn destructure-two ((a b) (a2 b2)) (list a b a2 b2))
+(test::assert-equal '(1 2 3 4) (destructure-two '(1 2) '(3 4)))
+(defn destructure-sum ((n acc)) (if (= n 0) acc (recur (list (- n 1) (+ acc n)))))
+(test::assert-equal 5050 (destructure-sum '(100 0)))
+(defmacro destructure-macro ((a b)) `(+ ,a ,b))
+(test::assert-equal 3 (destructure-macro (1 2)))
+(def destructure-closure (let (((a b) '(1 2))) (fn () (+ a b))))
+(test::assert-equal 3 (destructure-closure))
+
+; for.
+(def destructure-total 0)
+(iterator::for (k v) in '((1 2) (3 4)) (set! destructure-total (+ destructure-total (* k v))))
+(test::assert-equal 14 destructure-total)
+(def destructure-total 0)
+(iterator::for {:keys (n)} in (list {:n 1} {:n 2} {:n 3}) (set! destructure-total (+ destructure-total n)))
+(test::assert-equal 6 destructure-total)
+
+; Reader.
+(test::assert-equal '(hash-map :a 1) (read "{:a 1}"))
+(test::assert-error (read "{:a}"))
+(test::assert-error (read "(a })"))
+(test::assert-error (read "{a )"))
+(test::assert-equal #\} (str-nth 0 "}"))
+; {} is an empty map in lisp forms, shell forms still pass {} through.
+(test::assert-equal '(hash-map) (read "{}"))
+(test::assert-equal "x {}\n" (str $(echo x {})))
+(test::assert-equal "\n" (str #\u{a}))
@@ -0,0 +1,23 @@
+; A lambda that captured its own binding is a cycle, reference counting alone
+; never frees it.
+(gc)
+(dotimes-i i 100 (let ((f nil)) (set! f (fn () f)) nil))
+(test::assert-true (>= (gc) 100))
+
+; Containers that hold themselves.
+(let ((h (make-hash))) (hash-set! h :self h) nil)
+(let ((v (vec 1 2))) (vec-push! v v) nil)
+(test::assert-equal 2 (gc))
+
+; Cycles still referenced are left alone.
+(def gc-keep (let ((f nil)) (set! f (fn () f)) f))
+(gc)
+(test::assert-true (lambda? (gc-keep)))
+(test::assert-true (lambda? ((gc-keep))))
+
+(def gc-stats-before (gc-stats))
+(let ((f nil)) (set! f (fn () f)) nil)
+(gc)
+(test::assert-equal (+ (hash-get gc-stats-before :collections) 1) (hash-get (gc-stats) :collections))
+(test::assert-true (> (hash-get (gc-stats) :reclaimed) (hash-get gc-stats-before :reclaimed)))
+(test::assert-true (> (hash-get (gc-stats) :cycles) (hash-get gc-stats-before :cycles)))
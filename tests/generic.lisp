@@ -0,0 +1,81 @@
+; Generic functions, defgeneric and defmethod.
+(defgeneric generic-to-json "Convert x to a json string." (x))
+(defmethod generic-to-json ((x Int)) (str x))
+(defmethod generic-to-json ((x String)) (str "\"" x "\""))
+(defmethod generic-to-json ((x Vector))
+  (str "[" (str-cat-list "," (iterator::collect (iterator::map generic-to-json x))) "]"))
+(defmethod generic-to-json ((x HashMap))
+  (str "{" (str-cat-list "," (iterator::collect
+                              (iterator::map (fn (k) (str "\"" k "\":" (generic-to-json (hash-get x k))))
+                                             (qsort (hash-keys x))))) "}"))
+(test::assert-equal "[1,\"a\",[2]]" (generic-to-json (vec 1 "a" (vec 2))))
+(test::assert-equal "{\"a\":1,\"b\":[2]}" (generic-to-json {"a" 1 "b" (vec 2)}))
+
+; Structs dispatch on their name.
+(struct::defstruct generic-point (x 0 :rw) (y 0 :rw))
+(defmethod generic-to-json ((p generic-point)) (str "{\"x\":" (p :x) ",\"y\":" (p :y) "}"))
+(def generic-pt (generic-point))
+(generic-pt :set-x 3)
+(test::assert-equal "[{\"x\":3,\"y\":0}]" (generic-to-json (vec generic-pt)))
+
+; No method.
+(def generic-err (get-error (generic-to-json 1.5)))
+(test::assert-equal :no-applicable-method (error-kind generic-err))
+(test::assert-equal "generic-to-json: no applicable method for (Float)" (error-message generic-err))
+(test::assert-equal '(1.5) (error-data generic-err))
+(test::assert-equal :arity (error-kind (get-error (generic-to-json))))
+
+; A method added after a call (the cache is cleared) and a default method.
+(defmethod generic-to-json ((x Float)) (str x))
+(test::assert-equal "1.5" (generic-to-json 1.5))
+(defmethod generic-to-json (x) "null")
+(test::assert-equal "null" (generic-to-json nil))
+(test::assert-equal "1" (generic-to-json 1))
+
+; Specificity, the first specialized param decides.
+(defgeneric generic-pair (a b &optional c))
+(defmethod generic-pair (a b &optional c) (list :any c))
+(defmethod generic-pair ((a Int) b &optional c) (list :int-any c))
+(defmethod generic-pair (a (b Int) &optional c) (list :any-int c))
+(test::assert-equal '(:int-any nil) (generic-pair 1 2))
+(test::assert-equal '(:any-int 3) (generic-pair "a" 2 3))
+(test::assert-equal '(:any nil) (generic-pair "a" "b"))
+(test::assert-error (defmethod generic-pair ((a Int)) a))
+
+; Redefining the generic keeps the methods.
+(defgeneric generic-pair (a b &optional c))
+(test::assert-equal '(:int-any nil) (generic-pair 1 2))
+; Unless that changes the required params, the methods would no longer match.
+(test::assert-equal :arity (error-kind (get-error (defgeneric generic-pair (a)))))
+(test::assert-equal '(:int-any nil) (generic-pair 1 2))
+(defgeneric generic-none (a))
+(defgeneric generic-none (a b))
+(defmethod generic-none (a b) (list a b))
+(test::assert-equal '(1 2) (generic-none 1 2))
+
+; A dispatch function, returning one value or a list of values.
+(defgeneric generic-area (shape) :dispatch (fn (shape) (hash-get shape :kind)))
+(defmethod generic-area ((s :square)) (* (hash-get s :side) (hash-get s :side)))
+(defmethod generic-area ((s :rect)) (* (hash-get s :w) (hash-get s :h)))
+(test::assert-equal 9 (generic-area {:kind :square :side 3}))
+(test::assert-equal 6 (generic-area {:kind :rect :w 2 :h 3}))
+(test::assert-equal "generic-area: no applicable method for (:circle)"
+  (error-message (get-error (generic-area {:kind :circle}))))
+(defgeneric generic-collide (a b) :dispatch (fn (a b) (list (hash-get a :kind) (hash-get b :kind))))
+(defmethod generic-collide ((a :ship) (b :rock)) :boom)
+(defmethod generic-collide ((a :ship) b) :miss)
+(test::assert-equal :boom (generic-collide {:kind :ship} {:kind :rock}))
+(test::assert-equal :miss (generic-collide {:kind :ship} {:kind :ship}))
+
+; Methods can destructure with a vector pattern.
+(defgeneric generic-first (x))
+(defmethod generic-first (#(a & rest)) a)
+(test::assert-equal 1 (generic-first '(1 2)))
+
+; Dispatch values are matched as hash keys, a value that can not be one is an error.
+(defgeneric generic-http (code) :dispatch (fn (code) code))
+(defmethod generic-http ((c 404)) :not-found)
+(defmethod generic-http ((c "teapot")) :teapot)
+(test::assert-equal :not-found (generic-http 404))
+(test::assert-equal :teapot (generic-http 'teapot))
+(test::assert-equal :type (error-kind (get-error (generic-http 1.5))))
@@ -0,0 +1,29 @@
+; Hash maps with non-string keys.
+(def hash-counts (make-hash))
+(for i in (list 1 2 2 3 3 3)
+  (hash-set! hash-counts i (+ 1 (hash-get hash-counts i 0))))
+(test::assert-equal '(1 2 3) (qsort (hash-keys hash-counts)))
+(test::assert-equal 3 (hash-get hash-counts 3))
+(test::assert-false (hash-haskey hash-counts "3"))
+
+; Lists are compared by value and copied when used as a key.
+(def hash-list-key (list 1 "a" #\b))
+(def hash-lists {hash-list-key :found '() :empty})
+(test::assert-equal :found (hash-get hash-lists (list 1 "a" #\b)))
+(test::assert-equal :empty (hash-get hash-lists nil))
+(xar! hash-list-key 2)
+(test::assert-equal :found (hash-get hash-lists (list 1 "a" #\b)))
+(test::assert-false (hash-haskey hash-lists hash-list-key))
+
+; Symbols, strings and chars with the same text are the same key.
+(def hash-text {"a" 1})
+(test::assert-equal 1 (hash-get hash-text 'a))
+(test::assert-equal 1 (hash-get hash-text #\a))
+(hash-set! hash-text 'a 2)
+(test::assert-equal 1 (length (hash-keys hash-text)))
+(test::assert-equal 2 (hash-get hash-text "a"))
+
+; Keys that can not be hashed are errors.
+(test::assert-equal :type (error-kind (get-error (hash-set! hash-text 1.0 1))))
+(test::assert-equal :type (error-kind (get-error (hash-get hash-text (join 1 2)))))
+(test::assert-equal :type (error-kind (get-error {(make-hash) 1})))
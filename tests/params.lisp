@@ -0,0 +1,37 @@
+; &optional and &key params in lambdas and macros.
+(defn params-opt (a &optional b (c 10) (d (+ a c) d?)) (list a b c d d?))
+(test::assert-equal '(1 nil 10 11 #f) (params-opt 1))
+(test::assert-equal '(1 2 3 4 #t) (params-opt 1 2 3 4))
+(test::assert-equal "wrong number of parameters, expected 1 to 4 got 0" (error-message (get-error (params-opt))))
+
+(defn params-key (a &key b (c 5) (d nil d?)) (list a b c d d?))
+(test::assert-equal '(1 nil 5 nil #f) (params-key 1))
+(test::assert-equal '(1 2 7 nil #f) (params-key 1 :c 7 :b 2))
+(test::assert-equal '(1 nil 5 nil #t) (params-key 1 :d nil))
+(test::assert-equal '(1 2 5 nil #f) (params-key 1 :b 2 :b 3))
+(test::assert-equal "unknown keyword argument :x" (error-message (get-error (params-key 1 :x 2))))
+(test::assert-equal :arity (error-kind (get-error (params-key 1 :c))))
+
+; &rest gets the keywords too and allows unknown ones.
+(defn params-rest-key (a &optional b &rest r &key k) (list a b r k))
+(test::assert-equal '(1 2 #(:k 3 :z 4) 3) (params-rest-key 1 2 :k 3 :z 4))
+(test::assert-equal '(1 nil nil nil) (params-rest-key 1))
+
+; Defaults close over the lambda's environment.
+(def params-closure (let ((z 4)) (fn (&optional (x (* z 2))) x)))
+(test::assert-equal 8 (params-closure))
+(test::assert-equal 1 (params-closure 1))
+
+; Recursion, recur and tail calls.
+(defn params-recur (n &optional (acc 0)) (if (= n 0) acc (recur (- n 1) (+ acc n))))
+(test::assert-equal 5050 (params-recur 100))
+(defn params-tail (n &key (acc 0)) (if (= n 0) acc (params-tail (- n 1) :acc (+ acc n))))
+(test::assert-equal 50005000 (params-tail 10000))
+
+(defmacro params-macro (a &optional (b 2)) `(+ ,a ,b))
+(test::assert-equal 3 (params-macro 1))
+(test::assert-equal 6 (params-macro 1 5))
+
+; Shown in usage and the printed lambda.
+(test::assert-true (str-contains "Usage: (params-opt a &optional b (c 10) (d (+ a c) d?))" (doc 'user::params-opt)))
+(test::assert-true (str-contains "(fn (a &key b (c 5) (d nil d?))" (str params-key)))
@@ -0,0 +1,54 @@
+; Dividing ints is exact, whole results are ints.
+(test::assert-equal 1/3 (/ 1 3))
+(test::assert-equal "Rational" (type (/ 1 3)))
+(test::assert-equal 1 (* (/ 1 3) 3))
+(test::assert-equal "Int" (type (* (/ 1 3) 3)))
+(test::assert-equal 1 (+ 1/10 1/5 7/10))
+(test::assert-equal 3/4 (/ 6 8))
+(test::assert-equal -1/2 (/ 2 -4))
+(test::assert-equal -1/2 (- 1/2))
+(test::assert-equal 1/2 (math::abs -1/2))
+(test::assert-equal 5/2 (/ 10 4))
+(test::assert-error (/ 1/2 0))
+
+; Mixed with bignums stays exact, mixed with floats becomes a float.
+(test::assert-equal 18446744073709551616/3 (/ 18446744073709551616 3))
+(test::assert-equal 18446744073709551616 (* (/ 18446744073709551616 3) 3))
+(test::assert-equal "Float" (type (+ 1/2 0.5)))
+(test::assert-equal 1.0 (+ 1/2 0.5))
+(test::assert-equal 0.25 (* 1/2 0.5))
+
+; Comparisons are exact.
+(test::assert-true (< 1/3 1/2 1))
+(test::assert-true (> -1/3 -1/2))
+(test::assert-true (= 1/2 (/ 2 4) (/ 5 10)))
+(test::assert-false (= 1/3 (/ 333333333333 1000000000000)))
+(test::assert-true (< 1/3 (/ 333333333334 1000000000000)))
+(test::assert-true (= 1/2 0.5))
+(test::assert-equal '(-1/2 1/3 1/2 1) (qsort (list 1/2 1 -1/2 1/3)))
+
+; Parts and rounding.
+(test::assert-equal 3 (math::numerator 3/4))
+(test::assert-equal 4 (math::denominator 3/4))
+(test::assert-equal 3 (math::floor 7/2))
+(test::assert-equal 4 (math::ceil 7/2))
+(test::assert-equal 4 (math::round 7/2))
+(test::assert-equal 27/8 (math::pow 3/2 3))
+(test::assert-error (math::pow 3/2 4000000000))
+(test::assert-error (math::pow 2/3 4000000000))
+(test::assert-equal 0.0 (math::pow 0.5 4000000000))
+
+; The old truncating behavior.
+(test::assert-equal 3 (let ((*int-div-truncate* #t)) (/ 7 2)))
+(test::assert-equal -3 (let ((*int-div-truncate* #t)) (/ -7 2)))
+(test::assert-equal 7/2 (/ 7 2))
+
+; Only the printed form reads as a rational, paths and dates stay symbols.
+(test::assert-equal "Symbol" (type (read "2024/10")))
+(test::assert-equal "Symbol" (type '4/2))
+(test::assert-equal "4/2" (str (str-trim $(echo 4/2))))
+
+; Printing reads back and rationals can be keys.
+(test::assert-equal 3/4 (read (str 3/4)))
+(test::assert-equal :half (hash-get {1/2 :half} (/ 2 4)))
+(test::assert-true (set-contains? #{1/3} (/ 2 6)))
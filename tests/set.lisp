@@ -0,0 +1,36 @@
+; Sets.
+(def set-seen (make-set))
+(for word in (str-split " " "a b a c b a")
+  (set-add! set-seen word))
+(test::assert-equal '("a" "b" "c") (qsort (set-items set-seen)))
+(test::assert-true (set-contains? set-seen 'a))
+
+; Same key types as hash maps, lists are compared by value.
+(def set-keys #{1 #\a '(1 2) (vec 1 :b)})
+(test::assert-true (set-contains? set-keys (list 1 2)))
+(test::assert-true (set-contains? set-keys '#(1 :b)))
+(test::assert-true (set-contains? set-keys "a"))
+(test::assert-false (set-contains? set-keys '(1 2 3)))
+(test::assert-equal :type (error-kind (get-error (set-add! set-keys 1.0))))
+
+; Iteration works on a snapshot of the items.
+(def set-nums #{1 2 3})
+(def set-sum 0)
+(iterator::for n in set-nums (do (set-remove! set-nums n) (set! set-sum (+ set-sum n))))
+(test::assert-equal 6 set-sum)
+(test::assert-equal 0 (length set-nums))
+(test::assert-equal '(2 4) (qsort (iterator::collect (iterator::map (fn (n) (* n 2)) #{1 2}))))
+
+; Set algebra returns new sets.
+(def set-a #{1 2 3})
+(def set-b #{3 4})
+(test::assert-true (= #{1 2 3 4} (union set-a set-b)))
+(test::assert-true (= #{3} (intersection set-a set-b)))
+(test::assert-true (= #{1 2} (difference set-a set-b)))
+(test::assert-true (= #{1 2 3} set-a))
+(test::assert-true (subset? (intersection set-a set-b) set-b))
+(test::assert-equal :type (error-kind (get-error (union set-a (list 1)))))
+
+; Printing reads back as the same set.
+(test::assert-true (= set-a (eval (read (str set-a)))))
+(test::assert-equal "#{\"a\"}" (str #{"a"}))
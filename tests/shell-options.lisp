@@ -0,0 +1,23 @@
+(ns-import 'shell)
+
+; *errexit*, a failed command is an error unless it is a condition.
+(def errexit-test (get-error (dyn *errexit* #t (syscall 'false))))
+(test::assert-equal :error (car errexit-test))
+(test::assert-true (str-contains "exited with status 1" (car (cdr errexit-test))))
+(test::assert-equal :ok (car (get-error (syscall 'false))))
+(test::assert-equal :ok (car (get-error (dyn *errexit* #t (if (syscall 'false) 1 2)))))
+(test::assert-equal :ok (car (get-error (dyn *errexit* #t (when (not (syscall 'false)) #t)))))
+(test::assert-equal :ok (car (get-error (dyn *errexit* #t (or (syscall 'false) (syscall 'true))))))
+(test::assert-equal :error (car (get-error (dyn *errexit* #t (and (syscall 'true) (syscall 'false))))))
+(test::assert-equal :error (car (get-error (dyn *errexit* #t (pipe (syscall 'true) (syscall 'false))))))
+(test::assert-equal :ok (car (get-error (dyn *errexit* #t (pipe (syscall 'false) (syscall 'true))))))
+(test::assert-equal :error (car (get-error (dyn *errexit* #t (dyn *pipefail* #t (pipe (syscall 'false) (syscall 'true)))))))
+(test::assert-equal :error (car (get-error (dyn *errexit* #t $(sh -c "exit 3")))))
+
+; *xtrace*, commands are printed to *stderr* with args quoted as needed.
+(def xtrace-file (str (temp-dir) "/shell-options-xtrace"))
+(err> xtrace-file (dyn *xtrace* #t (syscall 'true "a b" "c" "it's" "")))
+(test::assert-equal "+ true 'a b' c 'it'\\''s' ''\n" (str (syscall 'cat xtrace-file)))
+(err> xtrace-file (dyn *xtrace* #t $(true x | true y)))
+(test::assert-equal "+ true y\n" (str (syscall 'grep "y" xtrace-file)))
+(syscall 'rm xtrace-file)
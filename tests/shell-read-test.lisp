@@ -155,3 +155,70 @@ $(export sig1 "post"; im-nil || export sig2 "post" ; im-true || export sig3 "pos
 $(export XXX xxx)
 (test::assert-equal "y z/1 z/2 z/xxx" (str-trim $(echo {y,z/{'(1 2), "$XXX"}})))
 (test::assert-equal "y z/1 z/2 z/xxx" (str-trim $(echo {y,z/{'(1 2), xxx   }})))
+
+$(sh -c "exit 3" | grep -q nothing | true)
+(test::assert-equal '#(3 1 0) *pipe-status*)
+(test::assert-equal 0 *last-status*)
+(dyn *pipefail* #t $(sh -c "exit 3" | grep -q nothing | true))
+(test::assert-equal '#(3 1 0) *pipe-status*)
+(test::assert-equal 1 *last-status*)
+
+(def fdtest (str (temp-dir) "/shell-read-fd-test"))
+$(sh -c "echo three >&3" 3>$fdtest)
+(test::assert-equal "three\n" (str $(cat $fdtest)))
+$(sh -c "echo four >&4" 4>>$fdtest)
+(test::assert-equal "three\nfour\n" (str $(cat $fdtest)))
+(test::assert-equal "three\n" (str $(head -n 1 /dev/fd/3 3<$fdtest)))
+(test::assert-equal "err\n" (str $(sh -c "echo err >&2" 2>&1)))
+(test::assert-equal "out\n" (str $(sh -c "echo out >&3" 3>&1)))
+$(sh -c "echo both >&4" 3>$fdtest 4>&3)
+(test::assert-equal "both\n" (str $(cat $fdtest)))
+$(sh -c "echo to-err >&2" 2>$fdtest)
+(test::assert-equal "to-err\n" (str $(cat $fdtest)))
+(test::assert-equal "closed\n" (str $(sh -c "cat 2>/dev/null || echo closed" 0<&-)))
+(test::assert-equal "closed\n" (str $(sh -c "echo x 2>/dev/null || echo closed >&3" 3>&1 >&-)))
+$(rm $fdtest)
+
+(def heredoc-var "expanded")
+(test::assert-equal "one\ntwo (three)\n" (str $(cat <<EOF
+one
+two (three)
+EOF
+)))
+(test::assert-equal "var expanded\nhome \$HOME\n" (str $(cat <<END
+var $heredoc-var
+home \$HOME
+END
+)))
+(test::assert-equal "var \$heredoc-var\n" (str $(cat <<'END'
+var $heredoc-var
+END
+)))
+(test::assert-equal "2" (str-trim $(cat <<EOF | wc -l
+a
+b
+EOF
+)))
+(test::assert-equal "expanded\n" (str $(cat <<< $heredoc-var)))
+(test::assert-equal "a b\n" (str $(cat <<< "a b")))
+(test::assert-error (read "\$(cat <<EOF)"))
+
+(test::assert-equal "a\nb\n" (str $(cat <(echo a) <(echo b))))
+(test::assert-equal "" (str $(diff <(echo x | sort) <(echo x))))
+(test::assert-equal "a\n" (str $(cat <(echo a) | cat)))
+; A long running <(...) is stopped once the command using it is done.
+(test::assert-equal "a\n" (str $(head -n 1 <(sh -c "echo a; exec sleep 1000"))))
+(def proc-sub-file (str (temp-dir) "/shell-read-proc-sub"))
+$(echo hi | tee >(tr a-z A-Z > $proc-sub-file) > /dev/null)
+(test::assert-equal "HI\n" (str $(cat $proc-sub-file)))
+$(rm $proc-sub-file)
+; A command that stops keeps its <(...) running until it exits.
+(def proc-sub-pid (str (temp-dir) "/shell-read-proc-sub-pid"))
+(syscall 'sh "-c" "echo \$\$ > \$2; kill -STOP \$\$; cat \$1 > \$3" "sh"
+         (proc-sub-in (do (syscall 'sleep "0.2") (syscall 'echo "later")))
+         proc-sub-pid proc-sub-file)
+(def proc-sub-stopped (str->int (str-trim $(cat $proc-sub-pid))))
+$(kill -CONT $proc-sub-stopped)
+(test::assert-equal 0 (wait proc-sub-stopped))
+(test::assert-equal "later\n" (str $(cat $proc-sub-file)))
+$(rm $proc-sub-file $proc-sub-pid)
@@ -0,0 +1,78 @@
+; Native structs and defstruct.
+(struct::defstruct struct-point
+  (x 0 :rw)
+  (y 0 :ro)
+  (tag "a private tag" "none")
+  (:fn sum (self) (+ x y))
+  (:fn move! (self dx dy) (do (set! x (+ x dx)) (set! y (+ y dy)) self))
+  (:fn getter (self) (fn () x)))
+
+(def struct-pt (struct-point :x 1 :y 2))
+(test::assert-equal 3 (struct-pt :sum))
+(test::assert-true (= struct-pt (struct-pt :move! 1 1)))
+(test::assert-equal '(2 3) (list (struct-pt :x) (struct-pt :y)))
+(test::assert-equal 0 ((struct-point) :x))
+
+; Closures made by a method see the instance's fields.
+(def struct-get-x (struct-pt :getter))
+(struct-pt :set-x 10)
+(test::assert-equal 10 (struct-get-x))
+
+; Instances are separate.
+(def struct-pt2 (struct-point))
+(struct-pt2 :move! 5 5)
+(test::assert-equal 10 (struct-pt :x))
+(test::assert-equal 5 (struct-pt2 :x))
+
+; Access modes and messages.
+(test::assert-error (struct-pt :set-y 1))
+(test::assert-error (struct-pt :tag))
+(test::assert-error (struct-pt :nope))
+(test::assert-equal :arity (error-kind (get-error (struct-pt :x 1))))
+(test::assert-equal "struct-point" (struct-pt :type))
+
+; Type, printing and equality.
+(test::assert-equal "struct-point" (type struct-pt))
+(test::assert-true (struct? struct-pt))
+(test::assert-equal 'struct-point (struct-name struct-pt))
+(test::assert-equal '(x y tag) (struct-fields struct-pt))
+(test::assert-equal "(struct-point :x 10 :y 3 :tag \"none\")" (str struct-pt))
+(test::assert-true (= (struct-point :x 1) (struct-point :x 1)))
+(test::assert-false (= (struct-point :x 1) (struct-point :x 2)))
+(struct::defstruct struct-other (x 0 :rw) (y 0 :ro) (tag "a private tag" "none"))
+(test::assert-false (= (struct-point) (struct-other)))
+
+; Slot access ignores the access mode.
+(test::assert-equal "none" (struct-get struct-pt 'tag))
+(struct-set! struct-pt :y 7)
+(test::assert-equal 7 (struct-pt :y))
+
+; Traits, the struct's methods win over the trait's.
+(struct::deftrait struct-named
+  (:fn name (self) "trait")
+  (:fn greet (self) (str "hi " (self :name))))
+(struct::defstruct struct-person
+  (name "" "anon" :ro)
+  (:fn name (self) (str "person " name))
+  (:impl user::struct-named))
+(test::assert-equal "hi person bob" ((struct-person :name "bob") :greet))
+(test::assert-true (meta-tag? (struct-person) :trait-struct-named))
+(test::assert-true (meta-tag? (struct-person) :struct-struct-person))
+
+; Iterators are structs, apply works on instances.
+(test::assert-true (struct? (iterator::iter '(1 2))))
+(test::assert-equal '(1 2 3) (apply (iterator::iter '(1 2 3)) '(:collect)))
+
+; A struct that holds itself is collected.
+(gc)
+(dotimes-i i 10 (let ((p (struct-point))) (p :set-x p) nil))
+(test::assert-true (>= (gc) 10))
+
+; So is a struct type whose method holds the type.
+(gc)
+(dotimes-i i 10
+  (let ((st nil))
+    (set! st (make-struct-type 'struct-cycle '(n)))
+    (struct-add-method st :me (fn (self) st))
+    nil))
+(test::assert-true (>= (gc) 10))